@@ -1,23 +1,97 @@
 // Импортируем нужные типы из стандартной библиотеки
 use std::collections::VecDeque;
+use serde::{Deserialize, Serialize};
 
 // Структура для хранения позиции курсора в тексте
 // line - номер строки (начиная с 0)
 // column - номер столбца в строке (начиная с 0)
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 pub struct Position {
     pub line: usize,    // Номер строки
     pub column: usize,  // Номер столбца
 }
 
+// Как вводится табуляция: символом '\t' или пробелами до следующей позиции табуляции
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TabSettings {
+    pub width: usize,   // расстояние между позициями табуляции
+    pub use_tabs: bool, // вставлять '\t', а не пробелы
+}
+
+impl Default for TabSettings {
+    fn default() -> Self {
+        Self { width: 4, use_tabs: false }
+    }
+}
+
+impl TabSettings {
+    // Один уровень отступа: '\t' или width пробелов
+    pub fn unit(&self) -> String {
+        if self.use_tabs { "\t".to_string() } else { " ".repeat(self.width) }
+    }
+}
+
+// Автоотступ: после открывающих символов в конце строки отступ растет на уровень,
+// закрывающая скобка в начале строки его уменьшает
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IndentRules {
+    pub openers: Vec<char>,         // '{', '(', ':' в Python
+    pub brackets: Vec<(char, char)>, // пары, закрывающая часть которых уменьшает отступ
+}
+
+// Знаки комментариев языка
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CommentTokens {
+    pub line: Option<String>,            // "//"
+    pub block: Option<(String, String)>, // ("/*", "*/")
+}
+
+// Настройки буфера, от которых зависит результат набора текста
+// Окно задает их по языку и файлу, журнал записывает их смену
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BufferSettings {
+    pub tabs: TabSettings,
+    pub indent_rules: IndentRules,
+    pub auto_pairs: Vec<(char, char)>,
+    pub comments: CommentTokens,
+}
+
+// Изменение строк: с строки first строки removed заменены строками inserted
+// По нему история отменяет и возвращает правку, не храня весь текст
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct LineChange {
+    pub(crate) first: usize,
+    pub(crate) removed: Vec<String>,
+    pub(crate) inserted: Vec<String>,
+}
+
+// Запись изменений: исходные строки отрезка, в котором менялся текст
+struct Recording {
+    span: Option<(usize, Vec<String>)>, // первая строка отрезка и его исходные строки
+    total: usize,                       // число строк в начале записи
+}
+
+// Скобки, для которых ищется пара
+const BRACKETS: [(char, char); 3] = [('(', ')'), ('[', ']'), ('{', '}')];
+
 // Основная структура для работы с текстом
 // lines - массив строк текста
 // cursor - текущая позиция курсора
 // selection - выделение текста (начало и конец)
+// extra_cursors - дополнительные курсоры (мультикурсор)
 pub struct Buffer {
     lines: VecDeque<String>,  // Строки текста
     cursor: Position,         // Позиция курсора
     selection: Option<(Position, Position)>,  // Выделение текста (начало и конец)
+    extra_cursors: Vec<Position>,  // Дополнительные курсоры
+    revision: u64,  // Номер изменения текста, растет при каждой правке
+    tabs: TabSettings,  // Ввод табуляции
+    indent_rules: IndentRules,  // Автоотступ
+    auto_pairs: Vec<(char, char)>,  // Символы, к которым при наборе добавляется закрывающий
+    literals: Vec<Vec<(usize, usize)>>,  // Строки и комментарии по строкам: в них скобки не считаются
+    literals_revision: Option<u64>,  // Правка, к которой относятся literals
+    comments: CommentTokens,  // Знаки комментариев для их включения и снятия
+    recording: Option<Recording>,  // Запись изменений для истории отмены
 }
 
 // Реализация методов для работы с буфером
@@ -29,6 +103,15 @@ impl Buffer {
             lines: VecDeque::from(vec![String::new()]),  // Создаем одну пустую строку
             cursor: Position { line: 0, column: 0 },      // Курсор в начале
             selection: None,                              // Нет выделения
+            extra_cursors: Vec::new(),                    // Нет дополнительных курсоров
+            revision: 0,
+            tabs: TabSettings::default(),
+            indent_rules: IndentRules::default(),
+            auto_pairs: Vec::new(),
+            literals: Vec::new(),
+            literals_revision: None,
+            comments: CommentTokens::default(),
+            recording: None,
         }
     }
 
@@ -38,11 +121,15 @@ impl Buffer {
     pub fn delete_selection(&mut self) -> Result<(), String> {
         // Проверяем есть ли выделение
         if let Some((start, end)) = &self.selection {
+            // Выделение могло быть сделано справа налево
+            let (start, end) = if start <= end { (start, end) } else { (end, start) };
+
             // Сохраняем координаты начала и конца выделения
             let start_line = start.line;
             let start_col = start.column;
             let end_line = end.line;
             let end_col = end.column;
+            self.touch(start_line, end_line + 1);
 
             // Если выделение в одной строке
             if start_line == end_line {
@@ -70,6 +157,7 @@ impl Buffer {
 
             // Очищаем выделение
             self.selection = None;
+            self.revision += 1;
             // Ставим курсор в начало выделения
             self.cursor = Position {
                 line: start_line,
@@ -86,20 +174,20 @@ impl Buffer {
     // Возвращает ошибку если что-то пошло не так
     pub fn insert_char(&mut self, c: char) -> Result<(), String> {
         // Если есть выделение, удаляем его
-        if let Some((start, _)) = self.selection.take() {
+        // (delete_selection сам ставит курсор в начало выделения)
+        if self.selection.is_some() {
             self.delete_selection()?;
-            // Ставим курсор в начало выделения
-            self.cursor = Position {
-                line: start.line,
-                column: start.column,
-            };
         }
 
         // Проверяем что строка существует
         if self.cursor.line >= self.lines.len() {
+            self.touch(self.lines.len(), self.lines.len());
             self.lines.push_back(String::new());
         }
 
+        self.revision += 1;
+        self.touch(self.cursor.line, self.cursor.line + 1);
+
         // Получаем текущую строку
         let line = &mut self.lines[self.cursor.line];
         
@@ -127,17 +215,22 @@ impl Buffer {
                 return Ok(());
             }
             '\t' => {
-                // Вставляем 4 пробела вместо табуляции
-                for _ in 0..4 {
-                    line.insert(self.cursor.column, ' ');
-                    self.cursor.column += 1;
-                }
+                // Табуляция или пробелы до следующей позиции табуляции
+                let tab = if self.tabs.use_tabs {
+                    "\t".to_string()
+                } else {
+                    let column = display_column(&line[..self.cursor.column], self.tabs.width);
+                    " ".repeat(self.tabs.width - column % self.tabs.width)
+                };
+                line.insert_str(self.cursor.column, &tab);
+                self.cursor.column += tab.len();
                 return Ok(());
             }
             _ => {
                 // Просто вставляем символ
+                // (столбец считается в байтах, поэтому сдвигаемся на длину символа в UTF-8)
                 line.insert(self.cursor.column, c);
-                self.cursor.column += 1;
+                self.cursor.column += c.len_utf8();
             }
         }
 
@@ -145,6 +238,7 @@ impl Buffer {
     }
 
     // Удаляем символ перед курсором
+    // В начале строки объединяет её с предыдущей
     // Возвращает Ok(()) если удаление успешно
     // Возвращает ошибку если что-то пошло не так
     pub fn delete_char(&mut self) -> Result<(), String> {
@@ -153,29 +247,42 @@ impl Buffer {
             return Err("Курсор за пределами буфера".to_string());
         }
 
-        // Получаем текущую строку
-        let line = &mut self.lines[self.cursor.line];
-        
         // Проверяем что курсор в пределах строки
-        if self.cursor.column >= line.len() {
+        if self.cursor.column > self.lines[self.cursor.line].len() {
             return Err("Курсор за пределами строки".to_string());
         }
 
-        // Проверяем что строка не пустая
-        if line.is_empty() {
-            return Err("Строка пуста".to_string());
-        }
-
-        // Проверяем что курсор не в начале строки
+        // Курсор в начале строки: присоединяем её к предыдущей
         if self.cursor.column == 0 {
-            return Err("Курсор в начале строки".to_string());
+            if self.cursor.line == 0 {
+                return Err("Курсор в начале буфера".to_string());
+            }
+            self.revision += 1;
+            self.touch(self.cursor.line - 1, self.cursor.line + 1);
+            let current = self.lines.remove(self.cursor.line).unwrap_or_default();
+            self.cursor.line -= 1;
+            let previous = &mut self.lines[self.cursor.line];
+            self.cursor.column = previous.len();
+            previous.push_str(&current);
+            return Ok(());
         }
+
+        // Получаем текущую строку
+        self.touch(self.cursor.line, self.cursor.line + 1);
+        let line = &mut self.lines[self.cursor.line];
+
+        // Ищем начало символа перед курсором (символ может занимать несколько байт)
+        let previous = match line[..self.cursor.column].char_indices().next_back() {
+            Some((index, _)) => index,
+            None => return Err("Курсор в начале строки".to_string()),
+        };
         
         // Удаляем символ
-        line.remove(self.cursor.column - 1);
+        line.remove(previous);
+        self.revision += 1;
         
         // Обновляем позицию курсора
-        self.cursor.column -= 1;
+        self.cursor.column = previous;
         
         Ok(())
     }
@@ -183,7 +290,13 @@ impl Buffer {
     // Вставляем новую строку
     // Возвращает Ok(()) если вставка успешна
     // Возвращает ошибку если что-то пошло не так
+    // Новая строка получает отступ текущей, а после открывающего символа - на уровень больше
+    // Если курсор стоит между скобками, закрывающая уходит на свою строку
     pub fn insert_newline(&mut self) -> Result<(), String> {
+        if self.selection.is_some() {
+            self.delete_selection()?;
+        }
+
         // Проверяем что курсор в пределах буфера
         if self.cursor.line >= self.lines.len() {
             return Err("Курсор за пределами буфера".to_string());
@@ -197,13 +310,323 @@ impl Buffer {
             return Err("Курсор за пределами строки".to_string());
         }
 
-        // Проверяем что буфер не переполнен
-        if self.lines.len() >= usize::MAX {
-            return Err("Буфер переполнен".to_string());
+        // В отступе или в начале строки строка просто разбивается
+        let (before, after) = line.split_at(self.cursor.column);
+        if before.trim().is_empty() {
+            return self.insert_char('\n');
+        }
+
+        let indent = before[..before.len() - before.trim_start().len()].to_string();
+        let before = before.trim_end();
+        let after = after.trim_start();
+        let last = before.chars().next_back();
+        let opens = last.is_some_and(|c| self.indent_rules.openers.contains(&c));
+        let inner = if opens { format!("{}{}", indent, self.indent_unit()) } else { indent.clone() };
+        let closes = opens
+            && self.indent_rules.brackets.iter().any(|&(open, close)| Some(open) == last && after.starts_with(close));
+
+        let mut new_lines = vec![before.to_string()];
+        if closes {
+            new_lines.push(inner.clone());
+            new_lines.push(format!("{}{}", indent, after));
+        } else {
+            new_lines.push(format!("{}{}", inner, after));
+        }
+        let line = self.cursor.line;
+        self.touch(line, line + 1);
+        self.lines.remove(line);
+        for (offset, text) in new_lines.into_iter().enumerate() {
+            self.lines.insert(line + offset, text);
+        }
+        self.cursor = Position { line: line + 1, column: inner.len() };
+        self.revision += 1;
+        Ok(())
+    }
+
+    // Набранный символ с автоотступом и парными символами:
+    // - открывающий символ пары вокруг выделения обрамляет его
+    // - закрывающий символ перед таким же символом перешагивает через него
+    // - закрывающая скобка в начале строки сдвигается на уровень влево
+    // - к открывающему символу добавляется закрывающий, курсор остается между ними
+    pub fn type_char(&mut self, c: char) -> Result<(), String> {
+        let close = self.auto_pairs.iter().find(|&&(open, _)| open == c).map(|&(_, close)| close);
+        if let (Some(close), Some((start, end))) = (close, self.selection) {
+            if start != end {
+                return self.wrap_selection(c, close);
+            }
+        }
+        if self.selection.is_some() || self.cursor.line >= self.lines.len() {
+            return self.insert_char(c);
+        }
+
+        let line = &self.lines[self.cursor.line];
+        let column = self.cursor.column.min(line.len());
+        let previous = line[..column].chars().next_back();
+        let next = line[column..].chars().next();
+        if next == Some(c) && self.auto_pairs.iter().any(|&(_, close)| close == c) {
+            self.cursor.column = column + c.len_utf8();
+            return Ok(());
         }
 
-        // Вставляем символ новой строки
-        self.insert_char('\n')
+        if self.indent_rules.brackets.iter().any(|&(_, close)| close == c) {
+            self.dedent_closer();
+        }
+
+        let Some(close) = close else { return self.insert_char(c) };
+        // Кавычка внутри слова - апостроф; скобка перед словом его не обрамляет
+        let pair = if close == c {
+            !previous.is_some_and(char::is_alphanumeric) && !next.is_some_and(char::is_alphanumeric)
+        } else {
+            next.is_none_or(|next| next.is_whitespace() || self.auto_pairs.iter().any(|&(_, close)| close == next))
+        };
+        self.insert_char(c)?;
+        if pair {
+            self.touch(self.cursor.line, self.cursor.line + 1);
+            self.lines[self.cursor.line].insert(self.cursor.column, close);
+        }
+        Ok(())
+    }
+
+    // Между открывающим и закрывающим символом пары ничего нет (Backspace удалит оба)
+    pub fn empty_pair_at_cursor(&self) -> bool {
+        let Some(line) = self.lines.get(self.cursor.line) else { return false };
+        let Some((before, after)) = line.split_at_checked(self.cursor.column) else { return false };
+        match (before.chars().next_back(), after.chars().next()) {
+            (Some(open), Some(close)) => self.auto_pairs.contains(&(open, close)),
+            _ => false,
+        }
+    }
+
+    // Обрамляет выделение парой символов; выделенным остается прежний текст
+    fn wrap_selection(&mut self, open: char, close: char) -> Result<(), String> {
+        let Some((anchor, head)) = self.selection else { return Ok(()) };
+        let (start, end) = if anchor <= head { (anchor, head) } else { (head, anchor) };
+        if end.line >= self.lines.len() || end.column > self.lines[end.line].len() {
+            return Err("Выделение за пределами буфера".to_string());
+        }
+        self.touch(start.line, end.line + 1);
+        self.lines[end.line].insert(end.column, close);
+        self.lines[start.line].insert(start.column, open);
+        let shift = |position: Position| match position.line == start.line {
+            true => Position { line: position.line, column: position.column + open.len_utf8() },
+            false => position,
+        };
+        self.selection = Some((shift(anchor), shift(head)));
+        self.cursor = shift(self.cursor);
+        self.revision += 1;
+        Ok(())
+    }
+
+    // Закрывающая скобка в начале строки: строка сдвигается на уровень влево
+    fn dedent_closer(&mut self) {
+        let width = self.tabs.width;
+        let line = &self.lines[self.cursor.line];
+        let column = self.cursor.column.min(line.len());
+        let before = &line[..column];
+        if before.is_empty() || !before.trim_start().is_empty() {
+            return;
+        }
+        let remove = if before.ends_with('\t') {
+            1
+        } else {
+            (before.len() - before.trim_end_matches(' ').len()).min(width)
+        };
+        self.touch(self.cursor.line, self.cursor.line + 1);
+        self.lines[self.cursor.line].replace_range(column - remove..column, "");
+        self.cursor.column = column - remove;
+        self.revision += 1;
+    }
+
+    // Сдвигает строки выделения (без выделения - строку курсора) на уровень отступа
+    // Строка, в начале которой кончается выделение, не сдвигается
+    pub fn indent_lines(&mut self, unindent: bool) -> Result<(), String> {
+        let (first, last) = match self.selection {
+            Some((start, end)) => {
+                let (start, end) = if start <= end { (start, end) } else { (end, start) };
+                let last = if end.line > start.line && end.column == 0 { end.line - 1 } else { end.line };
+                (start.line, last)
+            }
+            None => (self.cursor.line, self.cursor.line),
+        };
+        if last >= self.lines.len() {
+            return Err("Строка за пределами буфера".to_string());
+        }
+
+        let unit = self.indent_unit();
+        let width = self.tabs.width;
+        self.touch(first, last + 1);
+        let mut shifts = Vec::new(); // (строка, сдвиг столбцов)
+        for index in first..=last {
+            let line = &mut self.lines[index];
+            if unindent {
+                let remove = if line.starts_with('\t') {
+                    1
+                } else {
+                    let spaces = line.len() - line.trim_start_matches(' ').len();
+                    match spaces % width {
+                        0 => width,
+                        rest => rest,
+                    }
+                    .min(spaces)
+                };
+                if remove > 0 {
+                    line.replace_range(..remove, "");
+                    shifts.push((index, -(remove as isize)));
+                }
+            } else if !line.is_empty() {
+                line.insert_str(0, &unit);
+                shifts.push((index, unit.len() as isize));
+            }
+        }
+        if shifts.is_empty() {
+            return Ok(());
+        }
+        self.revision += 1;
+
+        // Курсор и границы выделения сдвигаются вместе с текстом; начало строки остается началом
+        let shift = |position: Position| match shifts.iter().find(|(line, _)| *line == position.line) {
+            Some(&(_, offset)) if position.column > 0 => {
+                Position { line: position.line, column: position.column.saturating_add_signed(offset) }
+            }
+            _ => position,
+        };
+        self.cursor = shift(self.cursor);
+        self.selection = self.selection.map(|(start, end)| (shift(start), shift(end)));
+        Ok(())
+    }
+
+    // Один уровень отступа
+    fn indent_unit(&self) -> String {
+        if self.tabs.use_tabs {
+            "\t".to_string()
+        } else {
+            " ".repeat(self.tabs.width)
+        }
+    }
+
+    // Символы от позиции from (включительно) до конца буфера
+    pub fn chars_forward(&self, from: Position) -> impl Iterator<Item = (Position, char)> + '_ {
+        (from.line..self.lines.len()).flat_map(move |line| {
+            let start = if line == from.line { from.column } else { 0 };
+            let text = self.lines[line].get(start..).unwrap_or_default();
+            text.char_indices().map(move |(offset, c)| (Position { line, column: start + offset }, c))
+        })
+    }
+
+    // Символы перед позицией from до начала буфера, от ближнего к дальнему
+    pub fn chars_backward(&self, from: Position) -> impl Iterator<Item = (Position, char)> + '_ {
+        (0..=from.line.min(self.lines.len().saturating_sub(1))).rev().flat_map(move |line| {
+            let text = &self.lines[line];
+            let end = if line == from.line { from.column.min(text.len()) } else { text.len() };
+            let text = text.get(..end).unwrap_or_default();
+            text.char_indices().rev().map(move |(column, c)| (Position { line, column }, c))
+        })
+    }
+
+    // Отрезки строк и комментариев из подсветки для правки revision
+    // Пока подсветка не догнала правку, скобки считаются везде
+    pub fn set_literals(&mut self, revision: u64, literals: &[Vec<(usize, usize)>]) {
+        if revision != self.revision || self.literals_revision == Some(revision) {
+            return;
+        }
+        self.literals = literals.to_vec();
+        self.literals_revision = Some(revision);
+    }
+
+    // У языка нет грамматики: строк и комментариев не видно
+    pub fn clear_literals(&mut self) {
+        self.literals.clear();
+        self.literals_revision = None;
+    }
+
+    // Известно ли, где в тексте строки и комментарии
+    pub fn has_literals(&self) -> bool {
+        self.literals_revision == Some(self.revision)
+    }
+
+    // Позиция внутри строки или комментария
+    pub fn is_literal(&self, position: Position) -> bool {
+        self.has_literals()
+            && self.literals.get(position.line).is_some_and(|ranges| {
+                ranges.iter().any(|&(start, end)| start <= position.column && position.column < end)
+            })
+    }
+
+    // Непарная скобка пары (open, close): вперед от from - закрывающая, назад от from - открывающая
+    // Вложенные пары пропускаются, скобки в строках и комментариях не считаются
+    pub fn unmatched_bracket(&self, from: Position, (open, close): (char, char), forward: bool) -> Option<Position> {
+        let (enter, leave) = if forward { (open, close) } else { (close, open) };
+        let mut depth = 0usize;
+        let chars: Box<dyn Iterator<Item = (Position, char)>> = match forward {
+            true => Box::new(self.chars_forward(from)),
+            false => Box::new(self.chars_backward(from)),
+        };
+        for (position, c) in chars.filter(|&(position, c)| (c == enter || c == leave) && !self.is_literal(position)) {
+            if c == enter {
+                depth += 1;
+            } else if depth == 0 {
+                return Some(position);
+            } else {
+                depth -= 1;
+            }
+        }
+        None
+    }
+
+    // Парная скобка для скобки в позиции at
+    pub fn matching_bracket(&self, at: Position) -> Option<Position> {
+        let c = self.lines.get(at.line)?.get(at.column..)?.chars().next()?;
+        if self.is_literal(at) {
+            return None;
+        }
+        BRACKETS.iter().find_map(|&pair| {
+            if c == pair.0 {
+                self.unmatched_bracket(Position { line: at.line, column: at.column + c.len_utf8() }, pair, true)
+            } else if c == pair.1 {
+                self.unmatched_bracket(at, pair, false)
+            } else {
+                None
+            }
+        })
+    }
+
+    // Скобка у позиции и ее пара: сначала проверяется символ за позицией, потом перед ней
+    pub fn bracket_pair_near(&self, at: Position) -> Option<(Position, Position)> {
+        let line = self.lines.get(at.line)?;
+        let before = line.get(..at.column)?.char_indices().next_back().map(|(column, _)| Position { line: at.line, column });
+        [Some(at), before].into_iter().flatten().find_map(|bracket| Some((bracket, self.matching_bracket(bracket)?)))
+    }
+
+    // Ближайшая пара скобок вокруг позиции: открывающая и закрывающая скобки
+    pub fn enclosing_brackets(&self, at: Position) -> Option<(Position, Position)> {
+        BRACKETS
+            .iter()
+            .filter_map(|&pair| {
+                let open = self.unmatched_bracket(at, pair, false)?;
+                let close = self.unmatched_bracket(at, pair, true)?;
+                Some((open, close))
+            })
+            .max_by_key(|&(open, _)| open)
+    }
+
+    // Скобки каждой строки с глубиной вложенности (0 - внешние) для раскраски пар
+    // Лишняя закрывающая скобка получает глубину 0
+    pub fn bracket_depths(&self) -> Vec<Vec<(usize, usize)>> {
+        let mut depth = 0usize;
+        let mut result = vec![Vec::new(); self.lines.len()];
+        for (position, c) in self.chars_forward(Position { line: 0, column: 0 }) {
+            if !BRACKETS.iter().any(|&(open, close)| c == open || c == close) || self.is_literal(position) {
+                continue;
+            }
+            if BRACKETS.iter().any(|&(open, _)| c == open) {
+                result[position.line].push((position.column, depth));
+                depth += 1;
+            } else {
+                depth = depth.saturating_sub(1);
+                result[position.line].push((position.column, depth));
+            }
+        }
+        result
     }
 
     // Получаем весь текст из буфера
@@ -215,18 +638,6 @@ impl Buffer {
             return Err("Буфер пуст".to_string());
         }
 
-        // Проверяем что есть хотя бы одна строка
-        if self.lines.len() == 0 {
-            return Err("Нет строк в буфере".to_string());
-        }
-
-        // Проверяем что все строки валидны
-        for (i, line) in self.lines.iter().enumerate() {
-            if line.is_empty() && i != self.lines.len() - 1 {
-                return Err("Обнаружена пустая строка в середине буфера".to_string());
-            }
-        }
-
         // Объединяем все строки с разделителем новой строки
         let text = self.lines
             .iter()
@@ -241,15 +652,8 @@ impl Buffer {
     // Возвращает Ok(()) если установка успешна
     // Возвращает ошибку если что-то пошло не так
     pub fn set_text(&mut self, text: String) -> Result<(), String> {
-        // Проверяем что текст не пустой
-        if text.is_empty() {
-            return Err("Текст не может быть пустым".to_string());
-        }
+        // Пустой текст - это одна пустая строка (новый документ)
 
-        // Проверяем что текст не слишком большой
-        if text.len() > usize::MAX {
-            return Err("Текст слишком большой".to_string());
-        }
         // Проверяем что текст содержит только валидные символы
         if text.chars().any(|c| c.is_control() && c != '\n' && c != '\t' && c != '\r') {
             return Err("Текст содержит недопустимые управляющие символы".to_string());
@@ -262,17 +666,14 @@ impl Buffer {
             .map(|s| s.to_string())
             .collect();
 
-        //замена табуляции на пробелы
-        let lines: Vec<String> = lines
-            .iter()
-            .map(|s| s.replace('\t', "    "))
-            .collect();
-
-        //проверка на количество строк
-        if lines.len() > usize::MAX {
-            return Err("Слишком много строк".to_string());
-        }
+        //замена табуляции на пробелы, если в документе табуляция не используется
+        let lines: Vec<String> = if self.tabs.use_tabs {
+            lines
+        } else {
+            lines.iter().map(|s| expand_tabs(s, self.tabs.width)).collect()
+        };
 
+        self.touch(0, self.lines.len());
         //если нет строк, добавляем одну пустую
         if lines.is_empty() {
             self.lines.clear();
@@ -286,11 +687,14 @@ impl Buffer {
             }
         }
 
+        self.revision += 1;
+
         //сбрасываем позицию курсора в начало
         self.cursor = Position { line: 0, column: 0 };
 
-        //очищаем выделение
+        //очищаем выделение и дополнительные курсоры
         self.selection = None;
+        self.extra_cursors.clear();
 
         Ok(())
     }
@@ -303,6 +707,53 @@ impl Buffer {
         &self.lines
     }
 
+    // Номер изменения текста: если он не изменился, строки те же
+    // (по нему разметка на экране понимает, что пересчитывать нечего)
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    pub fn settings(&self) -> BufferSettings {
+        BufferSettings {
+            tabs: self.tabs,
+            indent_rules: self.indent_rules.clone(),
+            auto_pairs: self.auto_pairs.clone(),
+            comments: self.comments.clone(),
+        }
+    }
+
+    pub fn set_settings(&mut self, settings: BufferSettings) {
+        self.set_tab_settings(settings.tabs);
+        self.set_indent_rules(settings.indent_rules);
+        self.set_auto_pairs(settings.auto_pairs);
+        self.set_comment_tokens(settings.comments);
+    }
+
+    pub fn tab_settings(&self) -> TabSettings {
+        self.tabs
+    }
+
+    pub fn set_indent_rules(&mut self, rules: IndentRules) {
+        self.indent_rules = rules;
+    }
+
+    pub fn set_auto_pairs(&mut self, pairs: Vec<(char, char)>) {
+        self.auto_pairs = pairs;
+    }
+
+    pub fn comment_tokens(&self) -> &CommentTokens {
+        &self.comments
+    }
+
+    pub fn set_comment_tokens(&mut self, comments: CommentTokens) {
+        self.comments = comments;
+    }
+
+    // Меняет ввод табуляции; уже набранный текст не трогается
+    pub fn set_tab_settings(&mut self, tabs: TabSettings) {
+        self.tabs = TabSettings { width: tabs.width.max(1), ..tabs };
+    }
+
     pub fn get_current_line(&self) -> &str {
         if self.cursor.line < self.lines.len() {
             &self.lines[self.cursor.line]
@@ -375,6 +826,9 @@ impl Buffer {
     
     // Получает текст в указанном диапазоне
     pub fn get_text_in_range(&self, start: Position, end: Position) -> Result<String, String> {
+        // Диапазон может быть задан в обратном порядке
+        let (start, end) = if start <= end { (start, end) } else { (end, start) };
+
         // Проверяем что начальная позиция в пределах буфера
         if start.line >= self.lines.len() {
             return Err("Начальная позиция за пределами буфера".to_string());
@@ -431,11 +885,6 @@ impl Buffer {
             return Ok(());
         }
         
-        // Проверяем что текст не слишком большой
-        if text.len() > usize::MAX {
-            return Err("Текст слишком большой".to_string());
-        }
-        
         // Проверяем что текст содержит только валидные символы
         if text.chars().any(|c| c.is_control() && c != '\n' && c != '\t' && c != '\r') {
             return Err("Текст содержит недопустимые управляющие символы".to_string());
@@ -451,17 +900,453 @@ impl Buffer {
     
     // Очищает буфер
     pub fn clear(&mut self) -> Result<(), String> {
+        self.touch(0, self.lines.len());
         self.lines.clear();
         self.lines.push_back(String::new());
+        self.revision += 1;
         self.cursor = Position { line: 0, column: 0 };
         self.selection = None;
+        self.extra_cursors.clear();
         Ok(())
     }
     
-    // Добавляет строку в конец буфера
-    pub fn append_line(&mut self, line: String) -> Result<(), String> {
-        self.lines.push_back(line);
+    // Заменяет все строки буфера, курсор ставится в начало
+    pub fn set_lines(&mut self, lines: Vec<String>) -> Result<(), String> {
+        if lines.is_empty() {
+            return self.clear();
+        }
+        self.touch(0, self.lines.len());
+        self.lines = VecDeque::from(lines);
+        self.revision += 1;
+        self.cursor = Position { line: 0, column: 0 };
+        self.selection = None;
+        self.extra_cursors.clear();
+        Ok(())
+    }
+
+    // Заменяет count строк с first строками lines; курсоры не трогаются
+    // (построчные команды ставят их сами), в буфере остается хотя бы одна строка
+    pub(crate) fn splice_lines(&mut self, first: usize, count: usize, lines: Vec<String>) -> Result<(), String> {
+        if first + count > self.lines.len() {
+            return Err("Строка за пределами буфера".to_string());
+        }
+        self.touch(first, first + count);
+        if lines.len() == count {
+            // Число строк не меняется: хвост буфера не сдвигается
+            for (index, line) in lines.into_iter().enumerate() {
+                self.lines[first + index] = line;
+            }
+        } else {
+            let tail = self.lines.split_off(first + count);
+            self.lines.truncate(first);
+            self.lines.extend(lines);
+            self.lines.extend(tail);
+        }
+        if self.lines.is_empty() {
+            self.lines.push_back(String::new());
+        }
+        self.revision += 1;
+        Ok(())
+    }
+
+    // Строка для правки на месте; после правок вызывается touch_revision
+    pub(crate) fn line_mut(&mut self, line: usize) -> Option<&mut String> {
+        if line >= self.lines.len() {
+            return None;
+        }
+        self.touch(line, line + 1);
+        self.lines.get_mut(line)
+    }
+
+    // Отмечает правку, сделанную через line_mut
+    pub(crate) fn touch_revision(&mut self) {
+        self.revision += 1;
+    }
+
+    // Начинает запись изменений строк для истории отмены
+    pub(crate) fn start_recording(&mut self) {
+        self.recording = Some(Recording { span: None, total: self.lines.len() });
+    }
+
+    // Заканчивает запись; None - текст не изменился
+    pub(crate) fn finish_recording(&mut self) -> Option<LineChange> {
+        let recording = self.recording.take()?;
+        let (first, removed) = recording.span?;
+        let count = removed.len() + self.lines.len() - recording.total;
+        let inserted: Vec<String> = self.lines.range(first..first + count).cloned().collect();
+        (removed != inserted).then_some(LineChange { first, removed, inserted })
+    }
+
+    // Отменяет изменение (undo) или делает его снова
+    pub(crate) fn apply_change(&mut self, change: &LineChange, undo: bool) -> Result<(), String> {
+        let (count, lines) = match undo {
+            true => (change.inserted.len(), &change.removed),
+            false => (change.removed.len(), &change.inserted),
+        };
+        self.splice_lines(change.first, count, lines.clone())
+    }
+
+    // Перед правкой строк [first, end): во время записи отрезок изменений расширяется
+    // так, чтобы в нем были исходные тексты этих строк
+    fn touch(&mut self, first: usize, end: usize) {
+        let Some(recording) = self.recording.as_mut() else { return };
+        let end = end.min(self.lines.len());
+        let first = first.min(end);
+        match &mut recording.span {
+            None => recording.span = Some((first, self.lines.range(first..end).cloned().collect())),
+            Some((start, removed)) => {
+                // Текущий конец отрезка: его строки сдвинуты вставками и удалениями
+                let current_end = *start + removed.len() + self.lines.len() - recording.total;
+                if first < *start {
+                    removed.splice(0..0, self.lines.range(first..*start).cloned());
+                    *start = first;
+                }
+                if end > current_end {
+                    removed.extend(self.lines.range(current_end..end).cloned());
+                }
+            }
+        }
+    }
+
+    // Добавляет дополнительный курсор
+    pub fn add_cursor(&mut self, pos: Position) -> Result<(), String> {
+        // Проверяем что позиция в пределах буфера
+        if pos.line >= self.lines.len() || pos.column > self.lines[pos.line].len() {
+            return Err("Позиция курсора за пределами буфера".to_string());
+        }
+        if pos != self.cursor && !self.extra_cursors.contains(&pos) {
+            self.extra_cursors.push(pos);
+        }
+        Ok(())
+    }
+
+    // Убирает дополнительные курсоры, остается только основной
+    pub fn clear_extra_cursors(&mut self) {
+        self.extra_cursors.clear();
+    }
+
+    // Все курсоры (основной и дополнительные) сверху вниз
+    pub fn get_cursors(&self) -> Vec<Position> {
+        let mut cursors = self.extra_cursors.clone();
+        cursors.push(self.cursor);
+        cursors.sort();
+        cursors.dedup();
+        cursors
+    }
+
+    // Вставляет по одному тексту в каждый курсор (тексты идут сверху вниз)
+    // После вставки каждый курсор стоит в конце своего текста
+    pub fn insert_at_cursors(&mut self, texts: Vec<String>) -> Result<(), String> {
+        let cursors = self.get_cursors();
+        if texts.len() != cursors.len() {
+            return Err("Число фрагментов не совпадает с числом курсоров".to_string());
+        }
+        let main = self.cursor;
+        let mut new_main = main;
+        let mut moved = Vec::new();
+
+        // Вставки сдвигают следующие курсоры: по строкам и по столбцу в той же строке
+        let mut line_shift = 0usize;
+        let mut column_shift: Option<(usize, isize)> = None; // (исходная строка, сдвиг)
+        self.selection = None;
+        for (original, text) in cursors.into_iter().zip(texts) {
+            let mut pos = original;
+            if let Some((line, shift)) = column_shift {
+                if line == original.line {
+                    pos.column = (original.column as isize + shift) as usize;
+                }
+            }
+            pos.line += line_shift;
+            self.set_cursor_position(pos)?;
+            self.insert_text(text)?;
+            let end = self.cursor;
+            line_shift += end.line - pos.line;
+            column_shift = Some((original.line, end.column as isize - original.column as isize));
+            if original == main {
+                new_main = end;
+            } else {
+                moved.push(end);
+            }
+        }
+
+        self.cursor = new_main;
+        self.extra_cursors = moved;
         Ok(())
     }
 }
 
+// Столбец на экране после текста: табуляция доходит до следующей позиции
+fn display_column(text: &str, tab_width: usize) -> usize {
+    text.chars().fold(0, |column, c| if c == '\t' { column + tab_width - column % tab_width } else { column + 1 })
+}
+
+// Заменяет табуляцию пробелами до следующей позиции табуляции
+fn expand_tabs(text: &str, tab_width: usize) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut column = 0;
+    for c in text.chars() {
+        if c == '\t' {
+            let spaces = tab_width - column % tab_width;
+            result.push_str(&" ".repeat(spaces));
+            column += spaces;
+        } else {
+            result.push(c);
+            column += 1;
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::editor::fixtures::position;
+
+    // Буфер с правилами отступа и парными символами как у Rust, курсор в позиции at
+    fn rust(text: &str, tabs: TabSettings, at: Position) -> Buffer {
+        let mut buffer = Buffer::new();
+        buffer.set_tab_settings(tabs);
+        buffer.set_indent_rules(IndentRules { openers: vec!['{', '(', '['], brackets: BRACKETS.to_vec() });
+        buffer.set_auto_pairs(vec![('(', ')'), ('[', ']'), ('{', '}'), ('"', '"'), ('\'', '\'')]);
+        buffer.set_text(text.to_string()).unwrap();
+        buffer.set_cursor_position(at).unwrap();
+        buffer
+    }
+
+    const SPACES: TabSettings = TabSettings { width: 4, use_tabs: false };
+    const TABS: TabSettings = TabSettings { width: 4, use_tabs: true };
+
+    #[test]
+    fn newline_carries_indent() {
+        let mut buffer = rust("    let a = 1;", SPACES, position(0, 14));
+        buffer.insert_newline().unwrap();
+        assert_eq!(buffer.get_text().unwrap(), "    let a = 1;\n    ");
+        assert_eq!(buffer.get_cursor_position(), position(1, 4));
+
+        // Пробелы по обе стороны разрыва убираются
+        let mut buffer = rust("    a = 1;   b", SPACES, position(0, 11));
+        buffer.insert_newline().unwrap();
+        assert_eq!(buffer.get_text().unwrap(), "    a = 1;\n    b");
+        assert_eq!(buffer.get_cursor_position(), position(1, 4));
+
+        // В отступе строка разбивается как есть
+        let mut buffer = rust("    x", SPACES, position(0, 2));
+        buffer.insert_newline().unwrap();
+        assert_eq!(buffer.get_text().unwrap(), "  \n  x");
+        assert_eq!(buffer.get_cursor_position(), position(1, 0));
+    }
+
+    #[test]
+    fn newline_after_opener() {
+        let mut buffer = rust("fn main() {", SPACES, position(0, 11));
+        buffer.insert_newline().unwrap();
+        assert_eq!(buffer.get_text().unwrap(), "fn main() {\n    ");
+        assert_eq!(buffer.get_cursor_position(), position(1, 4));
+
+        // Уровень отступа - табуляция, если она включена
+        let mut buffer = rust("\tif x {", TABS, position(0, 7));
+        buffer.insert_newline().unwrap();
+        assert_eq!(buffer.get_text().unwrap(), "\tif x {\n\t\t");
+        assert_eq!(buffer.get_cursor_position(), position(1, 2));
+
+        // Открывающий символ не в конце строки отступ не меняет
+        let mut buffer = rust("f(a, b", SPACES, position(0, 4));
+        buffer.insert_newline().unwrap();
+        assert_eq!(buffer.get_text().unwrap(), "f(a,\nb");
+    }
+
+    #[test]
+    fn newline_splits_brackets() {
+        let mut buffer = rust("    call()", SPACES, position(0, 9));
+        buffer.insert_newline().unwrap();
+        assert_eq!(buffer.get_text().unwrap(), "    call(\n        \n    )");
+        assert_eq!(buffer.get_cursor_position(), position(1, 8));
+
+        // Закрывающая скобка другой пары остается на строке курсора
+        let mut buffer = rust("[)", SPACES, position(0, 1));
+        buffer.insert_newline().unwrap();
+        assert_eq!(buffer.get_text().unwrap(), "[\n    )");
+    }
+
+    #[test]
+    fn closer_dedents_line() {
+        let mut buffer = rust("fn f() {\n        ", SPACES, position(1, 8));
+        buffer.type_char('}').unwrap();
+        assert_eq!(buffer.get_text().unwrap(), "fn f() {\n    }");
+        assert_eq!(buffer.get_cursor_position(), position(1, 5));
+
+        // Отступ не кратен уровню: удаляются только лишние пробелы до уровня
+        let mut buffer = rust("  ", SPACES, position(0, 2));
+        buffer.type_char(')').unwrap();
+        assert_eq!(buffer.get_text().unwrap(), ")");
+
+        let mut buffer = rust("\t\t", TABS, position(0, 2));
+        buffer.type_char(']').unwrap();
+        assert_eq!(buffer.get_text().unwrap(), "\t]");
+
+        // После текста скобка просто вставляется
+        let mut buffer = rust("x   ", SPACES, position(0, 4));
+        buffer.type_char('}').unwrap();
+        assert_eq!(buffer.get_text().unwrap(), "x   }");
+    }
+
+    #[test]
+    fn indent_lines_shift_selection() {
+        // Пустые строки не получают отступ, начало строки остается началом
+        let mut buffer = rust("a\n\nb", SPACES, position(2, 1));
+        buffer.set_selection(position(0, 0), position(2, 1)).unwrap();
+        buffer.indent_lines(false).unwrap();
+        assert_eq!(buffer.get_text().unwrap(), "    a\n\n    b");
+        assert_eq!(buffer.get_selection(), Some((position(0, 0), position(2, 5))));
+        assert_eq!(buffer.get_cursor_position(), position(2, 5));
+        buffer.indent_lines(true).unwrap();
+        assert_eq!(buffer.get_text().unwrap(), "a\n\nb");
+        assert_eq!(buffer.get_selection(), Some((position(0, 0), position(2, 1))));
+
+        // Строка, в начале которой кончается выделение, не сдвигается
+        let mut buffer = rust("a\nb", TABS, position(1, 0));
+        buffer.set_selection(position(0, 0), position(1, 0)).unwrap();
+        buffer.indent_lines(false).unwrap();
+        assert_eq!(buffer.get_text().unwrap(), "\ta\nb");
+    }
+
+    #[test]
+    fn unindent_to_tab_stop() {
+        let mut buffer = rust("      x", SPACES, position(0, 7));
+        buffer.indent_lines(true).unwrap();
+        assert_eq!(buffer.get_text().unwrap(), "    x");
+        assert_eq!(buffer.get_cursor_position(), position(0, 5));
+        buffer.indent_lines(true).unwrap();
+        assert_eq!(buffer.get_text().unwrap(), "x");
+        let revision = buffer.revision();
+        buffer.indent_lines(true).unwrap();
+        assert_eq!(buffer.revision(), revision);
+
+        let mut buffer = rust("\t\tx", TABS, position(0, 0));
+        buffer.indent_lines(true).unwrap();
+        assert_eq!(buffer.get_text().unwrap(), "\tx");
+    }
+
+    // Набирает символы по одному, как с клавиатуры
+    fn typed(mut buffer: Buffer, chars: &str) -> Buffer {
+        for c in chars.chars() {
+            buffer.type_char(c).unwrap();
+        }
+        buffer
+    }
+
+    #[test]
+    fn auto_pairs_and_overtype() {
+        let buffer = typed(rust("", SPACES, position(0, 0)), "f(");
+        assert_eq!(buffer.get_text().unwrap(), "f()");
+        assert_eq!(buffer.get_cursor_position(), position(0, 2));
+        assert!(buffer.empty_pair_at_cursor());
+
+        // Закрывающий символ перешагивает через уже вставленный
+        let buffer = typed(buffer, "x)");
+        assert_eq!(buffer.get_text().unwrap(), "f(x)");
+        assert_eq!(buffer.get_cursor_position(), position(0, 4));
+
+        let buffer = typed(rust("", SPACES, position(0, 0)), "[\"a\"]");
+        assert_eq!(buffer.get_text().unwrap(), "[\"a\"]");
+        assert_eq!(buffer.get_cursor_position(), position(0, 5));
+
+        // Перед словом скобка не получает пары, перед закрывающей - получает
+        let buffer = typed(rust("word", SPACES, position(0, 0)), "(");
+        assert_eq!(buffer.get_text().unwrap(), "(word");
+        let buffer = typed(rust("()", SPACES, position(0, 1)), "[");
+        assert_eq!(buffer.get_text().unwrap(), "([])");
+    }
+
+    #[test]
+    fn quotes_inside_words() {
+        // Кавычка после буквы - апостроф, пары нет
+        let buffer = typed(rust("", SPACES, position(0, 0)), "don't");
+        assert_eq!(buffer.get_text().unwrap(), "don't");
+        let buffer = typed(rust("s", SPACES, position(0, 0)), "'");
+        assert_eq!(buffer.get_text().unwrap(), "'s");
+        let buffer = typed(rust("", SPACES, position(0, 0)), "x = '");
+        assert_eq!(buffer.get_text().unwrap(), "x = ''");
+        assert_eq!(buffer.get_cursor_position(), position(0, 5));
+    }
+
+    #[test]
+    fn pair_wraps_selection() {
+        let mut buffer = rust("a word b", SPACES, position(0, 6));
+        buffer.set_selection(position(0, 2), position(0, 6)).unwrap();
+        buffer.type_char('(').unwrap();
+        assert_eq!(buffer.get_text().unwrap(), "a (word) b");
+        assert_eq!(buffer.get_selection(), Some((position(0, 3), position(0, 7))));
+        assert_eq!(buffer.get_cursor_position(), position(0, 7));
+        // Выделение остается, поэтому его можно обрамить еще раз
+        buffer.type_char('"').unwrap();
+        assert_eq!(buffer.get_text().unwrap(), "a (\"word\") b");
+
+        // Многострочное выделение: сдвигается только начало на первой строке
+        let mut buffer = rust("ab\ncd", SPACES, position(1, 1));
+        buffer.set_selection(position(1, 1), position(0, 1)).unwrap();
+        buffer.type_char('[').unwrap();
+        assert_eq!(buffer.get_text().unwrap(), "a[b\nc]d");
+        assert_eq!(buffer.get_selection(), Some((position(1, 1), position(0, 2))));
+
+        // Символ без пары заменяет выделение
+        let mut buffer = rust("abc", SPACES, position(0, 2));
+        buffer.set_selection(position(0, 1), position(0, 2)).unwrap();
+        buffer.type_char('x').unwrap();
+        assert_eq!(buffer.get_text().unwrap(), "axc");
+    }
+
+    #[test]
+    fn matching_brackets() {
+        let buffer = rust("f(a[0], {b})", SPACES, position(0, 0));
+        assert_eq!(buffer.matching_bracket(position(0, 1)), Some(position(0, 11)));
+        assert_eq!(buffer.matching_bracket(position(0, 11)), Some(position(0, 1)));
+        assert_eq!(buffer.matching_bracket(position(0, 3)), Some(position(0, 5)));
+        assert_eq!(buffer.matching_bracket(position(0, 8)), Some(position(0, 10)));
+        assert_eq!(buffer.matching_bracket(position(0, 2)), None);
+
+        let buffer = rust("{\n  x(1)\n}", SPACES, position(0, 0));
+        assert_eq!(buffer.matching_bracket(position(0, 0)), Some(position(2, 0)));
+        assert_eq!(buffer.matching_bracket(position(2, 0)), Some(position(0, 0)));
+        assert_eq!(rust("(()", SPACES, position(0, 0)).matching_bracket(position(0, 0)), None);
+
+        // Скобка ищется за позицией, затем перед ней
+        let buffer = rust("(x)", SPACES, position(0, 0));
+        assert_eq!(buffer.bracket_pair_near(position(0, 0)), Some((position(0, 0), position(0, 2))));
+        assert_eq!(buffer.bracket_pair_near(position(0, 3)), Some((position(0, 2), position(0, 0))));
+        assert_eq!(buffer.bracket_pair_near(position(0, 1)), Some((position(0, 0), position(0, 2))));
+    }
+
+    #[test]
+    fn enclosing_pairs() {
+        let buffer = rust("f(a[0], {b})", SPACES, position(0, 0));
+        assert_eq!(buffer.enclosing_brackets(position(0, 9)), Some((position(0, 8), position(0, 10))));
+        assert_eq!(buffer.enclosing_brackets(position(0, 4)), Some((position(0, 3), position(0, 5))));
+        assert_eq!(buffer.enclosing_brackets(position(0, 7)), Some((position(0, 1), position(0, 11))));
+        assert_eq!(buffer.enclosing_brackets(position(0, 0)), None);
+    }
+
+    #[test]
+    fn rainbow_depths() {
+        let buffer = rust("f(a[0], {b})\n)(", SPACES, position(0, 0));
+        // Лишняя закрывающая скобка получает глубину 0
+        assert_eq!(buffer.bracket_depths(), [vec![(1, 0), (3, 1), (5, 1), (8, 1), (10, 1), (11, 0)], vec![(0, 0), (1, 0)]]);
+    }
+
+    #[test]
+    fn literals_are_skipped() {
+        let mut buffer = rust("f(\"(\", x)", SPACES, position(0, 0));
+        assert_eq!(buffer.matching_bracket(position(0, 1)), None);
+
+        buffer.set_literals(buffer.revision(), &[vec![(2, 5)]]);
+        assert_eq!(buffer.matching_bracket(position(0, 1)), Some(position(0, 8)));
+        assert_eq!(buffer.matching_bracket(position(0, 3)), None);
+        assert_eq!(buffer.bracket_depths(), [vec![(1, 0), (8, 0)]]);
+
+        // Отрезки от прежней правки не действуют, пока подсветка не догонит текст
+        buffer.type_char(' ').unwrap();
+        assert!(!buffer.has_literals());
+        buffer.set_literals(buffer.revision() - 1, &[vec![(2, 5)]]);
+        assert!(!buffer.has_literals());
+    }
+}
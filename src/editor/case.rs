@@ -0,0 +1,296 @@
+// Смена регистра и стиля имен: ВЕРХНИЙ, нижний, Каждое Слово, Как в предложении,
+// snake_case, camelCase, PascalCase, kebab-case и CONSTANT_CASE.
+// Команда меняет выделенный текст, а без выделения - слово под каждым курсором.
+// Регистр меняется по правилам Unicode: кириллица, ß -> SS, конечная сигма
+
+use serde::{Deserialize, Serialize};
+
+use crate::editor::{Buffer, Position};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CaseStyle {
+    Upper,    // ВЕРХНИЙ РЕГИСТР
+    Lower,    // нижний регистр
+    Title,    // Каждое Слово С Заглавной
+    Sentence, // Первое слово предложения с заглавной
+    Snake,    // snake_case
+    Camel,    // camelCase
+    Pascal,   // PascalCase
+    Kebab,    // kebab-case
+    Constant, // CONSTANT_CASE
+    Swap,     // сМЕНА рЕГИСТРА каждой буквы
+}
+
+impl CaseStyle {
+    pub const ALL: [CaseStyle; 10] = [
+        CaseStyle::Upper,
+        CaseStyle::Lower,
+        CaseStyle::Title,
+        CaseStyle::Sentence,
+        CaseStyle::Swap,
+        CaseStyle::Snake,
+        CaseStyle::Camel,
+        CaseStyle::Pascal,
+        CaseStyle::Kebab,
+        CaseStyle::Constant,
+    ];
+
+    // Имя в тексте макроса
+    pub fn name(&self) -> &'static str {
+        match self {
+            CaseStyle::Upper => "upper",
+            CaseStyle::Lower => "lower",
+            CaseStyle::Title => "title",
+            CaseStyle::Sentence => "sentence",
+            CaseStyle::Snake => "snake",
+            CaseStyle::Camel => "camel",
+            CaseStyle::Pascal => "pascal",
+            CaseStyle::Kebab => "kebab",
+            CaseStyle::Constant => "constant",
+            CaseStyle::Swap => "swap",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|style| style.name() == name)
+    }
+
+    // Название для меню
+    pub fn title(&self) -> &'static str {
+        match self {
+            CaseStyle::Upper => "ВЕРХНИЙ РЕГИСТР",
+            CaseStyle::Lower => "нижний регистр",
+            CaseStyle::Title => "Каждое Слово С Заглавной",
+            CaseStyle::Sentence => "Как в предложении",
+            CaseStyle::Snake => "snake_case",
+            CaseStyle::Camel => "camelCase",
+            CaseStyle::Pascal => "PascalCase",
+            CaseStyle::Kebab => "kebab-case",
+            CaseStyle::Constant => "CONSTANT_CASE",
+            CaseStyle::Swap => "сМЕНИТЬ рЕГИСТР",
+        }
+    }
+
+    // Текст в этом стиле; переводы строк сохраняются
+    pub fn convert(&self, text: &str) -> String {
+        match self {
+            CaseStyle::Upper => text.to_uppercase(),
+            CaseStyle::Lower => text.to_lowercase(),
+            CaseStyle::Title => title_case(text),
+            CaseStyle::Sentence => sentence_case(text),
+            CaseStyle::Swap => text
+                .chars()
+                .map(|c| match c {
+                    c if c.is_lowercase() => c.to_uppercase().collect::<String>(),
+                    c if c.is_uppercase() => c.to_lowercase().collect::<String>(),
+                    c => c.to_string(),
+                })
+                .collect(),
+            // Стили имен применяются к каждой строке отдельно, отступ и пробелы в конце остаются
+            _ => text
+                .split('\n')
+                .map(|line| {
+                    let inner = line.trim();
+                    let lead = line.len() - line.trim_start().len();
+                    format!("{}{}{}", &line[..lead], self.identifier(inner), &line[lead + inner.len()..])
+                })
+                .collect::<Vec<_>>()
+                .join("\n"),
+        }
+    }
+
+    // Имя из слов текста: fooBar, foo_bar, foo-bar и "foo bar" дают одни и те же слова
+    fn identifier(&self, text: &str) -> String {
+        let words = split_words(text);
+        match self {
+            CaseStyle::Snake => words.iter().map(|word| word.to_lowercase()).collect::<Vec<_>>().join("_"),
+            CaseStyle::Kebab => words.iter().map(|word| word.to_lowercase()).collect::<Vec<_>>().join("-"),
+            CaseStyle::Constant => words.iter().map(|word| word.to_uppercase()).collect::<Vec<_>>().join("_"),
+            CaseStyle::Pascal => words.iter().map(|word| capitalize(word)).collect(),
+            CaseStyle::Camel => words
+                .iter()
+                .enumerate()
+                .map(|(i, word)| if i == 0 { word.to_lowercase() } else { capitalize(word) })
+                .collect(),
+            _ => self.convert(text),
+        }
+    }
+}
+
+impl Buffer {
+    // Меняет регистр выделения или слов под курсорами
+    pub fn convert_case(&mut self, style: CaseStyle) -> Result<(), String> {
+        let mut ranges = self.case_ranges();
+        let mut marks = self.marks();
+        // Снизу вверх: правка не сдвигает еще не обработанные отрезки
+        ranges.sort();
+        ranges.dedup();
+        for &(start, end) in ranges.iter().rev() {
+            let text = self.get_text_in_range(start, end)?;
+            let converted = style.convert(&text);
+            // Число строк не меняется, поэтому каждая строка правится на своем месте
+            for (i, (old, new)) in text.split('\n').zip(converted.split('\n')).enumerate() {
+                let column = if i == 0 { start.column } else { 0 };
+                if old != new {
+                    self.edit_line(&mut marks, Position { line: start.line + i, column }, old.len(), new, false)?;
+                }
+            }
+        }
+        self.restore_marks(marks, |position| position)
+    }
+
+    // Выделение, а без него - слова под курсорами
+    fn case_ranges(&self) -> Vec<(Position, Position)> {
+        if let Some((start, end)) = self.get_selection().filter(|(start, end)| start != end) {
+            return vec![(start.min(end), start.max(end))];
+        }
+        self.get_cursors().into_iter().filter_map(|position| self.identifier_at(position)).collect()
+    }
+
+    // Границы имени под позицией или сразу перед ней
+    // Дефис входит в имя, только если с обеих сторон от него буквы или цифры
+    fn identifier_at(&self, position: Position) -> Option<(Position, Position)> {
+        let chars: Vec<(usize, char)> = self.get_all_lines().get(position.line)?.char_indices().collect();
+        let word = |i: usize| {
+            let c = chars[i].1;
+            c.is_alphanumeric()
+                || c == '_'
+                || (c == '-' && i > 0 && i + 1 < chars.len() && chars[i - 1].1.is_alphanumeric() && chars[i + 1].1.is_alphanumeric())
+        };
+        let at = chars.iter().position(|&(column, _)| column >= position.column).unwrap_or(chars.len());
+        let at = match at {
+            at if at < chars.len() && word(at) => at,
+            at if at > 0 && word(at - 1) => at - 1,
+            _ => return None,
+        };
+        let mut first = at;
+        while first > 0 && word(first - 1) {
+            first -= 1;
+        }
+        let mut last = at;
+        while last + 1 < chars.len() && word(last + 1) {
+            last += 1;
+        }
+        let end = chars[last].0 + chars[last].1.len_utf8();
+        Some((Position { line: position.line, column: chars[first].0 }, Position { line: position.line, column: end }))
+    }
+}
+
+// Слова имени: разделители - все, кроме букв и цифр, а также переход
+// от строчной буквы или цифры к заглавной (fooBar) и от аббревиатуры к слову (HTTPServer)
+fn split_words(text: &str) -> Vec<String> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut words = Vec::new();
+    let mut word = String::new();
+    for (i, &c) in chars.iter().enumerate() {
+        if !c.is_alphanumeric() {
+            if !word.is_empty() {
+                words.push(std::mem::take(&mut word));
+            }
+            continue;
+        }
+        if c.is_uppercase() && i > 0 && !word.is_empty() {
+            let previous = chars[i - 1];
+            let next_lower = chars.get(i + 1).is_some_and(|next| next.is_lowercase());
+            if previous.is_lowercase() || previous.is_numeric() || (previous.is_uppercase() && next_lower) {
+                words.push(std::mem::take(&mut word));
+            }
+        }
+        word.push(c);
+    }
+    if !word.is_empty() {
+        words.push(word);
+    }
+    words
+}
+
+// Первая буква заглавная, остальные строчные
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars.as_str().to_lowercase().chars()).collect(),
+        None => String::new(),
+    }
+}
+
+// Каждое слово с заглавной; апостроф не начинает нового слова (Don't)
+fn title_case(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut previous = ' ';
+    for c in text.chars() {
+        if c.is_alphanumeric() && !previous.is_alphanumeric() && previous != '\'' && previous != '’' {
+            result.extend(c.to_uppercase());
+        } else {
+            result.extend(c.to_lowercase());
+        }
+        previous = c;
+    }
+    result
+}
+
+// Все строчными, кроме первой буквы текста и первых букв после . ! ? и пробела
+fn sentence_case(text: &str) -> String {
+    let lower = text.to_lowercase();
+    let mut result = String::with_capacity(lower.len());
+    let mut start = true;
+    let mut previous = ' ';
+    for c in lower.chars() {
+        if start && c.is_alphanumeric() {
+            result.extend(c.to_uppercase());
+            start = false;
+        } else {
+            result.push(c);
+        }
+        if c.is_whitespace() && matches!(previous, '.' | '!' | '?') {
+            start = true;
+        }
+        previous = c;
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_identifiers() {
+        assert_eq!(split_words("HTTPServer"), ["HTTP", "Server"]);
+        assert_eq!(split_words("fooBar2"), ["foo", "Bar2"]);
+        assert_eq!(split_words("foo2Bar"), ["foo2", "Bar"]);
+        assert_eq!(split_words("parseXMLFile_v2"), ["parse", "XML", "File", "v2"]);
+        assert_eq!(split_words("  привет-мирДом "), ["привет", "мир", "Дом"]);
+        assert!(split_words("-_ ").is_empty());
+    }
+
+    #[test]
+    fn styles() {
+        assert_eq!(CaseStyle::Snake.convert("HTTPServer"), "http_server");
+        assert_eq!(CaseStyle::Camel.convert("foo_bar_baz"), "fooBarBaz");
+        assert_eq!(CaseStyle::Pascal.convert("foo-bar"), "FooBar");
+        assert_eq!(CaseStyle::Kebab.convert("  fooBar2\n\tXMLHttp "), "  foo-bar2\n\txml-http ");
+        assert_eq!(CaseStyle::Constant.convert("максимум строк"), "МАКСИМУМ_СТРОК");
+        assert_eq!(CaseStyle::Title.convert("don't STOP me"), "Don't Stop Me");
+        assert_eq!(CaseStyle::Sentence.convert("ОДИН. два? три"), "Один. Два? Три");
+        assert_eq!(CaseStyle::Swap.convert("aБв1"), "AбВ1");
+        for style in CaseStyle::ALL {
+            assert_eq!(CaseStyle::from_name(style.name()), Some(style));
+        }
+    }
+
+    #[test]
+    fn unicode_case() {
+        assert_eq!(CaseStyle::Upper.convert("straße"), "STRASSE");
+        assert_eq!(CaseStyle::Lower.convert("ΟΔΟΣ"), "οδος");
+        assert_eq!(CaseStyle::Constant.convert("weißBier"), "WEISS_BIER");
+
+        // Слово под курсором становится длиннее, курсор остается в нем
+        let mut buffer = Buffer::new();
+        buffer.set_text("x straße y".to_string()).unwrap();
+        buffer.set_cursor_position(Position { line: 0, column: 4 }).unwrap();
+        buffer.convert_case(CaseStyle::Upper).unwrap();
+        assert_eq!(buffer.get_text().unwrap(), "x STRASSE y");
+        let cursor = buffer.get_cursor_position();
+        assert!((2..=9).contains(&cursor.column));
+    }
+}
@@ -0,0 +1,140 @@
+// Буфер обмена за общим интерфейсом
+// Editor работает только с ClipboardProvider, а конкретная реализация
+// выбирается при запуске: системный буфер (X11/Wayland) или память
+
+use anyhow::{anyhow, Result};
+
+// Какой буфер используется
+// В X11 и Wayland их два: обычный (Ctrl+C / Ctrl+V) и PRIMARY
+// (выделенный текст, вставляется средней кнопкой мыши)
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Selection {
+    Clipboard,
+    Primary,
+}
+
+// Интерфейс буфера обмена
+pub trait ClipboardProvider: Send {
+    // Название реализации (для логов)
+    fn name(&self) -> &'static str;
+
+    // Получает текст из буфера, None - если буфер пуст или недоступен
+    fn get_text(&mut self, selection: Selection) -> Option<String>;
+
+    // Записывает текст в буфер
+    fn set_text(&mut self, selection: Selection, text: String) -> Result<()>;
+}
+
+// Буфер в памяти: для тестов и для систем без графического окружения
+#[derive(Default)]
+pub struct MemoryClipboard {
+    clipboard: Option<String>,
+    primary: Option<String>,
+}
+
+impl MemoryClipboard {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl ClipboardProvider for MemoryClipboard {
+    fn name(&self) -> &'static str {
+        "memory"
+    }
+
+    fn get_text(&mut self, selection: Selection) -> Option<String> {
+        match selection {
+            Selection::Clipboard => self.clipboard.clone(),
+            Selection::Primary => self.primary.clone(),
+        }
+    }
+
+    fn set_text(&mut self, selection: Selection, text: String) -> Result<()> {
+        match selection {
+            Selection::Clipboard => self.clipboard = Some(text),
+            Selection::Primary => self.primary = Some(text),
+        }
+        Ok(())
+    }
+}
+
+// Системный буфер обмена через arboard
+// В Linux arboard сам выбирает Wayland (data-control) или X11
+pub struct SystemClipboard {
+    clipboard: arboard::Clipboard,
+}
+
+impl SystemClipboard {
+    pub fn new() -> Result<Self> {
+        let clipboard = arboard::Clipboard::new()
+            .map_err(|e| anyhow!("Системный буфер обмена недоступен: {}", e))?;
+        Ok(Self { clipboard })
+    }
+}
+
+#[cfg(all(unix, not(any(target_os = "macos", target_os = "android", target_os = "emscripten"))))]
+impl ClipboardProvider for SystemClipboard {
+    fn name(&self) -> &'static str {
+        "system"
+    }
+
+    fn get_text(&mut self, selection: Selection) -> Option<String> {
+        use arboard::GetExtLinux;
+        self.clipboard.get().clipboard(linux_kind(selection)).text().ok()
+    }
+
+    fn set_text(&mut self, selection: Selection, text: String) -> Result<()> {
+        use arboard::SetExtLinux;
+        self.clipboard
+            .set()
+            .clipboard(linux_kind(selection))
+            .text(text)
+            .map_err(|e| anyhow!("Ошибка записи в буфер обмена: {}", e))
+    }
+}
+
+// На остальных системах PRIMARY нет, оба варианта идут в обычный буфер
+#[cfg(not(all(unix, not(any(target_os = "macos", target_os = "android", target_os = "emscripten")))))]
+impl ClipboardProvider for SystemClipboard {
+    fn name(&self) -> &'static str {
+        "system"
+    }
+
+    fn get_text(&mut self, _selection: Selection) -> Option<String> {
+        self.clipboard.get_text().ok()
+    }
+
+    fn set_text(&mut self, _selection: Selection, text: String) -> Result<()> {
+        self.clipboard
+            .set_text(text)
+            .map_err(|e| anyhow!("Ошибка записи в буфер обмена: {}", e))
+    }
+}
+
+#[cfg(all(unix, not(any(target_os = "macos", target_os = "android", target_os = "emscripten"))))]
+fn linux_kind(selection: Selection) -> arboard::LinuxClipboardKind {
+    match selection {
+        Selection::Clipboard => arboard::LinuxClipboardKind::Clipboard,
+        Selection::Primary => arboard::LinuxClipboardKind::Primary,
+    }
+}
+
+// Выбирает реализацию при запуске
+// В Linux без WAYLAND_DISPLAY и DISPLAY графического сеанса нет,
+// и системный буфер даже не пробуем
+pub fn detect() -> Box<dyn ClipboardProvider> {
+    let has_display = if cfg!(all(unix, not(target_os = "macos"))) {
+        std::env::var_os("WAYLAND_DISPLAY").is_some() || std::env::var_os("DISPLAY").is_some()
+    } else {
+        true
+    };
+
+    if has_display {
+        match SystemClipboard::new() {
+            Ok(clipboard) => return Box::new(clipboard),
+            Err(e) => log::warn!("{}, используется буфер в памяти", e),
+        }
+    }
+    Box::new(MemoryClipboard::new())
+}
@@ -1,28 +1,76 @@
-use crate::editor::buffer::Position;
+use crate::editor::buffer::{LineChange, Position};
+use crate::editor::case::CaseStyle;
+use crate::editor::lines::SortOrder;
+use crate::editor::journal::JournalEvent;
+use crate::editor::registers::{Register, RegisterKind};
 use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
 
 // Простое перечисление всех возможных команд
-#[derive(Clone)]
+// Команды сериализуются (JSON и двоичный формат) для журнала правок
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum Command {
     InsertChar(char),           // Вставка символа
     DeleteChar,                 // Удаление символа
     InsertNewline,              // Вставка новой строки
+    InsertText(String),         // Вставка произвольного текста
     MoveCursor(Position),       // Перемещение курсора
     Select(Position, Position), // Выделение текста
     DeleteSelection,            // Удаление выделения
     Copy,                       // Копирование
     Cut,                        // Вырезание
     Paste,                      // Вставка
+    CopyToRegister(char),       // Копирование в именованный регистр
+    CutToRegister(char),        // Вырезание в именованный регистр
+    PasteRegister(char),        // Вставка из именованного регистра
+    PasteHistory(usize),        // Вставка фрагмента из истории буфера обмена, 0 - самый новый
+    PasteDistributed(usize),    // Вставка фрагмента истории построчно по курсорам
+    AddCursor(Position),        // Добавление курсора
+    ClearCursors,               // Удаление дополнительных курсоров
     Undo,                       // Отмена
     Redo,                       // Возврат
+    Batch(Vec<Command>),        // Группа команд, отменяемая за один шаг
+    IndentLines,                // Сдвиг строк выделения на уровень отступа вправо
+    UnindentLines,              // Сдвиг строк выделения на уровень отступа влево
+    DuplicateLines,             // Копия строк (или выделенного текста) ниже
+    MoveLinesUp,                // Перенос строк на одну вверх
+    MoveLinesDown,              // Перенос строк на одну вниз
+    DeleteLines,                // Удаление строк
+    JoinLines,                  // Объединение строк через пробел
+    SortLines(SortOrder),       // Сортировка строк
+    ReverseLines,               // Обратный порядок строк
+    UniqueLines,                // Удаление повторяющихся строк
+    ShuffleLines(u64),          // Перемешивание строк; число - начальное значение генератора
+    ToggleLineComment,          // Строчный комментарий строк: включить или снять
+    ToggleBlockComment,         // Блочный комментарий выделения: включить или снять
+    ConvertCase(CaseStyle),     // Смена регистра выделения или слова под курсором
+    Transform(String),          // Преобразование выделения по имени из набора Transforms
+    ReplaceRange(Position, Position, String), // Замена текста между позициями (например, выводом внешней команды)
+}
+
+// Сколько правок помнит история отмены; более старые забываются
+const HISTORY_LIMIT: usize = 1000;
+
+// Курсор и выделение до или после команды
+#[derive(Clone)]
+pub(crate) struct BufferState {
+    pub(crate) cursor: Position,
+    pub(crate) selection: Option<(Position, Position)>,
 }
 
-// Структура для хранения состояния буфера перед выполнением команды
+// Запись в истории: изменения строк командой и курсор до и после неё
 #[derive(Clone)]
-struct BufferState {
-    lines: Vec<String>,
-    cursor: Position,
-    selection: Option<(Position, Position)>,
+pub(crate) struct HistoryEntry {
+    pub(crate) changes: Vec<LineChange>, // по порядку выполнения
+    pub(crate) before: BufferState,
+    pub(crate) after: BufferState,
+}
+
+// Открытая транзакция: все команды внутри неё отменяются одним шагом
+pub(crate) struct Transaction {
+    pub(crate) changes: Vec<LineChange>,
+    pub(crate) before: BufferState,
+    pub(crate) depth: usize,
 }
 
 // Интерфейс для выполнения команд
@@ -35,13 +83,100 @@ pub trait CommandExecutor {
 // Реализация CommandExecutor для Editor
 impl CommandExecutor for crate::editor::Editor {
     fn execute(&mut self, command: Command) -> Result<()> {
-        // Сохраняем текущее состояние буфера
+        // Отмена и возврат не попадают в историю
+        match command {
+            Command::Undo => return self.undo(),
+            Command::Redo => return self.redo(),
+            _ => {}
+        }
+
+        // Команда попадает в журнал до выполнения, даже если завершится ошибкой
+        let input = self.command_input(&command);
+        self.journal_event(JournalEvent::Command { command: command.clone(), input });
+
+        // Сохраняем курсор и записываем изменения строк
         let current_state = self.save_buffer_state();
+        self.buffer.start_recording();
         
         // Выполняем команду
-        let result = match command.clone() {
+        let result = self.apply_command(&command);
+        let change = self.buffer.finish_recording();
+        
+        // Если команда выполнилась с ошибкой, восстанавливаем состояние буфера
+        if let Err(e) = result {
+            if let Some(change) = change {
+                self.buffer.apply_change(&change, true).map_err(|e| anyhow!("Ошибка восстановления строк: {}", e))?;
+            }
+            self.restore_buffer_state(current_state)?;
+            return Err(e);
+        }
+
+        // Во время записи макроса выполненная команда добавляется в него
+        if let Some((recorded, base_line)) = self.recording.as_mut() {
+            recorded.record(&command, *base_line);
+        }
+
+        // Внутри транзакции изменения только копятся
+        if let Some(transaction) = self.transaction.as_mut() {
+            transaction.changes.extend(change);
+            return Ok(());
+        }
+
+        // Перемещение курсора и выделение не меняют текст и не попадают в историю
+        let Some(change) = change else { return Ok(()) };
+        let after = self.save_buffer_state();
+        self.push_history(HistoryEntry { changes: vec![change], before: current_state, after });
+        Ok(())
+    }
+    
+    fn undo(&mut self) -> Result<()> {
+        self.journal_event(JournalEvent::Command { command: Command::Undo, input: None });
+
+        // Незавершённая транзакция сначала закрывается
+        self.finish_transaction();
+
+        // Проверяем, есть ли команды для отмены
+        if let Some(entry) = self.command_history.pop() {
+            // Возвращаем буфер в состояние до команды: изменения отменяются с конца
+            for change in entry.changes.iter().rev() {
+                self.buffer.apply_change(change, true).map_err(|e| anyhow!("Ошибка отмены: {}", e))?;
+            }
+            self.restore_buffer_state(entry.before.clone())?;
+            
+            // Сохраняем команду в стек отмены
+            self.undo_stack.push(entry);
+        }
+        
+        Ok(())
+    }
+    
+    fn redo(&mut self) -> Result<()> {
+        self.journal_event(JournalEvent::Command { command: Command::Redo, input: None });
+        self.finish_transaction();
+
+        // Проверяем, есть ли команды для возврата
+        if let Some(entry) = self.undo_stack.pop() {
+            // Возвращаем буфер в состояние после команды
+            for change in entry.changes.iter() {
+                self.buffer.apply_change(change, false).map_err(|e| anyhow!("Ошибка возврата: {}", e))?;
+            }
+            self.restore_buffer_state(entry.after.clone())?;
+            
+            // Добавляем команду в историю
+            self.command_history.push(entry);
+        }
+        
+        Ok(())
+    }
+}
+
+// Расширение для Editor для поддержки команд
+impl crate::editor::Editor {
+    // Выполняет команду над буфером без записи в историю
+    fn apply_command(&mut self, command: &Command) -> Result<()> {
+        match command.clone() {
             Command::InsertChar(c) => {
-                self.buffer.insert_char(c).map_err(|e| anyhow!("Ошибка вставки символа: {}", e))
+                self.buffer.type_char(c).map_err(|e| anyhow!("Ошибка вставки символа: {}", e))
             },
             Command::DeleteChar => {
                 self.buffer.delete_char().map_err(|e| anyhow!("Ошибка удаления символа: {}", e))
@@ -49,12 +184,11 @@ impl CommandExecutor for crate::editor::Editor {
             Command::InsertNewline => {
                 self.buffer.insert_newline().map_err(|e| anyhow!("Ошибка вставки новой строки: {}", e))
             },
-            Command::MoveCursor(pos) => {
-                self.buffer.set_cursor_position(pos).map_err(|e| anyhow!("Ошибка перемещения курсора: {}", e))
-            },
-            Command::Select(start, end) => {
-                self.buffer.set_selection(start, end).map_err(|e| anyhow!("Ошибка выделения: {}", e))
+            Command::InsertText(text) => {
+                self.buffer.insert_text(text).map_err(|e| anyhow!("Ошибка вставки текста: {}", e))
             },
+            Command::MoveCursor(pos) => self.set_cursor_position(pos),
+            Command::Select(start, end) => self.set_selection(start, end),
             Command::DeleteSelection => {
                 self.buffer.delete_selection().map_err(|e| anyhow!("Ошибка удаления выделения: {}", e))
             },
@@ -83,208 +217,199 @@ impl CommandExecutor for crate::editor::Editor {
             Command::Paste => {
                 // Вставка текста из буфера обмена
                 if let Some(text) = self.get_clipboard() {
-                    self.buffer.insert_text(text).map_err(|e| anyhow!("Ошибка вставки текста: {}", e))
+                    self.paste_text(text)
                 } else {
                     Ok(()) // Нет текста в буфере обмена, ничего не делаем
                 }
             },
-            Command::Undo => {
-                // Отмена последней команды
-                return self.undo();
+            Command::CopyToRegister(name) => {
+                if let Some(text) = self.selected_text()? {
+                    self.set_register(name, Register { text, kind: RegisterKind::Charwise })?;
+                }
+                Ok(())
+            },
+            Command::CutToRegister(name) => {
+                if let Some(text) = self.selected_text()? {
+                    self.set_register(name, Register { text, kind: RegisterKind::Charwise })?;
+                    self.buffer.delete_selection().map_err(|e| anyhow!("Ошибка удаления выделения: {}", e))?;
+                }
+                Ok(())
+            },
+            Command::PasteRegister(name) => {
+                let register = self.register(name)
+                    .ok_or_else(|| anyhow!("Регистр {} пуст", name))?;
+                self.paste_text(register.text)
+            },
+            Command::PasteHistory(index) => {
+                let text = self.kill_ring_entry(index)
+                    .ok_or_else(|| anyhow!("История буфера обмена пуста"))?;
+                self.paste_text(text)
+            },
+            Command::PasteDistributed(index) => {
+                let text = self.kill_ring_entry(index)
+                    .ok_or_else(|| anyhow!("История буфера обмена пуста"))?;
+                // Если строк столько же, сколько курсоров, каждый курсор получает свою строку,
+                // иначе весь фрагмент вставляется у каждого курсора
+                let count = self.buffer.get_cursors().len();
+                let lines: Vec<String> = text.lines().map(|line| line.to_string()).collect();
+                let texts = if count > 1 && lines.len() == count {
+                    lines
+                } else {
+                    vec![text; count]
+                };
+                self.buffer.insert_at_cursors(texts).map_err(|e| anyhow!("Ошибка вставки текста: {}", e))
+            },
+            Command::AddCursor(pos) => {
+                self.buffer.add_cursor(pos).map_err(|e| anyhow!("Ошибка добавления курсора: {}", e))
+            },
+            Command::ClearCursors => {
+                self.buffer.clear_extra_cursors();
+                Ok(())
+            },
+            Command::Undo | Command::Redo => {
+                // Эти команды обрабатываются в execute
+                Ok(())
+            },
+            Command::IndentLines | Command::UnindentLines => {
+                let unindent = matches!(command, Command::UnindentLines);
+                self.buffer.indent_lines(unindent).map_err(|e| anyhow!("Ошибка сдвига строк: {}", e))
+            },
+            Command::DuplicateLines => {
+                self.buffer.duplicate_lines().map_err(|e| anyhow!("Ошибка копирования строк: {}", e))
+            },
+            Command::MoveLinesUp | Command::MoveLinesDown => {
+                let up = matches!(command, Command::MoveLinesUp);
+                self.buffer.move_lines(up).map_err(|e| anyhow!("Ошибка переноса строк: {}", e))
+            },
+            Command::DeleteLines => {
+                self.buffer.delete_lines().map_err(|e| anyhow!("Ошибка удаления строк: {}", e))
+            },
+            Command::JoinLines => {
+                self.buffer.join_lines().map_err(|e| anyhow!("Ошибка объединения строк: {}", e))
+            },
+            Command::SortLines(order) => {
+                self.buffer.sort_lines(order).map_err(|e| anyhow!("Ошибка сортировки строк: {}", e))
+            },
+            Command::ReverseLines => {
+                self.buffer.reverse_lines().map_err(|e| anyhow!("Ошибка перестановки строк: {}", e))
+            },
+            Command::UniqueLines => {
+                self.buffer.unique_lines().map_err(|e| anyhow!("Ошибка удаления повторов: {}", e))
+            },
+            Command::ShuffleLines(seed) => {
+                self.buffer.shuffle_lines(seed).map_err(|e| anyhow!("Ошибка перемешивания строк: {}", e))
+            },
+            Command::ToggleLineComment => {
+                self.buffer.toggle_line_comments().map_err(|e| anyhow!("Ошибка комментирования: {}", e))
+            },
+            Command::ToggleBlockComment => {
+                self.buffer.toggle_block_comments().map_err(|e| anyhow!("Ошибка комментирования: {}", e))
+            },
+            Command::ConvertCase(style) => {
+                self.buffer.convert_case(style).map_err(|e| anyhow!("Ошибка смены регистра: {}", e))
+            },
+            Command::Transform(name) => {
+                let transform = self.transforms.get(&name).ok_or_else(|| anyhow!("Неизвестное преобразование \"{}\"", name))?;
+                self.buffer.transform_selection(transform).map_err(|e| anyhow!("Ошибка преобразования: {}", e))
+            },
+            Command::ReplaceRange(start, end, text) => {
+                self.buffer.replace_range(start, end, &text).map_err(|e| anyhow!("Ошибка замены текста: {}", e))
             },
-            Command::Redo => {
-                // Возврат отмененной команды
-                return self.redo();
+            Command::Batch(commands) => {
+                // Выполняем команды группы по очереди, первая ошибка прерывает группу
+                for command in commands.iter() {
+                    self.apply_command(command)?;
+                }
+                Ok(())
             },
+        }
+    }
+
+    // Текст выделения, None - если ничего не выделено
+    fn selected_text(&self) -> Result<Option<String>> {
+        match self.buffer.get_selection() {
+            Some((start, end)) => self.buffer.get_text_in_range(start, end)
+                .map(Some)
+                .map_err(|e| anyhow!("Ошибка получения текста: {}", e)),
+            None => Ok(None),
+        }
+    }
+
+    // Вставляет текст у курсора или, если курсоров несколько, у каждого из них
+    fn paste_text(&mut self, text: String) -> Result<()> {
+        let count = self.buffer.get_cursors().len();
+        let result = if count > 1 {
+            self.buffer.insert_at_cursors(vec![text; count])
+        } else {
+            self.buffer.insert_text(text)
         };
-        
-        // Если команда выполнилась с ошибкой, восстанавливаем состояние буфера
-        if let Err(e) = result {
-            self.restore_buffer_state(current_state)?;
-            return Err(e);
+        result.map_err(|e| anyhow!("Ошибка вставки текста: {}", e))
+    }
+
+    // Открывает транзакцию: следующие команды отменяются одним шагом
+    pub fn begin_transaction(&mut self) {
+        self.journal_event(JournalEvent::BeginTransaction);
+        if let Some(transaction) = self.transaction.as_mut() {
+            transaction.depth += 1;
+            return;
         }
-        
-        // Добавляем команду в историю
-        self.command_history.push(command);
-        
-        // Очищаем стек отмены
-        self.undo_stack.clear();
-        
-        Ok(())
+        self.transaction = Some(Transaction {
+            changes: Vec::new(),
+            before: self.save_buffer_state(),
+            depth: 1,
+        });
     }
-    
-    fn undo(&mut self) -> Result<()> {
-        // Проверяем, есть ли команды для отмены
-        if let Some(command) = self.command_history.pop() {
-            // Сохраняем команду в стек отмены
-            self.undo_stack.push(command.clone());
-            
-            // Сохраняем текущее состояние буфера
-            let current_state = self.save_buffer_state();
-            
-            // Выполняем обратную команду
-            let result = match command {
-                Command::InsertChar(_) => {
-                    self.buffer.delete_char().map_err(|e| anyhow!("Ошибка отмены вставки символа: {}", e))
-                },
-                Command::DeleteChar => {
-                    // Для отмены удаления нужно знать, какой символ был удален
-                    // Это требует дополнительной информации, которую мы не храним
-                    // В реальном приложении нужно хранить удаленный символ
-                    Err(anyhow!("Невозможно отменить удаление символа без дополнительной информации"))
-                },
-                Command::InsertNewline => {
-                    // Для отмены вставки новой строки нужно удалить символ новой строки
-                    // и объединить строки
-                    self.buffer.delete_char().map_err(|e| anyhow!("Ошибка отмены вставки новой строки: {}", e))
-                },
-                Command::MoveCursor(_) => {
-                    // Возвращаем курсор на предыдущую позицию
-                    // Для этого нужно хранить предыдущую позицию
-                    // В реальном приложении нужно хранить предыдущую позицию
-                    Err(anyhow!("Невозможно отменить перемещение курсора без предыдущей позиции"))
-                },
-                Command::Select(_, _) => {
-                    // Отмена выделения - просто снимаем выделение
-                    self.buffer.clear_selection().map_err(|e| anyhow!("Ошибка отмены выделения: {}", e))
-                },
-                Command::DeleteSelection => {
-                    // Для отмены удаления выделения нужно знать, какой текст был удален
-                    // Это требует дополнительной информации, которую мы не храним
-                    Err(anyhow!("Невозможно отменить удаление выделения без дополнительной информации"))
-                },
-                Command::Copy => {
-                    // Копирование не изменяет буфер, поэтому отмена не требуется
-                    Ok(())
-                },
-                Command::Cut => {
-                    // Для отмены вырезания нужно знать, какой текст был вырезан
-                    // Это требует дополнительной информации, которую мы не храним
-                    Err(anyhow!("Невозможно отменить вырезание без дополнительной информации"))
-                },
-                Command::Paste => {
-                    // Для отмены вставки нужно удалить вставленный текст
-                    // Это требует знания длины вставленного текста
-                    // В реальном приложении нужно хранить длину вставленного текста
-                    Err(anyhow!("Невозможно отменить вставку без дополнительной информации"))
-                },
-                Command::Undo | Command::Redo => {
-                    // Эти команды обрабатываются отдельно
-                    Ok(())
-                },
-            };
-            
-            // Если команда выполнилась с ошибкой, восстанавливаем состояние буфера
-            if let Err(e) = result {
-                self.restore_buffer_state(current_state)?;
-                return Err(e);
+
+    // Закрывает транзакцию и записывает её в историю как одну команду
+    pub fn commit_transaction(&mut self) {
+        self.journal_event(JournalEvent::CommitTransaction);
+        if let Some(transaction) = self.transaction.as_mut() {
+            transaction.depth -= 1;
+            if transaction.depth > 0 {
+                return;
             }
         }
-        
-        Ok(())
+        self.finish_transaction();
     }
-    
-    fn redo(&mut self) -> Result<()> {
-        // Проверяем, есть ли команды для возврата
-        if let Some(command) = self.undo_stack.pop() {
-            // Сохраняем текущее состояние буфера
-            let current_state = self.save_buffer_state();
-            
-            // Выполняем команду напрямую, без вызова execute_command
-            let result = match command.clone() {
-                Command::InsertChar(c) => {
-                    self.buffer.insert_char(c).map_err(|e| anyhow!("Ошибка вставки символа: {}", e))
-                },
-                Command::DeleteChar => {
-                    self.buffer.delete_char().map_err(|e| anyhow!("Ошибка удаления символа: {}", e))
-                },
-                Command::InsertNewline => {
-                    self.buffer.insert_newline().map_err(|e| anyhow!("Ошибка вставки новой строки: {}", e))
-                },
-                Command::MoveCursor(pos) => {
-                    self.buffer.set_cursor_position(pos).map_err(|e| anyhow!("Ошибка перемещения курсора: {}", e))
-                },
-                Command::Select(start, end) => {
-                    self.buffer.set_selection(start, end).map_err(|e| anyhow!("Ошибка выделения: {}", e))
-                },
-                Command::DeleteSelection => {
-                    self.buffer.delete_selection().map_err(|e| anyhow!("Ошибка удаления выделения: {}", e))
-                },
-                Command::Copy => {
-                    // Копирование выделенного текста в буфер обмена
-                    if let Some((start, end)) = self.buffer.get_selection() {
-                        let text = self.buffer.get_text_in_range(start, end)
-                            .map_err(|e| anyhow!("Ошибка получения текста: {}", e))?;
-                        self.set_clipboard(text);
-                        Ok(())
-                    } else {
-                        Ok(()) // Нет выделения, ничего не делаем
-                    }
-                },
-                Command::Cut => {
-                    // Вырезание выделенного текста в буфер обмена
-                    if let Some((start, end)) = self.buffer.get_selection() {
-                        let text = self.buffer.get_text_in_range(start, end)
-                            .map_err(|e| anyhow!("Ошибка получения текста: {}", e))?;
-                        self.set_clipboard(text);
-                        self.buffer.delete_selection().map_err(|e| anyhow!("Ошибка удаления выделения: {}", e))
-                    } else {
-                        Ok(()) // Нет выделения, ничего не делаем
-                    }
-                },
-                Command::Paste => {
-                    // Вставка текста из буфера обмена
-                    if let Some(text) = self.get_clipboard() {
-                        self.buffer.insert_text(text).map_err(|e| anyhow!("Ошибка вставки текста: {}", e))
-                    } else {
-                        Ok(()) // Нет текста в буфере обмена, ничего не делаем
-                    }
-                },
-                Command::Undo => {
-                    // Отмена последней команды
-                    return self.undo();
-                },
-                Command::Redo => {
-                    // Возврат отмененной команды
-                    return self.redo();
-                },
-            };
-            
-            // Если команда выполнилась с ошибкой, восстанавливаем состояние буфера
-            if let Err(e) = result {
-                self.restore_buffer_state(current_state)?;
-                return Err(e);
-            }
-            
-            // Добавляем команду в историю
-            self.command_history.push(command);
+
+    // Принудительно закрывает транзакцию вместе со всеми вложенными
+    fn finish_transaction(&mut self) {
+        if let Some(transaction) = self.transaction.take() {
+            self.push_transaction(transaction);
         }
-        
-        Ok(())
     }
-}
 
-// Расширение для Editor для поддержки команд
-impl crate::editor::Editor {
-    // Сохраняет текущее состояние буфера
+    // Записывает транзакцию в историю, если она изменила текст
+    fn push_transaction(&mut self, transaction: Transaction) {
+        if transaction.changes.is_empty() {
+            return;
+        }
+        let after = self.save_buffer_state();
+        self.push_history(HistoryEntry { changes: transaction.changes, before: transaction.before, after });
+    }
+
+    // Добавляет правку в историю; новая правка отменяет возврат отмененных
+    fn push_history(&mut self, entry: HistoryEntry) {
+        if self.command_history.len() >= HISTORY_LIMIT {
+            self.command_history.remove(0);
+        }
+        self.command_history.push(entry);
+        self.undo_stack.clear();
+    }
+
+    // Сохраняет курсор и выделение
     fn save_buffer_state(&self) -> BufferState {
         BufferState {
-            lines: self.buffer.get_all_lines().iter().cloned().collect(),
             cursor: self.buffer.get_cursor_position(),
             selection: self.buffer.get_selection(),
         }
     }
     
-    // Восстанавливает состояние буфера
+    // Восстанавливает курсор и выделение (строки к этому моменту уже восстановлены)
     fn restore_buffer_state(&mut self, state: BufferState) -> Result<()> {
-        // Очищаем буфер
-        self.buffer.clear().map_err(|e| anyhow!("Ошибка очистки буфера: {}", e))?;
-        
-        // Восстанавливаем строки
-        for line in state.lines {
-            self.buffer.append_line(line).map_err(|e| anyhow!("Ошибка добавления строки: {}", e))?;
-        }
-        
+        self.buffer.clear_extra_cursors();
+        self.buffer.clear_selection().map_err(|e| anyhow!("Ошибка снятия выделения: {}", e))?;
+
         // Восстанавливаем позицию курсора
         self.buffer.set_cursor_position(state.cursor).map_err(|e| anyhow!("Ошибка установки позиции курсора: {}", e))?;
         
@@ -295,38 +420,83 @@ impl crate::editor::Editor {
         
         Ok(())
     }
-    
-    // Устанавливает текст в буфер обмена
-    fn set_clipboard(&mut self, text: String) {
-        // В реальном приложении здесь будет код для работы с буфером обмена
-        // Для простоты используем статическую переменную
-        use std::sync::Mutex;
-        use lazy_static::lazy_static;
-        
-        lazy_static! {
-            static ref CLIPBOARD: Mutex<Option<String>> = Mutex::new(None);
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::editor::fixtures::{editor, position};
+    use crate::editor::{Command, Selection};
+
+    #[test]
+    fn undo_and_redo_restore_every_step() {
+        let mut editor = editor("fn main() {\n    one();\n    two();\n}");
+        let commands = vec![
+            Command::MoveCursor(position(1, 10)),
+            Command::InsertNewline,
+            Command::InsertText("three();".to_string()),
+            Command::Select(position(0, 0), position(2, 4)),
+            Command::DeleteChar,
+            Command::MoveCursor(position(0, 0)),
+            Command::AddCursor(position(2, 0)),
+            Command::InsertText("// ".to_string()),
+            Command::MoveCursor(position(1, 0)),
+            Command::DeleteLines,
+            Command::SortLines(crate::editor::SortOrder::Natural),
+        ];
+        let mut texts = vec![editor.get_text().unwrap()];
+        for command in commands {
+            editor.execute_command(command).unwrap();
+            let text = editor.get_text().unwrap();
+            if texts.last() != Some(&text) {
+                texts.push(text);
+            }
         }
-        
-        if let Ok(mut clipboard) = CLIPBOARD.lock() {
-            *clipboard = Some(text);
+        for text in texts.iter().rev().skip(1) {
+            editor.undo().unwrap();
+            assert_eq!(&editor.get_text().unwrap(), text);
+        }
+        for text in texts.iter().skip(1) {
+            editor.redo().unwrap();
+            assert_eq!(&editor.get_text().unwrap(), text);
         }
     }
-    
-    // Получает текст из буфера обмена
-    fn get_clipboard(&self) -> Option<String> {
-        // В реальном приложении здесь будет код для работы с буфером обмена
-        // Для простоты используем статическую переменную
-        use std::sync::Mutex;
-        use lazy_static::lazy_static;
-        
-        lazy_static! {
-            static ref CLIPBOARD: Mutex<Option<String>> = Mutex::new(None);
+
+    #[test]
+    fn transaction_is_one_step_and_failed_command_changes_nothing() {
+        let mut editor = editor("a\nb");
+        editor.begin_transaction();
+        editor.execute_command(Command::InsertText("x".to_string())).unwrap();
+        editor.execute_command(Command::MoveCursor(position(1, 1))).unwrap();
+        editor.execute_command(Command::InsertNewline).unwrap();
+        editor.commit_transaction();
+        assert_eq!(editor.get_text().unwrap(), "xa\nb\n");
+        editor.undo().unwrap();
+        assert_eq!(editor.get_text().unwrap(), "a\nb");
+        assert_eq!(editor.get_cursor_position(), position(0, 0));
+
+        let failing = Command::Batch(vec![Command::InsertText("y".to_string()), Command::MoveCursor(position(9, 0))]);
+        assert!(editor.execute_command(failing).is_err());
+        assert_eq!(editor.get_text().unwrap(), "a\nb");
+    }
+
+    #[test]
+    fn history_is_limited() {
+        let mut editor = editor("");
+        for _ in 0..super::HISTORY_LIMIT + 10 {
+            editor.execute_command(Command::InsertChar('x')).unwrap();
         }
-        
-        if let Ok(clipboard) = CLIPBOARD.lock() {
-            clipboard.clone()
-        } else {
-            None
+        for _ in 0..super::HISTORY_LIMIT + 10 {
+            editor.undo().unwrap();
         }
+        assert_eq!(editor.get_text().unwrap(), "x".repeat(10));
+    }
+
+    #[test]
+    fn only_published_selection_becomes_primary() {
+        let mut editor = editor("one two");
+        editor.execute_command(Command::Select(position(0, 0), position(0, 3))).unwrap();
+        assert_eq!(editor.clipboard_text(Selection::Primary), None);
+        editor.publish_selection();
+        assert_eq!(editor.clipboard_text(Selection::Primary).as_deref(), Some("one"));
     }
 }
@@ -0,0 +1,690 @@
+// Раскладка в стиле Emacs поверх Editor
+// Движения C-a/C-e/M-f/M-b, метка и регион, кольцо удалений с C-k/C-y/M-y
+// и инкрементальный поиск C-s/C-r. Изменения выполняются через Command
+
+use anyhow::{anyhow, Result};
+
+use crate::editor::keys::Key;
+use crate::editor::text::{
+    byte_column, char_at, char_column, end_of_buffer, line_text, lines, move_to, next_position,
+    previous_position, replace_range,
+};
+use crate::editor::{Command, Editor, Position};
+
+// Сколько набранных подряд символов отменяются одним шагом (как в Emacs)
+const TYPING_GROUP: usize = 20;
+
+// Действия, которые раскладка передает интерфейсу
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EmacsEvent {
+    None, // Ничего не нужно делать
+    Save, // C-x C-s
+    Quit, // C-x C-c
+}
+
+// Какой была предыдущая команда: от этого зависят C-k и M-y
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum LastCommand {
+    Other,
+    Kill,
+    Yank,
+}
+
+// Состояние инкрементального поиска
+struct Search {
+    query: String,
+    forward: bool,
+    origin: Position,                        // откуда начался поиск
+    current: Option<(Position, Position)>,   // текущее совпадение
+    failing: bool,                           // совпадений больше нет
+}
+
+pub struct EmacsState {
+    mark: Option<Position>,               // метка
+    mark_active: bool,                    // виден ли регион
+    last_command: LastCommand,
+    yank: Option<(Position, Position)>,   // где лежит последний вставленный фрагмент
+    yank_index: usize,                    // номер фрагмента для M-y
+    prefix: Option<Key>,                  // префикс C-x
+    search: Option<Search>,
+    last_search: String,                  // запрос для повторного C-s
+    goal_column: Option<usize>,           // столбец для C-n/C-p (в символах)
+    typing: usize,                        // сколько символов набрано в текущей группе
+}
+
+impl EmacsState {
+    pub fn new() -> Self {
+        Self {
+            mark: None,
+            mark_active: false,
+            last_command: LastCommand::Other,
+            yank: None,
+            yank_index: 0,
+            prefix: None,
+            search: None,
+            last_search: String::new(),
+            goal_column: None,
+            typing: 0,
+        }
+    }
+
+    // Метка, если регион активен
+    pub fn active_mark(&self) -> Option<Position> {
+        if self.mark_active {
+            self.mark
+        } else {
+            None
+        }
+    }
+
+    // Строка для области сообщений: префикс или запрос поиска
+    pub fn status(&self) -> Option<String> {
+        if let Some(search) = &self.search {
+            let failing = if search.failing { "Failing " } else { "" };
+            let direction = if search.forward { "I-search" } else { "I-search backward" };
+            return Some(format!("{}{}: {}", failing, direction, search.query));
+        }
+        self.prefix.map(|key| format!("{}-", key))
+    }
+
+    // Обрабатывает нажатие клавиши
+    pub fn handle_key(&mut self, editor: &mut Editor, key: Key) -> Result<EmacsEvent> {
+        // Пока идет поиск, клавиши обрабатывает он
+        // (None - поиск завершен, клавиша обрабатывается как обычно)
+        if self.search.is_some() {
+            if let Some(result) = self.handle_search(editor, key) {
+                return result;
+            }
+        }
+
+        // Набор текста группируется, любая другая команда закрывает группу
+        let self_insert = matches!(key, Key::Char(_)) && self.prefix.is_none();
+        if !self_insert {
+            self.finish_typing(editor);
+        }
+
+        let previous = self.last_command;
+        self.last_command = LastCommand::Other;
+        let vertical = matches!(key, Key::Ctrl('n') | Key::Ctrl('p') | Key::Up | Key::Down) && self.prefix.is_none();
+        if !vertical {
+            self.goal_column = None;
+        }
+
+        if let Some(prefix) = self.prefix.take() {
+            return self.handle_prefixed(editor, prefix, key);
+        }
+
+        let cursor = editor.get_cursor_position();
+        match key {
+            // Движения
+            Key::Ctrl('f') | Key::Right => {
+                if let Some(next) = next_position(editor, cursor) {
+                    self.move_point(editor, next)?;
+                }
+            }
+            Key::Ctrl('b') | Key::Left => {
+                if let Some(previous) = previous_position(editor, cursor) {
+                    self.move_point(editor, previous)?;
+                }
+            }
+            Key::Ctrl('n') | Key::Down => self.move_vertically(editor, 1)?,
+            Key::Ctrl('p') | Key::Up => self.move_vertically(editor, -1)?,
+            Key::Ctrl('a') | Key::Home => self.move_point(editor, Position { line: cursor.line, column: 0 })?,
+            Key::Ctrl('e') | Key::End => {
+                let line = line_text(editor, cursor.line);
+                self.move_point(editor, Position { line: cursor.line, column: line.len() })?;
+            }
+            Key::Alt('f') => {
+                let target = forward_word(editor, cursor);
+                self.move_point(editor, target)?;
+            }
+            Key::Alt('b') => {
+                let target = backward_word(editor, cursor);
+                self.move_point(editor, target)?;
+            }
+            Key::Alt('<') => self.move_point(editor, Position { line: 0, column: 0 })?,
+            Key::Alt('>') => {
+                let end = end_of_buffer(editor);
+                self.move_point(editor, end)?;
+            }
+
+            // Метка и регион
+            Key::Ctrl(' ') | Key::Ctrl('@') => {
+                self.mark = Some(cursor);
+                self.mark_active = true;
+                self.update_region(editor)?;
+            }
+            Key::Ctrl('g') => self.deactivate_mark(editor)?,
+            Key::Ctrl('x') => self.prefix = Some(key),
+
+            // Удаление в кольцо и вставка из него
+            Key::Ctrl('k') => {
+                self.kill_line(editor, previous == LastCommand::Kill)?;
+                self.last_command = LastCommand::Kill;
+            }
+            Key::Ctrl('w') => {
+                self.kill_region(editor, previous == LastCommand::Kill, true)?;
+                self.last_command = LastCommand::Kill;
+            }
+            Key::Alt('w') => {
+                self.kill_region(editor, previous == LastCommand::Kill, false)?;
+                self.last_command = LastCommand::Kill;
+            }
+            Key::Alt('d') => {
+                let end = forward_word(editor, cursor);
+                self.kill_range(editor, cursor, end, previous == LastCommand::Kill, false)?;
+                self.last_command = LastCommand::Kill;
+            }
+            Key::Ctrl('y') => {
+                self.yank(editor)?;
+                self.last_command = LastCommand::Yank;
+            }
+            Key::Alt('y') => {
+                if previous != LastCommand::Yank {
+                    return Err(anyhow!("Предыдущая команда не была вставкой (C-y)"));
+                }
+                self.yank_pop(editor)?;
+                self.last_command = LastCommand::Yank;
+            }
+
+            // Редактирование
+            Key::Ctrl('d') | Key::Delete => {
+                self.deactivate_mark(editor)?;
+                if let Some(next) = next_position(editor, cursor) {
+                    editor.execute_command(Command::Batch(vec![Command::MoveCursor(next), Command::DeleteChar]))?;
+                }
+            }
+            Key::Backspace => {
+                self.deactivate_mark(editor)?;
+                if cursor.line > 0 || cursor.column > 0 {
+                    editor.execute_command(Command::DeleteChar)?;
+                }
+            }
+            Key::Enter => {
+                self.deactivate_mark(editor)?;
+                editor.execute_command(Command::InsertNewline)?;
+            }
+            Key::Tab => {
+                self.deactivate_mark(editor)?;
+                editor.execute_command(Command::InsertChar('\t'))?;
+            }
+            Key::Ctrl('o') => {
+                // Новая строка после курсора, курсор остается на месте
+                self.deactivate_mark(editor)?;
+                editor.execute_command(Command::Batch(vec![Command::InsertNewline, Command::MoveCursor(cursor)]))?;
+            }
+            Key::Ctrl('t') => self.transpose_chars(editor)?,
+            Key::Ctrl('/') | Key::Ctrl('_') => {
+                self.deactivate_mark(editor)?;
+                editor.undo()?;
+            }
+
+            // Поиск
+            Key::Ctrl('s') | Key::Ctrl('r') => {
+                self.deactivate_mark(editor)?;
+                self.search = Some(Search {
+                    query: String::new(),
+                    forward: key == Key::Ctrl('s'),
+                    origin: cursor,
+                    current: None,
+                    failing: false,
+                });
+            }
+
+            Key::Char(c) => self.self_insert(editor, c)?,
+            _ => {}
+        }
+        Ok(EmacsEvent::None)
+    }
+
+    // Команды после C-x
+    fn handle_prefixed(&mut self, editor: &mut Editor, prefix: Key, key: Key) -> Result<EmacsEvent> {
+        if prefix != Key::Ctrl('x') {
+            return Ok(EmacsEvent::None);
+        }
+        match key {
+            Key::Ctrl('s') => return Ok(EmacsEvent::Save),
+            Key::Ctrl('c') => return Ok(EmacsEvent::Quit),
+            Key::Ctrl('x') => {
+                // Меняем местами курсор и метку
+                let mark = self.mark.ok_or_else(|| anyhow!("Метка не установлена"))?;
+                let cursor = editor.get_cursor_position();
+                self.mark = Some(cursor);
+                self.mark_active = true;
+                move_to(editor, mark)?;
+                self.update_region(editor)?;
+            }
+            Key::Char('h') => {
+                // Выделить весь буфер
+                self.mark = Some(end_of_buffer(editor));
+                self.mark_active = true;
+                move_to(editor, Position { line: 0, column: 0 })?;
+                self.update_region(editor)?;
+            }
+            Key::Char('u') => {
+                self.deactivate_mark(editor)?;
+                editor.undo()?;
+            }
+            _ => {}
+        }
+        Ok(EmacsEvent::None)
+    }
+
+    // ---------- Курсор и регион ----------
+
+    // Перемещает курсор, обновляя регион, если метка активна
+    fn move_point(&mut self, editor: &mut Editor, position: Position) -> Result<()> {
+        move_to(editor, position)?;
+        self.update_region(editor)
+    }
+
+    // C-n / C-p с сохранением столбца
+    fn move_vertically(&mut self, editor: &mut Editor, delta: isize) -> Result<()> {
+        let cursor = editor.get_cursor_position();
+        let line_count = lines(editor).len();
+        let line = line_text(editor, cursor.line);
+        let goal = *self.goal_column.get_or_insert(char_column(&line, cursor.column));
+        let target_line = if delta < 0 {
+            cursor.line.saturating_sub(delta.unsigned_abs())
+        } else {
+            (cursor.line + delta as usize).min(line_count - 1)
+        };
+        let text = line_text(editor, target_line);
+        self.move_point(editor, Position { line: target_line, column: byte_column(&text, goal) })
+    }
+
+    // Показывает регион как выделение Editor
+    fn update_region(&mut self, editor: &mut Editor) -> Result<()> {
+        match self.active_mark() {
+            Some(mark) => editor.execute_command(Command::Select(mark, editor.get_cursor_position())),
+            None => Ok(()),
+        }
+    }
+
+    // Снимает регион, метка при этом остается
+    fn deactivate_mark(&mut self, editor: &mut Editor) -> Result<()> {
+        if self.mark_active {
+            self.mark_active = false;
+            editor.clear_selection()?;
+        }
+        Ok(())
+    }
+
+    // ---------- Набор текста ----------
+
+    fn self_insert(&mut self, editor: &mut Editor, c: char) -> Result<()> {
+        // Набор не заменяет регион, а только снимает его
+        self.deactivate_mark(editor)?;
+        if self.typing == 0 {
+            editor.begin_transaction();
+        }
+        self.typing += 1;
+        let result = editor.execute_command(Command::InsertChar(c));
+        if self.typing >= TYPING_GROUP || result.is_err() {
+            self.finish_typing(editor);
+        }
+        result
+    }
+
+    fn finish_typing(&mut self, editor: &mut Editor) {
+        if self.typing > 0 {
+            self.typing = 0;
+            editor.commit_transaction();
+        }
+    }
+
+    // C-t: меняет местами символы вокруг курсора
+    fn transpose_chars(&mut self, editor: &mut Editor) -> Result<()> {
+        self.deactivate_mark(editor)?;
+        let cursor = editor.get_cursor_position();
+        let line = line_text(editor, cursor.line);
+        // В конце строки меняются два последних символа
+        let point = if cursor.column >= line.len() {
+            previous_position(editor, cursor).unwrap_or(cursor)
+        } else {
+            cursor
+        };
+        let before = match previous_position(editor, point) {
+            Some(before) if before.line == point.line => before,
+            _ => return Err(anyhow!("Нечего переставлять")),
+        };
+        let after = match next_position(editor, point) {
+            Some(after) if after.line == point.line => after,
+            _ => return Err(anyhow!("Нечего переставлять")),
+        };
+        let swapped = format!("{}{}", char_at(editor, point), char_at(editor, before));
+        replace_range(editor, before, after, &swapped)
+    }
+
+    // ---------- Кольцо удалений ----------
+
+    // C-k: удаляет до конца строки, а если там только пробелы - вместе с переводом строки
+    fn kill_line(&mut self, editor: &mut Editor, append: bool) -> Result<()> {
+        let cursor = editor.get_cursor_position();
+        let line = line_text(editor, cursor.line);
+        let rest = &line[cursor.column.min(line.len())..];
+        let end = if rest.trim().is_empty() && cursor.line + 1 < lines(editor).len() {
+            Position { line: cursor.line + 1, column: 0 }
+        } else {
+            Position { line: cursor.line, column: line.len() }
+        };
+        self.kill_range(editor, cursor, end, append, false)
+    }
+
+    // C-w и M-w: удаляет или копирует регион
+    fn kill_region(&mut self, editor: &mut Editor, append: bool, delete: bool) -> Result<()> {
+        let mark = self.mark.ok_or_else(|| anyhow!("Метка не установлена, региона нет"))?;
+        let cursor = editor.get_cursor_position();
+        self.deactivate_mark(editor)?;
+        if delete {
+            self.kill_range(editor, mark, cursor, append, cursor < mark)
+        } else {
+            let text = editor
+                .get_buffer()
+                .get_text_in_range(mark, cursor)
+                .map_err(|e| anyhow!("Ошибка получения текста: {}", e))?;
+            editor.kill_ring_push(text, append, false);
+            Ok(())
+        }
+    }
+
+    // Удаляет диапазон и кладет его в кольцо
+    // backward - удаление назад, при добавлении текст пишется в начало фрагмента
+    fn kill_range(&mut self, editor: &mut Editor, start: Position, end: Position, append: bool, backward: bool) -> Result<()> {
+        self.deactivate_mark(editor)?;
+        let (start, end) = if start <= end { (start, end) } else { (end, start) };
+        if start == end {
+            return Ok(());
+        }
+        let text = editor
+            .get_buffer()
+            .get_text_in_range(start, end)
+            .map_err(|e| anyhow!("Ошибка получения текста: {}", e))?;
+        replace_range(editor, start, end, "")?;
+        editor.kill_ring_push(text, append, backward);
+        Ok(())
+    }
+
+    // C-y: вставляет последний фрагмент, метка ставится в начало вставки
+    fn yank(&mut self, editor: &mut Editor) -> Result<()> {
+        self.deactivate_mark(editor)?;
+        let text = editor.kill_ring_entry(0).ok_or_else(|| anyhow!("Кольцо удалений пусто"))?;
+        let start = editor.get_cursor_position();
+        editor.execute_command(Command::InsertText(text))?;
+        self.mark = Some(start);
+        self.yank = Some((start, editor.get_cursor_position()));
+        self.yank_index = 0;
+        Ok(())
+    }
+
+    // M-y: заменяет только что вставленный фрагмент более старым
+    fn yank_pop(&mut self, editor: &mut Editor) -> Result<()> {
+        let (start, end) = self.yank.ok_or_else(|| anyhow!("Нечего заменять"))?;
+        let count = editor.kill_ring_len();
+        if count == 0 {
+            return Err(anyhow!("Кольцо удалений пусто"));
+        }
+        self.yank_index = (self.yank_index + 1) % count;
+        let text = editor.kill_ring_entry(self.yank_index).unwrap_or_default();
+        replace_range(editor, start, end, &text)?;
+        self.yank = Some((start, editor.get_cursor_position()));
+        Ok(())
+    }
+
+    // ---------- Инкрементальный поиск ----------
+
+    // Возвращает None, если клавиша завершает поиск и должна обработаться дальше
+    fn handle_search(&mut self, editor: &mut Editor, key: Key) -> Option<Result<EmacsEvent>> {
+        let mut search = self.search.take()?;
+        let result = match key {
+            Key::Char(c) => {
+                search.query.push(c);
+                // Совпадение продолжается с того же места, если возможно
+                let from = match search.current {
+                    Some((start, _)) if search.forward => start,
+                    Some((start, _)) => Position { line: start.line, column: start.column + search.query.len() },
+                    None => search.origin,
+                };
+                self.find(editor, &mut search, from, false)
+            }
+            Key::Backspace => {
+                search.query.pop();
+                let origin = search.origin;
+                search.current = None;
+                if search.query.is_empty() {
+                    search.failing = false;
+                    move_to(editor, origin).and_then(|_| editor.clear_selection())
+                } else {
+                    self.find(editor, &mut search, origin, false)
+                }
+            }
+            Key::Ctrl('s') | Key::Ctrl('r') => {
+                let forward = key == Key::Ctrl('s');
+                if search.query.is_empty() {
+                    search.query = self.last_search.clone();
+                }
+                let changed_direction = forward != search.forward;
+                search.forward = forward;
+                let from = match search.current {
+                    Some((start, end)) if !changed_direction => if forward { end } else { start },
+                    Some((start, end)) => if forward { start } else { end },
+                    None => editor.get_cursor_position(),
+                };
+                // Повтор после неудачи начинает поиск с края буфера
+                let wrap = search.failing;
+                self.find(editor, &mut search, from, wrap)
+            }
+            Key::Enter => {
+                self.finish_search(editor, search, true);
+                return Some(Ok(EmacsEvent::None));
+            }
+            Key::Ctrl('g') => {
+                let origin = search.origin;
+                self.finish_search(editor, search, false);
+                return Some(move_to(editor, origin).map(|_| EmacsEvent::None));
+            }
+            _ => {
+                // Любая другая команда завершает поиск и выполняется
+                self.finish_search(editor, search, true);
+                return None;
+            }
+        };
+        self.search = Some(search);
+        Some(result.map(|_| EmacsEvent::None))
+    }
+
+    // Ищет запрос и показывает совпадение выделением
+    fn find(&mut self, editor: &mut Editor, search: &mut Search, from: Position, wrap: bool) -> Result<()> {
+        if search.query.is_empty() {
+            return Ok(());
+        }
+        let from = if wrap {
+            if search.forward { Position { line: 0, column: 0 } } else { end_of_buffer(editor) }
+        } else {
+            from
+        };
+        let found = if search.forward {
+            search_forward(editor, from, &search.query)
+        } else {
+            search_backward(editor, from, &search.query)
+        };
+        match found {
+            Some((start, end)) => {
+                search.failing = false;
+                search.current = Some((start, end));
+                let point = if search.forward { end } else { start };
+                let anchor = if search.forward { start } else { end };
+                move_to(editor, point)?;
+                editor.execute_command(Command::Select(anchor, point))
+            }
+            None => {
+                search.failing = true;
+                Ok(())
+            }
+        }
+    }
+
+    // Завершает поиск; при успехе метка ставится туда, откуда начали
+    fn finish_search(&mut self, editor: &mut Editor, search: Search, keep: bool) {
+        if !search.query.is_empty() {
+            self.last_search = search.query.clone();
+        }
+        if keep && search.current.is_some() {
+            self.mark = Some(search.origin);
+        }
+        let _ = editor.clear_selection();
+    }
+}
+
+impl Default for EmacsState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// ---------- Слова и поиск ----------
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+// M-f: конец следующего слова
+fn forward_word(editor: &Editor, start: Position) -> Position {
+    let mut position = start;
+    // Пропускаем все, что не является словом
+    while !is_word_char(char_at(editor, position)) {
+        match next_position(editor, position) {
+            Some(next) => position = next,
+            None => return position,
+        }
+    }
+    // Проходим слово
+    while is_word_char(char_at(editor, position)) {
+        match next_position(editor, position) {
+            Some(next) => position = next,
+            None => return end_of_buffer(editor),
+        }
+    }
+    position
+}
+
+// M-b: начало предыдущего слова
+fn backward_word(editor: &Editor, start: Position) -> Position {
+    let mut position = start;
+    loop {
+        match previous_position(editor, position) {
+            Some(previous) if !is_word_char(char_at(editor, previous)) => position = previous,
+            Some(_) => break,
+            None => return position,
+        }
+    }
+    loop {
+        match previous_position(editor, position) {
+            Some(previous) if is_word_char(char_at(editor, previous)) => position = previous,
+            _ => return position,
+        }
+    }
+}
+
+// Без заглавных букв в запросе регистр не учитывается (как case-fold-search)
+fn chars_equal(a: char, b: char, fold: bool) -> bool {
+    if fold {
+        a == b || a.to_lowercase().eq(b.to_lowercase())
+    } else {
+        a == b
+    }
+}
+
+// Совпадает ли запрос с текстом строки начиная с байта start; возвращает конец совпадения
+fn match_at(line: &str, start: usize, query: &str, fold: bool) -> Option<usize> {
+    let mut rest = line.get(start..)?.char_indices();
+    let mut end = start;
+    for expected in query.chars() {
+        let (offset, actual) = rest.next()?;
+        if !chars_equal(actual, expected, fold) {
+            return None;
+        }
+        end = start + offset + actual.len_utf8();
+    }
+    Some(end)
+}
+
+// Первое совпадение, начинающееся не раньше from
+fn search_forward(editor: &Editor, from: Position, query: &str) -> Option<(Position, Position)> {
+    let fold = !query.chars().any(|c| c.is_uppercase());
+    for (index, line) in lines(editor).iter().enumerate().skip(from.line) {
+        let first = if index == from.line { from.column.min(line.len()) } else { 0 };
+        for (offset, _) in line[first..].char_indices() {
+            if let Some(end) = match_at(line, first + offset, query, fold) {
+                return Some((Position { line: index, column: first + offset }, Position { line: index, column: end }));
+            }
+        }
+    }
+    None
+}
+
+// Последнее совпадение, заканчивающееся не позже before
+fn search_backward(editor: &Editor, before: Position, query: &str) -> Option<(Position, Position)> {
+    let fold = !query.chars().any(|c| c.is_uppercase());
+    let all = lines(editor);
+    for index in (0..=before.line.min(all.len() - 1)).rev() {
+        let line = &all[index];
+        let limit = if index == before.line { before.column.min(line.len()) } else { line.len() };
+        let mut found = None;
+        for (start, _) in line.char_indices() {
+            if let Some(end) = match_at(line, start, query, fold) {
+                if end <= limit {
+                    found = Some((Position { line: index, column: start }, Position { line: index, column: end }));
+                }
+            }
+        }
+        if found.is_some() {
+            return found;
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::editor::fixtures::{editor, text};
+
+    fn feed(emacs: &mut EmacsState, editor: &mut Editor, keys: &str) -> EmacsEvent {
+        let mut event = EmacsEvent::None;
+        for key in Key::parse_sequence(keys) {
+            event = emacs.handle_key(editor, key).unwrap();
+        }
+        event
+    }
+
+    #[test]
+    fn kill_and_yank() {
+        let mut editor = editor("one two\nthree\nfour");
+        let mut emacs = EmacsState::new();
+        feed(&mut emacs, &mut editor, "<C-k><C-k><C-k>");
+        assert_eq!(text(&editor), "\nfour");
+        feed(&mut emacs, &mut editor, "<C-y>");
+        assert_eq!(text(&editor), "one two\nthree\nfour");
+        feed(&mut emacs, &mut editor, "<M-<><M-f><C-@><M-f><C-w>");
+        assert_eq!(text(&editor), "one\nthree\nfour");
+        feed(&mut emacs, &mut editor, "<C-y><M-y>");
+        assert_eq!(text(&editor), "oneone two\nthree\nthree\nfour");
+        feed(&mut emacs, &mut editor, "<C-_>");
+        assert_eq!(text(&editor), "one two\nthree\nfour");
+    }
+
+    #[test]
+    fn search_and_file_events() {
+        let mut editor = editor("one\nthree\nfour");
+        let mut emacs = EmacsState::new();
+        feed(&mut emacs, &mut editor, "<C-s>fo");
+        assert_eq!(emacs.status().as_deref(), Some("I-search: fo"));
+        feed(&mut emacs, &mut editor, "<CR>xy");
+        assert_eq!(text(&editor), "one\nthree\nfoxyur");
+        assert_eq!(feed(&mut emacs, &mut editor, "<C-x>"), EmacsEvent::None);
+        assert_eq!(emacs.status().as_deref(), Some("<C-x>-"));
+        assert_eq!(feed(&mut emacs, &mut editor, "<C-s>"), EmacsEvent::Save);
+        assert_eq!(feed(&mut emacs, &mut editor, "<C-x><C-c>"), EmacsEvent::Quit);
+    }
+}
@@ -0,0 +1,26 @@
+// Общие заготовки для тестов модулей редактора
+
+use crate::editor::{Buffer, Editor, MemoryClipboard, Position};
+
+// Редактор с текстом и буфером обмена в памяти
+pub fn editor(text: &str) -> Editor {
+    let mut editor = Editor::with_clipboard(Box::new(MemoryClipboard::new()));
+    editor.set_text(text.to_string()).unwrap();
+    editor
+}
+
+// Буфер с текстом без редактора
+pub fn new_buffer(text: &str) -> Buffer {
+    let mut buffer = Buffer::new();
+    buffer.set_text(text.to_string()).unwrap();
+    buffer
+}
+
+// Текст редактора со строками через \n
+pub fn text(editor: &Editor) -> String {
+    editor.get_buffer().get_all_lines().iter().cloned().collect::<Vec<_>>().join("\n")
+}
+
+pub fn position(line: usize, column: usize) -> Position {
+    Position { line, column }
+}
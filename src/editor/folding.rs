@@ -0,0 +1,407 @@
+// Сворачивание блоков текста
+// Области сворачивания вычисляются по отступам: строка, за которой идут строки
+// с большим отступом, открывает область до последней из них. Закрывающая
+// скобка с тем же отступом, что и заголовок, остается видимой.
+// Если для языка есть синтаксическое дерево, области берутся из него (set_regions).
+// Свернутые области помнятся по первой строке и после правки сдвигаются
+// вместе с текстом: общие начало и конец старого и нового текста находятся
+// по хешам строк, как в разметке переноса
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::BTreeSet;
+use std::hash::{Hash, Hasher};
+
+use crate::editor::Buffer;
+
+// Область сворачивания: заголовок start остается видимым, строки start+1..=end скрываются
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct FoldRange {
+    pub start: usize,
+    pub end: usize,
+    pub level: usize, // вложенность, 1 - внешние области
+}
+
+pub struct Folding {
+    revision: Option<u64>,                // правка буфера, для которой посчитаны области
+    hashes: Vec<u64>,                     // хеши строк этой правки
+    ranges: Vec<FoldRange>,               // все области по возрастанию start
+    regions: Option<Vec<(usize, usize)>>, // области из синтаксического дерева, если оно есть
+    regions_revision: Option<u64>,        // правка, для которой пришли области из дерева
+    folded: BTreeSet<usize>,              // заголовки свернутых областей
+    hidden: Vec<(usize, usize)>,          // скрытые строки: непересекающиеся отрезки по порядку
+    generation: u64,                      // меняется при любом изменении областей или скрытых строк
+}
+
+impl Folding {
+    pub fn new() -> Self {
+        Self {
+            revision: None,
+            hashes: Vec::new(),
+            ranges: Vec::new(),
+            regions: None,
+            regions_revision: None,
+            folded: BTreeSet::new(),
+            hidden: Vec::new(),
+            generation: 0,
+        }
+    }
+
+    // Пересчитывает области после правки буфера
+    pub fn update(&mut self, buffer: &Buffer) {
+        if self.revision == Some(buffer.revision()) {
+            return;
+        }
+        self.revision = Some(buffer.revision());
+        let lines = buffer.get_all_lines();
+        let hashes: Vec<u64> = lines.iter().map(|line| hash_line(line)).collect();
+
+        // Свернутые области ниже правки сдвигаются на число вставленных строк
+        let (old_len, new_len) = (self.hashes.len(), hashes.len());
+        let common = old_len.min(new_len);
+        let mut prefix = 0;
+        while prefix < common && self.hashes[prefix] == hashes[prefix] {
+            prefix += 1;
+        }
+        let mut suffix = 0;
+        while suffix < common - prefix && self.hashes[old_len - 1 - suffix] == hashes[new_len - 1 - suffix] {
+            suffix += 1;
+        }
+        let shift = |line: usize| {
+            if line < prefix {
+                Some(line)
+            } else if line >= old_len - suffix {
+                Some(line + new_len - old_len)
+            } else {
+                // Правка в самой строке ее не сдвигает
+                (line < new_len - suffix).then_some(line)
+            }
+        };
+        self.folded = self.folded.iter().filter_map(|&start| shift(start)).collect();
+        self.hashes = hashes;
+
+        // Области из дерева сдвигаются так же до прихода нового разбора
+        match &mut self.regions {
+            Some(regions) => {
+                let changed_end = (new_len - suffix).saturating_sub(1);
+                *regions = regions
+                    .iter()
+                    .filter_map(|&(start, end)| Some((shift(start)?, shift(end).unwrap_or(changed_end))))
+                    .filter(|(start, end)| start < end)
+                    .collect();
+                self.ranges = nested_ranges(regions);
+            }
+            None => self.ranges = indent_ranges(lines.iter().map(String::as_str)),
+        }
+        self.retain_folded();
+    }
+
+    // Области из синтаксического дерева для правки revision
+    // Результат для устаревшей правки пропускается: следом придет свежий
+    pub fn set_regions(&mut self, revision: u64, regions: &[(usize, usize)]) {
+        if self.revision != Some(revision) || self.regions_revision == Some(revision) {
+            return;
+        }
+        self.regions_revision = Some(revision);
+        self.regions = Some(regions.to_vec());
+        self.ranges = nested_ranges(regions);
+        self.retain_folded();
+    }
+
+    // Возвращает области по отступам (у языка нет грамматики)
+    // Они пересчитываются при следующем обновлении
+    pub fn clear_regions(&mut self) {
+        if self.regions.is_none() {
+            return;
+        }
+        self.regions = None;
+        self.regions_revision = None;
+        self.revision = None;
+    }
+
+    // Все области по возрастанию первой строки
+    pub fn ranges(&self) -> &[FoldRange] {
+        &self.ranges
+    }
+
+    // Область, которая начинается в строке
+    pub fn range_at(&self, start: usize) -> Option<&FoldRange> {
+        let index = self.ranges.binary_search_by_key(&start, |range| range.start).ok()?;
+        Some(&self.ranges[index])
+    }
+
+    pub fn is_folded(&self, start: usize) -> bool {
+        self.folded.contains(&start)
+    }
+
+    // Есть ли скрытые строки
+    pub fn has_folds(&self) -> bool {
+        !self.hidden.is_empty()
+    }
+
+    pub fn is_hidden(&self, line: usize) -> bool {
+        self.hidden_range(line).is_some()
+    }
+
+    // Внешняя свернутая область, которая скрывает строку: (заголовок, последняя строка)
+    pub fn hidden_range(&self, line: usize) -> Option<(usize, usize)> {
+        let index = self.hidden.partition_point(|&(from, _)| from <= line);
+        let (from, to) = *self.hidden.get(index.checked_sub(1)?)?;
+        (line <= to).then_some((from - 1, to))
+    }
+
+    // Номер состояния: разметка экрана пересчитывается, когда он меняется
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    // Сворачивает или разворачивает область с заголовком в строке
+    pub fn toggle(&mut self, start: usize) {
+        if self.range_at(start).is_none() {
+            return;
+        }
+        if !self.folded.remove(&start) {
+            self.folded.insert(start);
+        }
+        self.rebuild();
+    }
+
+    // Сворачивает самую внутреннюю несвернутую область, в которой стоит строка
+    pub fn fold_at(&mut self, line: usize) {
+        let range = self
+            .ranges
+            .iter()
+            .rfind(|range| range.start <= line && line <= range.end && !self.folded.contains(&range.start))
+            .copied();
+        if let Some(range) = range {
+            self.folded.insert(range.start);
+            self.rebuild();
+        }
+    }
+
+    // Разворачивает самую внутреннюю свернутую область, в которой стоит строка
+    pub fn unfold_at(&mut self, line: usize) {
+        let range = self
+            .ranges
+            .iter()
+            .rfind(|range| range.start <= line && line <= range.end && self.folded.contains(&range.start))
+            .copied();
+        if let Some(range) = range {
+            self.folded.remove(&range.start);
+            self.rebuild();
+        }
+    }
+
+    pub fn fold_all(&mut self) {
+        self.folded = self.ranges.iter().map(|range| range.start).collect();
+        self.rebuild();
+    }
+
+    pub fn unfold_all(&mut self) {
+        self.folded.clear();
+        self.rebuild();
+    }
+
+    // Оставляет открытыми level уровней вложенности, более глубокие области сворачиваются
+    pub fn fold_to_level(&mut self, level: usize) {
+        self.folded = self.ranges.iter().filter(|range| range.level > level).map(|range| range.start).collect();
+        self.rebuild();
+    }
+
+    // Разворачивает все области, скрывающие строку (например, с найденным текстом)
+    pub fn reveal(&mut self, line: usize) {
+        if !self.is_hidden(line) {
+            return;
+        }
+        let ranges = &self.ranges;
+        self.folded.retain(|&start| match ranges.binary_search_by_key(&start, |range| range.start) {
+            Ok(index) => !(start < line && line <= ranges[index].end),
+            Err(_) => false,
+        });
+        self.rebuild();
+    }
+
+    // Свернутыми остаются только существующие области
+    fn retain_folded(&mut self) {
+        let ranges = &self.ranges;
+        self.folded.retain(|&start| ranges.binary_search_by_key(&start, |range| range.start).is_ok());
+        self.rebuild();
+    }
+
+    // Собирает скрытые строки из свернутых областей
+    fn rebuild(&mut self) {
+        self.hidden.clear();
+        for &start in self.folded.iter() {
+            let Some(range) = self.range_at(start).copied() else { continue };
+            match self.hidden.last_mut() {
+                // Вложенная область уже скрыта внешней
+                Some((_, to)) if range.start <= *to => *to = (*to).max(range.end),
+                _ => self.hidden.push((range.start + 1, range.end)),
+            }
+        }
+        self.generation += 1;
+    }
+}
+
+impl Default for Folding {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn hash_line(text: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    text.hash(&mut hasher);
+    hasher.finish()
+}
+
+// Области из пар (первая строка, последняя строка) с уровнями вложенности
+fn nested_ranges(regions: &[(usize, usize)]) -> Vec<FoldRange> {
+    let mut sorted = regions.to_vec();
+    sorted.sort_by_key(|&(start, end)| (start, std::cmp::Reverse(end)));
+    sorted.dedup_by_key(|(start, _)| *start);
+    let mut open: Vec<usize> = Vec::new(); // последние строки объемлющих областей
+    sorted
+        .into_iter()
+        .map(|(start, end)| {
+            while open.last().is_some_and(|&last| last < start) {
+                open.pop();
+            }
+            open.push(end);
+            FoldRange { start, end, level: open.len() }
+        })
+        .collect()
+}
+
+// Области по отступам; пустые строки принадлежат области, если за ними она продолжается
+fn indent_ranges<'a>(lines: impl Iterator<Item = &'a str>) -> Vec<FoldRange> {
+    let mut ranges = Vec::new();
+    let mut open: Vec<(usize, usize)> = Vec::new(); // незакрытые строки: номер и отступ
+    let mut last_text = 0; // последняя непустая строка
+    for (index, line) in lines.enumerate() {
+        let text = line.trim_start();
+        if text.is_empty() {
+            continue;
+        }
+        let indent = line.len() - text.len();
+        while let Some(&(start, start_indent)) = open.last() {
+            if start_indent < indent {
+                break;
+            }
+            open.pop();
+            if last_text > start {
+                ranges.push(FoldRange { start, end: last_text, level: open.len() + 1 });
+            }
+        }
+        open.push((index, indent));
+        last_text = index;
+    }
+    while let Some((start, _)) = open.pop() {
+        if last_text > start {
+            ranges.push(FoldRange { start, end: last_text, level: open.len() + 1 });
+        }
+    }
+    ranges.sort_by_key(|range| range.start);
+    ranges
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::editor::fixtures::new_buffer;
+
+    const CODE: &str = "fn a() {\n    if x {\n        y\n    }\n\n    z\n}\nfn b() {\n    2\n}";
+
+    fn ranges(folding: &Folding) -> Vec<(usize, usize, usize)> {
+        folding.ranges().iter().map(|range| (range.start, range.end, range.level)).collect()
+    }
+
+    fn folding(text: &str) -> Folding {
+        let mut folding = Folding::new();
+        folding.update(&new_buffer(text));
+        folding
+    }
+
+    #[test]
+    fn ranges_by_indent() {
+        // Закрывающая скобка остается видимой, пустая строка внутри области ей принадлежит
+        assert_eq!(ranges(&folding(CODE)), [(0, 5, 1), (1, 2, 2), (7, 8, 1)]);
+        // Пустые строки в конце области в нее не входят
+        assert_eq!(ranges(&folding("a:\n  b\n\nc")), [(0, 1, 1)]);
+        assert_eq!(ranges(&folding("a\nb\n  \nc")), []);
+    }
+
+    #[test]
+    fn ranges_from_regions() {
+        let nested = nested_ranges(&[(5, 8), (0, 10), (1, 3), (1, 2), (12, 14)]);
+        let nested: Vec<_> = nested.iter().map(|range| (range.start, range.end, range.level)).collect();
+        // Из областей с одной первой строкой остается самая длинная
+        assert_eq!(nested, [(0, 10, 1), (1, 3, 2), (5, 8, 2), (12, 14, 1)]);
+
+        // Результат для устаревшей правки пропускается, до нового разбора области сдвигаются с текстом
+        let mut buffer = new_buffer(CODE);
+        let mut folding = Folding::new();
+        folding.update(&buffer);
+        folding.set_regions(buffer.revision() + 1, &[(0, 6)]);
+        assert_eq!(ranges(&folding), [(0, 5, 1), (1, 2, 2), (7, 8, 1)]);
+        folding.set_regions(buffer.revision(), &[(0, 6), (7, 9)]);
+        assert_eq!(ranges(&folding), [(0, 6, 1), (7, 9, 1)]);
+        buffer.set_text(format!("// new\n{}", CODE)).unwrap();
+        folding.update(&buffer);
+        assert_eq!(ranges(&folding), [(1, 7, 1), (8, 10, 1)]);
+        folding.clear_regions();
+        folding.update(&buffer);
+        assert_eq!(ranges(&folding), [(1, 6, 1), (2, 3, 2), (8, 9, 1)]);
+    }
+
+    #[test]
+    fn folds_follow_edits() {
+        let mut buffer = new_buffer("x\nfn a() {\n    1\n}\ny");
+        let mut folding = Folding::new();
+        folding.update(&buffer);
+        folding.toggle(1);
+        assert!(folding.is_hidden(2));
+        assert_eq!(folding.hidden_range(2), Some((1, 2)));
+
+        // Строки выше области сдвигают ее, правки ниже и в заголовке - нет
+        buffer.set_text("new\nnew\nx\nfn a() {\n    1\n}\ny".to_string()).unwrap();
+        folding.update(&buffer);
+        assert!(folding.is_folded(3) && folding.is_hidden(4) && !folding.is_hidden(2));
+        buffer.set_text("new\nnew\nx\nfn a() {\n    1\n}\nyy".to_string()).unwrap();
+        folding.update(&buffer);
+        assert!(folding.is_folded(3));
+        buffer.set_text("new\nnew\nx\nfn b() {\n    1\n}\nyy".to_string()).unwrap();
+        folding.update(&buffer);
+        assert!(folding.is_folded(3));
+
+        // Вместе с заголовком исчезает и свернутая область
+        buffer.set_text("new\nnew\nx\n    1\n}\nyy".to_string()).unwrap();
+        folding.update(&buffer);
+        assert!(!folding.has_folds());
+    }
+
+    #[test]
+    fn fold_to_level_and_reveal() {
+        let mut folding = folding(CODE);
+        folding.fold_to_level(1);
+        assert!(folding.is_folded(1) && !folding.is_folded(0) && !folding.is_folded(7));
+        assert!(folding.is_hidden(2) && !folding.is_hidden(3));
+        folding.fold_to_level(0);
+        assert!(folding.is_folded(0) && folding.is_folded(1) && folding.is_folded(7));
+        assert_eq!(folding.hidden_range(2), Some((0, 5)));
+
+        // Разворачиваются все области над строкой, остальные остаются свернутыми
+        folding.reveal(2);
+        assert!(!folding.is_folded(0) && !folding.is_folded(1) && folding.is_folded(7));
+        assert!(!folding.is_hidden(2));
+        let generation = folding.generation();
+        folding.reveal(2);
+        assert_eq!(folding.generation(), generation);
+
+        folding.fold_at(2);
+        assert!(folding.is_folded(1));
+        folding.fold_at(2);
+        assert!(folding.is_folded(0));
+        folding.unfold_at(2);
+        assert!(!folding.is_folded(1) && folding.is_folded(0));
+    }
+}
@@ -0,0 +1,53 @@
+// Отступы текста
+// Стиль отступов файла угадывается по ведущим пробелам строк: табуляция, если
+// с нее начинается больше строк, чем с пробелов; иначе ширина отступа - самая
+// частая разница отступов соседних непустых строк
+
+use std::collections::BTreeMap;
+
+// Самый большой шаг отступа, который считается шагом, а не выравниванием
+const MAX_INDENT_STEP: usize = 8;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum IndentStyle {
+    Tabs,
+    Spaces(usize), // ширина отступа
+}
+
+// Стиль отступов текста; None - в тексте нет отступов
+pub fn detect_indentation<'a>(lines: impl IntoIterator<Item = &'a str>) -> Option<IndentStyle> {
+    let mut tab_lines = 0;
+    let mut space_lines = 0;
+    let mut deltas: BTreeMap<usize, usize> = BTreeMap::new(); // шаг отступа -> сколько раз встретился
+    let mut previous = 0; // отступ пробелами предыдущей непустой строки
+    for line in lines {
+        let text = line.trim_start();
+        if text.is_empty() {
+            continue;
+        }
+        let indent = &line[..line.len() - text.len()];
+        if indent.starts_with('\t') {
+            tab_lines += 1;
+            continue;
+        }
+        let spaces = indent.chars().take_while(|&c| c == ' ').count();
+        if spaces > 0 {
+            space_lines += 1;
+        }
+        // Строки блочного комментария (" * ") сдвинуты на один пробел для выравнивания
+        if !text.starts_with('*') && spaces > previous && spaces - previous <= MAX_INDENT_STEP {
+            *deltas.entry(spaces - previous).or_default() += 1;
+        }
+        previous = spaces;
+    }
+
+    if tab_lines == 0 && space_lines == 0 {
+        return None;
+    }
+    if tab_lines > space_lines {
+        return Some(IndentStyle::Tabs);
+    }
+    // При равенстве побеждает меньший шаг: двойной отступ встречается реже
+    let (&width, _) = deltas.iter().rev().max_by_key(|&(_, &count)| count)?;
+    Some(IndentStyle::Spaces(width))
+}
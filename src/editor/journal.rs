@@ -0,0 +1,445 @@
+// Журнал правок
+// Каждая выполненная команда записывается с отметкой времени, поэтому по
+// журналу можно заново собрать документ и воспроизвести ошибку пользователя.
+// Журнал пишется в поток по одной записи, чтобы он сохранился и при падении:
+// JSON - по объекту на строку, двоичный формат - записи bincode с длиной впереди
+
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::editor::{BufferSettings, Command, CommentTokens, Editor, IndentRules, MemoryClipboard, Position, TabSettings};
+
+// Текущая версия схемы журнала
+// При несовместимом изменении Command или записей журнала версия увеличивается
+// 2 - в заголовке появились настройки буфера
+// 3 - смена настроек буфера записывается событием
+pub const JOURNAL_VERSION: u32 = 3;
+
+// Начало двоичного журнала
+const BINARY_MAGIC: &[u8; 4] = b"STEJ";
+
+// Формат файла журнала
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum JournalFormat {
+    Json,
+    Binary,
+}
+
+// Заголовок журнала: версия схемы и состояние документа в начале записи
+// Настройки буфера нужны, чтобы набор текста (табуляция, автоотступ, пары, комментарии)
+// при воспроизведении дал тот же результат
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct JournalHeader {
+    pub version: u32,
+    pub created: u64, // миллисекунды с начала эпохи Unix
+    pub lines: Vec<String>,
+    pub cursor: Position,
+    pub selection: Option<(Position, Position)>,
+    pub tabs: TabSettings,
+    pub indent_rules: IndentRules,
+    pub auto_pairs: Vec<(char, char)>,
+    pub comments: CommentTokens,
+}
+
+impl JournalHeader {
+    pub fn settings(&self) -> BufferSettings {
+        BufferSettings {
+            tabs: self.tabs,
+            indent_rules: self.indent_rules.clone(),
+            auto_pairs: self.auto_pairs.clone(),
+            comments: self.comments.clone(),
+        }
+    }
+}
+
+// Заголовок журнала версии 1: без настроек буфера, они считаются настройками по умолчанию
+#[derive(Deserialize)]
+struct HeaderV1 {
+    version: u32,
+    created: u64,
+    lines: Vec<String>,
+    cursor: Position,
+    selection: Option<(Position, Position)>,
+}
+
+impl From<HeaderV1> for JournalHeader {
+    fn from(header: HeaderV1) -> Self {
+        let settings = BufferSettings::default();
+        Self {
+            version: header.version,
+            created: header.created,
+            lines: header.lines,
+            cursor: header.cursor,
+            selection: header.selection,
+            tabs: settings.tabs,
+            indent_rules: settings.indent_rules,
+            auto_pairs: settings.auto_pairs,
+            comments: settings.comments,
+        }
+    }
+}
+
+// Событие журнала
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum JournalEvent {
+    // Команда; input - текст, полученный извне (буфер обмена, регистр, история),
+    // чтобы вставку можно было повторить без того же буфера обмена
+    Command { command: Command, input: Option<String> },
+    BeginTransaction,
+    CommitTransaction,
+    // Смена настроек буфера (другой язык, стиль отступов, строка #!)
+    Settings(BufferSettings),
+}
+
+// Запись журнала
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct JournalEntry {
+    pub timestamp: u64, // миллисекунды с начала эпохи Unix
+    pub event: JournalEvent,
+}
+
+// Прочитанный журнал
+#[derive(Clone, Debug, PartialEq)]
+pub struct Journal {
+    pub header: JournalHeader,
+    pub entries: Vec<JournalEntry>,
+}
+
+impl Journal {
+    // Читает журнал из файла, формат определяется по первым байтам
+    pub fn load(path: &Path) -> Result<Self> {
+        let data = fs::read(path).map_err(|e| anyhow!("Не удалось прочитать журнал {}: {}", path.display(), e))?;
+        Self::from_bytes(&data)
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        if data.starts_with(BINARY_MAGIC) {
+            Self::from_binary(&data[BINARY_MAGIC.len()..])
+        } else {
+            let text = std::str::from_utf8(data).map_err(|_| anyhow!("Журнал не является ни JSON, ни двоичным"))?;
+            Self::from_json(text)
+        }
+    }
+
+    fn from_json(text: &str) -> Result<Self> {
+        let mut lines = text.lines().filter(|line| !line.trim().is_empty());
+        let first = lines.next().ok_or_else(|| anyhow!("Журнал пуст"))?;
+        // Версия проверяется до разбора остального заголовка
+        let value: serde_json::Value = serde_json::from_str(first)?;
+        let version = value.get("version").and_then(|v| v.as_u64()).unwrap_or(0) as u32;
+        check_version(version)?;
+        let header: JournalHeader = match version {
+            1 => serde_json::from_value::<HeaderV1>(value)?.into(),
+            _ => serde_json::from_value(value)?,
+        };
+
+        let mut entries = Vec::new();
+        for (index, line) in lines.enumerate() {
+            match serde_json::from_str(line) {
+                Ok(entry) => entries.push(entry),
+                // Последняя строка могла не дописаться при падении
+                Err(e) if e.is_eof() => {
+                    log::warn!("Журнал обрывается на записи {}", index + 1);
+                    break;
+                }
+                Err(e) => return Err(anyhow!("Запись {} журнала повреждена: {}", index + 1, e)),
+            }
+        }
+        Ok(Self { header, entries })
+    }
+
+    fn from_binary(data: &[u8]) -> Result<Self> {
+        if data.len() < 4 {
+            return Err(anyhow!("Журнал обрывается в заголовке"));
+        }
+        let version = u32::from_le_bytes([data[0], data[1], data[2], data[3]]);
+        check_version(version)?;
+
+        let mut records = BinaryRecords { data: &data[4..] };
+        let header = records.next().ok_or_else(|| anyhow!("Журнал обрывается в заголовке"))?;
+        let header: JournalHeader = match version {
+            1 => bincode::deserialize::<HeaderV1>(header)?.into(),
+            _ => bincode::deserialize(header)?,
+        };
+        let mut entries = Vec::new();
+        for record in records {
+            entries.push(bincode::deserialize(record)?);
+        }
+        Ok(Self { header, entries })
+    }
+}
+
+// Журналы новее программы не читаются; заголовок версии 1 дополняется настройками по умолчанию,
+// а в версии 2 просто нет событий Settings
+fn check_version(version: u32) -> Result<()> {
+    if version == 0 || version > JOURNAL_VERSION {
+        return Err(anyhow!(
+            "Неподдерживаемая версия журнала {} (поддерживается до {})",
+            version,
+            JOURNAL_VERSION
+        ));
+    }
+    Ok(())
+}
+
+// Записи двоичного журнала: u32 длина (little endian) и данные
+// Недописанная последняя запись пропускается
+struct BinaryRecords<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Iterator for BinaryRecords<'a> {
+    type Item = &'a [u8];
+
+    fn next(&mut self) -> Option<&'a [u8]> {
+        if self.data.len() < 4 {
+            return None;
+        }
+        let length = u32::from_le_bytes([self.data[0], self.data[1], self.data[2], self.data[3]]) as usize;
+        let record = self.data.get(4..4 + length)?;
+        self.data = &self.data[4 + length..];
+        Some(record)
+    }
+}
+
+// Пишет журнал в поток
+pub struct JournalWriter {
+    output: Box<dyn Write + Send>,
+    format: JournalFormat,
+}
+
+impl JournalWriter {
+    // Начинает журнал в потоке и записывает заголовок
+    pub fn new(output: Box<dyn Write + Send>, format: JournalFormat, header: &JournalHeader) -> Result<Self> {
+        let mut writer = Self { output, format };
+        if format == JournalFormat::Binary {
+            writer.output.write_all(BINARY_MAGIC)?;
+            writer.output.write_all(&header.version.to_le_bytes())?;
+        }
+        writer.write_record(header)?;
+        Ok(writer)
+    }
+
+    // Записывает событие и сразу сбрасывает его на диск
+    pub fn append(&mut self, entry: &JournalEntry) -> Result<()> {
+        self.write_record(entry)
+    }
+
+    fn write_record<T: Serialize>(&mut self, value: &T) -> Result<()> {
+        match self.format {
+            JournalFormat::Json => {
+                let mut line = serde_json::to_vec(value)?;
+                line.push(b'\n');
+                self.output.write_all(&line)?;
+            }
+            JournalFormat::Binary => {
+                let record = bincode::serialize(value)?;
+                self.output.write_all(&(record.len() as u32).to_le_bytes())?;
+                self.output.write_all(&record)?;
+            }
+        }
+        self.output.flush()?;
+        Ok(())
+    }
+}
+
+// Текущее время в миллисекундах с начала эпохи Unix
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+// ---------- Запись журнала редактором ----------
+
+impl Editor {
+    // Начинает журнал правок с текущего состояния документа
+    pub fn start_journal(&mut self, output: Box<dyn Write + Send>, format: JournalFormat) -> Result<()> {
+        let settings = self.buffer.settings();
+        let header = JournalHeader {
+            version: JOURNAL_VERSION,
+            created: now(),
+            lines: self.buffer.get_all_lines().iter().cloned().collect(),
+            cursor: self.buffer.get_cursor_position(),
+            selection: self.buffer.get_selection(),
+            tabs: settings.tabs,
+            indent_rules: settings.indent_rules,
+            auto_pairs: settings.auto_pairs,
+            comments: settings.comments,
+        };
+        self.journal = Some(JournalWriter::new(output, format, &header)?);
+        Ok(())
+    }
+
+    // Начинает журнал правок в файле
+    pub fn start_journal_file(&mut self, path: &Path, format: JournalFormat) -> Result<()> {
+        let file = File::create(path).map_err(|e| anyhow!("Не удалось создать журнал {}: {}", path.display(), e))?;
+        self.start_journal(Box::new(BufWriter::new(file)), format)
+    }
+
+    // Записывает событие; при ошибке записи журнал отключается, а правка продолжается
+    pub(crate) fn journal_event(&mut self, event: JournalEvent) {
+        if let Some(journal) = self.journal.as_mut() {
+            let entry = JournalEntry { timestamp: now(), event };
+            if let Err(e) = journal.append(&entry) {
+                log::error!("Журнал правок отключен: {}", e);
+                self.journal = None;
+            }
+        }
+    }
+
+    // Текст, который команда возьмет извне редактора
+    pub(crate) fn command_input(&mut self, command: &Command) -> Option<String> {
+        self.journal.as_ref()?;
+        match command {
+            Command::Paste => self.kill_ring_entry(0),
+            Command::PasteHistory(index) | Command::PasteDistributed(index) => self.kill_ring_entry(*index),
+            Command::PasteRegister(name) => self.register(*name).map(|register| register.text),
+            _ => None,
+        }
+    }
+}
+
+// ---------- Воспроизведение ----------
+
+// Собирает документ заново по журналу
+// limit - сколько записей воспроизвести (None - все), чтобы найти шаг с ошибкой
+pub fn replay(journal: &Journal, limit: Option<usize>) -> Result<Editor> {
+    let mut editor = Editor::with_clipboard(Box::new(MemoryClipboard::new()));
+    let header = &journal.header;
+    editor.set_buffer_settings(header.settings());
+    editor.buffer.set_lines(header.lines.clone()).map_err(|e| anyhow!(e))?;
+    editor.buffer.set_cursor_position(header.cursor).map_err(|e| anyhow!(e))?;
+    if let Some((start, end)) = header.selection {
+        editor.buffer.set_selection(start, end).map_err(|e| anyhow!(e))?;
+    }
+
+    let count = limit.unwrap_or(journal.entries.len()).min(journal.entries.len());
+    for (index, entry) in journal.entries[..count].iter().enumerate() {
+        match &entry.event {
+            JournalEvent::BeginTransaction => editor.begin_transaction(),
+            JournalEvent::CommitTransaction => editor.commit_transaction(),
+            JournalEvent::Settings(settings) => editor.set_buffer_settings(settings.clone()),
+            JournalEvent::Command { command, input } => {
+                let command = match input {
+                    // Внешний текст кладется в историю буфера обмена и вставляется оттуда
+                    Some(text) => {
+                        editor.kill_ring_push(text.clone(), false, false);
+                        match command {
+                            Command::PasteDistributed(_) => Command::PasteDistributed(0),
+                            _ => Command::Paste,
+                        }
+                    }
+                    None => command.clone(),
+                };
+                // Команды, завершившиеся ошибкой при записи, так же завершатся ошибкой здесь
+                if let Err(e) = editor.execute_command(command) {
+                    log::debug!("Запись {} журнала: {}", index + 1, e);
+                }
+            }
+        }
+    }
+    Ok(editor)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+    use crate::editor::fixtures::{position, text};
+    use crate::editor::Selection;
+
+    // Поток, содержимое которого остается доступным после передачи журналу
+    #[derive(Clone, Default)]
+    struct Shared(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, data: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(data);
+            Ok(data.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    // Правки, результат которых зависит от настроек буфера и буфера обмена
+    fn session(format: JournalFormat) -> (Editor, Vec<u8>) {
+        let mut editor = Editor::with_clipboard(Box::new(MemoryClipboard::new()));
+        let buffer = editor.get_buffer_mut();
+        buffer.set_tab_settings(TabSettings { width: 8, use_tabs: true });
+        buffer.set_indent_rules(IndentRules { openers: vec![':'], brackets: Vec::new() });
+        buffer.set_auto_pairs(vec![('(', ')')]);
+        buffer.set_comment_tokens(CommentTokens { line: Some("#".to_string()), block: None });
+        buffer.set_lines(vec!["def f".to_string(), "мир".to_string()]).unwrap();
+
+        let output = Shared::default();
+        editor.start_journal(Box::new(output.clone()), format).unwrap();
+        editor.execute_command(Command::MoveCursor(position(0, 5))).unwrap();
+        editor.execute_command(Command::InsertChar('(')).unwrap();
+        editor.execute_command(Command::MoveCursor(position(0, 7))).unwrap();
+        editor.execute_command(Command::InsertChar(':')).unwrap();
+        editor.execute_command(Command::InsertNewline).unwrap();
+        editor.set_clipboard_text(Selection::Clipboard, "pass".to_string());
+        editor.execute_command(Command::Paste).unwrap();
+        editor.begin_transaction();
+        editor.execute_command(Command::MoveCursor(position(2, 0))).unwrap();
+        editor.execute_command(Command::ToggleLineComment).unwrap();
+        editor.commit_transaction();
+        // Смена настроек после начала журнала (например, выбран другой стиль отступов)
+        editor.set_buffer_settings(BufferSettings { tabs: TabSettings { width: 2, use_tabs: false }, ..editor.get_buffer().settings() });
+        editor.execute_command(Command::MoveCursor(position(2, 0))).unwrap();
+        editor.execute_command(Command::InsertChar('\t')).unwrap();
+        let bytes = output.0.lock().unwrap().clone();
+        (editor, bytes)
+    }
+
+    #[test]
+    fn replay_restores_text_and_settings() {
+        for format in [JournalFormat::Json, JournalFormat::Binary] {
+            let (editor, bytes) = session(format);
+            assert_eq!(text(&editor), "def f():\n\tpass\n  # мир");
+
+            let journal = Journal::from_bytes(&bytes).unwrap();
+            assert_eq!(journal.header.version, JOURNAL_VERSION);
+            assert_eq!(journal.header.tabs, TabSettings { width: 8, use_tabs: true });
+            assert_eq!(journal.header.auto_pairs, vec![('(', ')')]);
+            let replayed = replay(&journal, None).unwrap();
+            assert_eq!(text(&replayed), text(&editor));
+            assert_eq!(replayed.get_buffer().settings(), editor.get_buffer().settings());
+            assert!(journal.entries.iter().any(|entry| matches!(entry.event, JournalEvent::Settings(_))));
+
+            // Недописанная последняя запись пропускается
+            let cut = Journal::from_bytes(&bytes[..bytes.len() - 3]).unwrap();
+            assert_eq!(cut.entries.len(), journal.entries.len() - 1);
+            assert_eq!(text(&replay(&journal, Some(2)).unwrap()), "def f()\nмир");
+        }
+    }
+
+    #[test]
+    fn header_versions() {
+        let v1 = "{\"version\":1,\"created\":0,\"lines\":[\"a\"],\"cursor\":{\"line\":0,\"column\":1},\"selection\":null}\n\
+                  {\"timestamp\":0,\"event\":{\"Command\":{\"command\":{\"InsertChar\":\"\\t\"},\"input\":null}}}\n";
+        let journal = Journal::from_bytes(v1.as_bytes()).unwrap();
+        assert_eq!(journal.header.tabs, TabSettings::default());
+        assert_eq!(text(&replay(&journal, None).unwrap()), "a   ");
+
+        // В версии 2 заголовок тот же, но нет событий Settings
+        let v2 = "{\"version\":2,\"created\":0,\"lines\":[\"a\"],\"cursor\":{\"line\":0,\"column\":1},\"selection\":null,\
+                  \"tabs\":{\"width\":8,\"use_tabs\":true},\"indent_rules\":{\"openers\":[],\"brackets\":[]},\
+                  \"auto_pairs\":[],\"comments\":{\"line\":null,\"block\":null}}\n";
+        let journal = Journal::from_bytes(v2.as_bytes()).unwrap();
+        assert_eq!(replay(&journal, None).unwrap().get_buffer().tab_settings(), TabSettings { width: 8, use_tabs: true });
+
+        let future = v1.replacen("\"version\":1", "\"version\":99", 1);
+        assert!(Journal::from_bytes(future.as_bytes()).unwrap_err().to_string().contains("99"));
+    }
+}
@@ -0,0 +1,114 @@
+// Нажатие клавиши, не зависящее от графической библиотеки
+// Используется режимами редактирования (Vim, Emacs), чтобы их можно было
+// вызывать и из интерфейса, и из кода без окна
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Key {
+    Char(char),  // Обычный символ (с учетом Shift)
+    Ctrl(char),  // Ctrl + символ, символ в нижнем регистре
+    Alt(char),   // Alt (Meta) + символ
+    Enter,       // Ввод
+    Esc,         // Escape
+    Backspace,   // Удаление символа слева
+    Delete,      // Удаление символа справа
+    Tab,         // Табуляция
+    Left,        // Стрелка влево
+    Right,       // Стрелка вправо
+    Up,          // Стрелка вверх
+    Down,        // Стрелка вниз
+    Home,        // Начало строки
+    End,         // Конец строки
+}
+
+impl Key {
+    // Разбирает последовательность клавиш в нотации Vim: "d3w", "<Esc>", "<C-r>"
+    // Используется для :normal и для записи макросов в текстовом виде
+    pub fn parse_sequence(text: &str) -> Vec<Key> {
+        let mut keys = Vec::new();
+        let mut chars = text.chars().peekable();
+        while let Some(c) = chars.next() {
+            if c != '<' {
+                keys.push(Key::Char(c));
+                continue;
+            }
+            // Пробуем прочитать имя клавиши до '>'
+            let rest: String = chars.clone().collect();
+            match rest.find('>') {
+                Some(end) => {
+                    let name = &rest[..end];
+                    match Key::from_name(name) {
+                        Some(key) => {
+                            keys.push(key);
+                            for _ in 0..=name.chars().count() {
+                                chars.next();
+                            }
+                        }
+                        None => keys.push(Key::Char('<')),
+                    }
+                }
+                None => keys.push(Key::Char('<')),
+            }
+        }
+        keys
+    }
+
+    // Записывает последовательность клавиш в нотации Vim
+    pub fn format_sequence(keys: &[Key]) -> String {
+        keys.iter().map(|key| key.to_string()).collect()
+    }
+
+    // Находит клавишу по имени без угловых скобок: "Esc", "CR", "C-r", "M-y"
+    fn from_name(name: &str) -> Option<Key> {
+        let key = match name.to_lowercase().as_str() {
+            "esc" => Key::Esc,
+            "cr" | "enter" | "return" => Key::Enter,
+            "bs" | "backspace" => Key::Backspace,
+            "del" | "delete" => Key::Delete,
+            "tab" => Key::Tab,
+            "left" => Key::Left,
+            "right" => Key::Right,
+            "up" => Key::Up,
+            "down" => Key::Down,
+            "home" => Key::Home,
+            "end" => Key::End,
+            "lt" => Key::Char('<'),
+            "space" => Key::Char(' '),
+            _ => {
+                // Сочетания с модификатором: C-x, M-x, A-x
+                let (modifier, rest) = name.split_once('-')?;
+                let mut rest_chars = rest.chars();
+                let c = rest_chars.next()?;
+                if rest_chars.next().is_some() {
+                    return None;
+                }
+                match modifier {
+                    "C" | "c" => Key::Ctrl(c.to_ascii_lowercase()),
+                    "M" | "m" | "A" | "a" => Key::Alt(c),
+                    _ => return None,
+                }
+            }
+        };
+        Some(key)
+    }
+}
+
+impl std::fmt::Display for Key {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Key::Char('<') => write!(f, "<lt>"),
+            Key::Char(c) => write!(f, "{}", c),
+            Key::Ctrl(c) => write!(f, "<C-{}>", c),
+            Key::Alt(c) => write!(f, "<M-{}>", c),
+            Key::Enter => write!(f, "<CR>"),
+            Key::Esc => write!(f, "<Esc>"),
+            Key::Backspace => write!(f, "<BS>"),
+            Key::Delete => write!(f, "<Del>"),
+            Key::Tab => write!(f, "<Tab>"),
+            Key::Left => write!(f, "<Left>"),
+            Key::Right => write!(f, "<Right>"),
+            Key::Up => write!(f, "<Up>"),
+            Key::Down => write!(f, "<Down>"),
+            Key::Home => write!(f, "<Home>"),
+            Key::End => write!(f, "<End>"),
+        }
+    }
+}
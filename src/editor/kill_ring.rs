@@ -0,0 +1,73 @@
+// Кольцо удалений (kill ring) в стиле Emacs
+// Хранит последние скопированные и вырезанные фрагменты, самый новый - первый
+
+use std::collections::VecDeque;
+
+// Сколько фрагментов хранится по умолчанию (как kill-ring-max в Emacs)
+pub const DEFAULT_CAPACITY: usize = 120;
+
+pub struct KillRing {
+    entries: VecDeque<String>, // фрагменты, самый новый в начале
+    capacity: usize,           // максимальное число фрагментов
+}
+
+impl KillRing {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: VecDeque::new(),
+            capacity: capacity.max(1),
+        }
+    }
+
+    // Добавляет новый фрагмент в начало кольца
+    pub fn push(&mut self, text: String) {
+        if text.is_empty() {
+            return;
+        }
+        self.entries.push_front(text);
+        self.entries.truncate(self.capacity);
+    }
+
+    // Дописывает текст к последнему фрагменту (для подряд идущих удалений)
+    // prepend - текст добавляется в начало, как при удалении назад
+    pub fn append(&mut self, text: &str, prepend: bool) {
+        match self.entries.front_mut() {
+            Some(latest) if prepend => latest.insert_str(0, text),
+            Some(latest) => latest.push_str(text),
+            None => self.push(text.to_string()),
+        }
+    }
+
+    // Самый новый фрагмент
+    pub fn latest(&self) -> Option<&String> {
+        self.entries.front()
+    }
+
+    // Фрагмент по номеру, 0 - самый новый; номер берется по кругу
+    pub fn get(&self, index: usize) -> Option<&String> {
+        if self.is_empty() {
+            return None;
+        }
+        self.entries.get(index % self.len())
+    }
+
+    // Число фрагментов в кольце
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    // Все фрагменты, самый новый первым
+    pub fn entries(&self) -> impl Iterator<Item = &String> {
+        self.entries.iter()
+    }
+}
+
+impl Default for KillRing {
+    fn default() -> Self {
+        Self::new(DEFAULT_CAPACITY)
+    }
+}
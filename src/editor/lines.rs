@@ -0,0 +1,826 @@
+// Построчные команды: копия, перенос вверх и вниз, удаление и объединение строк,
+// сортировка, обращение порядка, удаление повторов, перемешивание и комментарии.
+// Команда работает со строками, которых касаются выделение и курсоры,
+// а без них - со строкой курсора. Курсоры и выделение идут вместе со своими строками
+
+use std::cmp::Ordering;
+
+use serde::{Deserialize, Serialize};
+
+use crate::editor::{Buffer, Position};
+
+// Порядок сортировки строк
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SortOrder {
+    Natural,         // числа внутри строк сравниваются по значению: file2 < file10
+    Numeric,         // по первому числу строки, строки без чисел - в начале
+    CaseInsensitive, // без учета регистра
+    Locale,          // по алфавиту: ё рядом с е, строчные перед прописными
+}
+
+impl SortOrder {
+    pub const ALL: [SortOrder; 4] = [SortOrder::Natural, SortOrder::Numeric, SortOrder::CaseInsensitive, SortOrder::Locale];
+
+    // Имя в тексте макроса
+    pub fn name(&self) -> &'static str {
+        match self {
+            SortOrder::Natural => "natural",
+            SortOrder::Numeric => "numeric",
+            SortOrder::CaseInsensitive => "case-insensitive",
+            SortOrder::Locale => "locale",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|order| order.name() == name)
+    }
+
+    // Название для меню
+    pub fn title(&self) -> &'static str {
+        match self {
+            SortOrder::Natural => "Естественный порядок",
+            SortOrder::Numeric => "По числам",
+            SortOrder::CaseInsensitive => "Без учета регистра",
+            SortOrder::Locale => "По алфавиту",
+        }
+    }
+
+    fn compare(&self, a: &str, b: &str) -> Ordering {
+        match self {
+            SortOrder::Natural => natural_compare(a, b),
+            SortOrder::Numeric => {
+                let (x, y) = (first_number(a), first_number(b));
+                match (x, y) {
+                    (Some(x), Some(y)) => x.total_cmp(&y),
+                    _ => x.is_some().cmp(&y.is_some()),
+                }
+                .then_with(|| a.cmp(b))
+            }
+            SortOrder::CaseInsensitive => a.to_lowercase().cmp(&b.to_lowercase()).then_with(|| a.cmp(b)),
+            SortOrder::Locale => locale_key(a).cmp(&locale_key(b)).then_with(|| a.cmp(b)),
+        }
+    }
+}
+
+// Курсоры и выделение до команды
+pub(super) struct Marks {
+    cursor: Position,
+    selection: Option<(Position, Position)>,
+    extra: Vec<Position>,
+    edited: bool, // строки правились на месте (edit_line), номер правки еще не увеличен
+}
+
+impl Marks {
+    fn positions_mut(&mut self) -> impl Iterator<Item = &mut Position> {
+        let selection = self.selection.iter_mut().flat_map(|(start, end)| [start, end]);
+        std::iter::once(&mut self.cursor).chain(selection).chain(self.extra.iter_mut())
+    }
+}
+
+impl Buffer {
+    // Отрезки строк (первая, последняя), которых касаются выделение и курсоры,
+    // по порядку; соседние и пересекающиеся отрезки объединены
+    // Строка, в начале которой кончается выделение, не считается
+    pub fn touched_lines(&self) -> Vec<(usize, usize)> {
+        let cursor = self.get_cursor_position();
+        let selection = self.get_selection().filter(|(start, end)| start != end);
+        let mut ranges: Vec<(usize, usize)> = self
+            .get_cursors()
+            .into_iter()
+            // Основной курсор - один из концов выделения, его строки задает выделение
+            .filter(|&position| selection.is_none() || position != cursor)
+            .map(|position| (position.line, position.line))
+            .collect();
+        if let Some((start, end)) = selection {
+            let (start, end) = (start.min(end), start.max(end));
+            let last = if end.line > start.line && end.column == 0 { end.line - 1 } else { end.line };
+            ranges.push((start.line, last));
+        }
+        ranges.sort();
+        let mut merged: Vec<(usize, usize)> = Vec::new();
+        for (first, last) in ranges {
+            match merged.last_mut() {
+                Some(previous) if first <= previous.1 + 1 => previous.1 = previous.1.max(last),
+                _ => merged.push((first, last)),
+            }
+        }
+        merged
+    }
+
+    // Копия строк ниже них; выделение внутри строк копируется сразу за собой
+    // Курсоры и выделение переходят на копию
+    pub fn duplicate_lines(&mut self) -> Result<(), String> {
+        let marks = self.marks();
+        if let (Some((start, end)), true) = (marks.selection, marks.extra.is_empty()) {
+            if start != end {
+                return self.duplicate_selection(start, end);
+            }
+        }
+        let blocks = self.touched_lines();
+        for &(first, last) in blocks.iter().rev() {
+            let copy: Vec<String> = self.get_all_lines().range(first..=last).cloned().collect();
+            self.splice_lines(last + 1, 0, copy)?;
+        }
+        let shift = |line: usize| -> usize {
+            line + blocks.iter().filter(|&&(first, _)| first <= line).map(|&(first, last)| last - first + 1).sum::<usize>()
+        };
+        self.restore_marks(marks, |position| Position { line: shift(position.line), column: position.column })
+    }
+
+    // Переносит строки на одну вверх или вниз; у края буфера ничего не происходит
+    pub fn move_lines(&mut self, up: bool) -> Result<(), String> {
+        let blocks = self.touched_lines();
+        let line_count = self.get_all_lines().len();
+        let at_edge = match up {
+            true => blocks.first().is_some_and(|&(first, _)| first == 0),
+            false => blocks.last().is_some_and(|&(_, last)| last + 1 >= line_count),
+        };
+        if blocks.is_empty() || at_edge {
+            return Ok(());
+        }
+        let marks = self.marks();
+        // Строка рядом с отрезком перескакивает на другую его сторону
+        for &(first, last) in blocks.iter() {
+            let (from, to) = if up { (first - 1, last) } else { (last + 1, first) };
+            let line = self.get_all_lines()[from].clone();
+            self.splice_lines(from, 1, Vec::new())?;
+            self.splice_lines(to, 0, vec![line])?;
+        }
+        // Конец выделения в начале строки после отрезка остается за отрезком
+        let selection_end = marks.selection.filter(|(start, end)| start != end).map(|(start, end)| start.max(end));
+        let after_block = |position: Position| {
+            Some(position) == selection_end && position.column == 0 && blocks.iter().any(|&(_, last)| position.line == last + 1)
+        };
+        let moved = |line: usize| -> usize {
+            for &(first, last) in blocks.iter() {
+                match up {
+                    true if (first..=last).contains(&line) => return line - 1,
+                    true if line + 1 == first => return last,
+                    false if (first..=last).contains(&line) => return line + 1,
+                    false if line == last + 1 => return first,
+                    _ => {}
+                }
+            }
+            line
+        };
+        self.restore_marks(marks, |position| match (after_block(position), up) {
+            (true, true) => Position { line: position.line - 1, column: 0 },
+            // За последней строкой буфера - ее конец
+            (true, false) if position.line + 1 >= line_count => Position { line: line_count - 1, column: usize::MAX },
+            (true, false) => Position { line: position.line + 1, column: 0 },
+            (false, _) => Position { line: moved(position.line), column: position.column },
+        })
+    }
+
+    // Удаляет строки; курсоры остаются на месте удаленных строк, выделение снимается
+    pub fn delete_lines(&mut self) -> Result<(), String> {
+        let blocks = self.touched_lines();
+        let mut marks = self.marks();
+        marks.selection = None;
+        for &(first, last) in blocks.iter().rev() {
+            self.splice_lines(first, last - first + 1, Vec::new())?;
+        }
+        let removed_before = |line: usize| -> usize {
+            blocks.iter().filter(|&&(first, _)| first < line).map(|&(first, last)| last.min(line - 1) - first + 1).sum()
+        };
+        self.restore_marks(marks, |position| Position { line: position.line - removed_before(position.line), column: position.column })
+    }
+
+    // Объединяет строки отрезка, а строку без выделения - со следующей
+    // Пробелы на стыке сводятся к одному; перед ) и после пустой строки пробела нет
+    pub fn join_lines(&mut self) -> Result<(), String> {
+        let line_count = self.get_all_lines().len();
+        let blocks: Vec<(usize, usize)> = self
+            .touched_lines()
+            .into_iter()
+            .map(|(first, last)| (first, if first == last { last + 1 } else { last }))
+            .filter(|&(_, last)| last < line_count)
+            .collect();
+        if blocks.is_empty() {
+            return Ok(());
+        }
+        let mut marks = self.marks();
+        marks.selection = None;
+        let mut joints = Vec::new(); // столбец последнего стыка каждого отрезка
+        for &(first, last) in blocks.iter().rev() {
+            let lines = self.get_all_lines();
+            let mut joined = lines[first].trim_end().to_string();
+            let mut joint = joined.len();
+            for line in lines.range(first + 1..=last) {
+                let line = line.trim();
+                if line.is_empty() {
+                    continue;
+                }
+                joint = joined.len();
+                if !joined.is_empty() && !line.starts_with(')') {
+                    joined.push(' ');
+                }
+                joined.push_str(line);
+            }
+            self.splice_lines(first, last - first + 1, vec![joined])?;
+            joints.push(joint);
+        }
+        joints.reverse();
+        let joined = |position: Position| -> Position {
+            let mut removed = 0;
+            for (&(first, last), &joint) in blocks.iter().zip(joints.iter()) {
+                if position.line > last {
+                    removed += last - first;
+                } else if position.line >= first {
+                    return Position { line: first - removed, column: joint };
+                }
+            }
+            Position { line: position.line - removed, column: position.column }
+        };
+        self.restore_marks(marks, joined)
+    }
+
+    // Сортирует строки; одинаковые по порядку строки остаются в прежнем порядке
+    pub fn sort_lines(&mut self, order: SortOrder) -> Result<(), String> {
+        self.rearrange_lines(|lines| lines.sort_by(|a, b| order.compare(a, b)))
+    }
+
+    // Обращает порядок строк
+    pub fn reverse_lines(&mut self) -> Result<(), String> {
+        self.rearrange_lines(|lines| lines.reverse())
+    }
+
+    // Оставляет первое вхождение каждой строки
+    pub fn unique_lines(&mut self) -> Result<(), String> {
+        self.rearrange_lines(|lines| {
+            let mut seen = std::collections::HashSet::new();
+            lines.retain(|line| seen.insert(line.clone()));
+        })
+    }
+
+    // Перемешивает строки; одно и то же seed дает один и тот же порядок (повтор из журнала)
+    pub fn shuffle_lines(&mut self, seed: u64) -> Result<(), String> {
+        self.rearrange_lines(|lines| {
+            let mut random = Random::new(seed);
+            for index in (1..lines.len()).rev() {
+                lines.swap(index, random.below(index + 1));
+            }
+        })
+    }
+
+    // Переставляет затронутые строки как один список: строки курсоров на разных
+    // местах меняются местами между собой. Если строк стало меньше, они занимают
+    // первые места, а оставшиеся места удаляются
+    fn rearrange_lines(&mut self, change: impl FnOnce(&mut Vec<String>)) -> Result<(), String> {
+        let marks = self.marks();
+        let slots: Vec<usize> = self.touched_lines().into_iter().flat_map(|(first, last)| first..=last).collect();
+        let mut lines: Vec<String> = slots.iter().map(|&slot| self.get_all_lines()[slot].clone()).collect();
+        change(&mut lines);
+        if slots.iter().zip(lines.iter()).all(|(&slot, line)| self.get_all_lines()[slot] == *line) && lines.len() == slots.len() {
+            return Ok(());
+        }
+        let removed = &slots[lines.len()..];
+        // Каждый отрезок заменяется своей частью списка за одну правку, снизу вверх,
+        // чтобы укоротившийся отрезок не сдвигал еще не замененные
+        let mut parts = Vec::new();
+        let mut rest = lines.into_iter();
+        for (first, last) in self.touched_lines() {
+            let part: Vec<String> = rest.by_ref().take(last - first + 1).collect();
+            parts.push((first, last - first + 1, part));
+        }
+        for (first, count, part) in parts.into_iter().rev() {
+            self.splice_lines(first, count, part)?;
+        }
+        let shift = |position: Position| {
+            let line = position.line - removed.iter().filter(|&&slot| slot < position.line).count();
+            Position { line, column: position.column }
+        };
+        self.restore_marks(marks, shift)
+    }
+
+    // Включает или снимает строчный комментарий в каждом отрезке строк
+    // Если закомментированы все непустые строки отрезка, комментарий снимается,
+    // иначе добавляется ко всем на уровне самого левого отступа
+    // Без строчного комментария в языке строки обрамляются блочным
+    pub fn toggle_line_comments(&mut self) -> Result<(), String> {
+        let Some(token) = self.comment_tokens().line.clone() else {
+            return match self.comment_tokens().block.clone() {
+                Some((open, close)) => self.toggle_block_ranges(self.line_ranges(false), &open, &close),
+                None => Err("Для языка не заданы комментарии".to_string()),
+            };
+        };
+        let mut marks = self.marks();
+        for (first, last) in self.touched_lines() {
+            let lines: Vec<(usize, usize)> = (first..=last)
+                .map(|line| (line, &self.get_all_lines()[line]))
+                .filter(|(_, text)| !text.trim().is_empty())
+                .map(|(line, text)| (line, text.len() - text.trim_start().len()))
+                .collect();
+            let commented = lines.iter().all(|&(line, indent)| self.get_all_lines()[line][indent..].starts_with(token.as_str()));
+            if commented {
+                for &(line, indent) in lines.iter() {
+                    let space = self.get_all_lines()[line][indent + token.len()..].starts_with(' ');
+                    self.edit_line(&mut marks, Position { line, column: indent }, token.len() + space as usize, "", false)?;
+                }
+            } else {
+                let column = lines.iter().map(|&(_, indent)| indent).min().unwrap_or(0);
+                for &(line, _) in lines.iter() {
+                    self.edit_line(&mut marks, Position { line, column }, 0, &format!("{} ", token), true)?;
+                }
+            }
+        }
+        self.restore_marks(marks, |position| position)
+    }
+
+    // Включает или снимает блочный комментарий вокруг выделения, а без выделения -
+    // вокруг текста строки каждого курсора. Текст, который уже начинается и
+    // кончается знаками комментария, освобождается от них
+    // Без блочного комментария в языке строки комментируются строчным
+    pub fn toggle_block_comments(&mut self) -> Result<(), String> {
+        match self.comment_tokens().block.clone() {
+            Some((open, close)) => self.toggle_block_ranges(self.line_ranges(true), &open, &close),
+            None => self.toggle_line_comments(),
+        }
+    }
+
+    // Отрезки для блочного комментария: выделение (если with_selection) и текст строк
+    // курсоров без отступа; строки в пределах выделения не повторяются
+    fn line_ranges(&self, with_selection: bool) -> Vec<(Position, Position)> {
+        let cursor = self.get_cursor_position();
+        let selection = self.get_selection().filter(|(start, end)| with_selection && start != end);
+        let mut ranges: Vec<(Position, Position)> = selection.map(|(start, end)| (start.min(end), start.max(end))).into_iter().collect();
+        let lines: Vec<usize> = match with_selection {
+            true => self.get_cursors().into_iter().filter(|&position| selection.is_none() || position != cursor).map(|position| position.line).collect(),
+            false => self.touched_lines().into_iter().flat_map(|(first, last)| first..=last).collect(),
+        };
+        for line in lines {
+            let text = &self.get_all_lines()[line];
+            let covered = ranges.iter().any(|(start, end)| start.line <= line && line <= end.line);
+            if text.trim().is_empty() || covered {
+                continue;
+            }
+            let start = Position { line, column: text.len() - text.trim_start().len() };
+            ranges.push((start, Position { line, column: text.trim_end().len() }));
+        }
+        ranges
+    }
+
+    // Обрамляет каждый отрезок знаками open и close или снимает их
+    fn toggle_block_ranges(&mut self, mut ranges: Vec<(Position, Position)>, open: &str, close: &str) -> Result<(), String> {
+        let mut marks = self.marks();
+        // Снизу вверх: правка не сдвигает еще не обработанные отрезки
+        ranges.sort();
+        for &(start, end) in ranges.iter().rev() {
+            let text = self.get_text_in_range(start, end)?;
+            let inner = text.trim();
+            let lead = text.len() - text.trim_start().len();
+            if inner.len() >= open.len() + close.len() && inner.starts_with(open) && inner.ends_with(close) {
+                // Вместе со знаком снимается один пробел внутри комментария
+                let close_at = lead + inner.len() - close.len();
+                let space_before = text[..close_at].ends_with(' ') && close_at > lead + open.len();
+                let close_from = close_at - space_before as usize;
+                self.edit_line(&mut marks, advance(start, &text[..close_from]), close_at + close.len() - close_from, "", false)?;
+                let space_after = text[lead + open.len()..close_from].starts_with(' ');
+                self.edit_line(&mut marks, advance(start, &text[..lead]), open.len() + space_after as usize, "", false)?;
+            } else {
+                self.edit_line(&mut marks, end, 0, &format!(" {}", close), true)?;
+                self.edit_line(&mut marks, start, 0, &format!("{} ", open), true)?;
+            }
+        }
+        self.restore_marks(marks, |position| position)
+    }
+
+    // Заменяет remove байт строки с позиции at на text и сдвигает курсоры правее правки
+    // Курсор внутри заменяемого остается на месте, если хватает нового текста
+    // Курсор ровно в at сдвигается, только если push; начало выделения остается
+    // на месте, чтобы вставленный текст попал в выделение
+    // Строка правится на месте; номер правки увеличивается один раз в restore_marks
+    pub(super) fn edit_line(&mut self, marks: &mut Marks, at: Position, remove: usize, text: &str, push: bool) -> Result<(), String> {
+        let line = self.line_mut(at.line).ok_or("Строка за пределами буфера")?;
+        if !line.is_char_boundary(at.column) || !line.is_char_boundary(at.column + remove) {
+            return Err("Столбец за пределами строки".to_string());
+        }
+        line.replace_range(at.column..at.column + remove, text);
+        marks.edited = true;
+        let end = at.column + remove;
+        let anchor = marks.selection.map(|(start, end)| start.min(end));
+        for position in marks.positions_mut().filter(|position| position.line == at.line) {
+            let pushed = remove > 0 || (push && Some(*position) != anchor);
+            if position.column > end || (position.column == end && pushed) {
+                position.column = position.column - remove + text.len();
+            } else if position.column > at.column {
+                position.column = position.column.min(at.column + text.len());
+            }
+        }
+        Ok(())
+    }
+
+    pub(super) fn marks(&self) -> Marks {
+        let cursor = self.get_cursor_position();
+        let extra = self.get_cursors().into_iter().filter(|&position| position != cursor).collect();
+        Marks { cursor, selection: self.get_selection(), extra, edited: false }
+    }
+
+    // Ставит курсоры и выделение на новые места; столбец за концом строки - конец строки
+    pub(super) fn restore_marks(&mut self, marks: Marks, moved: impl Fn(Position) -> Position) -> Result<(), String> {
+        if marks.edited {
+            self.touch_revision();
+        }
+        let clamp = |position: Position| {
+            let lines = self.get_all_lines();
+            let line = position.line.min(lines.len() - 1);
+            let text = &lines[line];
+            let mut column = position.column.min(text.len());
+            while !text.is_char_boundary(column) {
+                column -= 1;
+            }
+            Position { line, column }
+        };
+        let cursor = clamp(moved(marks.cursor));
+        let selection = marks.selection.map(|(start, end)| (clamp(moved(start)), clamp(moved(end))));
+        let extra: Vec<Position> = marks.extra.iter().map(|&position| clamp(moved(position))).collect();
+        self.clear_extra_cursors();
+        self.clear_selection()?;
+        self.set_cursor_position(cursor)?;
+        if let Some((start, end)) = selection {
+            self.set_selection(start, end)?;
+        }
+        for position in extra {
+            self.add_cursor(position)?;
+        }
+        Ok(())
+    }
+
+    // Копия выделенного текста сразу за ним; выделяется копия
+    fn duplicate_selection(&mut self, start: Position, end: Position) -> Result<(), String> {
+        let text = self.get_text_in_range(start, end)?;
+        let to = start.max(end);
+        let line = &self.get_all_lines()[to.line];
+        let joined = format!("{}{}{}", &line[..to.column], text, &line[to.column..]);
+        self.splice_lines(to.line, 1, joined.split('\n').map(str::to_string).collect())?;
+        let copy_end = advance(to, &text);
+        let (anchor, head) = if start <= end { (to, copy_end) } else { (copy_end, to) };
+        self.clear_selection()?;
+        self.set_cursor_position(head)?;
+        self.set_selection(anchor, head)
+    }
+}
+
+// Позиция после текста text, начинающегося в позиции start
+pub(super) fn advance(start: Position, text: &str) -> Position {
+    match text.rfind('\n') {
+        Some(newline) => Position { line: start.line + text.matches('\n').count(), column: text.len() - newline - 1 },
+        None => Position { line: start.line, column: start.column + text.len() },
+    }
+}
+
+// Сравнение с числами по значению: цифры сравниваются как числа, остальное - посимвольно
+fn natural_compare(a: &str, b: &str) -> Ordering {
+    let (mut a_chunks, mut b_chunks) = (chunks(a), chunks(b));
+    loop {
+        match (a_chunks.next(), b_chunks.next()) {
+            (None, None) => return a.cmp(b),
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) => {
+                let digits = |chunk: &str| chunk.starts_with(|c: char| c.is_ascii_digit());
+                let ordering = if digits(x) && digits(y) {
+                    // Без ведущих нулей длиннее - значит больше; при равенстве 2 идет перед 02
+                    let (x_value, y_value) = (x.trim_start_matches('0'), y.trim_start_matches('0'));
+                    x_value.len().cmp(&y_value.len()).then_with(|| x_value.cmp(y_value)).then_with(|| x.len().cmp(&y.len()))
+                } else {
+                    x.cmp(y)
+                };
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+            }
+        }
+    }
+}
+
+// Части строки: подряд идущие цифры и подряд идущие остальные символы
+fn chunks(text: &str) -> impl Iterator<Item = &str> {
+    let mut rest = text;
+    std::iter::from_fn(move || {
+        let first = rest.chars().next()?;
+        let length = rest.find(|c: char| c.is_ascii_digit() != first.is_ascii_digit()).unwrap_or(rest.len());
+        let (chunk, tail) = rest.split_at(length);
+        rest = tail;
+        Some(chunk)
+    })
+}
+
+// Первое число строки: "-12", "3.5"
+fn first_number(text: &str) -> Option<f64> {
+    let start = text.find(|c: char| c.is_ascii_digit())?;
+    let start = if text[..start].ends_with('-') { start - 1 } else { start };
+    let rest = &text[start..];
+    let mut length = rest.find(|c: char| !c.is_ascii_digit() && c != '-').unwrap_or(rest.len());
+    // Дробная часть
+    if rest[length..].starts_with('.') && rest[length + 1..].starts_with(|c: char| c.is_ascii_digit()) {
+        length += 1 + rest[length + 1..].find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len() - length - 1);
+    }
+    rest[..length].parse().ok()
+}
+
+// Ключ алфавитной сортировки: сначала буквы без учета регистра (ё как е),
+// затем е перед ё, затем строчные перед прописными
+fn locale_key(text: &str) -> (String, Vec<bool>, Vec<bool>) {
+    let mut letters = String::new();
+    let mut yo = Vec::new();
+    let mut upper = Vec::new();
+    for c in text.chars() {
+        let lower = c.to_lowercase().next().unwrap_or(c);
+        letters.push(if lower == 'ё' { 'е' } else { lower });
+        yo.push(lower == 'ё');
+        upper.push(c != lower);
+    }
+    (letters, yo, upper)
+}
+
+// Простой генератор псевдослучайных чисел (xorshift64*): перемешиванию не нужна криптостойкость,
+// а одно и то же начальное значение должно давать тот же порядок
+struct Random(u64);
+
+impl Random {
+    fn new(seed: u64) -> Self {
+        Self(seed.max(1))
+    }
+
+    // Число от 0 до bound (не включая)
+    fn below(&mut self, bound: usize) -> usize {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        (self.0.wrapping_mul(0x2545_F491_4F6C_DD1D) % bound as u64) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::editor::CommentTokens;
+    use crate::editor::fixtures::{new_buffer, position};
+
+    // Буфер с выделением всех строк
+    fn selected(text: &str) -> Buffer {
+        let mut buffer = new_buffer(text);
+        let lines = buffer.get_all_lines();
+        let end = position(lines.len() - 1, lines[lines.len() - 1].len());
+        buffer.set_cursor_position(end).unwrap();
+        buffer.set_selection(position(0, 0), end).unwrap();
+        buffer
+    }
+
+    fn sorted(text: &str, order: SortOrder) -> String {
+        let mut buffer = selected(text);
+        buffer.sort_lines(order).unwrap();
+        buffer.get_text().unwrap()
+    }
+
+    #[test]
+    fn sort_orders() {
+        assert_eq!(sorted("file10\nfile2\nFile1\nfile02", SortOrder::Natural), "File1\nfile2\nfile02\nfile10");
+        assert_eq!(sorted("file10\nfile2\nFile1\nfile02", SortOrder::CaseInsensitive), "file02\nFile1\nfile10\nfile2");
+        assert_eq!(sorted("x 10\nnone\ny -3\nz 2.5", SortOrder::Numeric), "none\ny -3\nz 2.5\nx 10");
+        assert_eq!(sorted("жук\nёлка\nЕль\nель\nяблоко", SortOrder::Locale), "ёлка\nель\nЕль\nжук\nяблоко");
+        assert_eq!(sorted("ёж\nеж\nжаба", SortOrder::Locale), "еж\nёж\nжаба");
+        // Без учета порядка сравнения ё идет после я
+        assert_eq!(sorted("ёж\nяк", SortOrder::Natural), "як\nёж");
+    }
+
+    #[test]
+    fn unique_reverse_and_shuffle() {
+        let mut buffer = selected("b\na\nb\nc\na");
+        buffer.unique_lines().unwrap();
+        assert_eq!(buffer.get_text().unwrap(), "b\na\nc");
+        assert_eq!(buffer.get_selection(), Some((position(0, 0), position(2, 1))));
+        buffer.reverse_lines().unwrap();
+        assert_eq!(buffer.get_text().unwrap(), "c\na\nb");
+
+        let mut first = selected("1\n2\n3\n4\n5\n6");
+        let mut second = selected("1\n2\n3\n4\n5\n6");
+        first.shuffle_lines(42).unwrap();
+        second.shuffle_lines(42).unwrap();
+        assert_eq!(first.get_text().unwrap(), second.get_text().unwrap());
+        let mut lines: Vec<String> = first.get_all_lines().iter().cloned().collect();
+        lines.sort();
+        assert_eq!(lines.join("\n"), "1\n2\n3\n4\n5\n6");
+    }
+
+    #[test]
+    fn rearrange_separate_blocks() {
+        // Строки курсоров на разных местах сортируются как один список
+        let mut sorted = new_buffer("d\n-\nc\nb\n-\na");
+        sorted.set_cursor_position(position(0, 0)).unwrap();
+        sorted.add_cursor(position(2, 0)).unwrap();
+        sorted.add_cursor(position(3, 0)).unwrap();
+        sorted.add_cursor(position(5, 0)).unwrap();
+        sorted.sort_lines(SortOrder::Natural).unwrap();
+        assert_eq!(sorted.get_text().unwrap(), "a\n-\nb\nc\n-\nd");
+
+        // Повторы удаляются с конца списка, и следующие строки сдвигаются вверх
+        let mut unique = new_buffer("x\n-\nx\ny\n-\ny\nz");
+        unique.set_cursor_position(position(0, 0)).unwrap();
+        unique.add_cursor(position(2, 0)).unwrap();
+        unique.add_cursor(position(3, 0)).unwrap();
+        unique.add_cursor(position(5, 0)).unwrap();
+        unique.unique_lines().unwrap();
+        assert_eq!(unique.get_text().unwrap(), "x\n-\ny\n-\nz");
+        assert_eq!(unique.get_cursor_position(), position(0, 0));
+    }
+
+    fn rust() -> CommentTokens {
+        CommentTokens { line: Some("//".to_string()), block: Some(("/*".to_string(), "*/".to_string())) }
+    }
+
+    #[test]
+    fn line_comments() {
+        let mut buffer = new_buffer("fn a() {\n    x;\n\n  y;\n}");
+        buffer.set_comment_tokens(rust());
+        buffer.set_cursor_position(position(4, 0)).unwrap();
+        buffer.set_selection(position(1, 2), position(4, 0)).unwrap();
+        let revision = buffer.revision();
+        buffer.toggle_line_comments().unwrap();
+        assert_eq!(buffer.get_text().unwrap(), "fn a() {\n  //   x;\n\n  // y;\n}");
+        assert_eq!(buffer.get_selection(), Some((position(1, 2), position(4, 0))));
+        // Все строки правятся на месте одной правкой
+        assert_eq!(buffer.revision(), revision + 1);
+        buffer.toggle_line_comments().unwrap();
+        assert_eq!(buffer.get_text().unwrap(), "fn a() {\n    x;\n\n  y;\n}");
+
+        // Курсоры на разных строках
+        let mut buffer = lines_with_cursors("a\nb\nc", &[position(0, 1), position(2, 0)]);
+        buffer.set_comment_tokens(rust());
+        buffer.toggle_line_comments().unwrap();
+        assert_eq!(buffer.get_text().unwrap(), "// a\nb\n// c");
+        assert_eq!(buffer.get_cursor_position(), position(0, 4));
+
+        // Без строчного комментария строки обрамляются блочным, без комментариев - ошибка
+        let mut buffer = new_buffer("  <p>");
+        buffer.set_comment_tokens(CommentTokens { line: None, block: Some(("<!--".to_string(), "-->".to_string())) });
+        buffer.toggle_line_comments().unwrap();
+        assert_eq!(buffer.get_text().unwrap(), "  <!-- <p> -->");
+        buffer.toggle_line_comments().unwrap();
+        assert_eq!(buffer.get_text().unwrap(), "  <p>");
+        buffer.set_comment_tokens(CommentTokens::default());
+        let revision = buffer.revision();
+        assert!(buffer.toggle_line_comments().is_err());
+        assert_eq!(buffer.revision(), revision);
+    }
+
+    #[test]
+    fn block_comments() {
+        let mut buffer = new_buffer("let x = a + b;");
+        buffer.set_comment_tokens(rust());
+        buffer.set_cursor_position(position(0, 13)).unwrap();
+        buffer.set_selection(position(0, 8), position(0, 13)).unwrap();
+        buffer.toggle_block_comments().unwrap();
+        assert_eq!(buffer.get_text().unwrap(), "let x = /* a + b */;");
+        assert_eq!(buffer.get_selection(), Some((position(0, 8), position(0, 19))));
+        buffer.toggle_block_comments().unwrap();
+        assert_eq!(buffer.get_text().unwrap(), "let x = a + b;");
+        assert_eq!(buffer.get_selection(), Some((position(0, 8), position(0, 13))));
+
+        let mut buffer = lines_with_cursors("    x;\ny;", &[position(0, 0), position(1, 1)]);
+        buffer.set_comment_tokens(rust());
+        buffer.toggle_block_comments().unwrap();
+        assert_eq!(buffer.get_text().unwrap(), "    /* x; */\n/* y; */");
+        buffer.toggle_block_comments().unwrap();
+        assert_eq!(buffer.get_text().unwrap(), "    x;\ny;");
+    }
+
+    fn lines_with_cursors(text: &str, cursors: &[Position]) -> Buffer {
+        let mut buffer = new_buffer(text);
+        buffer.set_cursor_position(cursors[0]).unwrap();
+        for &cursor in &cursors[1..] {
+            buffer.add_cursor(cursor).unwrap();
+        }
+        buffer
+    }
+
+    // Буфер с курсором в позиции at и дополнительными курсорами
+    fn with_cursors(text: &str, at: Position, extra: &[Position]) -> Buffer {
+        let mut buffer = new_buffer(text);
+        buffer.set_cursor_position(at).unwrap();
+        for &position in extra {
+            buffer.add_cursor(position).unwrap();
+        }
+        buffer
+    }
+
+    #[test]
+    fn duplicate_lines() {
+        let mut buffer = with_cursors("a\nb\nc", position(1, 1), &[]);
+        buffer.duplicate_lines().unwrap();
+        assert_eq!(buffer.get_text().unwrap(), "a\nb\nb\nc");
+        assert_eq!(buffer.get_cursor_position(), position(2, 1));
+
+        // Последняя строка копируется в конец буфера
+        let mut buffer = with_cursors("a\nb", position(1, 0), &[]);
+        buffer.duplicate_lines().unwrap();
+        assert_eq!(buffer.get_text().unwrap(), "a\nb\nb");
+        assert_eq!(buffer.get_cursor_position(), position(2, 0));
+
+        // Каждый курсор копирует свою строку
+        let mut buffer = with_cursors("a\nb\nc", position(0, 0), &[position(2, 1)]);
+        buffer.duplicate_lines().unwrap();
+        assert_eq!(buffer.get_text().unwrap(), "a\na\nb\nc\nc");
+        assert_eq!(buffer.get_cursors(), [position(1, 0), position(4, 1)]);
+
+        // Выделение внутри строки копируется сразу за собой, многострочное - целиком
+        let mut buffer = with_cursors("say hi now", position(0, 6), &[]);
+        buffer.set_selection(position(0, 4), position(0, 6)).unwrap();
+        buffer.duplicate_lines().unwrap();
+        assert_eq!(buffer.get_text().unwrap(), "say hihi now");
+        assert_eq!(buffer.get_selection(), Some((position(0, 6), position(0, 8))));
+        let mut buffer = with_cursors("a(\nb\n)", position(2, 1), &[]);
+        buffer.set_selection(position(0, 0), position(2, 1)).unwrap();
+        buffer.duplicate_lines().unwrap();
+        assert_eq!(buffer.get_text().unwrap(), "a(\nb\n)a(\nb\n)");
+    }
+
+    #[test]
+    fn move_lines() {
+        let mut buffer = with_cursors("a\nb\nc", position(1, 1), &[]);
+        buffer.move_lines(true).unwrap();
+        assert_eq!(buffer.get_text().unwrap(), "b\na\nc");
+        assert_eq!(buffer.get_cursor_position(), position(0, 1));
+        // У края буфера ничего не происходит
+        let revision = buffer.revision();
+        buffer.move_lines(true).unwrap();
+        assert_eq!(buffer.revision(), revision);
+        buffer.set_cursor_position(position(2, 0)).unwrap();
+        buffer.move_lines(false).unwrap();
+        assert_eq!(buffer.get_text().unwrap(), "b\na\nc");
+
+        // Блок выделения переносится целиком, конец в начале строки остается за блоком
+        let mut buffer = with_cursors("a\nb\nc\nd", position(3, 0), &[]);
+        buffer.set_selection(position(1, 0), position(3, 0)).unwrap();
+        buffer.move_lines(true).unwrap();
+        assert_eq!(buffer.get_text().unwrap(), "b\nc\na\nd");
+        assert_eq!(buffer.get_selection(), Some((position(0, 0), position(2, 0))));
+        // Блок у конца буфера: конец выделения переходит в конец последней строки
+        buffer.move_lines(false).unwrap();
+        buffer.move_lines(false).unwrap();
+        assert_eq!(buffer.get_text().unwrap(), "a\nd\nb\nc");
+        assert_eq!(buffer.get_selection(), Some((position(2, 0), position(3, 1))));
+
+        // Курсоры на разных строках переносят свои строки; блок у края держит все
+        let mut buffer = with_cursors("a\nb\nc\nd", position(0, 0), &[position(2, 0)]);
+        buffer.move_lines(false).unwrap();
+        assert_eq!(buffer.get_text().unwrap(), "b\na\nd\nc");
+        assert_eq!(buffer.get_cursors(), [position(1, 0), position(3, 0)]);
+        buffer.move_lines(false).unwrap();
+        assert_eq!(buffer.get_text().unwrap(), "b\na\nd\nc");
+    }
+
+    #[test]
+    fn delete_lines() {
+        let mut buffer = with_cursors("x\ny\nz", position(0, 0), &[position(2, 0)]);
+        buffer.delete_lines().unwrap();
+        assert_eq!(buffer.get_text().unwrap(), "y");
+        assert_eq!(buffer.get_cursors(), [position(0, 0)]);
+
+        // Строка, в начале которой кончается выделение, остается; выделение снимается
+        let mut buffer = with_cursors("a\nb\nc", position(1, 0), &[]);
+        buffer.set_selection(position(0, 0), position(1, 0)).unwrap();
+        buffer.delete_lines().unwrap();
+        assert_eq!(buffer.get_text().unwrap(), "b\nc");
+        assert_eq!(buffer.get_selection(), None);
+        assert_eq!(buffer.get_cursor_position(), position(0, 0));
+
+        let mut buffer = with_cursors("a\nb", position(1, 1), &[]);
+        buffer.delete_lines().unwrap();
+        assert_eq!(buffer.get_text().unwrap(), "a");
+        assert_eq!(buffer.get_cursor_position().line, 0);
+        let mut buffer = with_cursors("one", position(0, 2), &[]);
+        buffer.delete_lines().unwrap();
+        assert_eq!(buffer.get_text().unwrap(), "");
+        assert_eq!(buffer.get_cursor_position(), position(0, 0));
+    }
+
+    #[test]
+    fn join_lines() {
+        let mut buffer = with_cursors("foo   \n    bar\n\n  baz\nqux", position(0, 0), &[]);
+        buffer.join_lines().unwrap();
+        assert_eq!(buffer.get_text().unwrap(), "foo bar\n\n  baz\nqux");
+        assert_eq!(buffer.get_cursor_position(), position(0, 3));
+        // Пустая строка внутри отрезка пропускается
+        buffer.set_selection(position(0, 0), position(2, 3)).unwrap();
+        buffer.join_lines().unwrap();
+        assert_eq!(buffer.get_text().unwrap(), "foo bar baz\nqux");
+
+        // Перед закрывающей скобкой пробел не ставится
+        let mut buffer = with_cursors("f(a,\n  b\n)", position(2, 1), &[]);
+        buffer.set_selection(position(0, 0), position(2, 1)).unwrap();
+        buffer.join_lines().unwrap();
+        assert_eq!(buffer.get_text().unwrap(), "f(a, b)");
+
+        // Каждый курсор объединяет свою строку со следующей; у последней строки нечего объединять
+        let mut buffer = with_cursors("a\nb\nc\nd", position(0, 0), &[position(2, 0)]);
+        buffer.join_lines().unwrap();
+        assert_eq!(buffer.get_text().unwrap(), "a b\nc d");
+        assert_eq!(buffer.get_cursors(), [position(0, 1), position(1, 1)]);
+        buffer.join_lines().unwrap();
+        assert_eq!(buffer.get_text().unwrap(), "a b c d");
+        buffer.join_lines().unwrap();
+        assert_eq!(buffer.get_text().unwrap(), "a b c d");
+    }
+}
@@ -0,0 +1,630 @@
+// Клавиатурные макросы
+// Макрос - это записанная последовательность команд (Command). Позиции в
+// командах хранятся относительно строки, на которой началась запись, поэтому
+// макрос можно повторять с любой строки. Макросы сохраняются в текстовый
+// файл по одной команде на строку, и их можно править как обычный текст
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::MutexGuard;
+
+use anyhow::{anyhow, Result};
+
+use crate::editor::{CaseStyle, Command, Position, SortOrder};
+
+// Имя файла с макросами в каталоге настроек
+const MACROS_FILE: &str = "macros";
+
+// Позиция в макросе: строка - смещение от начальной, столбец - как есть
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct RelativePosition {
+    pub line: isize,
+    pub column: usize,
+}
+
+impl RelativePosition {
+    fn from_position(position: Position, base_line: usize) -> Self {
+        Self {
+            line: position.line as isize - base_line as isize,
+            column: position.column,
+        }
+    }
+
+    fn resolve(&self, base_line: usize) -> Result<Position> {
+        let line = base_line as isize + self.line;
+        if line < 0 {
+            return Err(anyhow!("Макрос выходит за начало буфера"));
+        }
+        Ok(Position { line: line as usize, column: self.column })
+    }
+}
+
+// Шаг макроса
+#[derive(Clone)]
+pub enum MacroStep {
+    Command(Command),                          // команда без позиций
+    MoveCursor(RelativePosition),              // перемещение курсора
+    Select(RelativePosition, RelativePosition), // выделение
+    AddCursor(RelativePosition),               // добавление курсора
+}
+
+impl MacroStep {
+    // Превращает шаг в команду для строки base_line
+    pub fn resolve(&self, base_line: usize) -> Result<Command> {
+        Ok(match self {
+            MacroStep::Command(command) => command.clone(),
+            MacroStep::MoveCursor(pos) => Command::MoveCursor(pos.resolve(base_line)?),
+            MacroStep::Select(start, end) => Command::Select(start.resolve(base_line)?, end.resolve(base_line)?),
+            MacroStep::AddCursor(pos) => Command::AddCursor(pos.resolve(base_line)?),
+        })
+    }
+}
+
+// Записанный макрос
+#[derive(Clone, Default)]
+pub struct Macro {
+    pub steps: Vec<MacroStep>,
+}
+
+impl Macro {
+    // Добавляет выполненную команду; группы раскладываются на отдельные команды,
+    // отмена, возврат и замена текста по абсолютным позициям не записываются
+    pub fn record(&mut self, command: &Command, base_line: usize) {
+        let step = match command {
+            Command::Batch(commands) => {
+                for command in commands.iter() {
+                    self.record(command, base_line);
+                }
+                return;
+            }
+            Command::Undo | Command::Redo | Command::ReplaceRange(_, _, _) => return,
+            Command::MoveCursor(pos) => MacroStep::MoveCursor(RelativePosition::from_position(*pos, base_line)),
+            Command::Select(start, end) => MacroStep::Select(
+                RelativePosition::from_position(*start, base_line),
+                RelativePosition::from_position(*end, base_line),
+            ),
+            Command::AddCursor(pos) => MacroStep::AddCursor(RelativePosition::from_position(*pos, base_line)),
+            other => MacroStep::Command(other.clone()),
+        };
+        self.steps.push(step);
+    }
+
+    // Текст макроса: по одной команде на строку
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        for step in self.steps.iter() {
+            text.push_str(&format_step(step));
+            text.push('\n');
+        }
+        text
+    }
+
+    // Разбирает текст макроса; пустые строки и комментарии (#) пропускаются
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut steps = Vec::new();
+        for (index, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let step = parse_step(line).map_err(|e| anyhow!("Строка {}: {}", index + 1, e))?;
+            steps.push(step);
+        }
+        Ok(Self { steps })
+    }
+}
+
+// Набор именованных макросов
+pub struct Macros {
+    entries: BTreeMap<String, Macro>,
+    path: Option<PathBuf>, // None - макросы только в памяти
+}
+
+impl Macros {
+    // Макросы без сохранения на диск
+    pub fn in_memory() -> Self {
+        Self {
+            entries: BTreeMap::new(),
+            path: None,
+        }
+    }
+
+    // Загружает макросы из файла в каталоге настроек
+    pub fn load_default() -> Self {
+        match crate::utils::config_dir() {
+            Some(dir) => Self::load(dir.join(MACROS_FILE)),
+            None => Self::in_memory(),
+        }
+    }
+
+    // Загружает макросы из файла; отсутствующий файл - это пустой набор
+    pub fn load(path: PathBuf) -> Self {
+        let mut macros = Self {
+            entries: BTreeMap::new(),
+            path: Some(path.clone()),
+        };
+        match fs::read_to_string(&path) {
+            Ok(content) => {
+                if let Err(e) = macros.parse(&content) {
+                    log::warn!("Файл макросов {} поврежден: {}", path.display(), e);
+                }
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => log::warn!("Не удалось прочитать макросы {}: {}", path.display(), e),
+        }
+        macros
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Macro> {
+        self.entries.get(name)
+    }
+
+    // Имена макросов по алфавиту
+    pub fn names(&self) -> impl Iterator<Item = &String> {
+        self.entries.keys()
+    }
+
+    // Сохраняет макрос под именем (старый с тем же именем заменяется)
+    pub fn set(&mut self, name: &str, value: Macro) -> Result<()> {
+        if name.is_empty() || name.chars().any(char::is_whitespace) {
+            return Err(anyhow!("Недопустимое имя макроса: \"{}\"", name));
+        }
+        self.entries.insert(name.to_string(), value);
+        self.save()
+    }
+
+    // Удаляет макрос
+    pub fn remove(&mut self, name: &str) -> Result<()> {
+        if self.entries.remove(name).is_none() {
+            return Err(anyhow!("Макрос {} не найден", name));
+        }
+        self.save()
+    }
+
+    // Сохраняет макросы в файл
+    fn save(&self) -> Result<()> {
+        let path = match &self.path {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut content = String::from("# Макросы: \"macro имя\", затем команды по одной на строку, \"end\"\n");
+        for (name, value) in self.entries.iter() {
+            content.push_str(&format!("macro {}\n{}end\n", name, value.to_text()));
+        }
+        fs::write(path, content).map_err(|e| anyhow!("Не удалось сохранить макросы: {}", e))
+    }
+
+    // Разбирает содержимое файла макросов
+    fn parse(&mut self, content: &str) -> Result<()> {
+        let mut current: Option<(String, String)> = None; // имя и текст макроса
+        for line in content.lines() {
+            let trimmed = line.trim();
+            match current.as_mut() {
+                None => {
+                    if trimmed.is_empty() || trimmed.starts_with('#') {
+                        continue;
+                    }
+                    let name = trimmed
+                        .strip_prefix("macro ")
+                        .map(str::trim)
+                        .ok_or_else(|| anyhow!("Ожидалось \"macro имя\", найдено \"{}\"", trimmed))?;
+                    current = Some((name.to_string(), String::new()));
+                }
+                Some(_) if trimmed == "end" => {
+                    if let Some((name, text)) = current.take() {
+                        let value = Macro::parse(&text).map_err(|e| anyhow!("Макрос {}: {}", name, e))?;
+                        self.entries.insert(name, value);
+                    }
+                }
+                Some((_, text)) => {
+                    text.push_str(line);
+                    text.push('\n');
+                }
+            }
+        }
+        match current {
+            Some((name, _)) => Err(anyhow!("Макрос {} не завершен строкой end", name)),
+            None => Ok(()),
+        }
+    }
+}
+
+// ---------- Текстовый формат команд ----------
+
+fn format_position(pos: &RelativePosition) -> String {
+    format!("{:+} {}", pos.line, pos.column)
+}
+
+fn format_step(step: &MacroStep) -> String {
+    match step {
+        MacroStep::MoveCursor(pos) => format!("move {}", format_position(pos)),
+        MacroStep::Select(start, end) => format!("select {} {}", format_position(start), format_position(end)),
+        MacroStep::AddCursor(pos) => format!("add-cursor {}", format_position(pos)),
+        MacroStep::Command(command) => match command {
+            Command::InsertChar(c) => format!("insert-char {}", quote(&c.to_string())),
+            Command::DeleteChar => "delete-char".to_string(),
+            Command::InsertNewline => "newline".to_string(),
+            Command::InsertText(text) => format!("insert-text {}", quote(text)),
+            Command::DeleteSelection => "delete-selection".to_string(),
+            Command::Copy => "copy".to_string(),
+            Command::Cut => "cut".to_string(),
+            Command::Paste => "paste".to_string(),
+            Command::CopyToRegister(name) => format!("copy-to-register {}", name),
+            Command::CutToRegister(name) => format!("cut-to-register {}", name),
+            Command::PasteRegister(name) => format!("paste-register {}", name),
+            Command::PasteHistory(index) => format!("paste-history {}", index),
+            Command::PasteDistributed(index) => format!("paste-distributed {}", index),
+            Command::ClearCursors => "clear-cursors".to_string(),
+            Command::IndentLines => "indent-lines".to_string(),
+            Command::UnindentLines => "unindent-lines".to_string(),
+            Command::DuplicateLines => "duplicate-lines".to_string(),
+            Command::MoveLinesUp => "move-lines-up".to_string(),
+            Command::MoveLinesDown => "move-lines-down".to_string(),
+            Command::DeleteLines => "delete-lines".to_string(),
+            Command::JoinLines => "join-lines".to_string(),
+            Command::SortLines(order) => format!("sort-lines {}", order.name()),
+            Command::ReverseLines => "reverse-lines".to_string(),
+            Command::UniqueLines => "unique-lines".to_string(),
+            Command::ShuffleLines(seed) => format!("shuffle-lines {}", seed),
+            Command::ToggleLineComment => "toggle-line-comment".to_string(),
+            Command::ToggleBlockComment => "toggle-block-comment".to_string(),
+            Command::ConvertCase(style) => format!("convert-case {}", style.name()),
+            Command::Transform(name) => format!("transform {}", name),
+            // Позиции, группы, отмена, возврат и замена текста в MacroStep::Command не попадают
+            Command::MoveCursor(_) | Command::Select(_, _) | Command::AddCursor(_) | Command::ReplaceRange(_, _, _)
+            | Command::Undo | Command::Redo | Command::Batch(_) => "# пропущено".to_string(),
+        },
+    }
+}
+
+fn parse_step(line: &str) -> Result<MacroStep> {
+    let (name, args) = match line.split_once(char::is_whitespace) {
+        Some((name, args)) => (name, args.trim()),
+        None => (line, ""),
+    };
+    let numbers = || -> Result<Vec<isize>> {
+        args.split_whitespace()
+            .map(|arg| arg.parse::<isize>().map_err(|_| anyhow!("Неверное число \"{}\"", arg)))
+            .collect()
+    };
+    let position = |line: isize, column: isize| -> Result<RelativePosition> {
+        if column < 0 {
+            return Err(anyhow!("Столбец не может быть отрицательным"));
+        }
+        Ok(RelativePosition { line, column: column as usize })
+    };
+    let register = || -> Result<char> {
+        let mut chars = args.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => Ok(c),
+            _ => Err(anyhow!("Ожидалось имя регистра")),
+        }
+    };
+    let index = || -> Result<usize> {
+        args.parse::<usize>().map_err(|_| anyhow!("Неверный номер \"{}\"", args))
+    };
+
+    let command = match name {
+        "move" | "add-cursor" => {
+            let numbers = numbers()?;
+            if numbers.len() != 2 {
+                return Err(anyhow!("{}: нужны строка и столбец", name));
+            }
+            let pos = position(numbers[0], numbers[1])?;
+            return Ok(if name == "move" { MacroStep::MoveCursor(pos) } else { MacroStep::AddCursor(pos) });
+        }
+        "select" => {
+            let numbers = numbers()?;
+            if numbers.len() != 4 {
+                return Err(anyhow!("select: нужны две позиции"));
+            }
+            return Ok(MacroStep::Select(position(numbers[0], numbers[1])?, position(numbers[2], numbers[3])?));
+        }
+        "insert-char" => {
+            let text = unquote(args)?;
+            let mut chars = text.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => Command::InsertChar(c),
+                _ => return Err(anyhow!("insert-char: нужен один символ")),
+            }
+        }
+        "insert-text" => Command::InsertText(unquote(args)?),
+        "delete-char" => Command::DeleteChar,
+        "newline" => Command::InsertNewline,
+        "delete-selection" => Command::DeleteSelection,
+        "copy" => Command::Copy,
+        "cut" => Command::Cut,
+        "paste" => Command::Paste,
+        "copy-to-register" => Command::CopyToRegister(register()?),
+        "cut-to-register" => Command::CutToRegister(register()?),
+        "paste-register" => Command::PasteRegister(register()?),
+        "paste-history" => Command::PasteHistory(index()?),
+        "paste-distributed" => Command::PasteDistributed(index()?),
+        "clear-cursors" => Command::ClearCursors,
+        "indent-lines" => Command::IndentLines,
+        "unindent-lines" => Command::UnindentLines,
+        "duplicate-lines" => Command::DuplicateLines,
+        "move-lines-up" => Command::MoveLinesUp,
+        "move-lines-down" => Command::MoveLinesDown,
+        "delete-lines" => Command::DeleteLines,
+        "join-lines" => Command::JoinLines,
+        "sort-lines" => {
+            let order = SortOrder::from_name(args).ok_or_else(|| anyhow!("Неизвестный порядок сортировки \"{}\"", args))?;
+            Command::SortLines(order)
+        }
+        "reverse-lines" => Command::ReverseLines,
+        "unique-lines" => Command::UniqueLines,
+        "shuffle-lines" => {
+            let seed = args.parse::<u64>().map_err(|_| anyhow!("Неверное число \"{}\"", args))?;
+            Command::ShuffleLines(seed)
+        }
+        "toggle-line-comment" => Command::ToggleLineComment,
+        "toggle-block-comment" => Command::ToggleBlockComment,
+        "convert-case" => {
+            let style = CaseStyle::from_name(args).ok_or_else(|| anyhow!("Неизвестный регистр \"{}\"", args))?;
+            Command::ConvertCase(style)
+        }
+        "transform" => Command::Transform(args.to_string()),
+        _ => return Err(anyhow!("Неизвестная команда \"{}\"", name)),
+    };
+    Ok(MacroStep::Command(command))
+}
+
+// Текст в кавычках: \", \\, \n и \t экранируются
+fn quote(text: &str) -> String {
+    let mut result = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            '\t' => result.push_str("\\t"),
+            c => result.push(c),
+        }
+    }
+    result.push('"');
+    result
+}
+
+fn unquote(text: &str) -> Result<String> {
+    let inner = text
+        .strip_prefix('"')
+        .and_then(|rest| rest.strip_suffix('"'))
+        .ok_or_else(|| anyhow!("Текст должен быть в кавычках: {}", text))?;
+    let mut result = String::new();
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            match chars.next() {
+                Some('n') => result.push('\n'),
+                Some('t') => result.push('\t'),
+                Some(other) => result.push(other),
+                None => return Err(anyhow!("Незавершенное экранирование в {}", text)),
+            }
+        } else {
+            result.push(c);
+        }
+    }
+    Ok(result)
+}
+
+// ---------- Запись и воспроизведение ----------
+
+impl crate::editor::Editor {
+    // Начинает запись макроса с текущей строки
+    pub fn start_macro_recording(&mut self) -> Result<()> {
+        if self.recording.is_some() {
+            return Err(anyhow!("Макрос уже записывается"));
+        }
+        let base_line = self.buffer.get_cursor_position().line;
+        self.recording = Some((Macro::default(), base_line));
+        Ok(())
+    }
+
+    // Заканчивает запись и сохраняет макрос под именем
+    pub fn stop_macro_recording(&mut self, name: &str) -> Result<()> {
+        let (recorded, _) = self.recording.take().ok_or_else(|| anyhow!("Макрос не записывается"))?;
+        self.lock_macros()?.set(name, recorded)
+    }
+
+    // Прерывает запись без сохранения
+    pub fn cancel_macro_recording(&mut self) {
+        self.recording = None;
+    }
+
+    pub fn is_recording_macro(&self) -> bool {
+        self.recording.is_some()
+    }
+
+    // Имена сохраненных макросов
+    pub fn macro_names(&self) -> Vec<String> {
+        self.macros.lock().map(|macros| macros.names().cloned().collect()).unwrap_or_default()
+    }
+
+    // Текст макроса для редактирования
+    pub fn macro_text(&self, name: &str) -> Option<String> {
+        self.macros.lock().ok()?.get(name).map(Macro::to_text)
+    }
+
+    // Заменяет макрос отредактированным текстом
+    pub fn set_macro_text(&mut self, name: &str, text: &str) -> Result<()> {
+        let value = Macro::parse(text)?;
+        self.lock_macros()?.set(name, value)
+    }
+
+    pub fn remove_macro(&mut self, name: &str) -> Result<()> {
+        self.lock_macros()?.remove(name)
+    }
+
+    // Воспроизводит макрос count раз подряд, каждый раз от строки курсора
+    // Все повторы отменяются одним шагом; первая ошибка останавливает воспроизведение,
+    // сделанные до нее изменения остаются
+    pub fn play_macro(&mut self, name: &str, count: usize) -> Result<()> {
+        let value = self.playable_macro(name)?;
+        self.begin_transaction();
+        let mut result = Ok(());
+        for _ in 0..count.max(1) {
+            let base_line = self.buffer.get_cursor_position().line;
+            result = self.run_macro(&value, base_line);
+            if result.is_err() {
+                break;
+            }
+        }
+        self.commit_transaction();
+        result
+    }
+
+    // Воспроизводит макрос на каждой строке выделения (или на строке курсора)
+    pub fn play_macro_on_selection(&mut self, name: &str) -> Result<()> {
+        let (first, last) = match self.buffer.get_selection() {
+            Some((start, end)) => (start.line.min(end.line), start.line.max(end.line)),
+            None => {
+                let line = self.buffer.get_cursor_position().line;
+                (line, line)
+            }
+        };
+        self.play_macro_on_lines(name, first, last)
+    }
+
+    // Воспроизводит макрос на строках first..=last, каждый раз с начала строки
+    pub fn play_macro_on_lines(&mut self, name: &str, first: usize, last: usize) -> Result<()> {
+        let value = self.playable_macro(name)?;
+        self.begin_transaction();
+        let mut result = Ok(());
+        let mut line = first as isize;
+        let mut last = last as isize;
+        while line <= last {
+            let before = self.buffer.get_all_lines().len() as isize;
+            // Выделение, по которому выбраны строки, не должно заменяться вводом макроса
+            result = self
+                .buffer
+                .clear_selection()
+                .map_err(|e| anyhow!(e))
+                .and_then(|_| self.execute_command(Command::MoveCursor(Position { line: line as usize, column: 0 })))
+                .and_then(|_| self.run_macro(&value, line as usize));
+            if result.is_err() {
+                break;
+            }
+            // Макрос мог добавить или удалить строки - следующие строки сдвигаются
+            let shift = self.buffer.get_all_lines().len() as isize - before;
+            line = (line + 1 + shift).max(0);
+            last += shift;
+        }
+        self.commit_transaction();
+        result
+    }
+
+    // Макрос для воспроизведения; во время записи воспроизводить нельзя
+    fn playable_macro(&self, name: &str) -> Result<Macro> {
+        if self.recording.is_some() {
+            return Err(anyhow!("Нельзя воспроизвести макрос во время записи"));
+        }
+        self.lock_macros()?.get(name).cloned().ok_or_else(|| anyhow!("Макрос {} не найден", name))
+    }
+
+    fn lock_macros(&self) -> Result<MutexGuard<'_, Macros>> {
+        self.macros.lock().map_err(|_| anyhow!("Макросы недоступны"))
+    }
+
+    // Выполняет шаги макроса до первой ошибки
+    fn run_macro(&mut self, value: &Macro, base_line: usize) -> Result<()> {
+        for (index, step) in value.steps.iter().enumerate() {
+            step.resolve(base_line)
+                .and_then(|command| self.execute_command(command))
+                .map_err(|e| anyhow!("Макрос остановлен на шаге {}: {}", index + 1, e))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::editor::fixtures::{editor, text};
+
+    #[test]
+    fn text_format_round_trip() {
+        let text = "move -2 3\n\
+                    select +0 0 +1 4\n\
+                    add-cursor +5 0\n\
+                    insert-char \"\\\"\"\n\
+                    insert-text \"# не комментарий\\n\\tи \\\\ слеш\"\n\
+                    paste-register a\n\
+                    sort-lines natural\n\
+                    shuffle-lines 42\n\
+                    convert-case snake\n\
+                    transform base64-encode\n";
+        let parsed = Macro::parse(text).unwrap();
+        assert_eq!(parsed.steps.len(), 10);
+        assert_eq!(parsed.to_text(), text);
+        assert_eq!(Macro::parse(&parsed.to_text()).unwrap().to_text(), text);
+        assert!(Macro::parse("move +1").is_err());
+        assert!(Macro::parse("insert-text без кавычек").is_err());
+        assert!(Macro::parse("frobnicate").is_err());
+    }
+
+    #[test]
+    fn file_round_trip() {
+        let path = std::env::temp_dir().join(format!("macros-test-{}", std::process::id()));
+        let mut macros = Macros::load(path.clone());
+        macros.set("one", Macro::parse("newline\ninsert-text \"#1\"\n").unwrap()).unwrap();
+        macros.set("two", Macro::parse("delete-lines\n").unwrap()).unwrap();
+        assert!(macros.set("with space", Macro::default()).is_err());
+
+        let loaded = Macros::load(path.clone());
+        assert_eq!(loaded.names().cloned().collect::<Vec<_>>(), ["one", "two"]);
+        assert_eq!(loaded.get("one").unwrap().to_text(), "newline\ninsert-text \"#1\"\n");
+        macros.remove("one").unwrap();
+        assert!(Macros::load(path.clone()).get("one").is_none());
+        let _ = fs::remove_file(path);
+    }
+
+    #[test]
+    fn record_and_play() {
+        let mut editor = editor("a\nb\nc\nd");
+        editor.start_macro_recording().unwrap();
+        editor.execute_command(Command::MoveCursor(Position { line: 0, column: 0 })).unwrap();
+        editor.execute_command(Command::InsertText("- ".to_string())).unwrap();
+        editor.execute_command(Command::MoveCursor(Position { line: 1, column: 0 })).unwrap();
+        editor.stop_macro_recording("m").unwrap();
+        assert_eq!(editor.macro_text("m").unwrap(), "move +0 0\ninsert-text \"- \"\nmove +1 0\n");
+
+        editor.play_macro("m", 2).unwrap();
+        assert_eq!(text(&editor), "- a\n- b\n- c\nd");
+        editor.undo().unwrap();
+        assert_eq!(text(&editor), "- a\nb\nc\nd");
+
+        editor.set_macro_text("end", "move +0 1\ninsert-char \";\"\n").unwrap();
+        editor.play_macro_on_lines("end", 1, 2).unwrap();
+        assert_eq!(text(&editor), "- a\nb;\nc;\nd");
+        let error = editor.play_macro("m", 100).unwrap_err().to_string();
+        assert!(error.contains("шаге"), "{}", error);
+    }
+
+    #[test]
+    fn shared_between_editors() {
+        use std::sync::{Arc, Mutex};
+
+        let path = std::env::temp_dir().join(format!("macros-shared-test-{}", std::process::id()));
+        let shared = Arc::new(Mutex::new(Macros::load(path.clone())));
+        let mut first = editor("a");
+        let mut second = editor("b");
+        first.macros = Arc::clone(&shared);
+        second.macros = Arc::clone(&shared);
+
+        first.start_macro_recording().unwrap();
+        first.execute_command(Command::InsertChar('!')).unwrap();
+        first.stop_macro_recording("bang").unwrap();
+        second.play_macro("bang", 1).unwrap();
+        assert_eq!(text(&second), "!b");
+        // Сохранение из второго документа не теряет макрос первого
+        second.set_macro_text("dup", "duplicate-lines\n").unwrap();
+        assert_eq!(first.macro_names(), ["bang", "dup"]);
+        assert_eq!(Macros::load(path.clone()).names().cloned().collect::<Vec<_>>(), ["bang", "dup"]);
+        let _ = fs::remove_file(path);
+    }
+}
@@ -1,32 +1,87 @@
 mod buffer;
+mod case;
+mod clipboard;
 mod commands;
+mod emacs;
+#[cfg(test)]
+mod fixtures;
+mod folding;
+mod indent;
+mod journal;
+mod keys;
+mod kill_ring;
+mod lines;
+mod macros;
+mod registers;
+pub(crate) mod text;
+mod transform;
+mod vim;
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use std::sync::Arc;
 use std::sync::Mutex;
 
-pub use buffer::{Buffer, Position};
+pub use buffer::{Buffer, BufferSettings, CommentTokens, IndentRules, Position, TabSettings};
+pub use case::CaseStyle;
+pub use clipboard::{ClipboardProvider, MemoryClipboard, Selection};
 pub use commands::{Command, CommandExecutor};
-
-// Структура для хранения буфера обмена
-struct ClipboardData {
-    content: Option<String>,
-}
+pub use emacs::{EmacsEvent, EmacsState};
+pub use folding::{FoldRange, Folding};
+pub use indent::{detect_indentation, IndentStyle};
+pub use journal::{replay, Journal, JournalFormat};
+pub use keys::Key;
+pub use kill_ring::KillRing;
+pub use lines::SortOrder;
+pub use macros::{Macro, Macros};
+pub use registers::{Register, RegisterKind, Registers};
+pub use transform::{Transform, Transforms};
+pub use vim::{VimEvent, VimMode, VimState};
+use commands::{HistoryEntry, Transaction};
+use journal::{JournalEvent, JournalWriter};
 
 pub struct Editor {
     buffer: Buffer,
-    command_history: Vec<Command>,
-    undo_stack: Vec<Command>,
-    clipboard: Arc<Mutex<ClipboardData>>,
+    command_history: Vec<HistoryEntry>,
+    undo_stack: Vec<HistoryEntry>,
+    transaction: Option<Transaction>,
+    clipboard: Arc<Mutex<Box<dyn ClipboardProvider>>>,
+    kill_ring: KillRing,
+    registers: Arc<Mutex<Registers>>, // общие для всех документов окна
+    macros: Arc<Mutex<Macros>>,       // тоже общие: записанный в одном документе макрос есть во всех
+    recording: Option<(Macro, usize)>, // записываемый макрос и его начальная строка
+    journal: Option<JournalWriter>,    // журнал правок, если он включен
+    folding: Folding,
+    transforms: Transforms, // преобразования выделения по именам
 }
 
 impl Editor {
-    pub fn new() -> Self {
+    // Создает редактор с буфером обмена, выбранным по окружению
+    // Именованные регистры общие у всех документов: окно загружает их один раз,
+    // а запись в регистр из любого документа видна остальным и сохраняется целиком
+    // Так же общие и сохраненные макросы
+    pub fn new(registers: Arc<Mutex<Registers>>, macros: Arc<Mutex<Macros>>) -> Self {
+        let mut editor = Self::with_clipboard(clipboard::detect());
+        editor.registers = registers;
+        editor.macros = macros;
+        editor
+    }
+
+    // Создает редактор с заданным буфером обмена (например, MemoryClipboard в тестах)
+    pub fn with_clipboard(provider: Box<dyn ClipboardProvider>) -> Self {
+        log::debug!("Буфер обмена: {}", provider.name());
         Self {
             buffer: Buffer::new(),
             command_history: Vec::new(),
             undo_stack: Vec::new(),
-            clipboard: Arc::new(Mutex::new(ClipboardData { content: None })),
+            transaction: None,
+            clipboard: Arc::new(Mutex::new(provider)),
+            kill_ring: KillRing::default(),
+            registers: Arc::new(Mutex::new(Registers::in_memory())),
+            macros: Arc::new(Mutex::new(Macros::in_memory())),
+            recording: None,
+            journal: None,
+            folding: Folding::new(),
+            transforms: Transforms::new(),
         }
     }
 
@@ -42,70 +97,164 @@ impl Editor {
         CommandExecutor::redo(self)
     }
     
+    // Очищает историю отмены (например, после открытия другого файла)
+    pub fn clear_history(&mut self) {
+        self.transaction = None;
+        self.command_history.clear();
+        self.undo_stack.clear();
+    }
+
     // Получает буфер
     pub fn get_buffer(&self) -> &Buffer {
         &self.buffer
     }
     
+    // Сворачивание в том виде, в каком его последний раз привели к тексту
+    pub fn folding(&self) -> &Folding {
+        &self.folding
+    }
+
+    // Сворачивание, приведенное к текущему тексту
+    pub fn folding_mut(&mut self) -> &mut Folding {
+        self.folding.update(&self.buffer);
+        &mut self.folding
+    }
+
+    // Преобразования выделения
+    pub fn transforms(&self) -> &Transforms {
+        &self.transforms
+    }
+
+    // Добавляет свое преобразование или заменяет одноименное
+    pub fn register_transform(&mut self, transform: Transform) {
+        self.transforms.register(transform);
+    }
+
+    // Меняет настройки буфера; смена попадает в журнал, чтобы при воспроизведении
+    // текст набирался с теми же настройками, что видел пользователь
+    pub fn set_buffer_settings(&mut self, settings: BufferSettings) {
+        if self.buffer.settings() == settings {
+            return;
+        }
+        // Форматирование JSON и XML делает отступы как в файле
+        for formatter in Transforms::formatters(settings.tabs) {
+            self.register_transform(formatter);
+        }
+        self.buffer.set_settings(settings.clone());
+        self.journal_event(JournalEvent::Settings(settings));
+    }
+
     // Получает мутабельную ссылку на буфер
     pub fn get_buffer_mut(&mut self) -> &mut Buffer {
         &mut self.buffer
     }
 
-    // Сохраняет текст в буфер обмена
-    fn set_clipboard(&mut self, text: String) {
+    // Получает текст из системного буфера или PRIMARY
+    pub fn clipboard_text(&self, selection: Selection) -> Option<String> {
+        self.clipboard.lock().ok().and_then(|mut clipboard| clipboard.get_text(selection))
+    }
+
+    // Записывает текст в системный буфер или PRIMARY
+    pub fn set_clipboard_text(&mut self, selection: Selection, text: String) {
         if let Ok(mut clipboard) = self.clipboard.lock() {
-            clipboard.content = Some(text);
+            if let Err(e) = clipboard.set_text(selection, text) {
+                log::warn!("{}", e);
+            }
         }
     }
 
-    // Получает текст из буфера обмена
-    fn get_clipboard(&self) -> Option<String> {
-        if let Ok(clipboard) = self.clipboard.lock() {
-            clipboard.content.clone()
-        } else {
-            None
+    // Выделенный текст становится PRIMARY, как принято в X11
+    // Вызывается интерфейсом только для выделения пользователем (мышью, Shift+стрелки),
+    // а не для выделения, которое команды ставят сами
+    pub fn publish_selection(&mut self) {
+        let text = match self.buffer.get_selection() {
+            Some((start, end)) if start != end => self.buffer.get_text_in_range(start.min(end), start.max(end)),
+            _ => return,
+        };
+        if let Ok(text) = text {
+            self.set_clipboard_text(Selection::Primary, text);
         }
     }
 
-    // Сохраняет текущее состояние буфера
-    fn save_buffer_state(&self) -> commands::BufferState {
-        commands::BufferState {
-            lines: self.buffer.get_all_lines().iter().cloned().collect(),
-            cursor: self.buffer.get_cursor_position(),
-            selection: self.buffer.get_selection(),
+    // Сохраняет текст в буфер обмена
+    // Текст также попадает в кольцо удалений, чтобы Copy/Cut были видны через M-y
+    fn set_clipboard(&mut self, text: String) {
+        self.kill_ring.push(text.clone());
+        self.set_clipboard_text(Selection::Clipboard, text);
+    }
+
+    // Получает текст из буфера обмена
+    fn get_clipboard(&mut self) -> Option<String> {
+        self.sync_kill_ring();
+        self.kill_ring.latest().cloned()
+    }
+
+    // Если другая программа положила текст в буфер обмена, он становится
+    // самым новым фрагментом кольца (как interprogram-paste в Emacs)
+    fn sync_kill_ring(&mut self) {
+        if let Some(text) = self.clipboard_text(Selection::Clipboard) {
+            if self.kill_ring.latest() != Some(&text) {
+                self.kill_ring.push(text);
+            }
         }
     }
 
-    // Восстанавливает состояние буфера
-    fn restore_buffer_state(&mut self, state: commands::BufferState) -> Result<()> {
-        // Очищаем текущий буфер
-        self.buffer.clear()?;
-        
-        // Восстанавливаем строки
-        for line in state.lines {
-            self.buffer.append_line(line)?;
+    // Добавляет фрагмент в кольцо удалений
+    // append - дописать к последнему фрагменту (подряд идущие удаления)
+    pub fn kill_ring_push(&mut self, text: String, append: bool, prepend: bool) {
+        if append {
+            self.kill_ring.append(&text, prepend);
+        } else {
+            self.kill_ring.push(text);
         }
-        
-        // Восстанавливаем позицию курсора
-        self.buffer.set_cursor_position(state.cursor)?;
-        
-        // Восстанавливаем выделение
-        if let Some((start, end)) = state.selection {
-            self.buffer.set_selection(start, end)?;
+        // Последний фрагмент доступен другим программам
+        if let Some(latest) = self.kill_ring.latest().cloned() {
+            self.set_clipboard_text(Selection::Clipboard, latest);
+        }
+    }
+
+    // Фрагмент кольца удалений по номеру, 0 - самый новый
+    pub fn kill_ring_entry(&mut self, index: usize) -> Option<String> {
+        if index == 0 {
+            self.sync_kill_ring();
         }
-        
-        Ok(())
+        self.kill_ring.get(index).cloned()
+    }
+
+    // Число фрагментов в кольце удалений
+    pub fn kill_ring_len(&self) -> usize {
+        self.kill_ring.len()
+    }
+
+    // История буфера обмена для выбора вставляемого фрагмента, новые первыми
+    pub fn clipboard_history(&mut self) -> Vec<String> {
+        self.sync_kill_ring();
+        self.kill_ring.entries().cloned().collect()
+    }
+
+    // Содержимое именованного регистра a-z
+    pub fn register(&self, name: char) -> Option<Register> {
+        self.registers.lock().ok()?.get(name).cloned()
+    }
+
+    // Записывает именованный регистр; заглавная буква дописывает к нему
+    pub fn set_register(&mut self, name: char, register: Register) -> Result<()> {
+        self.registers.lock().map_err(|_| anyhow!("Регистры недоступны"))?.set(name, register)
+    }
+
+    // Имена непустых именованных регистров
+    pub fn register_names(&self) -> Vec<char> {
+        self.registers.lock().map(|registers| registers.names().collect()).unwrap_or_default()
     }
 
     // Получает текущий текст из буфера
     pub fn get_text(&self) -> Result<String> {
-        self.buffer.get_text()
+        self.buffer.get_text().map_err(|e| anyhow!(e))
     }
 
     // Устанавливает текст в буфер
     pub fn set_text(&mut self, text: String) -> Result<()> {
-        self.buffer.set_text(text)
+        self.buffer.set_text(text).map_err(|e| anyhow!(e))
     }
 
     // Получает текущую позицию курсора
@@ -115,7 +264,7 @@ impl Editor {
 
     // Устанавливает позицию курсора
     pub fn set_cursor_position(&mut self, position: Position) -> Result<()> {
-        self.buffer.set_cursor_position(position)
+        self.buffer.set_cursor_position(position).map_err(|e| anyhow!("Ошибка перемещения курсора: {}", e))
     }
 
     // Получает текущее выделение
@@ -125,11 +274,11 @@ impl Editor {
 
     // Устанавливает выделение
     pub fn set_selection(&mut self, start: Position, end: Position) -> Result<()> {
-        self.buffer.set_selection(start, end)
+        self.buffer.set_selection(start, end).map_err(|e| anyhow!("Ошибка выделения: {}", e))
     }
 
     // Очищает выделение
     pub fn clear_selection(&mut self) -> Result<()> {
-        self.buffer.clear_selection()
+        self.buffer.clear_selection().map_err(|e| anyhow!(e))
     }
 }
\ No newline at end of file
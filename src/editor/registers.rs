@@ -0,0 +1,260 @@
+// Именованные регистры a-z для хранения фрагментов текста
+// Регистры сохраняются в файл после каждого изменения и загружаются
+// при запуске, поэтому переживают перезапуск редактора
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+
+use anyhow::{anyhow, Result};
+
+// Имя файла с регистрами в каталоге настроек
+const REGISTERS_FILE: &str = "registers";
+
+// Как вставлять содержимое регистра
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum RegisterKind {
+    Charwise,  // как обычный текст
+    Linewise,  // целыми строками
+    Blockwise, // прямоугольным блоком
+}
+
+impl RegisterKind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            RegisterKind::Charwise => "charwise",
+            RegisterKind::Linewise => "linewise",
+            RegisterKind::Blockwise => "blockwise",
+        }
+    }
+
+    fn parse(text: &str) -> Option<Self> {
+        match text {
+            "charwise" => Some(RegisterKind::Charwise),
+            "linewise" => Some(RegisterKind::Linewise),
+            "blockwise" => Some(RegisterKind::Blockwise),
+            _ => None,
+        }
+    }
+}
+
+// Содержимое регистра
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Register {
+    pub text: String,
+    pub kind: RegisterKind,
+}
+
+pub struct Registers {
+    entries: BTreeMap<char, Register>,
+    path: Option<PathBuf>, // None - регистры только в памяти
+}
+
+impl Registers {
+    // Регистры без сохранения на диск
+    pub fn in_memory() -> Self {
+        Self {
+            entries: BTreeMap::new(),
+            path: None,
+        }
+    }
+
+    // Загружает регистры из файла в каталоге настроек
+    pub fn load_default() -> Self {
+        match crate::utils::config_dir() {
+            Some(dir) => Self::load(dir.join(REGISTERS_FILE)),
+            None => Self::in_memory(),
+        }
+    }
+
+    // Загружает регистры из файла; отсутствующий файл - это пустые регистры
+    pub fn load(path: PathBuf) -> Self {
+        let mut registers = Self {
+            entries: BTreeMap::new(),
+            path: Some(path.clone()),
+        };
+        match fs::read_to_string(&path) {
+            Ok(content) => {
+                if let Err(e) = registers.parse(&content) {
+                    log::warn!("Файл регистров {} поврежден: {}", path.display(), e);
+                }
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => log::warn!("Не удалось прочитать регистры {}: {}", path.display(), e),
+        }
+        registers
+    }
+
+    // Проверяет, что имя подходит для именованного регистра
+    pub fn is_named(name: char) -> bool {
+        name.is_ascii_alphabetic()
+    }
+
+    // Содержимое регистра
+    pub fn get(&self, name: char) -> Option<&Register> {
+        self.entries.get(&name.to_ascii_lowercase())
+    }
+
+    // Имена непустых регистров по алфавиту
+    pub fn names(&self) -> impl Iterator<Item = char> + '_ {
+        self.entries.keys().copied()
+    }
+
+    // Записывает регистр; заглавная буква дописывает к существующему (как в Vim)
+    pub fn set(&mut self, name: char, register: Register) -> Result<()> {
+        if !Self::is_named(name) {
+            return Err(anyhow!("Недопустимое имя регистра: {}", name));
+        }
+        let key = name.to_ascii_lowercase();
+        if name.is_ascii_uppercase() {
+            match self.entries.get_mut(&key) {
+                Some(entry) => {
+                    if entry.kind == RegisterKind::Linewise && !entry.text.ends_with('\n') {
+                        entry.text.push('\n');
+                    }
+                    entry.text.push_str(&register.text);
+                    if register.kind == RegisterKind::Linewise {
+                        entry.kind = RegisterKind::Linewise;
+                    }
+                }
+                None => {
+                    self.entries.insert(key, register);
+                }
+            }
+        } else {
+            self.entries.insert(key, register);
+        }
+        self.save()
+    }
+
+    // Сохраняет регистры в файл
+    fn save(&self) -> Result<()> {
+        let path = match &self.path {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut content = String::from("# Именованные регистры: строка \"имя тип\", затем текст\n");
+        for (name, register) in self.entries.iter() {
+            content.push_str(&format!("{} {}\n{}\n", name, register.kind.as_str(), escape(&register.text)));
+        }
+        fs::write(path, content).map_err(|e| anyhow!("Не удалось сохранить регистры: {}", e))
+    }
+
+    // Разбирает содержимое файла регистров
+    // Комментарии (#) и пустые строки пропускаются только между регистрами:
+    // строка после заголовка - всегда текст регистра
+    fn parse(&mut self, content: &str) -> Result<()> {
+        let mut lines = content.lines();
+        while let Some(header) = lines.next() {
+            if header.trim().is_empty() || header.starts_with('#') {
+                continue;
+            }
+            let mut parts = header.split_whitespace();
+            let name = parts
+                .next()
+                .and_then(|name| name.chars().next())
+                .filter(|name| Self::is_named(*name))
+                .ok_or_else(|| anyhow!("Неверное имя регистра в строке \"{}\"", header))?;
+            let kind = parts
+                .next()
+                .and_then(RegisterKind::parse)
+                .ok_or_else(|| anyhow!("Неверный тип регистра в строке \"{}\"", header))?;
+            let text = lines.next().ok_or_else(|| anyhow!("Нет текста для регистра {}", name))?;
+            self.entries.insert(name, Register { text: unescape(text), kind });
+        }
+        Ok(())
+    }
+}
+
+// Текст регистра записывается одной строкой: \n и \ экранируются,
+// а # в начале - чтобы строка не выглядела комментарием при правке файла вручную
+fn escape(text: &str) -> String {
+    let escaped = text.replace('\\', "\\\\").replace('\n', "\\n");
+    match escaped.strip_prefix('#') {
+        Some(rest) => format!("\\#{}", rest),
+        None => escaped,
+    }
+}
+
+fn unescape(text: &str) -> String {
+    let mut result = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            match chars.next() {
+                Some('n') => result.push('\n'),
+                Some(other) => result.push(other),
+                None => result.push('\\'),
+            }
+        } else {
+            result.push(c);
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn register(text: &str, kind: RegisterKind) -> Register {
+        Register { text: text.to_string(), kind }
+    }
+
+    #[test]
+    fn escape_round_trip() {
+        for text in ["", "#include <stdio.h>", "# заголовок\n## подзаголовок", "a\\nb\\", "\\#", "строка\n"] {
+            let escaped = escape(text);
+            assert!(!escaped.contains('\n') && !escaped.starts_with('#'), "{}", escaped);
+            assert_eq!(unescape(&escaped), text);
+        }
+    }
+
+    #[test]
+    fn file_round_trip() {
+        let path = std::env::temp_dir().join(format!("registers-test-{}", std::process::id()));
+        let mut registers = Registers::load(path.clone());
+        registers.set('a', register("#!/bin/sh\necho", RegisterKind::Linewise)).unwrap();
+        registers.set('b', register("", RegisterKind::Charwise)).unwrap();
+        registers.set('c', register("# x", RegisterKind::Blockwise)).unwrap();
+        registers.set('A', register("exit", RegisterKind::Charwise)).unwrap();
+        assert!(registers.set('1', register("x", RegisterKind::Charwise)).is_err());
+
+        let loaded = Registers::load(path.clone());
+        assert_eq!(loaded.get('a'), Some(&register("#!/bin/sh\necho\nexit", RegisterKind::Linewise)));
+        assert_eq!(loaded.get('b'), Some(&register("", RegisterKind::Charwise)));
+        assert_eq!(loaded.get('C'), Some(&register("# x", RegisterKind::Blockwise)));
+        let _ = fs::remove_file(path);
+
+        // Комментарии между регистрами пропускаются, строка после заголовка - текст
+        let mut parsed = Registers::in_memory();
+        parsed.parse("# регистры\n\nq charwise\n# не комментарий\n# конец\n").unwrap();
+        assert_eq!(parsed.get('q'), Some(&register("# не комментарий", RegisterKind::Charwise)));
+    }
+
+    #[test]
+    fn shared_between_editors() {
+        use std::sync::{Arc, Mutex};
+
+        use crate::editor::fixtures::editor;
+
+        let path = std::env::temp_dir().join(format!("registers-shared-test-{}", std::process::id()));
+        let shared = Arc::new(Mutex::new(Registers::load(path.clone())));
+        let mut first = editor("");
+        let mut second = editor("");
+        first.registers = Arc::clone(&shared);
+        second.registers = Arc::clone(&shared);
+
+        first.set_register('a', register("один", RegisterKind::Charwise)).unwrap();
+        assert_eq!(second.register('a'), Some(register("один", RegisterKind::Charwise)));
+        // Запись из второго документа не теряет регистр первого
+        second.set_register('b', register("два", RegisterKind::Linewise)).unwrap();
+        let loaded = Registers::load(path.clone());
+        assert_eq!(loaded.get('a'), Some(&register("один", RegisterKind::Charwise)));
+        assert_eq!(loaded.get('b'), Some(&register("два", RegisterKind::Linewise)));
+        let _ = fs::remove_file(path);
+    }
+}
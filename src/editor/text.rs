@@ -0,0 +1,122 @@
+// Общие функции для работы с текстом буфера
+// Столбцы в Position считаются в байтах UTF-8, поэтому переходы между
+// символами делаются через границы символов, а не через +1/-1
+
+use std::collections::VecDeque;
+
+use anyhow::Result;
+
+use crate::editor::{Command, Editor, Position};
+
+// Копия строки буфера (пустая, если строки нет)
+pub(crate) fn line_text(editor: &Editor, line: usize) -> String {
+    editor.get_buffer().get_all_lines().get(line).cloned().unwrap_or_default()
+}
+
+pub(crate) fn lines(editor: &Editor) -> &VecDeque<String> {
+    editor.get_buffer().get_all_lines()
+}
+
+// Перемещает курсор через Command
+pub(crate) fn move_to(editor: &mut Editor, position: Position) -> Result<()> {
+    editor.execute_command(Command::MoveCursor(position))
+}
+
+// Заменяет текст в диапазоне через Select + DeleteSelection + InsertText
+pub(crate) fn replace_range(editor: &mut Editor, start: Position, end: Position, text: &str) -> Result<()> {
+    let mut commands = Vec::new();
+    if start != end {
+        commands.push(Command::Select(start, end));
+        commands.push(Command::DeleteSelection);
+    } else {
+        commands.push(Command::MoveCursor(start));
+    }
+    if !text.is_empty() {
+        commands.push(Command::InsertText(text.to_string()));
+    }
+    editor.execute_command(Command::Batch(commands))
+}
+
+// Байтовая позиция следующего символа
+pub(crate) fn next_boundary(line: &str, column: usize) -> usize {
+    match line.get(column..).and_then(|rest| rest.chars().next()) {
+        Some(c) => column + c.len_utf8(),
+        None => line.len(),
+    }
+}
+
+// Байтовая позиция предыдущего символа
+pub(crate) fn prev_boundary(line: &str, column: usize) -> usize {
+    line.get(..column.min(line.len()))
+        .and_then(|head| head.char_indices().next_back())
+        .map(|(index, _)| index)
+        .unwrap_or(0)
+}
+
+// Байтовый столбец по номеру символа
+pub(crate) fn byte_column(line: &str, chars: usize) -> usize {
+    line.char_indices().nth(chars).map(|(index, _)| index).unwrap_or(line.len())
+}
+
+// Номер символа по байтовому столбцу
+pub(crate) fn char_column(line: &str, column: usize) -> usize {
+    line.get(..column.min(line.len())).map(|head| head.chars().count()).unwrap_or(0)
+}
+
+// Первый непробельный символ строки
+pub(crate) fn first_non_blank(line: &str) -> usize {
+    line.len() - line.trim_start().len()
+}
+
+// Символ в позиции; конец строки считается переводом строки
+pub(crate) fn char_at(editor: &Editor, position: Position) -> char {
+    lines(editor)
+        .get(position.line)
+        .and_then(|line| line.get(position.column..))
+        .and_then(|rest| rest.chars().next())
+        .unwrap_or('\n')
+}
+
+// Класс символа для движений по словам: 0 - пробел, 1 - слово, 2 - знаки
+pub(crate) fn char_class(c: char, big: bool) -> u8 {
+    if c.is_whitespace() {
+        0
+    } else if big || c.is_alphanumeric() || c == '_' {
+        1
+    } else {
+        2
+    }
+}
+
+// Следующая позиция с переходом на новую строку
+pub(crate) fn next_position(editor: &Editor, position: Position) -> Option<Position> {
+    let all = lines(editor);
+    let line = all.get(position.line)?;
+    if position.column < line.len() {
+        Some(Position { line: position.line, column: next_boundary(line, position.column) })
+    } else if position.line + 1 < all.len() {
+        Some(Position { line: position.line + 1, column: 0 })
+    } else {
+        None
+    }
+}
+
+// Предыдущая позиция с переходом на предыдущую строку
+pub(crate) fn previous_position(editor: &Editor, position: Position) -> Option<Position> {
+    let all = lines(editor);
+    if position.column > 0 {
+        let line = all.get(position.line)?;
+        Some(Position { line: position.line, column: prev_boundary(line, position.column) })
+    } else if position.line > 0 {
+        Some(Position { line: position.line - 1, column: all[position.line - 1].len() })
+    } else {
+        None
+    }
+}
+
+// Позиция после последнего символа буфера
+pub(crate) fn end_of_buffer(editor: &Editor) -> Position {
+    let all = lines(editor);
+    let line = all.len() - 1;
+    Position { line, column: all[line].len() }
+}
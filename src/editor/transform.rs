@@ -0,0 +1,518 @@
+// Преобразования выделенного текста: кодирование и декодирование, форматирование, хеши.
+// Преобразование - функция над текстом выделения (а без выделения - всего буфера),
+// зарегистрированная под именем. По имени оно попадает в команду, журнал и макросы.
+// Функция может хранить состояние (например, отступ форматирования из настроек файла).
+// Ошибка преобразования оставляет текст нетронутым
+
+use std::sync::Arc;
+
+use base64::Engine;
+use md5::Md5;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use crate::editor::lines::advance;
+use crate::editor::{Buffer, Position, TabSettings};
+
+pub type TransformFn = Arc<dyn Fn(&str) -> Result<String, String> + Send + Sync>;
+
+// Куда попадает результат преобразования
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TransformOutput {
+    Replace, // вместо выделения
+    Append,  // после выделения, исходный текст остается (хеши)
+}
+
+#[derive(Clone)]
+pub struct Transform {
+    pub name: String,  // имя в команде и макросе
+    pub title: String, // название для меню
+    pub apply: TransformFn,
+    pub output: TransformOutput,
+}
+
+impl Transform {
+    // Преобразование, результат которого заменяет выделение
+    pub fn new(name: &str, title: &str, apply: impl Fn(&str) -> Result<String, String> + Send + Sync + 'static) -> Self {
+        Self { name: name.to_string(), title: title.to_string(), apply: Arc::new(apply), output: TransformOutput::Replace }
+    }
+}
+
+// Набор преобразований по именам; порядок регистрации - порядок в меню
+#[derive(Clone)]
+pub struct Transforms {
+    items: Vec<Transform>,
+}
+
+impl Transforms {
+    // Набор со встроенными преобразованиями; форматирование - с отступом по умолчанию
+    pub fn new() -> Self {
+        let [json_pretty, xml_format] = Self::formatters(TabSettings::default());
+        let builtin = [
+            Transform::new("base64-encode", "Base64: закодировать", base64_encode),
+            Transform::new("base64-decode", "Base64: раскодировать", base64_decode),
+            Transform::new("url-encode", "URL: закодировать", url_encode),
+            Transform::new("url-decode", "URL: раскодировать", url_decode),
+            Transform::new("html-encode", "HTML: заменить знаки на сущности", html_encode),
+            Transform::new("html-decode", "HTML: заменить сущности на знаки", html_decode),
+            json_pretty,
+            Transform::new("json-minify", "JSON: в одну строку", json_minify),
+            xml_format,
+            Transform::new("escape-string", "Строка: экранировать", escape_string),
+            Transform::new("unescape-string", "Строка: снять экранирование", unescape_string),
+            // Хеш вставляется после выделения, чтобы не потерять сам текст
+            Transform {
+                output: TransformOutput::Append,
+                ..Transform::new("md5", "Хеш MD5", |text| Ok(hex(&Md5::digest(text.as_bytes()))))
+            },
+            Transform {
+                output: TransformOutput::Append,
+                ..Transform::new("sha256", "Хеш SHA-256", |text| Ok(hex(&Sha256::digest(text.as_bytes()))))
+            },
+        ];
+        Self { items: Vec::from(builtin) }
+    }
+
+    // Форматирование JSON и XML с отступом из настроек табуляции
+    pub fn formatters(tabs: TabSettings) -> [Transform; 2] {
+        let (json, xml) = (tabs.unit(), tabs.unit());
+        [
+            Transform::new("json-pretty", "JSON: форматировать", move |text| json_pretty(text, &json)),
+            Transform::new("xml-format", "XML: форматировать", move |text| xml_format(text, &xml)),
+        ]
+    }
+
+    // Добавляет преобразование; одноименное заменяется на своем месте в меню
+    pub fn register(&mut self, transform: Transform) {
+        match self.items.iter_mut().find(|item| item.name == transform.name) {
+            Some(item) => *item = transform,
+            None => self.items.push(transform),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Transform> {
+        self.items.iter().find(|item| item.name == name)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Transform> {
+        self.items.iter()
+    }
+}
+
+impl Default for Transforms {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Buffer {
+    // Заменяет выделение (без него - весь текст) результатом преобразования или вставляет
+    // результат после него; выделяется результат
+    pub fn transform_selection(&mut self, transform: &Transform) -> Result<(), String> {
+        let (start, end) = self.selection_or_all();
+        let result = (transform.apply)(&self.get_text_in_range(start, end)?)?;
+        if transform.output == TransformOutput::Append {
+            // Хеш отделяется от текста пробелом, если тот не кончается пробелом или строкой
+            let previous = self.get_all_lines()[end.line][..end.column].chars().last();
+            let separator = if previous.is_some_and(|c| !c.is_whitespace()) { " " } else { "" };
+            self.replace_range(end, end, &format!("{}{}", separator, result))?;
+            let (_, text_end) = self.get_selection().ok_or("Нет выделения")?;
+            return self.set_selection(advance(end, separator), text_end);
+        }
+        let backward = self.get_cursor_position() == start && start != end;
+        self.replace_range(start, end, &result)?;
+        if backward {
+            let (start, end) = self.get_selection().ok_or("Нет выделения")?;
+            self.set_cursor_position(start)?;
+            self.set_selection(end, start)?;
+        }
+        Ok(())
+    }
+
+    // Границы выделения, а без него - всего текста
+    pub fn selection_or_all(&self) -> (Position, Position) {
+        match self.get_selection().filter(|(start, end)| start != end) {
+            Some((start, end)) => (start.min(end), start.max(end)),
+            None => {
+                let lines = self.get_all_lines();
+                (Position { line: 0, column: 0 }, Position { line: lines.len() - 1, column: lines[lines.len() - 1].len() })
+            }
+        }
+    }
+
+    // Заменяет текст от start до end на text и выделяет вставленное
+    pub fn replace_range(&mut self, start: Position, end: Position, text: &str) -> Result<(), String> {
+        let (start, end) = (start.min(end), start.max(end));
+        // Диапазон из журнала или от внешней команды мог устареть
+        let valid = |position: Position| self.get_all_lines().get(position.line).is_some_and(|line| line.is_char_boundary(position.column));
+        if !valid(start) || !valid(end) {
+            return Err("Позиция за пределами текста".to_string());
+        }
+        let lines = self.get_all_lines();
+        let joined = format!("{}{}{}", &lines[start.line][..start.column], text, &lines[end.line][end.column..]);
+        self.splice_lines(start.line, end.line - start.line + 1, joined.split('\n').map(str::to_string).collect())?;
+        let text_end = advance(start, text);
+        self.clear_extra_cursors();
+        self.clear_selection()?;
+        self.set_cursor_position(text_end)?;
+        self.set_selection(start, text_end)
+    }
+}
+
+fn base64_encode(text: &str) -> Result<String, String> {
+    Ok(base64::engine::general_purpose::STANDARD.encode(text))
+}
+
+// Переводы строк и пробелы внутри Base64 пропускаются
+fn base64_decode(text: &str) -> Result<String, String> {
+    let compact: String = text.chars().filter(|c| !c.is_whitespace()).collect();
+    let bytes = base64::engine::general_purpose::STANDARD
+        .decode(compact)
+        .map_err(|e| format!("Неверный Base64: {}", e))?;
+    utf8(bytes)
+}
+
+// Кодируется все, кроме букв и цифр ASCII и знаков - _ . ~
+fn url_encode(text: &str) -> Result<String, String> {
+    Ok(text
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => (byte as char).to_string(),
+            _ => format!("%{:02X}", byte),
+        })
+        .collect())
+}
+
+fn url_decode(text: &str) -> Result<String, String> {
+    let bytes = text.as_bytes();
+    let mut result = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let code = text
+                .get(i + 1..i + 3)
+                .filter(|code| code.bytes().all(|byte| byte.is_ascii_hexdigit()))
+                .and_then(|code| u8::from_str_radix(code, 16).ok());
+            result.push(code.ok_or_else(|| format!("Неверная последовательность % в позиции {}", i))?);
+            i += 3;
+        } else {
+            result.push(bytes[i]);
+            i += 1;
+        }
+    }
+    utf8(result)
+}
+
+fn html_encode(text: &str) -> Result<String, String> {
+    Ok(text
+        .chars()
+        .map(|c| match c {
+            '&' => "&amp;".to_string(),
+            '<' => "&lt;".to_string(),
+            '>' => "&gt;".to_string(),
+            '"' => "&quot;".to_string(),
+            '\'' => "&#39;".to_string(),
+            c => c.to_string(),
+        })
+        .collect())
+}
+
+// Понимает основные именованные сущности и числовые &#NN; и &#xHH;
+fn html_decode(text: &str) -> Result<String, String> {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(amp) = rest.find('&') {
+        result.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+        let end = rest.find(';').filter(|&end| end <= 10).ok_or_else(|| format!("Незакрытая сущность \"{}\"", rest.chars().take(10).collect::<String>()))?;
+        let entity = &rest[1..end];
+        let c = match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            "nbsp" => Some('\u{a0}'),
+            _ => match entity.strip_prefix("#x").or_else(|| entity.strip_prefix("#X")) {
+                Some(code) => u32::from_str_radix(code, 16).ok().and_then(char::from_u32),
+                None => entity.strip_prefix('#').and_then(|code| code.parse::<u32>().ok()).and_then(char::from_u32),
+            },
+        };
+        result.push(c.ok_or_else(|| format!("Неизвестная сущность \"&{};\"", entity))?);
+        rest = &rest[end + 1..];
+    }
+    result.push_str(rest);
+    Ok(result)
+}
+
+fn json_pretty(text: &str, indent: &str) -> Result<String, String> {
+    let value: serde_json::Value = serde_json::from_str(text).map_err(|e| format!("Неверный JSON: {}", e))?;
+    let mut output = Vec::new();
+    let formatter = serde_json::ser::PrettyFormatter::with_indent(indent.as_bytes());
+    let mut serializer = serde_json::Serializer::with_formatter(&mut output, formatter);
+    value.serialize(&mut serializer).map_err(|e| e.to_string())?;
+    utf8(output)
+}
+
+fn json_minify(text: &str) -> Result<String, String> {
+    let value: serde_json::Value = serde_json::from_str(text).map_err(|e| format!("Неверный JSON: {}", e))?;
+    serde_json::to_string(&value).map_err(|e| e.to_string())
+}
+
+// Части XML: теги, комментарии и объявления, текст
+enum XmlToken<'a> {
+    Open(&'a str, &'a str), // имя и тег целиком
+    Close(&'a str, &'a str),
+    Empty(&'a str),         // <a/>, <!-- -->, <?xml ?>, <!DOCTYPE>, CDATA
+    Text(&'a str),
+}
+
+// Каждый элемент с новой строки с отступом indent на уровень;
+// элемент, внутри которого только текст, остается в одной строке
+fn xml_format(text: &str, indent: &str) -> Result<String, String> {
+    let tokens = xml_tokens(text)?;
+    let mut lines: Vec<String> = Vec::new();
+    let mut open: Vec<&str> = Vec::new();
+    let mut i = 0;
+    while i < tokens.len() {
+        let padding = indent.repeat(open.len());
+        match tokens[i] {
+            XmlToken::Open(name, tag) => {
+                // <a>текст</a> - одной строкой
+                if let (Some(XmlToken::Text(inner)), Some(XmlToken::Close(close, end))) = (tokens.get(i + 1), tokens.get(i + 2)) {
+                    if *close == name {
+                        lines.push(format!("{}{}{}{}", padding, tag, inner, end));
+                        i += 3;
+                        continue;
+                    }
+                }
+                lines.push(format!("{}{}", padding, tag));
+                open.push(name);
+            }
+            XmlToken::Close(name, tag) => {
+                match open.pop() {
+                    Some(expected) if expected == name => {}
+                    Some(expected) => return Err(format!("Ожидался </{}>, а найден </{}>", expected, name)),
+                    None => return Err(format!("Лишний закрывающий тег </{}>", name)),
+                }
+                lines.push(format!("{}{}", indent.repeat(open.len()), tag));
+            }
+            XmlToken::Empty(tag) | XmlToken::Text(tag) => lines.push(format!("{}{}", padding, tag)),
+        }
+        i += 1;
+    }
+    if let Some(name) = open.pop() {
+        return Err(format!("Не закрыт тег <{}>", name));
+    }
+    Ok(lines.join("\n"))
+}
+
+fn xml_tokens(text: &str) -> Result<Vec<XmlToken<'_>>, String> {
+    let mut tokens = Vec::new();
+    let mut rest = text;
+    loop {
+        let lt = rest.find('<').unwrap_or(rest.len());
+        let content = rest[..lt].trim();
+        if !content.is_empty() {
+            tokens.push(XmlToken::Text(content));
+        }
+        rest = &rest[lt..];
+        if rest.is_empty() {
+            return Ok(tokens);
+        }
+        // Конец тега; внутри комментария, CDATA и кавычек '>' не считается
+        let end = if rest.starts_with("<!--") {
+            rest.find("-->").map(|end| end + 3)
+        } else if rest.starts_with("<![CDATA[") {
+            rest.find("]]>").map(|end| end + 3)
+        } else {
+            let mut quote = None;
+            rest.char_indices()
+                .find(|&(_, c)| {
+                    match quote {
+                        Some(q) if c == q => quote = None,
+                        Some(_) => {}
+                        None if c == '"' || c == '\'' => quote = Some(c),
+                        None => return c == '>',
+                    }
+                    false
+                })
+                .map(|(end, _)| end + 1)
+        };
+        let end = end.ok_or_else(|| format!("Незакрытый тег \"{}\"", rest.chars().take(20).collect::<String>()))?;
+        let tag = &rest[..end];
+        let name = |from: usize| tag[from..].split(|c: char| c.is_whitespace() || c == '>' || c == '/').next().unwrap_or("");
+        tokens.push(if tag.starts_with("</") {
+            XmlToken::Close(name(2), tag)
+        } else if tag.starts_with("<!") || tag.starts_with("<?") || tag.ends_with("/>") {
+            XmlToken::Empty(tag)
+        } else {
+            XmlToken::Open(name(1), tag)
+        });
+        rest = &rest[end..];
+    }
+}
+
+// Экранирование как в строках Rust и JSON: \\ \" \n \r \t, прочие управляющие - \u{..}
+fn escape_string(text: &str) -> Result<String, String> {
+    Ok(text
+        .chars()
+        .map(|c| match c {
+            '\\' => "\\\\".to_string(),
+            '"' => "\\\"".to_string(),
+            '\n' => "\\n".to_string(),
+            '\r' => "\\r".to_string(),
+            '\t' => "\\t".to_string(),
+            c if c.is_control() => format!("\\u{{{:x}}}", c as u32),
+            c => c.to_string(),
+        })
+        .collect())
+}
+
+// Понимает \\ \" \' \n \r \t \0, \u{XXXX} (Rust) и \uXXXX (JSON)
+fn unescape_string(text: &str) -> Result<String, String> {
+    let mut result = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        let escaped = match chars.next() {
+            Some('\\') => '\\',
+            Some('"') => '"',
+            Some('\'') => '\'',
+            Some('n') => '\n',
+            Some('r') => '\r',
+            Some('t') => '\t',
+            Some('0') => '\0',
+            Some('u') => {
+                let (mut code, mut rest) = unicode_escape(chars.as_str())?;
+                // Знак вне BMP в JSON записывается суррогатной парой: \uD83D\uDE00
+                if (0xD800..0xDC00).contains(&code) {
+                    if let Some((low @ 0xDC00..=0xDFFF, after)) = rest.strip_prefix("\\u").map(unicode_escape).transpose()? {
+                        code = 0x10000 + ((code - 0xD800) << 10) + (low - 0xDC00);
+                        rest = after;
+                    }
+                }
+                chars = rest.chars();
+                char::from_u32(code).ok_or_else(|| format!("Неверный код знака \\u{:04X}", code))?
+            }
+            Some(other) => return Err(format!("Неизвестная последовательность \\{}", other)),
+            None => return Err("Строка кончается на \\".to_string()),
+        };
+        result.push(escaped);
+    }
+    Ok(result)
+}
+
+// Код после \u - XXXX или {X..} - и остаток строки
+fn unicode_escape(rest: &str) -> Result<(u32, &str), String> {
+    let (code, used) = match rest.strip_prefix('{') {
+        Some(braced) => {
+            let close = braced.find('}').ok_or("Незакрытая последовательность \\u{")?;
+            (&braced[..close], close + 2)
+        }
+        None => (rest.get(..4).ok_or("Короткая последовательность \\u")?, 4),
+    };
+    let code = u32::from_str_radix(code, 16).map_err(|_| format!("Неверный код знака \\u{}", code))?;
+    Ok((code, &rest[used..]))
+}
+
+fn utf8(bytes: Vec<u8>) -> Result<String, String> {
+    String::from_utf8(bytes).map_err(|_| "Результат не является текстом UTF-8".to_string())
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+    use crate::editor::fixtures::editor;
+    use crate::editor::{BufferSettings, Command};
+
+    // Результат преобразования по имени
+    fn apply(name: &str, text: &str) -> Result<String, String> {
+        (Transforms::new().get(name).unwrap().apply)(text)
+    }
+
+    #[test]
+    fn encode_decode_round_trips() {
+        let text = "Привет, мир & <друзья>? a+b=c/100% \"1\" 'x'\n😀";
+        for (encode, decode) in [("base64-encode", "base64-decode"), ("url-encode", "url-decode"), ("html-encode", "html-decode")] {
+            let encoded = apply(encode, text).unwrap();
+            assert_ne!(encoded, text, "{}", encode);
+            assert_eq!(apply(decode, &encoded), Ok(text.to_string()), "{}", decode);
+        }
+        assert_eq!(apply("url-encode", "a b/ё"), Ok("a%20b%2F%D1%91".to_string()));
+        assert_eq!(apply("html-decode", "&lt;&#65;&#x42;&amp;&gt;"), Ok("<AB&>".to_string()));
+        assert!(apply("html-decode", "&nope;").is_err());
+        assert!(apply("base64-decode", "не base64").is_err());
+    }
+
+    #[test]
+    fn string_escapes() {
+        let text = "tab\t\"q\" \\ ё\u{1}";
+        let escaped = apply("escape-string", text).unwrap();
+        assert_eq!(apply("unescape-string", &escaped), Ok(text.to_string()));
+        assert_eq!(apply("unescape-string", "\\u0041\\u{1F600}"), Ok("A😀".to_string()));
+        // Суррогатная пара из JSON - один знак
+        assert_eq!(apply("unescape-string", "\\uD83D\\uDE00!"), Ok("😀!".to_string()));
+        assert!(apply("unescape-string", "\\uD83D").is_err());
+        assert!(apply("unescape-string", "\\uD83D\\u0041").is_err());
+        assert!(apply("unescape-string", "\\uDE00").is_err());
+        assert!(apply("unescape-string", "\\q").is_err());
+    }
+
+    #[test]
+    fn xml_format_and_errors() {
+        assert_eq!(
+            apply("xml-format", "<?xml version=\"1.0\"?><a x=\"1>2\"><b>text</b><c/><!-- <d> --></a>"),
+            Ok("<?xml version=\"1.0\"?>\n<a x=\"1>2\">\n    <b>text</b>\n    <c/>\n    <!-- <d> -->\n</a>".to_string())
+        );
+        assert_eq!(apply("xml-format", "<a><b></a>"), Err("Ожидался </b>, а найден </a>".to_string()));
+        assert_eq!(apply("xml-format", "<a></a></b>"), Err("Лишний закрывающий тег </b>".to_string()));
+        assert_eq!(apply("xml-format", "<a><b/>"), Err("Не закрыт тег <a>".to_string()));
+        assert!(apply("xml-format", "<a").is_err());
+    }
+
+    #[test]
+    fn hash_is_inserted_after_selection() {
+        let mut editor = editor("abc\nx");
+        let (start, end) = (Position { line: 0, column: 0 }, Position { line: 0, column: 3 });
+        editor.execute_command(Command::Batch(vec![Command::MoveCursor(end), Command::Select(start, end)])).unwrap();
+        editor.execute_command(Command::Transform("md5".to_string())).unwrap();
+        let md5 = "900150983cd24fb0d6963f7d28e17f72";
+        assert_eq!(editor.get_text().unwrap(), format!("abc {}\nx", md5));
+        assert_eq!(editor.get_selection(), Some((Position { line: 0, column: 4 }, Position { line: 0, column: 4 + md5.len() })));
+        editor.undo().unwrap();
+        assert_eq!(editor.get_text().unwrap(), "abc\nx");
+    }
+
+    #[test]
+    fn registered_transforms_and_settings() {
+        // Преобразование со своим состоянием
+        let mut numbered = editor("a");
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&calls);
+        numbered.register_transform(Transform::new("number", "Номер вызова", move |text| {
+            Ok(format!("{}{}", text, counter.fetch_add(1, Ordering::Relaxed) + 1))
+        }));
+        numbered.execute_command(Command::Transform("number".to_string())).unwrap();
+        numbered.execute_command(Command::Transform("number".to_string())).unwrap();
+        assert_eq!(numbered.get_text().unwrap(), "a12");
+        assert_eq!(calls.load(Ordering::Relaxed), 2);
+        assert_eq!(numbered.transforms().iter().last().map(|transform| transform.title.as_str()), Some("Номер вызова"));
+
+        // Отступ форматирования берется из настроек табуляции
+        let mut json = editor("{\"a\":[1]}");
+        let tabs = TabSettings { width: 4, use_tabs: true };
+        json.set_buffer_settings(BufferSettings { tabs, ..BufferSettings::default() });
+        json.execute_command(Command::Transform("json-pretty".to_string())).unwrap();
+        assert_eq!(json.get_text().unwrap(), "{\n\t\"a\": [\n\t\t1\n\t]\n}");
+    }
+}
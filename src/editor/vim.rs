@@ -0,0 +1,1976 @@
+// Модальное редактирование в стиле Vim поверх Editor
+// Все изменения текста выполняются через Command, поэтому отмена работает
+// так же, как в обычном режиме: одна команда Normal-режима или один сеанс
+// вставки отменяются одним шагом (через транзакции Editor)
+
+use std::collections::HashMap;
+
+use anyhow::{anyhow, Result};
+use regex::RegexBuilder;
+
+use crate::editor::keys::Key;
+use crate::editor::text::{
+    byte_column, char_at, char_class, char_column, end_of_buffer, first_non_blank, line_text, lines,
+    move_to, next_boundary, next_position, prev_boundary, previous_position, replace_range,
+};
+use crate::editor::{Command, Editor, Position, Register, RegisterKind, Selection};
+
+// Режимы Vim
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum VimMode {
+    Normal,      // Обычный режим
+    Insert,      // Вставка
+    Visual,      // Посимвольное выделение (v)
+    VisualLine,  // Построчное выделение (V)
+    VisualBlock, // Блочное выделение (Ctrl+V)
+    CommandLine, // Ввод ex-команды после ':'
+}
+
+// Действия, которые режим не может выполнить сам и передает интерфейсу
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum VimEvent {
+    None,                 // Ничего не нужно делать
+    Write(Option<String>), // :w [файл]
+    Quit { force: bool },  // :q и :q!
+    WriteQuit,             // :wq и :x
+}
+
+// Операторы, которые комбинируются с движениями и текстовыми объектами
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Operator {
+    Delete, // d
+    Change, // c
+    Yank,   // y
+    Indent, // >
+    Outdent, // <
+}
+
+// Движения курсора
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Motion {
+    Left,
+    Right,
+    Up,
+    Down,
+    WordForward(bool),  // w / W (true - WORD)
+    WordBackward(bool), // b / B
+    WordEnd(bool),      // e / E
+    LineStart,          // 0
+    FirstNonBlank,      // ^
+    LineEnd,            // $
+    FileStart,          // gg
+    FileEnd,            // G
+    Find { c: char, forward: bool, till: bool }, // f F t T
+    RepeatFind(bool),   // ; и , (true - в обратную сторону)
+    MatchPair,          // %
+}
+
+// Как движение определяет диапазон для оператора
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum MotionKind {
+    Exclusive,
+    Inclusive,
+    Linewise,
+}
+
+impl Motion {
+    fn kind(&self) -> MotionKind {
+        match self {
+            Motion::Up | Motion::Down | Motion::FileStart | Motion::FileEnd => MotionKind::Linewise,
+            Motion::WordEnd(_) | Motion::LineEnd | Motion::MatchPair => MotionKind::Inclusive,
+            Motion::Find { .. } | Motion::RepeatFind(_) => MotionKind::Inclusive,
+            _ => MotionKind::Exclusive,
+        }
+    }
+}
+
+// Текстовые объекты: iw, aw, i", a(, ...
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+struct TextObject {
+    inner: bool, // i или a
+    kind: char,  // w, W, ", ', `, (, [, {, <
+}
+
+// Цель оператора
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Target {
+    Motion(Motion),
+    Object(TextObject),
+    Lines, // dd, yy, cc, >>, <<
+}
+
+// Команды Normal-режима, не являющиеся оператором с движением
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Simple {
+    Insert,          // i
+    Append,          // a
+    InsertLineStart, // I
+    AppendLineEnd,   // A
+    OpenBelow,       // o
+    OpenAbove,       // O
+    PutAfter,        // p
+    PutBefore,       // P
+    Undo,            // u
+    Redo,            // Ctrl+R
+    Visual,          // v
+    VisualLine,      // V
+    VisualBlock,     // Ctrl+V
+    Repeat,          // .
+    CommandLine,     // :
+    JoinLines,       // J
+    ReplaceChar(char), // r
+}
+
+// Разобранная команда Normal-режима
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Action {
+    Move(Motion),
+    Operate(Operator, Target),
+    Simple(Simple),
+}
+
+#[derive(Copy, Clone, Debug)]
+struct NormalCommand {
+    register: Option<char>,
+    count: Option<usize>,
+    action: Action,
+}
+
+// Результат разбора накопленных клавиш
+enum Parse {
+    Incomplete,
+    Invalid,
+    Done(NormalCommand),
+}
+
+// Диапазон, к которому применяется оператор
+#[derive(Copy, Clone, Debug)]
+struct Range {
+    start: Position,
+    end: Position, // не включая
+    linewise: bool,
+}
+
+// Состояние модального режима
+pub struct VimState {
+    mode: VimMode,
+    pending: Vec<Key>,                 // клавиши незавершенной команды
+    registers: HashMap<char, Register>, // безымянный, нумерованные и "-; a-z хранит Editor
+    visual_anchor: Position,           // где началось выделение
+    last_visual: Option<(usize, usize)>, // строки последнего выделения для '<,'>
+    command_line: String,              // текст после ':'
+    last_find: Option<(char, bool, bool)>, // последний f/t для ; и ,
+    desired_column: Option<usize>,     // столбец для j/k (в символах)
+    change_keys: Vec<Key>,             // клавиши текущего изменения
+    last_change: Vec<Key>,             // клавиши последнего изменения для '.'
+    record_change: bool,               // запоминать ли текущее изменение для '.'
+    replaying: bool,                   // идет повтор через '.'
+}
+
+impl VimState {
+    pub fn new() -> Self {
+        Self {
+            mode: VimMode::Normal,
+            pending: Vec::new(),
+            registers: HashMap::new(),
+            visual_anchor: Position { line: 0, column: 0 },
+            last_visual: None,
+            command_line: String::new(),
+            last_find: None,
+            desired_column: None,
+            change_keys: Vec::new(),
+            last_change: Vec::new(),
+            record_change: true,
+            replaying: false,
+        }
+    }
+
+    // Текущий режим
+    pub fn mode(&self) -> VimMode {
+        self.mode
+    }
+
+    // Текст командной строки, если она открыта
+    pub fn command_line(&self) -> Option<&str> {
+        if self.mode == VimMode::CommandLine {
+            Some(&self.command_line)
+        } else {
+            None
+        }
+    }
+
+    // Клавиши незавершенной команды (для строки состояния)
+    pub fn pending_keys(&self) -> String {
+        Key::format_sequence(&self.pending)
+    }
+
+    // Обрабатывает нажатие клавиши
+    pub fn handle_key(&mut self, editor: &mut Editor, key: Key) -> Result<VimEvent> {
+        let result = match self.mode {
+            VimMode::Normal => self.handle_normal(editor, key),
+            VimMode::Insert => self.handle_insert(editor, key),
+            VimMode::Visual | VimMode::VisualLine | VimMode::VisualBlock => self.handle_visual(editor, key),
+            VimMode::CommandLine => self.handle_command_line(editor, key),
+        };
+
+        // При ошибке сбрасываем незавершенную команду
+        if result.is_err() {
+            self.pending.clear();
+        }
+        result
+    }
+
+    // ---------- Normal ----------
+
+    fn handle_normal(&mut self, editor: &mut Editor, key: Key) -> Result<VimEvent> {
+        if key == Key::Esc {
+            self.pending.clear();
+            return Ok(VimEvent::None);
+        }
+
+        if self.pending.is_empty() && !self.replaying {
+            self.change_keys.clear();
+            self.record_change = true;
+        }
+        self.pending.push(key);
+        if !self.replaying {
+            self.change_keys.push(key);
+        }
+
+        let command = match parse_normal(&self.pending) {
+            Parse::Incomplete => return Ok(VimEvent::None),
+            Parse::Invalid => {
+                self.pending.clear();
+                return Ok(VimEvent::None);
+            }
+            Parse::Done(command) => command,
+        };
+        self.pending.clear();
+
+        // Вертикальные движения запоминают столбец, остальные команды его сбрасывают
+        let vertical = matches!(command.action, Action::Move(Motion::Up) | Action::Move(Motion::Down));
+        if !vertical {
+            self.desired_column = None;
+        }
+
+        match command.action {
+            Action::Simple(Simple::Undo) => {
+                for _ in 0..command.count.unwrap_or(1) {
+                    editor.undo()?;
+                }
+                self.clamp_cursor(editor)?;
+                return Ok(VimEvent::None);
+            }
+            Action::Simple(Simple::Redo) => {
+                for _ in 0..command.count.unwrap_or(1) {
+                    editor.redo()?;
+                }
+                self.clamp_cursor(editor)?;
+                return Ok(VimEvent::None);
+            }
+            Action::Simple(Simple::Repeat) => {
+                return self.repeat_last_change(editor, command.count.unwrap_or(1));
+            }
+            _ => {}
+        }
+
+        // Команда целиком отменяется одним шагом
+        editor.begin_transaction();
+        let result = self.execute_normal(editor, command);
+        if self.mode == VimMode::Insert && result.is_ok() {
+            // Транзакция закроется при выходе из вставки
+            return result;
+        }
+        editor.commit_transaction();
+        let event = result?;
+
+        if is_change(&command.action) && !self.replaying {
+            self.last_change = self.change_keys.clone();
+        }
+        Ok(event)
+    }
+
+    fn execute_normal(&mut self, editor: &mut Editor, command: NormalCommand) -> Result<VimEvent> {
+        let count = command.count.unwrap_or(1);
+        match command.action {
+            Action::Move(motion) => {
+                let target = self.motion_target(editor, motion, command.count)?;
+                move_to(editor, clamp_normal(editor, target))?;
+            }
+            Action::Operate(operator, target) => {
+                let range = self.target_range(editor, operator, target, command.count)?;
+                if let Some(range) = range {
+                    self.apply_operator(editor, operator, range, command.register)?;
+                }
+            }
+            Action::Simple(simple) => self.execute_simple(editor, simple, count, command.register)?,
+        }
+        Ok(VimEvent::None)
+    }
+
+    fn execute_simple(&mut self, editor: &mut Editor, simple: Simple, count: usize, register: Option<char>) -> Result<()> {
+        let cursor = editor.get_cursor_position();
+        match simple {
+            Simple::Insert => self.enter_insert(),
+            Simple::Append => {
+                let line = line_text(editor, cursor.line);
+                move_to(editor, Position { line: cursor.line, column: next_boundary(&line, cursor.column) })?;
+                self.enter_insert();
+            }
+            Simple::InsertLineStart => {
+                let line = line_text(editor, cursor.line);
+                move_to(editor, Position { line: cursor.line, column: first_non_blank(&line) })?;
+                self.enter_insert();
+            }
+            Simple::AppendLineEnd => {
+                let line = line_text(editor, cursor.line);
+                move_to(editor, Position { line: cursor.line, column: line.len() })?;
+                self.enter_insert();
+            }
+            Simple::OpenBelow => {
+                let line = line_text(editor, cursor.line);
+                move_to(editor, Position { line: cursor.line, column: line.len() })?;
+                editor.execute_command(Command::InsertNewline)?;
+                self.enter_insert();
+            }
+            Simple::OpenAbove => {
+                move_to(editor, Position { line: cursor.line, column: 0 })?;
+                editor.execute_command(Command::InsertNewline)?;
+                move_to(editor, Position { line: cursor.line, column: 0 })?;
+                self.enter_insert();
+            }
+            Simple::PutAfter => self.put(editor, register, count, true)?,
+            Simple::PutBefore => self.put(editor, register, count, false)?,
+            Simple::Visual => self.enter_visual(editor, VimMode::Visual)?,
+            Simple::VisualLine => self.enter_visual(editor, VimMode::VisualLine)?,
+            Simple::VisualBlock => self.enter_visual(editor, VimMode::VisualBlock)?,
+            Simple::CommandLine => {
+                self.mode = VimMode::CommandLine;
+                self.command_line.clear();
+            }
+            Simple::JoinLines => {
+                // J с числом N объединяет N строк (минимум две)
+                for _ in 0..count.max(2) - 1 {
+                    self.join_line(editor)?;
+                }
+            }
+            Simple::ReplaceChar(c) => {
+                let line = line_text(editor, cursor.line);
+                let chars_left = line[cursor.column.min(line.len())..].chars().count();
+                if chars_left < count {
+                    return Err(anyhow!("Недостаточно символов для замены"));
+                }
+                let mut end = cursor.column;
+                for _ in 0..count {
+                    end = next_boundary(&line, end);
+                }
+                let replacement = c.to_string().repeat(count);
+                replace_range(editor, cursor, Position { line: cursor.line, column: end }, &replacement)?;
+                let last = prev_boundary(&line_text(editor, cursor.line), cursor.column + replacement.len());
+                move_to(editor, Position { line: cursor.line, column: last })?;
+            }
+            Simple::Undo | Simple::Redo | Simple::Repeat => {}
+        }
+        Ok(())
+    }
+
+    // Повторяет последнее изменение нужное число раз одной транзакцией
+    fn repeat_last_change(&mut self, editor: &mut Editor, count: usize) -> Result<VimEvent> {
+        if self.last_change.is_empty() {
+            return Ok(VimEvent::None);
+        }
+        let keys = self.last_change.clone();
+        self.replaying = true;
+        editor.begin_transaction();
+        let mut result = Ok(VimEvent::None);
+        'repeat: for _ in 0..count {
+            for key in keys.iter() {
+                if let Err(e) = self.handle_key(editor, *key) {
+                    result = Err(e);
+                    break 'repeat;
+                }
+            }
+        }
+        // Повтор не должен оставить режим вставки открытым
+        if self.mode == VimMode::Insert {
+            if let Err(e) = self.leave_insert(editor) {
+                result = Err(e);
+            }
+        }
+        editor.commit_transaction();
+        self.replaying = false;
+        result
+    }
+
+    // ---------- Insert ----------
+
+    fn enter_insert(&mut self) {
+        self.mode = VimMode::Insert;
+    }
+
+    fn leave_insert(&mut self, editor: &mut Editor) -> Result<()> {
+        self.mode = VimMode::Normal;
+        editor.commit_transaction();
+        if !self.replaying && self.record_change {
+            self.last_change = self.change_keys.clone();
+        }
+        // Как в Vim, курсор сдвигается на символ влево
+        let cursor = editor.get_cursor_position();
+        if cursor.column > 0 {
+            let line = line_text(editor, cursor.line);
+            move_to(editor, Position { line: cursor.line, column: prev_boundary(&line, cursor.column) })?;
+        }
+        Ok(())
+    }
+
+    fn handle_insert(&mut self, editor: &mut Editor, key: Key) -> Result<VimEvent> {
+        if !self.replaying {
+            self.change_keys.push(key);
+        }
+        let cursor = editor.get_cursor_position();
+        match key {
+            Key::Esc => self.leave_insert(editor)?,
+            Key::Char(c) => editor.execute_command(Command::InsertChar(c))?,
+            Key::Tab => editor.execute_command(Command::InsertChar('\t'))?,
+            Key::Enter => editor.execute_command(Command::InsertNewline)?,
+            Key::Backspace => {
+                if let Some(next) = editor.get_buffer().empty_pair_at_cursor().then(|| next_position(editor, cursor)).flatten() {
+                    // Пустая пара, добавленная при наборе, удаляется целиком
+                    editor.execute_command(Command::Batch(vec![Command::MoveCursor(next), Command::DeleteChar, Command::DeleteChar]))?;
+                } else if cursor.line > 0 || cursor.column > 0 {
+                    editor.execute_command(Command::DeleteChar)?;
+                }
+            }
+            Key::Delete => {
+                if let Some(next) = next_position(editor, cursor) {
+                    move_to(editor, next)?;
+                    editor.execute_command(Command::DeleteChar)?;
+                }
+            }
+            Key::Left | Key::Right | Key::Up | Key::Down | Key::Home | Key::End => {
+                let motion = match key {
+                    Key::Left => Motion::Left,
+                    Key::Right => Motion::Right,
+                    Key::Up => Motion::Up,
+                    Key::Down => Motion::Down,
+                    Key::Home => Motion::LineStart,
+                    _ => Motion::LineEnd,
+                };
+                let mut target = self.motion_target(editor, motion, None)?;
+                if motion == Motion::LineEnd || motion == Motion::Right {
+                    // В режиме вставки курсор может стоять после последнего символа
+                    let line = line_text(editor, target.line);
+                    target.column = if motion == Motion::LineEnd {
+                        line.len()
+                    } else {
+                        next_boundary(&line, cursor.column)
+                    };
+                }
+                move_to(editor, target)?;
+            }
+            Key::Ctrl('w') => {
+                // Удаление слова перед курсором
+                let start = word_backward(editor, cursor, false);
+                if start < cursor {
+                    replace_range(editor, start, cursor, "")?;
+                }
+            }
+            Key::Ctrl(_) | Key::Alt(_) => {}
+        }
+        Ok(VimEvent::None)
+    }
+
+    // ---------- Visual ----------
+
+    fn enter_visual(&mut self, editor: &mut Editor, mode: VimMode) -> Result<()> {
+        self.mode = mode;
+        self.visual_anchor = editor.get_cursor_position();
+        self.update_visual_selection(editor)
+    }
+
+    fn leave_visual(&mut self, editor: &mut Editor) -> Result<()> {
+        let cursor = editor.get_cursor_position();
+        self.last_visual = Some((
+            self.visual_anchor.line.min(cursor.line),
+            self.visual_anchor.line.max(cursor.line),
+        ));
+        self.mode = VimMode::Normal;
+        editor.clear_selection()
+    }
+
+    // Отражает визуальное выделение в выделении Editor (для отрисовки)
+    fn update_visual_selection(&mut self, editor: &mut Editor) -> Result<()> {
+        let range = self.visual_range(editor);
+        editor.execute_command(Command::Select(range.start, range.end))
+    }
+
+    // Диапазон, покрываемый визуальным выделением
+    fn visual_range(&self, editor: &Editor) -> Range {
+        let cursor = editor.get_cursor_position();
+        let start = self.visual_anchor.min(cursor);
+        let end = self.visual_anchor.max(cursor);
+        match self.mode {
+            VimMode::VisualLine => {
+                let last = line_text(editor, end.line);
+                Range {
+                    start: Position { line: start.line, column: 0 },
+                    end: Position { line: end.line, column: last.len() },
+                    linewise: true,
+                }
+            }
+            VimMode::VisualBlock => {
+                // Для отрисовки берем охватывающий прямоугольник
+                let (top, bottom, left, right) = self.block_bounds(editor);
+                let top_line = line_text(editor, top);
+                let bottom_line = line_text(editor, bottom);
+                Range {
+                    start: Position { line: top, column: byte_column(&top_line, left) },
+                    end: Position { line: bottom, column: byte_column(&bottom_line, right + 1) },
+                    linewise: false,
+                }
+            }
+            _ => {
+                let line = line_text(editor, end.line);
+                Range {
+                    start,
+                    end: Position { line: end.line, column: next_boundary(&line, end.column) },
+                    linewise: false,
+                }
+            }
+        }
+    }
+
+    // Границы блочного выделения: строки и столбцы (в символах) включительно
+    fn block_bounds(&self, editor: &Editor) -> (usize, usize, usize, usize) {
+        let cursor = editor.get_cursor_position();
+        let anchor_col = char_column(&line_text(editor, self.visual_anchor.line), self.visual_anchor.column);
+        let cursor_col = char_column(&line_text(editor, cursor.line), cursor.column);
+        (
+            self.visual_anchor.line.min(cursor.line),
+            self.visual_anchor.line.max(cursor.line),
+            anchor_col.min(cursor_col),
+            anchor_col.max(cursor_col),
+        )
+    }
+
+    fn handle_visual(&mut self, editor: &mut Editor, key: Key) -> Result<VimEvent> {
+        // Смена или выход из визуального режима
+        let switch = match key {
+            Key::Esc => Some(None),
+            Key::Char('v') => Some(Some(VimMode::Visual)),
+            Key::Char('V') => Some(Some(VimMode::VisualLine)),
+            Key::Ctrl('v') => Some(Some(VimMode::VisualBlock)),
+            _ => None,
+        };
+        if let Some(mode) = switch {
+            self.pending.clear();
+            match mode {
+                Some(mode) if mode != self.mode => {
+                    self.mode = mode;
+                    self.update_visual_selection(editor)?;
+                }
+                _ => self.leave_visual(editor)?,
+            }
+            return Ok(VimEvent::None);
+        }
+
+        if self.pending.is_empty() {
+            match key {
+                Key::Char('o') => {
+                    // Меняем местами начало выделения и курсор
+                    let cursor = editor.get_cursor_position();
+                    move_to(editor, self.visual_anchor)?;
+                    self.visual_anchor = cursor;
+                    self.update_visual_selection(editor)?;
+                    return Ok(VimEvent::None);
+                }
+                Key::Char(':') => {
+                    self.leave_visual(editor)?;
+                    self.mode = VimMode::CommandLine;
+                    self.command_line = "'<,'>".to_string();
+                    return Ok(VimEvent::None);
+                }
+                _ => {}
+            }
+        }
+
+        self.pending.push(key);
+
+        // Текстовые объекты расширяют выделение: viw, vi(
+        if matches!(self.pending.as_slice(), [Key::Char('i' | 'a')]) {
+            return Ok(VimEvent::None);
+        }
+        if let [Key::Char(c @ ('i' | 'a')), Key::Char(kind)] = self.pending.as_slice() {
+            let object = TextObject { inner: *c == 'i', kind: *kind };
+            self.pending.clear();
+            if let Some((start, end)) = text_object_range(editor, object, editor.get_cursor_position()) {
+                self.visual_anchor = start;
+                let line = line_text(editor, end.line);
+                move_to(editor, Position { line: end.line, column: prev_boundary(&line, end.column) })?;
+                self.update_visual_selection(editor)?;
+            }
+            return Ok(VimEvent::None);
+        }
+
+        // Операторы применяются к выделению сразу
+        let operator = match self.pending.as_slice() {
+            [Key::Char('d')] | [Key::Char('x')] | [Key::Delete] => Some(Operator::Delete),
+            [Key::Char('c')] | [Key::Char('s')] => Some(Operator::Change),
+            [Key::Char('y')] => Some(Operator::Yank),
+            [Key::Char('>')] => Some(Operator::Indent),
+            [Key::Char('<')] => Some(Operator::Outdent),
+            _ => None,
+        };
+        if let Some(operator) = operator {
+            self.pending.clear();
+            return self.apply_visual_operator(editor, operator);
+        }
+
+        let command = match parse_normal(&self.pending) {
+            Parse::Incomplete => return Ok(VimEvent::None),
+            Parse::Invalid => {
+                self.pending.clear();
+                return Ok(VimEvent::None);
+            }
+            Parse::Done(command) => command,
+        };
+        self.pending.clear();
+
+        if let Action::Move(motion) = command.action {
+            if !matches!(motion, Motion::Up | Motion::Down) {
+                self.desired_column = None;
+            }
+            let target = self.motion_target(editor, motion, command.count)?;
+            move_to(editor, clamp_normal(editor, target))?;
+            self.update_visual_selection(editor)?;
+        }
+        Ok(VimEvent::None)
+    }
+
+    fn apply_visual_operator(&mut self, editor: &mut Editor, operator: Operator) -> Result<VimEvent> {
+        let mode = self.mode;
+        let range = self.visual_range(editor);
+        let bounds = self.block_bounds(editor);
+        self.leave_visual(editor)?;
+        // Изменения из визуального режима не повторяются через '.'
+        self.record_change = false;
+
+        editor.begin_transaction();
+        let result = if mode == VimMode::VisualBlock && matches!(operator, Operator::Delete | Operator::Yank | Operator::Change) {
+            self.apply_block_operator(editor, operator, bounds)
+        } else {
+            self.apply_operator(editor, operator, range, None)
+        };
+        if self.mode != VimMode::Insert || result.is_err() {
+            editor.commit_transaction();
+        }
+        result.map(|_| VimEvent::None)
+    }
+
+    // Удаление и копирование прямоугольного блока
+    fn apply_block_operator(&mut self, editor: &mut Editor, operator: Operator, bounds: (usize, usize, usize, usize)) -> Result<()> {
+        let (top, bottom, left, right) = bounds;
+        let mut rows = Vec::new();
+        for line_index in top..=bottom {
+            let line = line_text(editor, line_index);
+            let start = byte_column(&line, left);
+            let end = byte_column(&line, right + 1);
+            rows.push(line[start..end].to_string());
+            if operator != Operator::Yank && start < end {
+                replace_range(
+                    editor,
+                    Position { line: line_index, column: start },
+                    Position { line: line_index, column: end },
+                    "",
+                )?;
+            }
+        }
+        self.store_register(editor, None, rows.join("\n"), RegisterKind::Blockwise, operator == Operator::Yank);
+
+        let top_line = line_text(editor, top);
+        move_to(editor, Position { line: top, column: byte_column(&top_line, left) })?;
+        if operator == Operator::Change {
+            self.enter_insert();
+        }
+        Ok(())
+    }
+
+    // ---------- Командная строка ----------
+
+    fn handle_command_line(&mut self, editor: &mut Editor, key: Key) -> Result<VimEvent> {
+        match key {
+            Key::Esc => {
+                self.mode = VimMode::Normal;
+                self.command_line.clear();
+            }
+            Key::Backspace => {
+                // Backspace в пустой строке закрывает ее
+                if self.command_line.is_empty() {
+                    self.mode = VimMode::Normal;
+                } else {
+                    self.command_line.pop();
+                }
+            }
+            Key::Enter => {
+                self.mode = VimMode::Normal;
+                let line = std::mem::take(&mut self.command_line);
+                return self.execute_ex(editor, &line);
+            }
+            Key::Char(c) => self.command_line.push(c),
+            Key::Tab => self.command_line.push('\t'),
+            _ => {}
+        }
+        Ok(VimEvent::None)
+    }
+
+    // Выполняет ex-команду (без ведущего ':')
+    pub fn execute_ex(&mut self, editor: &mut Editor, line: &str) -> Result<VimEvent> {
+        let (range, rest) = self.parse_ex_range(editor, line.trim_start())?;
+        let rest = rest.trim_start();
+
+        // Имя команды - буквы в начале, остальное - аргументы
+        let name_len = rest.find(|c: char| !c.is_ascii_alphabetic()).unwrap_or(rest.len());
+        let (name, args) = rest.split_at(name_len);
+        let bang = args.starts_with('!');
+        let args = if bang { &args[1..] } else { args };
+
+        let cursor_line = editor.get_cursor_position().line;
+        let (first, last) = range.unwrap_or((cursor_line, cursor_line));
+
+        match name {
+            "" if args.trim().is_empty() => {
+                // :N - переход на строку
+                if range.is_some() {
+                    let text = line_text(editor, last);
+                    move_to(editor, Position { line: last, column: first_non_blank(&text) })?;
+                }
+                Ok(VimEvent::None)
+            }
+            "w" | "write" => {
+                let file = args.trim();
+                Ok(VimEvent::Write(if file.is_empty() { None } else { Some(file.to_string()) }))
+            }
+            "q" | "quit" => Ok(VimEvent::Quit { force: bang }),
+            "wq" | "x" | "xit" => Ok(VimEvent::WriteQuit),
+            "s" | "substitute" => {
+                self.substitute(editor, first, last, args)?;
+                Ok(VimEvent::None)
+            }
+            "norm" | "normal" => {
+                self.normal_on_lines(editor, first, last, args.trim_start())?;
+                Ok(VimEvent::None)
+            }
+            "d" | "delete" | "y" | "yank" => {
+                let text = line_text(editor, last);
+                let range = Range {
+                    start: Position { line: first, column: 0 },
+                    end: Position { line: last, column: text.len() },
+                    linewise: true,
+                };
+                let operator = if name.starts_with('d') { Operator::Delete } else { Operator::Yank };
+                let register = args.trim().chars().next();
+                editor.begin_transaction();
+                let result = self.apply_operator(editor, operator, range, register);
+                editor.commit_transaction();
+                result.map(|_| VimEvent::None)
+            }
+            "u" | "undo" => {
+                editor.undo()?;
+                Ok(VimEvent::None)
+            }
+            "red" | "redo" => {
+                editor.redo()?;
+                Ok(VimEvent::None)
+            }
+            _ => Err(anyhow!("Неизвестная команда: {}", line.trim())),
+        }
+    }
+
+    // Разбирает диапазон строк в начале ex-команды: %, N, N,M, ., $, '<,'>
+    fn parse_ex_range<'a>(&self, editor: &Editor, text: &'a str) -> Result<(Option<(usize, usize)>, &'a str)> {
+        let line_count = editor.get_buffer().get_all_lines().len();
+        let current = editor.get_cursor_position().line;
+
+        if let Some(rest) = text.strip_prefix('%') {
+            return Ok((Some((0, line_count - 1)), rest));
+        }
+        if let Some(rest) = text.strip_prefix("'<,'>") {
+            let (first, last) = self.last_visual.ok_or_else(|| anyhow!("Нет предыдущего выделения"))?;
+            return Ok((Some((first, last.min(line_count - 1))), rest));
+        }
+
+        // Одиночный адрес: число, '.' или '$'
+        let parse_address = |text: &'a str| -> (Option<usize>, &'a str) {
+            if let Some(rest) = text.strip_prefix('.') {
+                return (Some(current), rest);
+            }
+            if let Some(rest) = text.strip_prefix('$') {
+                return (Some(line_count - 1), rest);
+            }
+            let digits = text.find(|c: char| !c.is_ascii_digit()).unwrap_or(text.len());
+            match text[..digits].parse::<usize>() {
+                Ok(number) => (Some(number.saturating_sub(1).min(line_count - 1)), &text[digits..]),
+                Err(_) => (None, text),
+            }
+        };
+
+        let (first, rest) = parse_address(text);
+        let first = match first {
+            Some(first) => first,
+            None => return Ok((None, text)),
+        };
+        if let Some(after_comma) = rest.strip_prefix(',') {
+            let (last, rest) = parse_address(after_comma);
+            let last = last.ok_or_else(|| anyhow!("Неверный диапазон строк"))?;
+            if last < first {
+                return Err(anyhow!("Обратный диапазон строк"));
+            }
+            return Ok((Some((first, last)), rest));
+        }
+        Ok((Some((first, first)), rest))
+    }
+
+    // :s/шаблон/замена/флаги на строках first..=last
+    fn substitute(&mut self, editor: &mut Editor, first: usize, last: usize, args: &str) -> Result<()> {
+        let mut chars = args.chars();
+        let delimiter = match chars.next() {
+            Some(c) if !c.is_alphanumeric() && c != '\\' && c != ' ' => c,
+            _ => return Err(anyhow!("Ожидается разделитель после :s")),
+        };
+        let parts = split_unescaped(chars.as_str(), delimiter);
+        let pattern = parts.first().cloned().unwrap_or_default();
+        let replacement = parts.get(1).cloned().unwrap_or_default();
+        let flags = parts.get(2).cloned().unwrap_or_default();
+        if pattern.is_empty() {
+            return Err(anyhow!("Пустой шаблон"));
+        }
+
+        let regex = RegexBuilder::new(&pattern)
+            .case_insensitive(flags.contains('i'))
+            .build()
+            .map_err(|e| anyhow!("Неверный шаблон: {}", e))?;
+        let replacement = vim_replacement(&replacement);
+        let global = flags.contains('g');
+
+        editor.begin_transaction();
+        let mut line_index = first;
+        let mut last = last;
+        let mut last_changed = None;
+        while line_index <= last && line_index < editor.get_buffer().get_all_lines().len() {
+            let text = line_text(editor, line_index);
+            let replaced = if global {
+                regex.replace_all(&text, replacement.as_str())
+            } else {
+                regex.replacen(&text, 1, replacement.as_str())
+            };
+            let added_lines = if regex.is_match(&text) {
+                let replaced = replaced.into_owned();
+                let added = replaced.matches('\n').count();
+                let result = replace_range(
+                    editor,
+                    Position { line: line_index, column: 0 },
+                    Position { line: line_index, column: text.len() },
+                    &replaced,
+                );
+                if let Err(e) = result {
+                    editor.commit_transaction();
+                    return Err(e);
+                }
+                last_changed = Some(line_index + added);
+                added
+            } else {
+                0
+            };
+            // Замена могла добавить строки, сдвигаем границы
+            line_index += added_lines + 1;
+            last += added_lines;
+        }
+        let result = match last_changed {
+            Some(line) => {
+                let text = line_text(editor, line);
+                move_to(editor, Position { line, column: first_non_blank(&text) })
+            }
+            None => Err(anyhow!("Шаблон не найден: {}", pattern)),
+        };
+        editor.commit_transaction();
+        result
+    }
+
+    // :normal - выполняет клавиши Normal-режима на каждой строке диапазона
+    fn normal_on_lines(&mut self, editor: &mut Editor, first: usize, last: usize, keys: &str) -> Result<()> {
+        let keys = Key::parse_sequence(keys);
+        editor.begin_transaction();
+        let mut result = Ok(());
+        for line in first..=last {
+            if line >= editor.get_buffer().get_all_lines().len() {
+                break;
+            }
+            if let Err(e) = move_to(editor, Position { line, column: 0 }) {
+                result = Err(e);
+                break;
+            }
+            for key in keys.iter() {
+                if let Err(e) = self.handle_key(editor, *key) {
+                    result = Err(e);
+                    break;
+                }
+            }
+            // Каждая строка начинается в Normal-режиме
+            self.pending.clear();
+            let reset = match self.mode {
+                VimMode::Insert => self.leave_insert(editor),
+                VimMode::Visual | VimMode::VisualLine | VimMode::VisualBlock => self.leave_visual(editor),
+                VimMode::CommandLine => {
+                    self.mode = VimMode::Normal;
+                    self.command_line.clear();
+                    Ok(())
+                }
+                VimMode::Normal => Ok(()),
+            };
+            if result.is_ok() {
+                result = reset;
+            }
+            if result.is_err() {
+                break;
+            }
+        }
+        editor.commit_transaction();
+        result
+    }
+
+    // ---------- Движения и диапазоны ----------
+
+    // Вычисляет позицию, в которую ведет движение
+    fn motion_target(&mut self, editor: &Editor, motion: Motion, count: Option<usize>) -> Result<Position> {
+        let cursor = editor.get_cursor_position();
+        let times = count.unwrap_or(1);
+        let line_count = editor.get_buffer().get_all_lines().len();
+        let line = line_text(editor, cursor.line);
+
+        let target = match motion {
+            Motion::Left => {
+                let mut column = cursor.column;
+                for _ in 0..times {
+                    column = prev_boundary(&line, column);
+                }
+                Position { line: cursor.line, column }
+            }
+            Motion::Right => {
+                let mut column = cursor.column;
+                for _ in 0..times {
+                    column = next_boundary(&line, column);
+                }
+                Position { line: cursor.line, column }
+            }
+            Motion::Up | Motion::Down => {
+                let desired = *self.desired_column.get_or_insert(char_column(&line, cursor.column));
+                let target_line = if motion == Motion::Up {
+                    cursor.line.saturating_sub(times)
+                } else {
+                    (cursor.line + times).min(line_count - 1)
+                };
+                let text = line_text(editor, target_line);
+                Position { line: target_line, column: byte_column(&text, desired) }
+            }
+            Motion::WordForward(big) => {
+                let mut position = cursor;
+                for _ in 0..times {
+                    position = word_forward(editor, position, big);
+                }
+                position
+            }
+            Motion::WordBackward(big) => {
+                let mut position = cursor;
+                for _ in 0..times {
+                    position = word_backward(editor, position, big);
+                }
+                position
+            }
+            Motion::WordEnd(big) => {
+                let mut position = cursor;
+                for _ in 0..times {
+                    position = word_end(editor, position, big);
+                }
+                position
+            }
+            Motion::LineStart => Position { line: cursor.line, column: 0 },
+            Motion::FirstNonBlank => Position { line: cursor.line, column: first_non_blank(&line) },
+            Motion::LineEnd => {
+                let target_line = (cursor.line + times - 1).min(line_count - 1);
+                let text = line_text(editor, target_line);
+                Position { line: target_line, column: prev_boundary(&text, text.len()) }
+            }
+            Motion::FileStart | Motion::FileEnd => {
+                let target_line = match count {
+                    Some(number) => number.saturating_sub(1).min(line_count - 1),
+                    None if motion == Motion::FileStart => 0,
+                    None => line_count - 1,
+                };
+                let text = line_text(editor, target_line);
+                Position { line: target_line, column: first_non_blank(&text) }
+            }
+            Motion::Find { c, forward, till } => {
+                self.last_find = Some((c, forward, till));
+                find_in_line(&line, cursor, c, forward, till, times)
+                    .ok_or_else(|| anyhow!("Символ '{}' не найден", c))?
+            }
+            Motion::RepeatFind(reverse) => {
+                let (c, forward, till) = self.last_find.ok_or_else(|| anyhow!("Нет предыдущего поиска"))?;
+                let forward = if reverse { !forward } else { forward };
+                // Повтор t не должен застревать перед тем же символом
+                let start = if till {
+                    let shifted = if forward { next_boundary(&line, cursor.column) } else { prev_boundary(&line, cursor.column) };
+                    Position { line: cursor.line, column: shifted }
+                } else {
+                    cursor
+                };
+                find_in_line(&line, start, c, forward, till, times)
+                    .ok_or_else(|| anyhow!("Символ '{}' не найден", c))?
+            }
+            Motion::MatchPair => match_pair(editor, cursor).ok_or_else(|| anyhow!("Парная скобка не найдена"))?,
+        };
+        Ok(target)
+    }
+
+    // Диапазон для оператора с учетом типа движения
+    fn target_range(&mut self, editor: &Editor, operator: Operator, target: Target, count: Option<usize>) -> Result<Option<Range>> {
+        let cursor = editor.get_cursor_position();
+        let line_count = editor.get_buffer().get_all_lines().len();
+        match target {
+            Target::Lines => {
+                let last = (cursor.line + count.unwrap_or(1) - 1).min(line_count - 1);
+                let text = line_text(editor, last);
+                Ok(Some(Range {
+                    start: Position { line: cursor.line, column: 0 },
+                    end: Position { line: last, column: text.len() },
+                    linewise: true,
+                }))
+            }
+            Target::Object(object) => Ok(text_object_range(editor, object, cursor).map(|(start, end)| Range {
+                start,
+                end,
+                linewise: false,
+            })),
+            Target::Motion(motion) => {
+                // cw на слове работает как ce
+                let on_word = !char_at(editor, cursor).is_whitespace();
+                let motion = match motion {
+                    Motion::WordForward(big) if operator == Operator::Change && on_word => Motion::WordEnd(big),
+                    other => other,
+                };
+                let kind = motion.kind();
+
+                let mut target = match motion {
+                    Motion::WordEnd(big) if operator == Operator::Change && on_word => {
+                        // Первое слово меняется до своего конца, даже если курсор на последней букве
+                        let mut target = current_word_end(editor, cursor, big);
+                        for _ in 1..count.unwrap_or(1) {
+                            target = word_end(editor, target, big);
+                        }
+                        target
+                    }
+                    _ => self.motion_target(editor, motion, count)?,
+                };
+                // Для dw последнее слово строки удаляется без перевода строки
+                if let Motion::WordForward(_) = motion {
+                    if target.line > cursor.line {
+                        let previous = target.line - 1;
+                        target = Position { line: previous, column: line_text(editor, previous).len() };
+                    }
+                }
+
+                let (start, end) = if target < cursor { (target, cursor) } else { (cursor, target) };
+                let range = match kind {
+                    MotionKind::Linewise => {
+                        let text = line_text(editor, end.line);
+                        Range {
+                            start: Position { line: start.line, column: 0 },
+                            end: Position { line: end.line, column: text.len() },
+                            linewise: true,
+                        }
+                    }
+                    MotionKind::Inclusive => {
+                        let text = line_text(editor, end.line);
+                        Range {
+                            start,
+                            end: Position { line: end.line, column: next_boundary(&text, end.column) },
+                            linewise: false,
+                        }
+                    }
+                    MotionKind::Exclusive => Range { start, end, linewise: false },
+                };
+                Ok(Some(range))
+            }
+        }
+    }
+
+    // ---------- Операторы ----------
+
+    fn apply_operator(&mut self, editor: &mut Editor, operator: Operator, range: Range, register: Option<char>) -> Result<()> {
+        let line_count = editor.get_buffer().get_all_lines().len();
+        let text = if range.linewise {
+            let lines: Vec<String> = (range.start.line..=range.end.line).map(|i| line_text(editor, i)).collect();
+            format!("{}\n", lines.join("\n"))
+        } else {
+            editor
+                .get_buffer()
+                .get_text_in_range(range.start, range.end)
+                .map_err(|e| anyhow!("Ошибка получения текста: {}", e))?
+        };
+        let kind = if range.linewise { RegisterKind::Linewise } else { RegisterKind::Charwise };
+
+        match operator {
+            Operator::Yank => {
+                self.store_register(editor, register, text, kind, true);
+                move_to(editor, Position { line: range.start.line, column: range.start.column })?;
+            }
+            Operator::Delete => {
+                self.store_register(editor, register, text, kind, false);
+                if range.linewise {
+                    let (first, last) = (range.start.line, range.end.line);
+                    let (start, end) = if last + 1 < line_count {
+                        (Position { line: first, column: 0 }, Position { line: last + 1, column: 0 })
+                    } else if first > 0 {
+                        let previous = line_text(editor, first - 1);
+                        (Position { line: first - 1, column: previous.len() }, range.end)
+                    } else {
+                        (Position { line: 0, column: 0 }, range.end)
+                    };
+                    replace_range(editor, start, end, "")?;
+                    let line = first.min(editor.get_buffer().get_all_lines().len() - 1);
+                    let text = line_text(editor, line);
+                    move_to(editor, Position { line, column: first_non_blank(&text) })?;
+                } else {
+                    replace_range(editor, range.start, range.end, "")?;
+                    move_to(editor, clamp_normal(editor, range.start))?;
+                }
+            }
+            Operator::Change => {
+                self.store_register(editor, register, text, kind, false);
+                if range.linewise {
+                    // Строки очищаются, но отступ первой сохраняется
+                    let indent_end = first_non_blank(&line_text(editor, range.start.line));
+                    let start = Position { line: range.start.line, column: indent_end };
+                    replace_range(editor, start, range.end, "")?;
+                    move_to(editor, start)?;
+                } else {
+                    replace_range(editor, range.start, range.end, "")?;
+                    move_to(editor, range.start)?;
+                }
+                self.enter_insert();
+            }
+            Operator::Indent | Operator::Outdent => {
+                // Ширина сдвига и символ отступа берутся из настроек табуляции буфера
+                let tabs = editor.get_buffer().tab_settings();
+                let unit = tabs.unit();
+                for line_index in range.start.line..=range.end.line {
+                    let text = line_text(editor, line_index);
+                    if operator == Operator::Indent {
+                        if !text.is_empty() {
+                            move_to(editor, Position { line: line_index, column: 0 })?;
+                            editor.execute_command(Command::InsertText(unit.clone()))?;
+                        }
+                    } else {
+                        let spaces = text.len() - text.trim_start_matches(' ').len();
+                        let remove = if text.starts_with('\t') { 1 } else { spaces.min(tabs.width) };
+                        if remove > 0 {
+                            replace_range(
+                                editor,
+                                Position { line: line_index, column: 0 },
+                                Position { line: line_index, column: remove },
+                                "",
+                            )?;
+                        }
+                    }
+                }
+                let text = line_text(editor, range.start.line);
+                move_to(editor, Position { line: range.start.line, column: first_non_blank(&text) })?;
+            }
+        }
+        Ok(())
+    }
+
+    // Объединяет текущую строку со следующей через один пробел
+    fn join_line(&mut self, editor: &mut Editor) -> Result<()> {
+        let cursor = editor.get_cursor_position();
+        if cursor.line + 1 >= editor.get_buffer().get_all_lines().len() {
+            return Err(anyhow!("Нет следующей строки"));
+        }
+        let current = line_text(editor, cursor.line);
+        let next = line_text(editor, cursor.line + 1);
+        let trimmed_len = current.trim_end().len();
+        let next_indent = next.len() - next.trim_start().len();
+        let separator = if trimmed_len == 0 || next.trim_start().is_empty() || next.trim_start().starts_with(')') {
+            ""
+        } else {
+            " "
+        };
+        replace_range(
+            editor,
+            Position { line: cursor.line, column: trimmed_len },
+            Position { line: cursor.line + 1, column: next_indent },
+            separator,
+        )?;
+        move_to(editor, Position { line: cursor.line, column: trimmed_len })
+    }
+
+    // ---------- Регистры ----------
+
+    // Сохраняет текст в регистр по правилам Vim
+    fn store_register(&mut self, editor: &mut Editor, register: Option<char>, text: String, kind: RegisterKind, yank: bool) {
+        let register = register.unwrap_or('"');
+        if register == '_' {
+            return;
+        }
+        // "+ и "* - системный буфер обмена и PRIMARY
+        if let Some(selection) = system_selection(register) {
+            editor.set_clipboard_text(selection, text);
+            return;
+        }
+        let value = Register { text, kind };
+
+        // a-z - именованные регистры редактора, они сохраняются между запусками
+        // Заглавная буква дописывает в регистр
+        if register.is_ascii_alphabetic() {
+            if let Err(e) = editor.set_register(register, value) {
+                log::warn!("{}", e);
+            }
+            if let Some(stored) = editor.register(register) {
+                self.registers.insert('"', stored);
+            }
+            return;
+        }
+
+        if yank {
+            self.registers.insert('0', value.clone());
+        } else if kind == RegisterKind::Linewise || value.text.contains('\n') {
+            // Нумерованные регистры сдвигаются: "1 -> "2 -> ... -> "9
+            for number in (1..9).rev() {
+                let from = char::from_digit(number, 10).unwrap_or('1');
+                let to = char::from_digit(number + 1, 10).unwrap_or('2');
+                if let Some(previous) = self.registers.get(&from).cloned() {
+                    self.registers.insert(to, previous);
+                }
+            }
+            self.registers.insert('1', value.clone());
+        } else {
+            self.registers.insert('-', value.clone());
+        }
+        self.registers.insert('"', value);
+    }
+
+    // Вставка содержимого регистра (p и P)
+    fn put(&mut self, editor: &mut Editor, register: Option<char>, count: usize, after: bool) -> Result<()> {
+        let name = register.unwrap_or('"').to_ascii_lowercase();
+        let stored = match system_selection(name) {
+            Some(selection) => editor.clipboard_text(selection).map(|text| Register {
+                kind: if text.ends_with('\n') { RegisterKind::Linewise } else { RegisterKind::Charwise },
+                text,
+            }),
+            None if name.is_ascii_lowercase() => editor.register(name),
+            None => self.registers.get(&name).cloned(),
+        };
+        let value = match stored {
+            Some(value) => value,
+            None => return Err(anyhow!("Регистр \"{} пуст", name)),
+        };
+        let cursor = editor.get_cursor_position();
+
+        match value.kind {
+            RegisterKind::Linewise => {
+                let body = value.text.strip_suffix('\n').unwrap_or(&value.text);
+                let block = vec![body; count].join("\n");
+                let first_line = if after {
+                    let text = line_text(editor, cursor.line);
+                    move_to(editor, Position { line: cursor.line, column: text.len() })?;
+                    editor.execute_command(Command::InsertText(format!("\n{}", block)))?;
+                    cursor.line + 1
+                } else {
+                    move_to(editor, Position { line: cursor.line, column: 0 })?;
+                    editor.execute_command(Command::InsertText(format!("{}\n", block)))?;
+                    cursor.line
+                };
+                let text = line_text(editor, first_line);
+                move_to(editor, Position { line: first_line, column: first_non_blank(&text) })?;
+            }
+            RegisterKind::Charwise => {
+                let line = line_text(editor, cursor.line);
+                let start = if after { next_boundary(&line, cursor.column) } else { cursor.column };
+                move_to(editor, Position { line: cursor.line, column: start })?;
+                editor.execute_command(Command::InsertText(value.text.repeat(count)))?;
+                // Курсор встает на последний вставленный символ
+                let end = editor.get_cursor_position();
+                let text = line_text(editor, end.line);
+                move_to(editor, Position { line: end.line, column: prev_boundary(&text, end.column) })?;
+            }
+            RegisterKind::Blockwise => {
+                let line = line_text(editor, cursor.line);
+                let mut column = char_column(&line, cursor.column);
+                if after && !line.is_empty() {
+                    column += 1;
+                }
+                for (offset, row) in value.text.split('\n').enumerate() {
+                    let line_index = cursor.line + offset;
+                    if line_index >= editor.get_buffer().get_all_lines().len() {
+                        let last = editor.get_buffer().get_all_lines().len() - 1;
+                        let text = line_text(editor, last);
+                        move_to(editor, Position { line: last, column: text.len() })?;
+                        editor.execute_command(Command::InsertNewline)?;
+                    }
+                    let text = line_text(editor, line_index);
+                    let length = text.chars().count();
+                    move_to(editor, Position { line: line_index, column: text.len().min(byte_column(&text, column)) })?;
+                    // Короткие строки дополняются пробелами до столбца блока
+                    let padding = " ".repeat(column.saturating_sub(length));
+                    editor.execute_command(Command::InsertText(format!("{}{}", padding, row.repeat(count))))?;
+                }
+                let text = line_text(editor, cursor.line);
+                move_to(editor, Position { line: cursor.line, column: byte_column(&text, column) })?;
+            }
+        }
+        Ok(())
+    }
+
+    // Возвращает курсор на последний символ строки, если он оказался за ней
+    fn clamp_cursor(&mut self, editor: &mut Editor) -> Result<()> {
+        let cursor = editor.get_cursor_position();
+        let clamped = clamp_normal(editor, cursor);
+        if clamped != cursor {
+            move_to(editor, clamped)?;
+        }
+        Ok(())
+    }
+}
+
+impl Default for VimState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// ---------- Разбор команд Normal-режима ----------
+
+// Разбирает накопленные клавиши: ["x][count]cmd[count][motion]
+fn parse_normal(keys: &[Key]) -> Parse {
+    let mut index = 0;
+
+    // Регистр
+    let mut register = None;
+    if keys.first() == Some(&Key::Char('"')) {
+        match keys.get(1) {
+            None => return Parse::Incomplete,
+            Some(Key::Char(c)) if c.is_ascii_alphanumeric() || "\"-_+*".contains(*c) => register = Some(*c),
+            Some(_) => return Parse::Invalid,
+        }
+        index = 2;
+    }
+
+    let (count, next) = parse_count(keys, index);
+    index = next;
+
+    let key = match keys.get(index) {
+        Some(key) => *key,
+        None => return Parse::Incomplete,
+    };
+    index += 1;
+
+    let done = |action| Parse::Done(NormalCommand { register, count, action });
+
+    // Операторы
+    let operator = match key {
+        Key::Char('d') => Some(Operator::Delete),
+        Key::Char('c') => Some(Operator::Change),
+        Key::Char('y') => Some(Operator::Yank),
+        Key::Char('>') => Some(Operator::Indent),
+        Key::Char('<') => Some(Operator::Outdent),
+        _ => None,
+    };
+    if let Some(operator) = operator {
+        let (second_count, next) = parse_count(keys, index);
+        let total = match (count, second_count) {
+            (None, None) => None,
+            (a, b) => Some(a.unwrap_or(1) * b.unwrap_or(1)),
+        };
+        let with_count = |action| Parse::Done(NormalCommand { register, count: total, action });
+
+        let next_key = match keys.get(next) {
+            Some(key) => *key,
+            None => return Parse::Incomplete,
+        };
+        // dd, cc, yy, >>, <<
+        if next_key == key {
+            return with_count(Action::Operate(operator, Target::Lines));
+        }
+        // Текстовые объекты
+        if let Key::Char(c @ ('i' | 'a')) = next_key {
+            return match keys.get(next + 1) {
+                None => Parse::Incomplete,
+                Some(Key::Char(kind)) if "wW\"'`()b[]{}B<>".contains(*kind) => {
+                    with_count(Action::Operate(operator, Target::Object(TextObject { inner: c == 'i', kind: *kind })))
+                }
+                Some(_) => Parse::Invalid,
+            };
+        }
+        return match parse_motion(keys, next) {
+            MotionParse::Incomplete => Parse::Incomplete,
+            MotionParse::Invalid => Parse::Invalid,
+            MotionParse::Done(motion) => with_count(Action::Operate(operator, Target::Motion(motion))),
+        };
+    }
+
+    // Команды-сокращения для операторов
+    let shortcut = match key {
+        Key::Char('x') | Key::Delete => Some((Operator::Delete, Target::Motion(Motion::Right))),
+        Key::Char('X') => Some((Operator::Delete, Target::Motion(Motion::Left))),
+        Key::Char('D') => Some((Operator::Delete, Target::Motion(Motion::LineEnd))),
+        Key::Char('C') => Some((Operator::Change, Target::Motion(Motion::LineEnd))),
+        Key::Char('s') => Some((Operator::Change, Target::Motion(Motion::Right))),
+        Key::Char('S') => Some((Operator::Change, Target::Lines)),
+        Key::Char('Y') => Some((Operator::Yank, Target::Lines)),
+        _ => None,
+    };
+    if let Some((operator, target)) = shortcut {
+        return done(Action::Operate(operator, target));
+    }
+
+    let simple = match key {
+        Key::Char('i') => Some(Simple::Insert),
+        Key::Char('a') => Some(Simple::Append),
+        Key::Char('I') => Some(Simple::InsertLineStart),
+        Key::Char('A') => Some(Simple::AppendLineEnd),
+        Key::Char('o') => Some(Simple::OpenBelow),
+        Key::Char('O') => Some(Simple::OpenAbove),
+        Key::Char('p') => Some(Simple::PutAfter),
+        Key::Char('P') => Some(Simple::PutBefore),
+        Key::Char('u') => Some(Simple::Undo),
+        Key::Ctrl('r') => Some(Simple::Redo),
+        Key::Char('v') => Some(Simple::Visual),
+        Key::Char('V') => Some(Simple::VisualLine),
+        Key::Ctrl('v') => Some(Simple::VisualBlock),
+        Key::Char('.') => Some(Simple::Repeat),
+        Key::Char(':') => Some(Simple::CommandLine),
+        Key::Char('J') => Some(Simple::JoinLines),
+        _ => None,
+    };
+    if let Some(simple) = simple {
+        return done(Action::Simple(simple));
+    }
+    if key == Key::Char('r') {
+        return match keys.get(index) {
+            None => Parse::Incomplete,
+            Some(Key::Char(c)) => done(Action::Simple(Simple::ReplaceChar(*c))),
+            Some(_) => Parse::Invalid,
+        };
+    }
+
+    match parse_motion(keys, index - 1) {
+        MotionParse::Incomplete => Parse::Incomplete,
+        MotionParse::Invalid => Parse::Invalid,
+        MotionParse::Done(motion) => done(Action::Move(motion)),
+    }
+}
+
+// Читает число повторений, начиная с index
+fn parse_count(keys: &[Key], index: usize) -> (Option<usize>, usize) {
+    let mut index = index;
+    let mut count: Option<usize> = None;
+    while let Some(Key::Char(c)) = keys.get(index) {
+        // Ноль в начале - это движение к началу строки, а не число
+        let digit = match c.to_digit(10) {
+            Some(0) if count.is_none() => break,
+            Some(digit) => digit as usize,
+            None => break,
+        };
+        count = Some(count.unwrap_or(0).saturating_mul(10).saturating_add(digit));
+        index += 1;
+    }
+    (count, index)
+}
+
+enum MotionParse {
+    Incomplete,
+    Invalid,
+    Done(Motion),
+}
+
+// Разбирает движение, начинающееся с keys[index]
+fn parse_motion(keys: &[Key], index: usize) -> MotionParse {
+    let key = match keys.get(index) {
+        Some(key) => *key,
+        None => return MotionParse::Incomplete,
+    };
+    let motion = match key {
+        Key::Char('h') | Key::Left | Key::Backspace => Motion::Left,
+        Key::Char('l') | Key::Right | Key::Char(' ') => Motion::Right,
+        Key::Char('k') | Key::Up => Motion::Up,
+        Key::Char('j') | Key::Down | Key::Enter => Motion::Down,
+        Key::Char('w') => Motion::WordForward(false),
+        Key::Char('W') => Motion::WordForward(true),
+        Key::Char('b') => Motion::WordBackward(false),
+        Key::Char('B') => Motion::WordBackward(true),
+        Key::Char('e') => Motion::WordEnd(false),
+        Key::Char('E') => Motion::WordEnd(true),
+        Key::Char('0') | Key::Home => Motion::LineStart,
+        Key::Char('^') => Motion::FirstNonBlank,
+        Key::Char('$') | Key::End => Motion::LineEnd,
+        Key::Char('G') => Motion::FileEnd,
+        Key::Char(';') => Motion::RepeatFind(false),
+        Key::Char(',') => Motion::RepeatFind(true),
+        Key::Char('%') => Motion::MatchPair,
+        Key::Char('g') => {
+            return match keys.get(index + 1) {
+                None => MotionParse::Incomplete,
+                Some(Key::Char('g')) => MotionParse::Done(Motion::FileStart),
+                Some(_) => MotionParse::Invalid,
+            };
+        }
+        Key::Char(find @ ('f' | 'F' | 't' | 'T')) => {
+            return match keys.get(index + 1) {
+                None => MotionParse::Incomplete,
+                Some(Key::Char(c)) => MotionParse::Done(Motion::Find {
+                    c: *c,
+                    forward: find == 'f' || find == 't',
+                    till: find == 't' || find == 'T',
+                }),
+                Some(_) => MotionParse::Invalid,
+            };
+        }
+        _ => return MotionParse::Invalid,
+    };
+    if index + 1 < keys.len() {
+        return MotionParse::Invalid;
+    }
+    MotionParse::Done(motion)
+}
+
+// Регистры, связанные с буфером обмена
+fn system_selection(register: char) -> Option<Selection> {
+    match register {
+        '+' => Some(Selection::Clipboard),
+        '*' => Some(Selection::Primary),
+        _ => None,
+    }
+}
+
+// Изменяет ли команда текст (для повтора через '.')
+fn is_change(action: &Action) -> bool {
+    match action {
+        Action::Operate(Operator::Yank, _) => false,
+        Action::Operate(_, _) => true,
+        Action::Simple(simple) => matches!(
+            simple,
+            Simple::PutAfter | Simple::PutBefore | Simple::JoinLines | Simple::ReplaceChar(_)
+        ),
+        Action::Move(_) => false,
+    }
+}
+
+// ---------- Работа с текстом ----------
+
+// В Normal-режиме курсор стоит на символе, а не после конца строки
+fn clamp_normal(editor: &Editor, position: Position) -> Position {
+    let text = line_text(editor, position.line);
+    if !text.is_empty() && position.column >= text.len() {
+        Position { line: position.line, column: prev_boundary(&text, text.len()) }
+    } else {
+        position
+    }
+}
+
+// Пустая строка считается отдельным словом
+fn is_empty_line(editor: &Editor, position: Position) -> bool {
+    position.column == 0 && lines(editor).get(position.line).is_some_and(|line| line.is_empty())
+}
+
+// w / W
+fn word_forward(editor: &Editor, start: Position, big: bool) -> Position {
+    let mut position = start;
+    let class = char_class(char_at(editor, position), big);
+    // Пропускаем текущее слово
+    if class != 0 {
+        loop {
+            match next_position(editor, position) {
+                Some(next) => position = next,
+                None => return end_of_buffer(editor),
+            }
+            if char_class(char_at(editor, position), big) != class || position.column == 0 {
+                break;
+            }
+        }
+    }
+    // Пропускаем пробелы и переводы строк
+    while char_class(char_at(editor, position), big) == 0 {
+        if position != start && is_empty_line(editor, position) {
+            break;
+        }
+        match next_position(editor, position) {
+            Some(next) => position = next,
+            None => return end_of_buffer(editor),
+        }
+    }
+    position
+}
+
+// b / B
+fn word_backward(editor: &Editor, start: Position, big: bool) -> Position {
+    let mut position = match previous_position(editor, start) {
+        Some(previous) => previous,
+        None => return start,
+    };
+    // Пропускаем пробелы
+    while char_class(char_at(editor, position), big) == 0 {
+        if is_empty_line(editor, position) {
+            return position;
+        }
+        match previous_position(editor, position) {
+            Some(previous) => position = previous,
+            None => return position,
+        }
+    }
+    // Идем к началу слова
+    let class = char_class(char_at(editor, position), big);
+    while position.column > 0 {
+        match previous_position(editor, position) {
+            Some(previous) if char_class(char_at(editor, previous), big) == class => position = previous,
+            _ => break,
+        }
+    }
+    position
+}
+
+// e / E
+fn word_end(editor: &Editor, start: Position, big: bool) -> Position {
+    let mut position = match next_position(editor, start) {
+        Some(next) => next,
+        None => return start,
+    };
+    while char_class(char_at(editor, position), big) == 0 {
+        match next_position(editor, position) {
+            Some(next) => position = next,
+            None => return start,
+        }
+    }
+    let class = char_class(char_at(editor, position), big);
+    loop {
+        match next_position(editor, position) {
+            Some(next) if next.line == position.line && char_class(char_at(editor, next), big) == class => {
+                position = next
+            }
+            _ => break,
+        }
+    }
+    position
+}
+
+// Последний символ слова под курсором (в пределах строки)
+fn current_word_end(editor: &Editor, start: Position, big: bool) -> Position {
+    let class = char_class(char_at(editor, start), big);
+    let mut position = start;
+    while let Some(next) = next_position(editor, position) {
+        if next.line != position.line || char_class(char_at(editor, next), big) != class {
+            break;
+        }
+        position = next;
+    }
+    position
+}
+
+// Поиск символа в строке для f/F/t/T
+fn find_in_line(line: &str, from: Position, c: char, forward: bool, till: bool, times: usize) -> Option<Position> {
+    let mut found = None;
+    let mut remaining = times;
+    if forward {
+        let start = next_boundary(line, from.column);
+        for (index, candidate) in line.get(start..)?.char_indices() {
+            if candidate == c {
+                remaining -= 1;
+                if remaining == 0 {
+                    found = Some(start + index);
+                    break;
+                }
+            }
+        }
+        let column = found?;
+        let column = if till { prev_boundary(line, column) } else { column };
+        Some(Position { line: from.line, column })
+    } else {
+        for (index, candidate) in line.get(..from.column)?.char_indices().rev() {
+            if candidate == c {
+                remaining -= 1;
+                if remaining == 0 {
+                    found = Some(index);
+                    break;
+                }
+            }
+        }
+        let column = found?;
+        let column = if till { next_boundary(line, column) } else { column };
+        Some(Position { line: from.line, column })
+    }
+}
+
+// Парная скобка для %: берется первая скобка под курсором или правее в строке
+// Скобки в строках и комментариях пропускаются, если о них знает подсветка
+fn match_pair(editor: &Editor, cursor: Position) -> Option<Position> {
+    let buffer = editor.get_buffer();
+    let line = lines(editor).get(cursor.line)?;
+    let start = line
+        .get(cursor.column..)?
+        .char_indices()
+        .map(|(offset, c)| (Position { line: cursor.line, column: cursor.column + offset }, c))
+        .find(|&(position, c)| "()[]{}".contains(c) && !buffer.is_literal(position))?
+        .0;
+    buffer.matching_bracket(start)
+}
+
+// Диапазон текстового объекта (конец не включается)
+fn text_object_range(editor: &Editor, object: TextObject, cursor: Position) -> Option<(Position, Position)> {
+    match object.kind {
+        'w' | 'W' => word_object(editor, cursor, object.inner, object.kind == 'W'),
+        '"' | '\'' | '`' => quote_object(editor, cursor, object.kind, object.inner),
+        '(' | ')' | 'b' => bracket_object(editor, cursor, '(', ')', object.inner),
+        '[' | ']' => bracket_object(editor, cursor, '[', ']', object.inner),
+        '{' | '}' | 'B' => bracket_object(editor, cursor, '{', '}', object.inner),
+        '<' | '>' => bracket_object(editor, cursor, '<', '>', object.inner),
+        _ => None,
+    }
+}
+
+// iw / aw
+fn word_object(editor: &Editor, cursor: Position, inner: bool, big: bool) -> Option<(Position, Position)> {
+    let line = lines(editor).get(cursor.line)?;
+    if line.is_empty() {
+        return None;
+    }
+    let column = cursor.column.min(prev_boundary(line, line.len()));
+    let class_at = |column: usize| line[column..].chars().next().map(|c| char_class(c, big));
+    let class = class_at(column)?;
+
+    let mut start = column;
+    while start > 0 && class_at(prev_boundary(line, start)) == Some(class) {
+        start = prev_boundary(line, start);
+    }
+    let mut end = column;
+    while end < line.len() && class_at(end) == Some(class) {
+        end = next_boundary(line, end);
+    }
+
+    if !inner {
+        // aw захватывает пробелы после слова, а если их нет - перед ним
+        let mut extended = end;
+        while extended < line.len() && class_at(extended) == Some(0) {
+            extended = next_boundary(line, extended);
+        }
+        if extended > end && class != 0 {
+            end = extended;
+        } else {
+            while start > 0 && class_at(prev_boundary(line, start)) == Some(0) {
+                start = prev_boundary(line, start);
+            }
+        }
+    }
+    Some((Position { line: cursor.line, column: start }, Position { line: cursor.line, column: end }))
+}
+
+// i" / a" - кавычки ищутся только в текущей строке
+fn quote_object(editor: &Editor, cursor: Position, quote: char, inner: bool) -> Option<(Position, Position)> {
+    let line = lines(editor).get(cursor.line)?;
+    let mut quotes = Vec::new();
+    let mut escaped = false;
+    for (index, c) in line.char_indices() {
+        if c == quote && !escaped {
+            quotes.push(index);
+        }
+        escaped = c == '\\' && !escaped;
+    }
+    // Пара, внутри которой курсор, или первая пара правее курсора
+    let pair = quotes
+        .chunks(2)
+        .filter(|pair| pair.len() == 2)
+        .find(|pair| cursor.column <= pair[1])?;
+    let (open, close) = (pair[0], pair[1]);
+    let (start, end) = if inner {
+        (open + quote.len_utf8(), close)
+    } else {
+        (open, close + quote.len_utf8())
+    };
+    Some((Position { line: cursor.line, column: start }, Position { line: cursor.line, column: end }))
+}
+
+// i( / a( и другие скобки, могут занимать несколько строк
+fn bracket_object(editor: &Editor, cursor: Position, open: char, close: char, inner: bool) -> Option<(Position, Position)> {
+    // Если курсор стоит на открывающей скобке, берем ее
+    let buffer = editor.get_buffer();
+    let open_position = if char_at(editor, cursor) == open {
+        cursor
+    } else {
+        buffer.unmatched_bracket(cursor, (open, close), false)?
+    };
+    let after_open = Position { line: open_position.line, column: open_position.column + open.len_utf8() };
+    let close_position = buffer.unmatched_bracket(after_open, (open, close), true)?;
+
+    if !inner {
+        let line = lines(editor).get(close_position.line)?;
+        return Some((open_position, Position { line: close_position.line, column: next_boundary(line, close_position.column) }));
+    }
+
+    let mut start = Position { line: open_position.line, column: open_position.column + open.len_utf8() };
+    let mut end = close_position;
+    // Многострочный блок: не захватываем перевод строки после '{' и отступ перед '}'
+    if start.column == lines(editor)[start.line].len() && start.line < end.line {
+        start = Position { line: start.line + 1, column: 0 };
+    }
+    let close_line = &lines(editor)[end.line];
+    if end.line > start.line && close_line[..end.column].trim().is_empty() {
+        end = Position { line: end.line - 1, column: lines(editor)[end.line - 1].len() };
+    }
+    if end < start {
+        end = start;
+    }
+    Some((start, end))
+}
+
+// Делит строку по разделителю, учитывая экранирование обратной косой чертой
+fn split_unescaped(text: &str, delimiter: char) -> Vec<String> {
+    let mut parts = vec![String::new()];
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            match chars.next() {
+                Some(next) if next == delimiter => parts.last_mut().unwrap().push(next),
+                Some(next) => {
+                    let part = parts.last_mut().unwrap();
+                    part.push('\\');
+                    part.push(next);
+                }
+                None => parts.last_mut().unwrap().push('\\'),
+            }
+        } else if c == delimiter {
+            parts.push(String::new());
+        } else {
+            parts.last_mut().unwrap().push(c);
+        }
+    }
+    parts
+}
+
+// Переводит строку замены Vim (& и \1) в синтаксис regex ($0 и ${1})
+fn vim_replacement(replacement: &str) -> String {
+    let mut result = String::new();
+    let mut chars = replacement.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '&' => result.push_str("${0}"),
+            '$' => result.push_str("$$"),
+            '\\' => match chars.next() {
+                Some(digit) if digit.is_ascii_digit() => result.push_str(&format!("${{{}}}", digit)),
+                Some('n') | Some('r') => result.push('\n'),
+                Some('t') => result.push('\t'),
+                Some(other) => result.push(other),
+                None => result.push('\\'),
+            },
+            _ => result.push(c),
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::editor::fixtures::{editor, text};
+    use crate::editor::TabSettings;
+
+    fn feed(vim: &mut VimState, editor: &mut Editor, keys: &str) {
+        for key in Key::parse_sequence(keys) {
+            vim.handle_key(editor, key).unwrap();
+        }
+    }
+
+    #[test]
+    fn operators_repeat_and_undo() {
+        let mut editor = editor("hello world foo");
+        let mut vim = VimState::new();
+        feed(&mut vim, &mut editor, "dw.");
+        assert_eq!(text(&editor), "foo");
+        feed(&mut vim, &mut editor, "u");
+        assert_eq!(text(&editor), "world foo");
+        feed(&mut vim, &mut editor, "cwпривет<Esc>");
+        assert_eq!(text(&editor), "привет foo");
+        assert_eq!(vim.mode(), VimMode::Normal);
+        feed(&mut vim, &mut editor, "u");
+        assert_eq!(text(&editor), "world foo");
+        feed(&mut vim, &mut editor, "yyp:%s/o/0/g<CR>");
+        assert_eq!(text(&editor), "w0rld f00\nw0rld f00");
+    }
+
+    #[test]
+    fn shift_width_follows_tab_settings() {
+        let mut editor = editor("a\nb");
+        let mut vim = VimState::new();
+        editor.get_buffer_mut().set_tab_settings(TabSettings { width: 2, use_tabs: false });
+        feed(&mut vim, &mut editor, ">j");
+        assert_eq!(text(&editor), "  a\n  b");
+        feed(&mut vim, &mut editor, "<<");
+        assert_eq!(text(&editor), "a\n  b");
+
+        editor.get_buffer_mut().set_tab_settings(TabSettings { width: 8, use_tabs: true });
+        feed(&mut vim, &mut editor, ">>>>");
+        assert_eq!(text(&editor), "\t\ta\n  b");
+        feed(&mut vim, &mut editor, "<<");
+        assert_eq!(text(&editor), "\ta\n  b");
+    }
+
+    // Результат клавиш на новом тексте
+    fn run(initial: &str, keys: &str) -> String {
+        let mut editor = editor(initial);
+        let mut vim = VimState::new();
+        feed(&mut vim, &mut editor, keys);
+        text(&editor)
+    }
+
+    #[test]
+    fn counts_and_text_objects() {
+        assert_eq!(run("one two three four", "d3w"), "four");
+        assert_eq!(run("one two three four", "w2dw"), "one four");
+        assert_eq!(run("x = \"abc\";", "fbci\"zz<Esc>"), "x = \"zz\";");
+        assert_eq!(run("x = \"abc\";", "fbda\""), "x = ;");
+        assert_eq!(run("f(a, b)", "fadi("), "f()");
+    }
+
+    #[test]
+    fn visual_block() {
+        assert_eq!(run("abcd\nefgh\nijkl", "l<C-v>jld"), "ad\neh\nijkl");
+        // Блок вставляется столбцом с позиции после курсора на каждой строке
+        assert_eq!(run("abc\ndef", "<C-v>jy$p"), "abca\ndefd");
+        assert_eq!(run("abc\ndef\nghi", "l<C-v>jdjp"), "ac\ndfb\nghei");
+    }
+
+    #[test]
+    fn substitute() {
+        assert_eq!(run("aaa\naaa", ":s/a/b/g<CR>"), "bbb\naaa");
+        assert_eq!(run("aaa\naaa", ":s/a/b<CR>"), "baa\naaa");
+        assert_eq!(run("aaa\naaa", ":%s/a/b<CR>"), "baa\nbaa");
+    }
+
+    #[test]
+    fn normal_on_every_line() {
+        assert_eq!(run("a\nb\nc", ":%norm A;<CR>"), "a;\nb;\nc;");
+        assert_eq!(run("a\nb\nc", ":2,3norm I- <CR>"), "a\n- b\n- c");
+    }
+
+    #[test]
+    fn named_register_put() {
+        assert_eq!(run("one\ntwo", "\"ayyj\"ap"), "one\ntwo\none");
+        // Заглавное имя дописывает к регистру
+        assert_eq!(run("one\ntwo", "\"ayyj\"Ayy\"aP"), "one\none\ntwo\ntwo");
+        assert_eq!(run("one two", "\"byw$\"bp"), "one twoone ");
+    }
+
+    #[test]
+    fn repeat_insert() {
+        assert_eq!(run("x\ny", "ifoo<Esc>j0."), "foox\nfooy");
+        assert_eq!(run("a\nb", "A;<Esc>j."), "a;\nb;");
+        assert_eq!(run("a\nb", "ox<Esc>j."), "a\nx\nb\nx");
+    }
+}
@@ -1,14 +1,72 @@
 mod editor;
+mod syntax;
 mod ui;
 mod utils;
 
-use anyhow::Result;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Result};
 
 fn main() -> Result<()> {
     env_logger::init();
-    
-    let mut app = ui::App::new()?;
+
+    // Восстановление документа по журналу правок:
+    // simple-text-editor --replay <журнал> [число записей]
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(1).map(String::as_str) == Some("--replay") {
+        return replay_journal(&args[2..]);
+    }
+
+    // Запись журналов правок:
+    // simple-text-editor --journal <каталог> [--journal-format json|binary]
+    let app = ui::App::new(journal_options(&args[1..])?)?;
     app.run()?;
 
     Ok(())
 }
+
+// Каталог и формат журналов правок из аргументов; None - журналы не пишутся
+fn journal_options(args: &[String]) -> Result<Option<(PathBuf, editor::JournalFormat)>> {
+    let mut directory = None;
+    let mut format = editor::JournalFormat::Json;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| anyhow!("После {} нужно значение", arg));
+        match arg.as_str() {
+            "--journal" => directory = Some(PathBuf::from(value()?)),
+            "--journal-format" => {
+                format = match value()?.as_str() {
+                    "json" => editor::JournalFormat::Json,
+                    "binary" => editor::JournalFormat::Binary,
+                    other => return Err(anyhow!("Неизвестный формат журнала: {}", other)),
+                }
+            }
+            other => return Err(anyhow!("Неизвестный аргумент: {}", other)),
+        }
+    }
+    Ok(directory.map(|directory| (directory, format)))
+}
+
+// Воспроизводит журнал и печатает получившийся документ
+fn replay_journal(args: &[String]) -> Result<()> {
+    let path = args.first().ok_or_else(|| anyhow!("Использование: --replay <журнал> [число записей]"))?;
+    let limit = match args.get(1) {
+        Some(count) => Some(count.parse::<usize>().map_err(|_| anyhow!("Неверное число записей: {}", count))?),
+        None => None,
+    };
+
+    let journal = editor::Journal::load(Path::new(path))?;
+    log::info!(
+        "Журнал версии {}: {} строк в начале, {} записей",
+        journal.header.version,
+        journal.header.lines.len(),
+        journal.entries.len()
+    );
+    let editor = editor::replay(&journal, limit)?;
+
+    let lines: Vec<String> = editor.get_buffer().get_all_lines().iter().cloned().collect();
+    let mut stdout = std::io::stdout();
+    writeln!(stdout, "{}", lines.join("\n"))?;
+    Ok(())
+}
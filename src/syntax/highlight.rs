@@ -0,0 +1,479 @@
+// Подсветка синтаксиса в отдельном потоке
+// Окно отправляет потоку измененные строки после каждой правки, поток дообрабатывает
+// прежнее дерево tree-sitter (разбирается только измененная часть) и возвращает
+// для каждой строки отрезки со стилями; заново раскрашиваются только строки, которые
+// задела правка или изменение дерева. Пока результат не готов, строки
+// показываются с прежней подсветкой или без нее, ввод текста не ждет разбора
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::hash::{Hash, Hasher};
+use std::ops::Range;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+
+use streaming_iterator::StreamingIterator;
+use tree_sitter::{InputEdit, Node, Parser, Point, Query, QueryCursor, Tree};
+
+use crate::editor::Buffer;
+use crate::syntax::Language;
+
+// Стиль отрезка текста; цвета выбирает интерфейс
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Style {
+    Plain,
+    Keyword,
+    Type,
+    Function,
+    String,
+    Number,
+    Constant,
+    Comment,
+    Operator,
+    Punctuation,
+    Property,
+    Attribute,
+    Heading,
+    Bracket(usize), // скобка с глубиной вложенности при раскраске пар
+}
+
+impl Style {
+    // Стиль по имени захвата из запроса подсветки (keyword, string.special, ...)
+    fn from_capture(name: &str) -> Style {
+        let name = name.trim_start_matches("markup.").trim_start_matches("text.");
+        match name.split('.').next().unwrap_or(name) {
+            "keyword" | "conditional" | "repeat" | "include" | "exception" | "storageclass" => Style::Keyword,
+            "type" | "constructor" | "namespace" | "module" => Style::Type,
+            "function" | "method" => Style::Function,
+            "string" | "character" | "escape" | "literal" | "raw" => Style::String,
+            "number" | "float" => Style::Number,
+            "constant" | "boolean" => Style::Constant,
+            "comment" => Style::Comment,
+            "operator" => Style::Operator,
+            "punctuation" | "delimiter" => Style::Punctuation,
+            "property" | "field" | "label" => Style::Property,
+            "attribute" | "tag" | "uri" | "link" | "reference" => Style::Attribute,
+            "title" | "heading" => Style::Heading,
+            _ => Style::Plain,
+        }
+    }
+}
+
+// Отрезок строки со стилем; границы - байтовые столбцы
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub style: Style,
+}
+
+// Отрезки одной строки без стилей: байтовые столбцы начала и конца
+type Ranges = Vec<(usize, usize)>;
+
+// Подсветка одной строки; по хешу видно, относится ли она к тексту на экране
+#[derive(Clone)]
+struct LineSpans {
+    hash: u64,
+    spans: Vec<Span>,
+}
+
+// Результат разбора одной правки
+struct Highlights {
+    revision: u64,
+    language: Language,
+    lines: Vec<LineSpans>,
+    regions: Vec<(usize, usize)>, // многострочные конструкции для сворачивания
+    literals: Vec<Ranges>,        // строки и комментарии по строкам текста
+}
+
+// Замена строк first..first + removed строками lines
+struct LineEdit {
+    first: usize,
+    removed: usize,
+    lines: Vec<String>,
+}
+
+// Задание потоку: правка revision относительно предыдущего задания
+struct Job {
+    revision: u64,
+    language: Language,
+    edit: LineEdit,
+}
+
+pub struct Highlighter {
+    jobs: Sender<Job>,
+    results: Receiver<Highlights>,
+    sent: Option<(u64, Language)>, // последнее отправленное задание
+    sent_lines: Vec<String>,       // текст, который есть у потока; правки считаются от него
+    current: Option<Highlights>,   // последний полученный результат
+}
+
+impl Highlighter {
+    // Запускает поток разбора; notify вызывается, когда готов новый результат
+    pub fn new(notify: impl Fn() + Send + 'static) -> Self {
+        let (jobs, job_receiver) = mpsc::channel();
+        let (result_sender, results) = mpsc::channel();
+        let spawned = thread::Builder::new()
+            .name("highlight".to_string())
+            .spawn(move || run(job_receiver, result_sender, notify));
+        if let Err(e) = spawned {
+            // Без потока редактор работает, только без подсветки
+            log::warn!("Не удалось запустить поток подсветки: {}", e);
+        }
+        Self { jobs, results, sent: None, sent_lines: Vec::new(), current: None }
+    }
+
+    // Забирает готовые результаты и отправляет измененные строки
+    pub fn update(&mut self, buffer: &Buffer, language: Option<Language>) {
+        while let Ok(highlights) = self.results.try_recv() {
+            if Some(highlights.language) == language {
+                self.current = Some(highlights);
+            }
+        }
+        let Some(language) = language else {
+            self.sent = None;
+            self.current = None;
+            return;
+        };
+        if self.current.as_ref().is_some_and(|current| current.language != language) {
+            self.current = None;
+        }
+        if self.sent == Some((buffer.revision(), language)) {
+            return;
+        }
+        self.sent = Some((buffer.revision(), language));
+        let edit = line_edit(&mut self.sent_lines, buffer.get_all_lines());
+        // Поток завершился - подсветки просто не будет
+        let _ = self.jobs.send(Job { revision: buffer.revision(), language, edit });
+    }
+
+    // Отрезки строки документа line с текстом text
+    // Пока разбор правки не готов, строки ниже нее ищутся со сдвигом на число
+    // вставленных строк; строка с другим текстом не подсвечивается
+    pub fn spans(&self, line: usize, text: &str, line_count: usize) -> &[Span] {
+        let Some(current) = &self.current else { return &[] };
+        let hash = hash_line(text);
+        let shifted = (line + current.lines.len()).checked_sub(line_count);
+        [Some(line), shifted]
+            .into_iter()
+            .flatten()
+            .filter_map(|index| current.lines.get(index))
+            .find(|spans| spans.hash == hash)
+            .map_or(&[], |spans| spans.spans.as_slice())
+    }
+
+    // Области для сворачивания из последнего разбора и правка, к которой они относятся
+    pub fn regions(&self) -> Option<(u64, &[(usize, usize)])> {
+        self.current.as_ref().map(|current| (current.revision, current.regions.as_slice()))
+    }
+
+    // Отрезки строк и комментариев из последнего разбора (для поиска парных скобок)
+    pub fn literals(&self) -> Option<(u64, &[Ranges])> {
+        self.current.as_ref().map(|current| (current.revision, current.literals.as_slice()))
+    }
+}
+
+// Строки между общими началом и концом прежнего и нового текста; sent становится новым текстом
+fn line_edit(sent: &mut Vec<String>, lines: &VecDeque<String>) -> LineEdit {
+    let prefix = sent.iter().zip(lines.iter()).take_while(|(a, b)| a == b).count();
+    let limit = sent.len().min(lines.len()) - prefix;
+    let suffix = sent.iter().rev().zip(lines.iter().rev()).take(limit).take_while(|(a, b)| a == b).count();
+    let removed = sent.len() - suffix - prefix;
+    let inserted: Vec<String> = lines.range(prefix..lines.len() - suffix).cloned().collect();
+    sent.splice(prefix..prefix + removed, inserted.iter().cloned());
+    LineEdit { first: prefix, removed, lines: inserted }
+}
+
+fn hash_line(text: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    text.hash(&mut hasher);
+    hasher.finish()
+}
+
+// Разобранный текст, от которого считается следующая правка
+struct Parsed {
+    language: Language,
+    text: String,
+    tree: Tree,
+    lines: Vec<LineSpans>,
+}
+
+// Цикл потока: выполняет задания, пока окно не закроет канал
+fn run(jobs: Receiver<Job>, results: Sender<Highlights>, notify: impl Fn()) {
+    let mut parser = Parser::new();
+    let mut queries: HashMap<Language, Option<Query>> = HashMap::new();
+    let mut parsed: Option<Parsed> = None;
+    let mut text_lines: Vec<String> = Vec::new(); // текст из всех полученных правок
+
+    while let Ok(job) = jobs.recv() {
+        // Правки, накопившиеся за время разбора, применяются все, а разбирается только последний текст
+        let (mut revision, mut language) = (job.revision, job.language);
+        apply_edit(&mut text_lines, job.edit);
+        while let Ok(next) = jobs.try_recv() {
+            (revision, language) = (next.revision, next.language);
+            apply_edit(&mut text_lines, next.edit);
+        }
+        let text = text_lines.join("\n");
+
+        let Some(grammar) = language.grammar() else { continue };
+        let query = queries.entry(language).or_insert_with(|| {
+            Query::new(&grammar, &language.highlights_query())
+                .map_err(|e| log::warn!("Ошибка в запросе подсветки {:?}: {}", language, e))
+                .ok()
+        });
+        let Some(query) = query else { continue };
+
+        // Прежнее дерево и подсветка годятся только для того же языка
+        let previous = match parsed.take() {
+            Some(old) if old.language == language => {
+                let edit = input_edit(&old.text, &text);
+                let mut tree = old.tree;
+                tree.edit(&edit);
+                Some((tree, edit, old.lines))
+            }
+            _ => {
+                if let Err(e) = parser.set_language(&grammar) {
+                    log::warn!("Грамматика {:?} не подходит: {}", language, e);
+                    continue;
+                }
+                None
+            }
+        };
+        let Some(tree) = parser.parse(&text, previous.as_ref().map(|(tree, _, _)| tree)) else { continue };
+
+        let lines = line_spans(query, &tree, &text, previous);
+        let literals = lines
+            .iter()
+            .map(|line| {
+                line.spans
+                    .iter()
+                    .filter(|span| matches!(span.style, Style::String | Style::Comment))
+                    .map(|span| (span.start, span.end))
+                    .collect()
+            })
+            .collect();
+        let highlights = Highlights {
+            revision,
+            language,
+            lines: lines.clone(),
+            regions: regions(&tree, &text),
+            literals,
+        };
+        parsed = Some(Parsed { language, text, tree, lines });
+        if results.send(highlights).is_err() {
+            break;
+        }
+        notify();
+    }
+}
+
+fn apply_edit(lines: &mut Vec<String>, edit: LineEdit) {
+    let end = (edit.first + edit.removed).min(lines.len());
+    lines.splice(edit.first.min(end)..end, edit.lines);
+}
+
+// Правка между двумя версиями текста: общие начало и конец остаются
+fn input_edit(old: &str, new: &str) -> InputEdit {
+    let prefix = old.bytes().zip(new.bytes()).take_while(|(a, b)| a == b).count();
+    let limit = old.len().min(new.len()) - prefix;
+    let suffix = old.bytes().rev().zip(new.bytes().rev()).take(limit).take_while(|(a, b)| a == b).count();
+    let (old_end, new_end) = (old.len() - suffix, new.len() - suffix);
+    InputEdit {
+        start_byte: prefix,
+        old_end_byte: old_end,
+        new_end_byte: new_end,
+        start_position: point_at(old, prefix),
+        old_end_position: point_at(old, old_end),
+        new_end_position: point_at(new, new_end),
+    }
+}
+
+fn point_at(text: &str, byte: usize) -> Point {
+    let head = &text.as_bytes()[..byte];
+    let row = head.iter().filter(|&&b| b == b'\n').count();
+    let column = head.iter().rposition(|&b| b == b'\n').map_or(byte, |newline| byte - newline - 1);
+    Point { row, column }
+}
+
+// Стили по строкам
+// previous - прежнее дерево (уже с правкой edit) и его подсветка: из нее берутся строки,
+// которых не коснулись ни правка, ни изменения дерева; остальные раскрашиваются заново
+// Каждый байт получает стиль первого захвата, который его накрыл: так же
+// разрешает пересечения tree-sitter-highlight, и так написаны запросы грамматик
+fn line_spans(query: &Query, tree: &Tree, text: &str, previous: Option<(Tree, InputEdit, Vec<LineSpans>)>) -> Vec<LineSpans> {
+    let text = TextLines::new(text);
+    let mut lines: Vec<Option<LineSpans>> = match previous {
+        Some((old_tree, edit, mut old)) => {
+            let first = edit.start_position.row;
+            let tail = old.split_off((edit.old_end_position.row + 1).min(old.len()));
+            old.truncate(first);
+            let mut lines: Vec<Option<LineSpans>> = old.into_iter().map(Some).collect();
+            lines.extend((first..=edit.new_end_position.row).map(|_| None));
+            lines.extend(tail.into_iter().map(Some));
+            for range in old_tree.changed_ranges(tree) {
+                for line in lines.iter_mut().take(range.end_point.row + 1).skip(range.start_point.row) {
+                    *line = None;
+                }
+            }
+            lines
+        }
+        None => Vec::new(),
+    };
+    // Если строки почему-то не сошлись, раскрашивается весь текст
+    if lines.len() != text.lines.len() {
+        lines = text.lines.iter().map(|_| None).collect();
+    }
+
+    let styles: Vec<Style> = query.capture_names().iter().map(|name| Style::from_capture(name)).collect();
+    let mut cursor = QueryCursor::new();
+    let mut row = 0;
+    while row < lines.len() {
+        if lines[row].is_some() {
+            row += 1;
+            continue;
+        }
+        // Подряд идущие строки без подсветки раскрашиваются одним запросом
+        let first = row;
+        while row < lines.len() && lines[row].is_none() {
+            row += 1;
+        }
+        let rows = first..row;
+        for (index, spans) in paint_rows(query, &styles, &mut cursor, tree, &text, rows.clone()).into_iter().enumerate() {
+            let line = rows.start + index;
+            lines[line] = Some(LineSpans { hash: hash_line(text.lines[line]), spans });
+        }
+    }
+    lines.into_iter().flatten().collect()
+}
+
+// Текст и начала его строк в байтах
+struct TextLines<'a> {
+    text: &'a str,
+    lines: Vec<&'a str>,
+    starts: Vec<usize>,
+}
+
+impl<'a> TextLines<'a> {
+    fn new(text: &'a str) -> Self {
+        let lines: Vec<&str> = text.split('\n').collect();
+        let mut starts = Vec::with_capacity(lines.len());
+        let mut offset = 0;
+        for line in lines.iter() {
+            starts.push(offset);
+            offset += line.len() + 1;
+        }
+        Self { text, lines, starts }
+    }
+
+    // Байты строки без перевода строки
+    fn bytes(&self, line: usize) -> Range<usize> {
+        self.starts[line]..self.starts[line] + self.lines[line].len()
+    }
+}
+
+// Отрезки строк rows по захватам запроса, которые их касаются
+fn paint_rows(query: &Query, styles: &[Style], cursor: &mut QueryCursor, tree: &Tree, text: &TextLines, rows: Range<usize>) -> Vec<Vec<Span>> {
+    let mut painted: Vec<Vec<Span>> = vec![Vec::new(); rows.len()];
+    cursor.set_byte_range(text.starts[rows.start]..text.bytes(rows.end - 1).end);
+    let mut captures = cursor.captures(query, tree.root_node(), text.text.as_bytes());
+    while let Some((found, index)) = captures.next() {
+        let capture = found.captures[*index];
+        let style = styles[capture.index as usize];
+        let (from, to) = (capture.node.byte_range().start, capture.node.byte_range().end);
+        let first = capture.node.start_position().row.max(rows.start);
+        let last = capture.node.end_position().row.min(rows.end - 1);
+        for line in first..=last {
+            let Range { start: line_start, end: line_end } = text.bytes(line);
+            if from < line_end && line_start < to {
+                let start = from.max(line_start) - line_start;
+                let end = to.min(line_end) - line_start;
+                fill_gaps(&mut painted[line - rows.start], start, end, style);
+            }
+        }
+    }
+    painted.into_iter().map(merge_spans).collect()
+}
+
+// Отмечает стилем непокрытые части отрезка start..end; отрезки идут по порядку и не пересекаются
+fn fill_gaps(spans: &mut Vec<Span>, start: usize, end: usize, style: Style) {
+    let mut pieces = Vec::new();
+    let mut from = start;
+    for span in spans.iter() {
+        if span.start >= end {
+            break;
+        }
+        if span.end <= from {
+            continue;
+        }
+        if span.start > from {
+            pieces.push(Span { start: from, end: span.start, style });
+        }
+        from = span.end;
+    }
+    if from < end {
+        pieces.push(Span { start: from, end, style });
+    }
+    if !pieces.is_empty() {
+        spans.extend(pieces);
+        spans.sort_by_key(|span| span.start);
+    }
+}
+
+// Соседние отрезки одного стиля сливаются; обычный текст не хранится
+fn merge_spans(spans: Vec<Span>) -> Vec<Span> {
+    let mut merged: Vec<Span> = Vec::new();
+    for span in spans {
+        match merged.last_mut() {
+            Some(last) if last.style == span.style && last.end == span.start => last.end = span.end,
+            _ => merged.push(span),
+        }
+    }
+    merged.retain(|span| span.style != Style::Plain);
+    merged
+}
+
+// Многострочные узлы дерева как области сворачивания (первая и последняя строка)
+// Строка с закрывающей скобкой остается видимой, как при сворачивании по отступам
+fn regions(tree: &Tree, text: &str) -> Vec<(usize, usize)> {
+    let lines: Vec<&str> = text.split('\n').collect();
+    let mut regions: BTreeMap<usize, usize> = BTreeMap::new();
+    let mut cursor = tree.walk();
+    let mut depth = 0;
+    loop {
+        let node = cursor.node();
+        let multiline = node.end_position().row > node.start_position().row;
+        // Корень - весь файл, его сворачивать незачем
+        if multiline && depth > 0 && node.is_named() {
+            if let Some(end) = region_end(node, &lines) {
+                let entry = regions.entry(node.start_position().row).or_insert(end);
+                *entry = (*entry).max(end);
+            }
+        }
+        // В однострочные узлы незачем спускаться
+        if multiline && cursor.goto_first_child() {
+            depth += 1;
+            continue;
+        }
+        loop {
+            if cursor.goto_next_sibling() {
+                break;
+            }
+            if !cursor.goto_parent() {
+                return regions.into_iter().collect();
+            }
+            depth -= 1;
+        }
+    }
+}
+
+fn region_end(node: Node, lines: &[&str]) -> Option<usize> {
+    let start = node.start_position().row;
+    let end = node.end_position();
+    // Узел, кончающийся переводом строки, заканчивается на предыдущей строке
+    let mut last = if end.column == 0 { end.row.checked_sub(1)? } else { end.row };
+    let closing = lines.get(last).and_then(|line| line.trim_start().chars().next());
+    if matches!(closing, Some('}' | ')' | ']')) && last > start {
+        last -= 1;
+    }
+    (last > start).then_some(last)
+}
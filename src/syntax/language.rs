@@ -0,0 +1,272 @@
+// Языки документов
+// Язык определяется по строке режима (modeline vim или emacs), имени файла,
+// расширению и строке #! - в таком порядке. Для части языков есть грамматика tree-sitter
+
+use std::path::Path;
+
+// Сколько строк в начале и в конце файла просматривается в поисках строки режима (как в vim)
+const MODELINE_LINES: usize = 5;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Language {
+    Rust,
+    Python,
+    JavaScript,
+    TypeScript,
+    Json,
+    Toml,
+    Markdown,
+    C,
+    Shell,
+    Makefile,
+    Dockerfile,
+}
+
+impl Language {
+    pub const ALL: [Language; 11] = [
+        Language::Rust,
+        Language::Python,
+        Language::JavaScript,
+        Language::TypeScript,
+        Language::Json,
+        Language::Toml,
+        Language::Markdown,
+        Language::C,
+        Language::Shell,
+        Language::Makefile,
+        Language::Dockerfile,
+    ];
+
+    // Язык документа; lines - строки текста по порядку
+    pub fn detect<'a, I>(path: Option<&Path>, lines: I) -> Option<Self>
+    where
+        I: DoubleEndedIterator<Item = &'a str> + ExactSizeIterator + Clone,
+    {
+        let count = lines.len();
+        let tail = lines.clone().skip(count.saturating_sub(MODELINE_LINES).max(MODELINE_LINES));
+        let modeline = lines.clone().take(MODELINE_LINES).chain(tail).find_map(Self::from_modeline);
+        modeline
+            .or_else(|| path.and_then(Self::from_path))
+            .or_else(|| lines.clone().next().and_then(Self::from_shebang))
+    }
+
+    // Язык по имени файла или его расширению
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let name = path.file_name()?.to_str()?;
+        match name {
+            "Makefile" | "makefile" | "GNUmakefile" => return Some(Language::Makefile),
+            "Dockerfile" | "Containerfile" => return Some(Language::Dockerfile),
+            ".bashrc" | ".bash_profile" | ".profile" | ".zshrc" => return Some(Language::Shell),
+            "Cargo.lock" => return Some(Language::Toml),
+            _ => {}
+        }
+        // Dockerfile.dev, app.Dockerfile
+        if name.starts_with("Dockerfile.") || name.ends_with(".Dockerfile") {
+            return Some(Language::Dockerfile);
+        }
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        let language = match extension.as_str() {
+            "rs" => Language::Rust,
+            "py" | "pyw" => Language::Python,
+            "js" | "mjs" | "cjs" | "jsx" => Language::JavaScript,
+            "ts" | "mts" | "cts" => Language::TypeScript,
+            "json" => Language::Json,
+            "toml" => Language::Toml,
+            "md" | "markdown" => Language::Markdown,
+            "c" | "h" => Language::C,
+            "sh" | "bash" | "zsh" => Language::Shell,
+            "mk" | "mak" => Language::Makefile,
+            _ => return None,
+        };
+        Some(language)
+    }
+
+    // Язык по интерпретатору из первой строки: "#!/usr/bin/env python3", "#!/bin/sh -e"
+    pub fn from_shebang(line: &str) -> Option<Self> {
+        let command = line.strip_prefix("#!")?;
+        let mut words = command.split_whitespace();
+        let mut program = words.next()?.rsplit('/').next()?;
+        if program == "env" {
+            // env -S python3 -u
+            program = words.find(|word| !word.starts_with('-') && !word.contains('='))?;
+        }
+        // python3.11 -> python
+        let program = program.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.');
+        let language = match program {
+            "python" | "pypy" => Language::Python,
+            "node" | "nodejs" => Language::JavaScript,
+            "deno" | "ts-node" | "tsx" | "bun" => Language::TypeScript,
+            "sh" | "bash" | "dash" | "zsh" | "ksh" => Language::Shell,
+            "make" => Language::Makefile,
+            _ => return None,
+        };
+        Some(language)
+    }
+
+    // Язык из строки режима: "vim: set ft=python:", "vi: filetype=sh", "-*- mode: rust -*-", "-*- python -*-"
+    // Если emacs-форма не дала языка (например, в строке один "-*-"), ищутся метки vim
+    pub fn from_modeline(line: &str) -> Option<Self> {
+        Self::from_emacs_modeline(line).or_else(|| Self::from_vim_modeline(line))
+    }
+
+    // "-*- mode: rust -*-" или "-*- python -*-"
+    fn from_emacs_modeline(line: &str) -> Option<Self> {
+        let (_, rest) = line.split_once("-*-")?;
+        let (settings, _) = rest.split_once("-*-")?;
+        let settings = settings.trim();
+        // Без двоеточия вся строка - имя режима
+        if !settings.contains(':') {
+            return Self::from_name(settings.trim_end_matches("-mode"));
+        }
+        settings.split(';').find_map(|setting| {
+            let (key, value) = setting.split_once(':')?;
+            if key.trim().eq_ignore_ascii_case("mode") {
+                Self::from_name(value.trim().trim_end_matches("-mode"))
+            } else {
+                None
+            }
+        })
+    }
+
+    // "vim: set ft=python:" или "vi: filetype=sh"
+    fn from_vim_modeline(line: &str) -> Option<Self> {
+        // Метка стоит в начале строки или после пробела, иначе "index:" тоже подошло бы
+        let index = ["vim:", "vi:", "ex:"]
+            .iter()
+            .flat_map(|marker| line.match_indices(marker))
+            .filter(|(index, _)| line[..*index].chars().next_back().is_none_or(char::is_whitespace))
+            .map(|(index, marker)| index + marker.len())
+            .min()?;
+        line[index..]
+            .split(|c: char| c == ':' || c.is_whitespace())
+            .filter_map(|option| option.strip_prefix("ft=").or_else(|| option.strip_prefix("filetype=")))
+            .find_map(Self::from_name)
+    }
+
+    // Язык по имени из строки режима или файла настройки
+    pub fn from_name(name: &str) -> Option<Self> {
+        let language = match name.to_ascii_lowercase().as_str() {
+            "rust" | "rs" => Language::Rust,
+            "python" | "py" => Language::Python,
+            "javascript" | "js" => Language::JavaScript,
+            "typescript" | "ts" => Language::TypeScript,
+            "json" => Language::Json,
+            "toml" => Language::Toml,
+            "markdown" | "md" => Language::Markdown,
+            "c" => Language::C,
+            "shell" | "sh" | "bash" | "zsh" | "shell-script" => Language::Shell,
+            "makefile" | "make" => Language::Makefile,
+            "dockerfile" | "docker" => Language::Dockerfile,
+            _ => return None,
+        };
+        Some(language)
+    }
+
+    // Название для строки состояния
+    pub fn title(&self) -> &'static str {
+        match self {
+            Language::Rust => "Rust",
+            Language::Python => "Python",
+            Language::JavaScript => "JavaScript",
+            Language::TypeScript => "TypeScript",
+            Language::Json => "JSON",
+            Language::Toml => "TOML",
+            Language::Markdown => "Markdown",
+            Language::C => "C",
+            Language::Shell => "Shell",
+            Language::Makefile => "Makefile",
+            Language::Dockerfile => "Dockerfile",
+        }
+    }
+
+    // Грамматика tree-sitter; у Makefile и Dockerfile ее нет
+    pub fn grammar(&self) -> Option<tree_sitter::Language> {
+        let grammar = match self {
+            Language::Rust => tree_sitter_rust::LANGUAGE,
+            Language::Python => tree_sitter_python::LANGUAGE,
+            Language::JavaScript => tree_sitter_javascript::LANGUAGE,
+            Language::TypeScript => tree_sitter_typescript::LANGUAGE_TYPESCRIPT,
+            Language::Json => tree_sitter_json::LANGUAGE,
+            Language::Toml => tree_sitter_toml_ng::LANGUAGE,
+            Language::Markdown => tree_sitter_md::LANGUAGE,
+            Language::C => tree_sitter_c::LANGUAGE,
+            Language::Shell => tree_sitter_bash::LANGUAGE,
+            Language::Makefile | Language::Dockerfile => return None,
+        };
+        Some(grammar.into())
+    }
+
+    // Запрос подсветки; TypeScript дополняет запрос JavaScript
+    pub fn highlights_query(&self) -> String {
+        match self {
+            Language::Rust => tree_sitter_rust::HIGHLIGHTS_QUERY.to_string(),
+            Language::Python => tree_sitter_python::HIGHLIGHTS_QUERY.to_string(),
+            Language::JavaScript => tree_sitter_javascript::HIGHLIGHT_QUERY.to_string(),
+            Language::TypeScript => {
+                format!("{}\n{}", tree_sitter_javascript::HIGHLIGHT_QUERY, tree_sitter_typescript::HIGHLIGHTS_QUERY)
+            }
+            Language::Json => tree_sitter_json::HIGHLIGHTS_QUERY.to_string(),
+            Language::Toml => tree_sitter_toml_ng::HIGHLIGHTS_QUERY.to_string(),
+            Language::Markdown => tree_sitter_md::HIGHLIGHT_QUERY_BLOCK.to_string(),
+            Language::C => tree_sitter_c::HIGHLIGHT_QUERY.to_string(),
+            Language::Shell => tree_sitter_bash::HIGHLIGHT_QUERY.to_string(),
+            Language::Makefile | Language::Dockerfile => String::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn detect(path: Option<&str>, text: &str) -> Option<Language> {
+        let lines: Vec<&str> = text.split('\n').collect();
+        Language::detect(path.map(Path::new), lines.iter().copied())
+    }
+
+    #[test]
+    fn modelines() {
+        assert_eq!(Language::from_modeline("# vim: set ft=python:"), Some(Language::Python));
+        assert_eq!(Language::from_modeline("// vi: filetype=sh"), Some(Language::Shell));
+        assert_eq!(Language::from_modeline("/* ex: ts=4 ft=c */"), Some(Language::C));
+        assert_eq!(Language::from_modeline("// -*- mode: rust -*-"), Some(Language::Rust));
+        assert_eq!(Language::from_modeline("# -*- coding: utf-8; Mode: Python -*-"), Some(Language::Python));
+        assert_eq!(Language::from_modeline("# -*- shell-script -*-"), Some(Language::Shell));
+        assert_eq!(Language::from_modeline("# -*- makefile-mode -*-"), Some(Language::Makefile));
+        // Один "-*-" или emacs-строка без языка не мешают меткам vim
+        assert_eq!(Language::from_modeline("# -*- vim: ft=python"), Some(Language::Python));
+        assert_eq!(Language::from_modeline("# -*- coding: utf-8 -*- vim: ft=toml"), Some(Language::Toml));
+        // Метка внутри слова - не метка
+        assert_eq!(Language::from_modeline("index: ft=python"), None);
+        assert_eq!(Language::from_modeline("vim: set ts=4:"), None);
+        assert_eq!(Language::from_modeline("-*- mode: cobol -*-"), None);
+    }
+
+    #[test]
+    fn shebangs() {
+        assert_eq!(Language::from_shebang("#!/usr/bin/env python3"), Some(Language::Python));
+        assert_eq!(Language::from_shebang("#!/usr/bin/python3.11 -u"), Some(Language::Python));
+        assert_eq!(Language::from_shebang("#!/usr/bin/env -S deno run"), Some(Language::TypeScript));
+        assert_eq!(Language::from_shebang("#!/usr/bin/env NODE_ENV=dev node"), Some(Language::JavaScript));
+        assert_eq!(Language::from_shebang("#!/bin/sh -e"), Some(Language::Shell));
+        assert_eq!(Language::from_shebang("#!/usr/bin/perl"), None);
+        assert_eq!(Language::from_shebang("# /bin/sh"), None);
+    }
+
+    #[test]
+    fn detect_order() {
+        // Строка режима важнее имени файла, имя файла - строки #!
+        assert_eq!(detect(Some("a.txt"), "#!/bin/sh\n# vim: ft=python"), Some(Language::Python));
+        assert_eq!(detect(Some("build.rs"), "#!/bin/sh\nfn main() {}"), Some(Language::Rust));
+        assert_eq!(detect(Some("run"), "#!/bin/bash\necho"), Some(Language::Shell));
+        assert_eq!(detect(None, "#!/usr/bin/env node"), Some(Language::JavaScript));
+        assert_eq!(detect(Some("Dockerfile.dev"), "FROM x"), Some(Language::Dockerfile));
+        assert_eq!(detect(Some("notes.txt"), "text"), None);
+
+        // Строка режима ищется только в первых и последних пяти строках
+        let middle = format!("{}vim: ft=c\n{}", "x\n".repeat(5), "x\n".repeat(5));
+        assert_eq!(detect(None, &middle), None);
+        let tail = format!("{}// vim: ft=c", "x\n".repeat(20));
+        assert_eq!(detect(Some("a.h.txt"), &tail), Some(Language::C));
+    }
+}
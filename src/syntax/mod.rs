@@ -0,0 +1,9 @@
+// Синтаксис документов: языки, их настройки и подсветка
+
+mod highlight;
+mod language;
+mod settings;
+
+pub use highlight::{Highlighter, Span, Style};
+pub use language::Language;
+pub use settings::{LanguageConfig, LanguageSettings};
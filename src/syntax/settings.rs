@@ -0,0 +1,247 @@
+// Настройки языков: табуляция, комментарии, столбец переноса и парные символы
+// У каждого языка есть встроенные значения; их можно поменять в файле "languages"
+// каталога настроек строками "<язык> <настройка> <значение>":
+//   python wrap_column 88
+//   makefile use_tabs yes
+//   text pairs ()[]{}""
+//   shell indent_after {(
+// Простой текст называется "text"; # - комментарий
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::editor::{CommentTokens, IndentRules, IndentStyle, TabSettings};
+use crate::syntax::Language;
+use crate::utils::EditorConfig;
+
+// Файл настройки языков в каталоге настроек
+const LANGUAGES_FILE: &str = "languages";
+
+// Стандартные пары скобок и кавычек
+const BRACKETS: [(char, char); 3] = [('(', ')'), ('[', ']'), ('{', '}')];
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct LanguageSettings {
+    pub language: Option<Language>, // None - простой текст
+    pub tab_width: usize,
+    pub use_tabs: bool,
+    pub line_comment: Option<String>,            // "//"
+    pub block_comment: Option<(String, String)>, // ("/*", "*/")
+    pub wrap_column: usize,                      // столбец для переноса "по столбцу"
+    pub pairs: Vec<(char, char)>,                // символы, которые закрываются сами
+    pub indent_after: Vec<char>,                 // после них в конце строки отступ растет
+}
+
+impl LanguageSettings {
+    // Встроенные значения для языка
+    pub fn new(language: Option<Language>) -> Self {
+        let mut settings = Self {
+            language,
+            tab_width: 4,
+            use_tabs: false,
+            line_comment: None,
+            block_comment: None,
+            wrap_column: 80,
+            pairs: BRACKETS.to_vec(),
+            indent_after: BRACKETS.iter().map(|&(open, _)| open).collect(),
+        };
+        let (line, block): (Option<&str>, Option<(&str, &str)>) = match language {
+            Some(Language::Rust) => {
+                settings.wrap_column = 100;
+                (Some("//"), Some(("/*", "*/")))
+            }
+            Some(Language::C) => (Some("//"), Some(("/*", "*/"))),
+            Some(Language::JavaScript | Language::TypeScript) => {
+                settings.tab_width = 2;
+                settings.pairs.push(('`', '`'));
+                (Some("//"), Some(("/*", "*/")))
+            }
+            Some(Language::Python) => {
+                settings.wrap_column = 79;
+                settings.indent_after.push(':');
+                (Some("#"), None)
+            }
+            Some(Language::Json) => {
+                settings.tab_width = 2;
+                (None, None)
+            }
+            Some(Language::Markdown) => {
+                settings.pairs.push(('`', '`'));
+                (None, Some(("<!--", "-->")))
+            }
+            Some(Language::Makefile) => {
+                // Команды правил обязаны начинаться с табуляции
+                settings.use_tabs = true;
+                settings.tab_width = 8;
+                // После "цель:" идут команды правила
+                settings.indent_after = vec![':'];
+                (Some("#"), None)
+            }
+            Some(Language::Toml | Language::Shell | Language::Dockerfile) => (Some("#"), None),
+            None => {
+                settings.indent_after.clear();
+                (None, None)
+            }
+        };
+        settings.line_comment = line.map(str::to_string);
+        settings.block_comment = block.map(|(open, close)| (open.to_string(), close.to_string()));
+        settings.pairs.push(('"', '"'));
+        settings.pairs.push(('«', '»'));
+        // В Rust одинарная кавычка начинает и время жизни, в тексте это апостроф
+        if !matches!(language, Some(Language::Rust | Language::Markdown) | None) {
+            settings.pairs.push(('\'', '\''));
+        }
+        settings
+    }
+
+    // Ввод табуляции для буфера
+    pub fn tabs(&self) -> TabSettings {
+        TabSettings { width: self.tab_width, use_tabs: self.use_tabs }
+    }
+
+    // Правила автоотступа для буфера
+    pub fn indent_rules(&self) -> IndentRules {
+        IndentRules {
+            openers: self.indent_after.clone(),
+            brackets: BRACKETS.iter().copied().filter(|(open, _)| self.indent_after.contains(open)).collect(),
+        }
+    }
+
+    // Знаки комментариев для буфера
+    pub fn comment_tokens(&self) -> CommentTokens {
+        CommentTokens { line: self.line_comment.clone(), block: self.block_comment.clone() }
+    }
+
+    // Стиль отступов, найденный в тексте или выбранный вручную
+    // У табуляции ширина остается прежней: по тексту ее не узнать
+    pub fn apply_indent_style(&mut self, style: IndentStyle) {
+        match style {
+            IndentStyle::Tabs => self.use_tabs = true,
+            IndentStyle::Spaces(width) => {
+                self.use_tabs = false;
+                self.tab_width = width;
+            }
+        }
+    }
+
+    // Свойства из .editorconfig важнее настроек языка
+    pub fn apply_editorconfig(&mut self, config: &EditorConfig) {
+        if let Some(use_tabs) = config.use_tabs {
+            self.use_tabs = use_tabs;
+        }
+        if let Some(width) = config.indent_width() {
+            self.tab_width = width;
+        }
+        if let Some(column) = config.max_line_length {
+            self.wrap_column = column;
+        }
+    }
+
+    // Применяет строку настройки "tab_width 2"; ошибка - текст для журнала
+    fn apply(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "tab_width" => self.tab_width = parse_count(value)?,
+            "wrap_column" => self.wrap_column = parse_count(value)?,
+            "use_tabs" => {
+                self.use_tabs = match value {
+                    "yes" | "true" => true,
+                    "no" | "false" => false,
+                    _ => return Err(format!("ожидалось yes или no: {}", value)),
+                }
+            }
+            "line_comment" => self.line_comment = (value != "none").then(|| value.to_string()),
+            "block_comment" => {
+                self.block_comment = match value.split_whitespace().collect::<Vec<_>>().as_slice() {
+                    ["none"] => None,
+                    [open, close] => Some((open.to_string(), close.to_string())),
+                    _ => return Err(format!("ожидались открывающий и закрывающий знаки: {}", value)),
+                }
+            }
+            "pairs" => {
+                let chars: Vec<char> = value.chars().filter(|c| !c.is_whitespace()).collect();
+                if !chars.len().is_multiple_of(2) {
+                    return Err(format!("непарный символ: {}", value));
+                }
+                self.pairs = chars.chunks(2).map(|pair| (pair[0], pair[1])).collect();
+            }
+            "indent_after" => self.indent_after = value.chars().filter(|c| !c.is_whitespace()).collect(),
+            _ => return Err(format!("неизвестная настройка {}", key)),
+        }
+        Ok(())
+    }
+}
+
+fn parse_count(value: &str) -> Result<usize, String> {
+    match value.parse::<usize>() {
+        Ok(count) if count > 0 => Ok(count),
+        _ => Err(format!("ожидалось положительное число: {}", value)),
+    }
+}
+
+// Настройки всех языков с учетом файла настройки
+pub struct LanguageConfig {
+    settings: HashMap<Option<Language>, LanguageSettings>, // только измененные файлом
+}
+
+impl LanguageConfig {
+    pub fn new() -> Self {
+        Self { settings: HashMap::new() }
+    }
+
+    // Настройка из каталога настроек; без файла - встроенные значения
+    pub fn load_default() -> Self {
+        match crate::utils::config_dir() {
+            Some(dir) => Self::load(dir.join(LANGUAGES_FILE)),
+            None => Self::new(),
+        }
+    }
+
+    pub fn load(path: PathBuf) -> Self {
+        let mut config = Self::new();
+        match fs::read_to_string(&path) {
+            Ok(content) => config.parse(&content),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => log::warn!("Не удалось прочитать {}: {}", path.display(), e),
+        }
+        config
+    }
+
+    // Настройки языка документа
+    pub fn settings(&self, language: Option<Language>) -> LanguageSettings {
+        self.settings.get(&language).cloned().unwrap_or_else(|| LanguageSettings::new(language))
+    }
+
+    fn parse(&mut self, content: &str) {
+        for line in content.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut parts = line.splitn(3, char::is_whitespace);
+            let (Some(name), Some(key), Some(value)) = (parts.next(), parts.next(), parts.next()) else {
+                log::warn!("Непонятная строка настройки языков: {}", line);
+                continue;
+            };
+            let language = match name {
+                "text" => None,
+                name => match Language::from_name(name) {
+                    Some(language) => Some(language),
+                    None => {
+                        log::warn!("Неизвестный язык в настройке языков: {}", name);
+                        continue;
+                    }
+                },
+            };
+            let settings = self.settings.entry(language).or_insert_with(|| LanguageSettings::new(language));
+            if let Err(e) = settings.apply(key, value.trim()) {
+                log::warn!("Ошибка в настройке языков ({}): {}", line, e);
+            }
+        }
+    }
+}
+
+impl Default for LanguageConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,71 @@
+// Подсветка скобок: пара для скобки у курсора и раскраска пар по глубине вложенности
+// Поиск идет по всему тексту, поэтому результат запоминается до правки, движения
+// курсора или прихода разбора со строками и комментариями
+
+use crate::editor::{Buffer, Position};
+use crate::syntax::{Span, Style};
+
+pub struct BracketMarks {
+    pair_key: Option<(u64, bool, Position)>, // правка, известны ли строки и комментарии, курсор
+    pair: Option<(Position, Position)>,      // скобка у курсора и ее пара
+    depths_key: Option<(u64, bool)>,
+    depths: Vec<Vec<(usize, usize)>>, // (столбец, глубина) скобок каждой строки; пусто без раскраски
+}
+
+impl BracketMarks {
+    pub fn new() -> Self {
+        Self { pair_key: None, pair: None, depths_key: None, depths: Vec::new() }
+    }
+
+    // Пересчитывает пару у курсора и, если включена раскраска, глубину скобок
+    pub fn update(&mut self, buffer: &Buffer, rainbow: bool) {
+        let (revision, literals, cursor) = (buffer.revision(), buffer.has_literals(), buffer.get_cursor_position());
+        if self.pair_key != Some((revision, literals, cursor)) {
+            self.pair_key = Some((revision, literals, cursor));
+            self.pair = buffer.bracket_pair_near(cursor);
+        }
+        if !rainbow {
+            self.depths_key = None;
+            self.depths.clear();
+        } else if self.depths_key != Some((revision, literals)) {
+            self.depths_key = Some((revision, literals));
+            self.depths = buffer.bracket_depths();
+        }
+    }
+
+    // Столбцы скобок строки line, которые обводятся рамкой
+    pub fn matched(&self, line: usize) -> Vec<usize> {
+        let Some((bracket, pair)) = self.pair else { return Vec::new() };
+        [bracket, pair].iter().filter(|position| position.line == line).map(|position| position.column).collect()
+    }
+
+    // Отрезки подсветки строки line, в которых скобки получили цвет своей глубины
+    pub fn colorize(&self, line: usize, spans: &[Span]) -> Vec<Span> {
+        let Some(brackets) = self.depths.get(line).filter(|brackets| !brackets.is_empty()) else {
+            return spans.to_vec();
+        };
+        // Скобки вырезаются из отрезков подсветки и добавляются отдельными отрезками
+        let mut result = Vec::new();
+        for span in spans {
+            let mut start = span.start;
+            for &(column, _) in brackets.iter().filter(|&&(column, _)| span.start <= column && column < span.end) {
+                if start < column {
+                    result.push(Span { start, end: column, style: span.style });
+                }
+                start = column + 1;
+            }
+            if start < span.end {
+                result.push(Span { start, end: span.end, style: span.style });
+            }
+        }
+        result.extend(brackets.iter().map(|&(column, depth)| Span { start: column, end: column + 1, style: Style::Bracket(depth) }));
+        result.sort_by_key(|span| span.start);
+        result
+    }
+}
+
+impl Default for BracketMarks {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,226 @@
+// Открытый документ: текст, его файл и положение на экране
+// Окно держит список документов и показывает один из них
+
+use std::cell::Cell;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+
+use crate::editor::{detect_indentation, BufferSettings, Command, Editor, IndentStyle, Position};
+use crate::syntax::{Language, LanguageConfig, LanguageSettings};
+use crate::ui::view::EditorView;
+use crate::utils::{FileManager, Filter};
+
+// Внешняя команда, через которую пропускается текст документа
+struct RunningFilter {
+    filter: Filter,
+    revision: u64,               // правка, с которой снят текст
+    range: (Position, Position), // заменяемый текст
+    newline_added: bool,         // к вводу добавлен перевод строки
+}
+
+pub struct Document {
+    pub editor: Editor,
+    pub file_manager: FileManager,
+    pub view: EditorView,
+    pub settings: LanguageSettings,         // настройки языка документа
+    language_fixed: bool,                   // язык выбран вручную и не определяется заново
+    detected: Option<(u64, Option<PathBuf>)>, // правка и файл, для которых язык определен
+    indent_detected: Option<IndentStyle>,     // стиль отступов, найденный в файле при открытии
+    indent_fixed: Option<IndentStyle>,        // стиль отступов, выбранный вручную
+    filter: Option<RunningFilter>,            // выполняющаяся внешняя команда
+    saved_hash: u64,                          // хеш текста, записанного в файл
+    modified: Cell<(u64, bool)>,              // правка, для которой сравнен текст, и результат
+}
+
+impl Document {
+    // id различает прокрутку разных документов; editor создает окно с общими регистрами
+    pub fn new(id: usize, editor: Editor) -> Self {
+        let revision = editor.get_buffer().revision();
+        let saved_hash = text_hash(&editor);
+        Self {
+            editor,
+            file_manager: FileManager::new(),
+            view: EditorView::new(("document", id)),
+            settings: LanguageSettings::new(None),
+            language_fixed: false,
+            detected: None,
+            indent_detected: None,
+            indent_fixed: None,
+            filter: None,
+            saved_hash,
+            modified: Cell::new((revision, false)),
+        }
+    }
+
+    // Документ с содержимым файла
+    // Язык и .editorconfig применяются до загрузки текста: от них зависит, остается ли табуляция
+    pub fn open(id: usize, editor: Editor, path: &Path, languages: &LanguageConfig) -> Result<Self> {
+        let mut document = Self::new(id, editor);
+        let text = document.file_manager.open_file(path)?;
+        let lines: Vec<&str> = text.split('\n').collect();
+        document.indent_detected = detect_indentation(lines.iter().copied());
+        document.apply_language(Language::detect(Some(path), lines.iter().copied()), languages);
+        document.editor.set_text(text)?;
+        document.editor.clear_history();
+        document.detected = Some((document.editor.get_buffer().revision(), Some(path.to_path_buf())));
+        document.mark_saved();
+        Ok(document)
+    }
+
+    // Отличается ли текст от открытого или последнего сохраненного
+    // Номер правки только растет, поэтому после отмены до сохраненного текста сравнивается сам текст
+    pub fn is_modified(&self) -> bool {
+        let revision = self.editor.get_buffer().revision();
+        let (checked, modified) = self.modified.get();
+        if checked == revision {
+            return modified;
+        }
+        let modified = text_hash(&self.editor) != self.saved_hash;
+        self.modified.set((revision, modified));
+        modified
+    }
+
+    // Текст записан в файл
+    pub fn mark_saved(&mut self) {
+        self.saved_hash = text_hash(&self.editor);
+        self.modified.set((self.editor.get_buffer().revision(), false));
+    }
+
+    // Имя для вкладки и заголовка окна
+    pub fn name(&self) -> String {
+        match self.file_manager.current_file().and_then(|path| path.file_name()) {
+            Some(name) => name.to_string_lossy().into_owned(),
+            None => "Без имени".to_string(),
+        }
+    }
+
+    // Новый пустой документ без файла: его можно заменить открываемым
+    pub fn is_blank(&self) -> bool {
+        let lines = self.editor.get_buffer().get_all_lines();
+        self.file_manager.current_file().is_none() && lines.len() == 1 && lines[0].is_empty()
+    }
+
+    pub fn language_fixed(&self) -> bool {
+        self.language_fixed
+    }
+
+    // Язык, выбранный вручную в строке состояния
+    pub fn set_language(&mut self, language: Option<Language>, languages: &LanguageConfig) {
+        self.language_fixed = true;
+        self.apply_language(language, languages);
+    }
+
+    // Возвращает автоматическое определение языка
+    pub fn detect_language(&mut self, languages: &LanguageConfig) {
+        self.language_fixed = false;
+        self.detected = None;
+        self.update_language(languages);
+    }
+
+    // Стиль отступов, выбранный в строке состояния; None - как в файле
+    pub fn set_indent_style(&mut self, style: Option<IndentStyle>, languages: &LanguageConfig) {
+        self.indent_fixed = style;
+        self.apply_language(self.settings.language, languages);
+    }
+
+    pub fn indent_fixed(&self) -> Option<IndentStyle> {
+        self.indent_fixed
+    }
+
+    // Определяет язык заново после правки или сохранения под другим именем:
+    // в новом документе может появиться строка #! или строка режима
+    pub fn update_language(&mut self, languages: &LanguageConfig) {
+        if self.language_fixed {
+            return;
+        }
+        let path = self.file_manager.current_file();
+        let revision = self.editor.get_buffer().revision();
+        if self.detected.as_ref().is_some_and(|(detected, file)| *detected == revision && file.as_deref() == path) {
+            return;
+        }
+        self.detected = Some((revision, path.map(Path::to_path_buf)));
+        let lines = self.editor.get_buffer().get_all_lines().iter().map(String::as_str);
+        let language = Language::detect(path, lines);
+        self.apply_language(language, languages);
+    }
+
+    // Пропускает выделение (без него - весь текст) через команду оболочки
+    // Вывод заменит текст, когда команда завершится; окно ждет его через poll_filter
+    pub fn start_filter(&mut self, command: &str, timeout: Duration, notify: impl Fn() + Send + 'static) -> Result<()> {
+        if let Some(running) = &self.filter {
+            return Err(anyhow!("Уже выполняется \"{}\"", running.filter.command()));
+        }
+        let buffer = self.editor.get_buffer();
+        let (start, end) = buffer.selection_or_all();
+        let mut input = buffer.get_text_in_range(start, end).map_err(|e| anyhow!(e))?;
+        // Построчные программы (sort, column) ждут перевода строки после последней строки
+        let newline_added = !input.ends_with('\n');
+        if newline_added {
+            input.push('\n');
+        }
+        let filter = Filter::start(command, input, timeout, notify)?;
+        self.filter = Some(RunningFilter { filter, revision: buffer.revision(), range: (start, end), newline_added });
+        Ok(())
+    }
+
+    // Выполняющаяся команда
+    pub fn filter_command(&self) -> Option<&str> {
+        self.filter.as_ref().map(|running| running.filter.command())
+    }
+
+    pub fn cancel_filter(&self) {
+        if let Some(running) = &self.filter {
+            running.filter.cancel();
+        }
+    }
+
+    // Вставляет вывод завершившейся команды одной отменяемой правкой
+    // None - команда еще выполняется или ее нет
+    pub fn poll_filter(&mut self) -> Option<Result<()>> {
+        let output = self.filter.as_ref()?.filter.poll()?;
+        let running = self.filter.take()?;
+        let command = running.filter.command();
+        let result = output.map_err(|e| anyhow!("{}: {}", command, e)).and_then(|mut output| {
+            if running.revision != self.editor.get_buffer().revision() {
+                return Err(anyhow!("{}: текст изменился, пока выполнялась команда; вывод не вставлен", command));
+            }
+            if running.newline_added && output.ends_with('\n') {
+                output.pop();
+            }
+            let (start, end) = running.range;
+            self.editor.execute_command(Command::ReplaceRange(start, end, output))
+        });
+        Some(result)
+    }
+
+    fn apply_language(&mut self, language: Option<Language>, languages: &LanguageConfig) {
+        // По возрастанию важности: язык, отступы файла, .editorconfig, выбор в строке состояния
+        // Отступы файла не угадываются, если их задает .editorconfig
+        self.settings = languages.settings(language);
+        let editorconfig = self.file_manager.editorconfig();
+        if let (Some(style), None, None) = (self.indent_detected, editorconfig.use_tabs, editorconfig.indent_width()) {
+            self.settings.apply_indent_style(style);
+        }
+        self.settings.apply_editorconfig(editorconfig);
+        if let Some(style) = self.indent_fixed {
+            self.settings.apply_indent_style(style);
+        }
+        self.editor.set_buffer_settings(BufferSettings {
+            tabs: self.settings.tabs(),
+            indent_rules: self.settings.indent_rules(),
+            auto_pairs: self.settings.pairs.clone(),
+            comments: self.settings.comment_tokens(),
+        });
+    }
+}
+
+// Хеш всего текста документа
+fn text_hash(editor: &Editor) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    editor.get_buffer().get_all_lines().hash(&mut hasher);
+    hasher.finish()
+}
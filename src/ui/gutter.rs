@@ -0,0 +1,671 @@
+// Поля слева от текста
+// Поля состоят из колонок: номера строк, закладки, диагностика, сворачивание,
+// изменения относительно git. Каждая колонка реализует GutterColumn и регистрируется в
+// Gutter; какие колонки видны и в каком порядке, задает GutterConfig
+
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+use anyhow::{anyhow, Result};
+use egui::{pos2, vec2, Color32, Id, Rect, Sense, Ui};
+use similar::{Algorithm, DiffOp};
+
+use crate::editor::{Buffer, Command, Editor, FoldRange, Position, TabSettings};
+use crate::ui::widgets::{editor_font, LineNumber};
+
+// Файл настройки полей в каталоге настроек
+const GUTTER_FILE: &str = "gutter";
+
+// Сколько можно сравнивать текст с версией из git, прежде чем взять грубый результат
+const DIFF_DEADLINE: Duration = Duration::from_millis(50);
+
+// Как показываются номера строк
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum NumberMode {
+    Absolute,
+    Relative, // расстояние до строки с курсором
+    Hybrid,   // у строки с курсором номер, у остальных расстояние
+}
+
+// Видимые колонки по порядку и режим номеров
+#[derive(Clone, Debug, PartialEq)]
+pub struct GutterConfig {
+    pub columns: Vec<String>, // имена колонок слева направо
+    pub numbers: NumberMode,
+    path: Option<PathBuf>, // файл настройки; None - только в памяти
+}
+
+impl GutterConfig {
+    pub fn new() -> Self {
+        Self {
+            columns: ["bookmarks", "diagnostics", "numbers", "folds", "changes"].iter().map(|id| id.to_string()).collect(),
+            numbers: NumberMode::Absolute,
+            path: None,
+        }
+    }
+
+    // Настройка из каталога настроек; без файла - по умолчанию
+    pub fn load_default() -> Self {
+        match crate::utils::config_dir() {
+            Some(dir) => Self::load(dir.join(GUTTER_FILE)),
+            None => Self::new(),
+        }
+    }
+
+    // Строки файла: "columns numbers changes", "numbers hybrid"; # - комментарий
+    pub fn load(path: PathBuf) -> Self {
+        let mut config = Self::new();
+        match fs::read_to_string(&path) {
+            Ok(content) => config.parse(&content),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => log::warn!("Не удалось прочитать {}: {}", path.display(), e),
+        }
+        config.path = Some(path);
+        config
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let Some(path) = &self.path else { return Ok(()) };
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let numbers = match self.numbers {
+            NumberMode::Absolute => "absolute",
+            NumberMode::Relative => "relative",
+            NumberMode::Hybrid => "hybrid",
+        };
+        let content = format!("columns {}\nnumbers {}\n", self.columns.join(" "), numbers);
+        fs::write(path, content).map_err(|e| anyhow!("Не удалось сохранить {}: {}", path.display(), e))
+    }
+
+    pub fn is_visible(&self, id: &str) -> bool {
+        self.columns.iter().any(|column| column == id)
+    }
+
+    // Показывает или прячет колонку; показанная добавляется справа
+    pub fn set_visible(&mut self, id: &str, visible: bool) {
+        self.columns.retain(|column| column != id);
+        if visible {
+            self.columns.push(id.to_string());
+        }
+    }
+
+    // Сдвигает видимую колонку влево (offset < 0) или вправо
+    pub fn move_column(&mut self, id: &str, offset: isize) {
+        if let Some(index) = self.columns.iter().position(|column| column == id) {
+            let target = index.saturating_add_signed(offset).min(self.columns.len() - 1);
+            let column = self.columns.remove(index);
+            self.columns.insert(target, column);
+        }
+    }
+
+    fn parse(&mut self, content: &str) {
+        for line in content.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (key, value) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+            match (key, value.trim()) {
+                ("columns", value) => self.columns = value.split_whitespace().map(str::to_string).collect(),
+                ("numbers", "absolute") => self.numbers = NumberMode::Absolute,
+                ("numbers", "relative") => self.numbers = NumberMode::Relative,
+                ("numbers", "hybrid") => self.numbers = NumberMode::Hybrid,
+                _ => log::warn!("Непонятная строка настройки полей: {}", line),
+            }
+        }
+    }
+}
+
+impl Default for GutterConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Строка экрана, для которой рисуются поля
+pub struct GutterRow<'a> {
+    pub line: usize,    // строка документа
+    pub segment: usize, // отрезок перенесенной строки, 0 - первый
+    pub text: &'a str,  // вся строка документа
+    pub cursor_line: usize,
+}
+
+// Колонка полей
+pub trait GutterColumn {
+    // Имя в файле настройки
+    fn id(&self) -> &'static str;
+
+    // Название в меню
+    fn title(&self) -> &'static str;
+
+    fn width(&self, ui: &Ui, line_count: usize) -> f32;
+
+    // Подготовка к кадру: колонка может пересчитать свои данные
+    fn update(&mut self, _ui: &Ui, _editor: &Editor, _path: Option<&Path>, _config: &GutterConfig) {}
+
+    fn paint(&self, ui: &Ui, rect: Rect, row: &GutterRow);
+
+    fn tooltip(&self, _row: &GutterRow) -> Option<String> {
+        None
+    }
+
+    fn click(&mut self, _editor: &mut Editor, _line: usize) -> Result<()> {
+        Ok(())
+    }
+}
+
+pub struct Gutter {
+    columns: Vec<Box<dyn GutterColumn>>,
+    visible: Vec<(usize, f32)>, // видимые колонки по порядку и их ширина в этом кадре
+}
+
+impl Gutter {
+    // Поля со встроенными колонками
+    pub fn new() -> Self {
+        let mut gutter = Self { columns: Vec::new(), visible: Vec::new() };
+        gutter.register(Box::new(LineNumbers::new()));
+        gutter.register(Box::new(Bookmarks::new()));
+        gutter.register(Box::new(Diagnostics));
+        gutter.register(Box::new(Folds::new()));
+        gutter.register(Box::new(Changes::new()));
+        gutter
+    }
+
+    pub fn register(&mut self, column: Box<dyn GutterColumn>) {
+        self.columns.push(column);
+    }
+
+    // Имена и названия колонок для меню
+    pub fn columns(&self) -> Vec<(&'static str, &'static str)> {
+        self.columns.iter().map(|column| (column.id(), column.title())).collect()
+    }
+
+    // Выбирает видимые колонки по настройке и готовит их к кадру
+    pub fn update(&mut self, ui: &Ui, editor: &Editor, path: Option<&Path>, config: &GutterConfig) {
+        let line_count = editor.get_buffer().get_all_lines().len();
+        self.visible.clear();
+        for id in config.columns.iter() {
+            // Неизвестные имена (например, от другой версии) пропускаются
+            if let Some(index) = self.columns.iter().position(|column| column.id() == id) {
+                let column = &mut self.columns[index];
+                column.update(ui, editor, path, config);
+                self.visible.push((index, column.width(ui, line_count)));
+            }
+        }
+    }
+
+    pub fn width(&self) -> f32 {
+        self.visible.iter().map(|(_, width)| width).sum()
+    }
+
+    // Рисует поля строки экрана от левого края rect
+    // Возвращает колонку, по которой щелкнули
+    pub fn show(&self, ui: &Ui, id: Id, rect: Rect, row: &GutterRow) -> Option<usize> {
+        let mut left = rect.left();
+        let mut clicked = None;
+        for &(index, width) in self.visible.iter() {
+            let column = &self.columns[index];
+            let cell = Rect::from_min_size(pos2(left, rect.top()), vec2(width, rect.height()));
+            left += width;
+            column.paint(ui, cell, row);
+            let mut response = ui.interact(cell, id.with((column.id(), row.line, row.segment)), Sense::click());
+            if let Some(tooltip) = column.tooltip(row) {
+                response = response.on_hover_text(tooltip);
+            }
+            if response.clicked() {
+                clicked = Some(index);
+            }
+        }
+        clicked
+    }
+
+    // Передает щелчок колонке (после рисования, когда редактор снова можно менять)
+    pub fn click(&mut self, index: usize, editor: &mut Editor, line: usize) -> Result<()> {
+        self.columns[index].click(editor, line)
+    }
+}
+
+impl Default for Gutter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn char_width(ui: &Ui) -> f32 {
+    ui.fonts(|fonts| fonts.glyph_width(&editor_font(), '0'))
+}
+
+// ---------- Номера строк ----------
+
+pub struct LineNumbers {
+    mode: NumberMode,
+    digits: usize,
+}
+
+impl LineNumbers {
+    pub fn new() -> Self {
+        Self { mode: NumberMode::Absolute, digits: 3 }
+    }
+}
+
+impl GutterColumn for LineNumbers {
+    fn id(&self) -> &'static str {
+        "numbers"
+    }
+
+    fn title(&self) -> &'static str {
+        "Номера строк"
+    }
+
+    fn width(&self, ui: &Ui, _line_count: usize) -> f32 {
+        LineNumber::width(ui, self.digits)
+    }
+
+    fn update(&mut self, _ui: &Ui, editor: &Editor, _path: Option<&Path>, config: &GutterConfig) {
+        self.mode = config.numbers;
+        self.digits = editor.get_buffer().get_all_lines().len().to_string().len().max(3);
+    }
+
+    fn paint(&self, ui: &Ui, rect: Rect, row: &GutterRow) {
+        // Номер только у первого отрезка строки
+        if row.segment > 0 {
+            return;
+        }
+        let current = row.line == row.cursor_line;
+        let number = match self.mode {
+            NumberMode::Absolute => row.line + 1,
+            NumberMode::Hybrid if current => row.line + 1,
+            NumberMode::Relative | NumberMode::Hybrid => row.line.abs_diff(row.cursor_line),
+        };
+        LineNumber::new(number).paint(ui, rect, current);
+    }
+
+    // Щелчок по номеру выделяет строку вместе с переводом строки
+    fn click(&mut self, editor: &mut Editor, line: usize) -> Result<()> {
+        let lines = editor.get_buffer().get_all_lines();
+        let start = Position { line, column: 0 };
+        let end = match lines.get(line + 1) {
+            Some(_) => Position { line: line + 1, column: 0 },
+            None => Position { line, column: lines.get(line).map_or(0, String::len) },
+        };
+        editor.execute_command(Command::Batch(vec![Command::MoveCursor(end), Command::Select(start, end)]))
+    }
+}
+
+// ---------- Закладки ----------
+
+// Закладки ставятся и снимаются щелчком
+// Они привязаны к номерам строк и не сдвигаются при вставке строк выше
+pub struct Bookmarks {
+    lines: BTreeSet<usize>,
+}
+
+impl Bookmarks {
+    pub fn new() -> Self {
+        Self { lines: BTreeSet::new() }
+    }
+}
+
+impl GutterColumn for Bookmarks {
+    fn id(&self) -> &'static str {
+        "bookmarks"
+    }
+
+    fn title(&self) -> &'static str {
+        "Закладки"
+    }
+
+    fn width(&self, ui: &Ui, _line_count: usize) -> f32 {
+        1.5 * char_width(ui)
+    }
+
+    fn update(&mut self, _ui: &Ui, editor: &Editor, _path: Option<&Path>, _config: &GutterConfig) {
+        // Закладки на удаленных строках пропадают
+        let line_count = editor.get_buffer().get_all_lines().len();
+        self.lines.retain(|&line| line < line_count);
+    }
+
+    fn paint(&self, ui: &Ui, rect: Rect, row: &GutterRow) {
+        if row.segment == 0 && self.lines.contains(&row.line) {
+            let radius = rect.width().min(rect.height()) / 3.0;
+            ui.painter().circle_filled(rect.center(), radius, Color32::from_rgb(70, 130, 220));
+        }
+    }
+
+    fn tooltip(&self, row: &GutterRow) -> Option<String> {
+        self.lines.contains(&row.line).then(|| format!("Закладка на строке {}", row.line + 1))
+    }
+
+    fn click(&mut self, _editor: &mut Editor, line: usize) -> Result<()> {
+        if !self.lines.remove(&line) {
+            self.lines.insert(line);
+        }
+        Ok(())
+    }
+}
+
+// ---------- Диагностика ----------
+
+// Замечания по тексту строки: пробелы в конце и испорченные при чтении символы
+// Строка проверяется при рисовании, поэтому замечания всегда свежие
+pub struct Diagnostics;
+
+impl Diagnostics {
+    // Столбец и текст первого замечания
+    fn check(text: &str) -> Option<(usize, &'static str)> {
+        if let Some(column) = text.find(char::REPLACEMENT_CHARACTER) {
+            return Some((column, "Символ, который не удалось прочитать в кодировке файла"));
+        }
+        let trimmed = text.trim_end().len();
+        (trimmed < text.len()).then_some((trimmed, "Пробелы в конце строки"))
+    }
+}
+
+impl GutterColumn for Diagnostics {
+    fn id(&self) -> &'static str {
+        "diagnostics"
+    }
+
+    fn title(&self) -> &'static str {
+        "Диагностика"
+    }
+
+    fn width(&self, ui: &Ui, _line_count: usize) -> f32 {
+        1.5 * char_width(ui)
+    }
+
+    fn paint(&self, ui: &Ui, rect: Rect, row: &GutterRow) {
+        if row.segment == 0 && Self::check(row.text).is_some() {
+            let radius = rect.width().min(rect.height()) / 4.0;
+            ui.painter().circle_filled(rect.center(), radius, ui.visuals().warn_fg_color);
+        }
+    }
+
+    fn tooltip(&self, row: &GutterRow) -> Option<String> {
+        Self::check(row.text).map(|(_, message)| message.to_string())
+    }
+
+    // Щелчок ставит курсор на место замечания
+    fn click(&mut self, editor: &mut Editor, line: usize) -> Result<()> {
+        let text = editor.get_buffer().get_all_lines().get(line).cloned().unwrap_or_default();
+        match Self::check(&text) {
+            Some((column, _)) => editor.execute_command(Command::MoveCursor(Position { line, column })),
+            None => Ok(()),
+        }
+    }
+}
+
+// ---------- Сворачивание ----------
+
+// Треугольники у заголовков областей; щелчок сворачивает или разворачивает
+pub struct Folds {
+    generation: Option<u64>, // состояние сворачивания, с которого сняты области
+    ranges: HashMap<usize, (FoldRange, bool)>, // область по заголовку и свернута ли она
+}
+
+impl Folds {
+    pub fn new() -> Self {
+        Self { generation: None, ranges: HashMap::new() }
+    }
+}
+
+impl GutterColumn for Folds {
+    fn id(&self) -> &'static str {
+        "folds"
+    }
+
+    fn title(&self) -> &'static str {
+        "Сворачивание"
+    }
+
+    fn width(&self, ui: &Ui, _line_count: usize) -> f32 {
+        1.5 * char_width(ui)
+    }
+
+    fn update(&mut self, _ui: &Ui, editor: &Editor, _path: Option<&Path>, _config: &GutterConfig) {
+        let folding = editor.folding();
+        if self.generation == Some(folding.generation()) {
+            return;
+        }
+        self.generation = Some(folding.generation());
+        self.ranges = folding.ranges().iter().map(|range| (range.start, (*range, folding.is_folded(range.start)))).collect();
+    }
+
+    fn paint(&self, ui: &Ui, rect: Rect, row: &GutterRow) {
+        let Some((_, folded)) = self.ranges.get(&row.line).filter(|_| row.segment == 0) else { return };
+        let size = rect.width().min(rect.height()) / 3.0;
+        let c = rect.center();
+        // Свернутая область - треугольник вправо, открытая - вниз
+        let points = if *folded {
+            vec![pos2(c.x - size / 2.0, c.y - size), pos2(c.x + size / 2.0, c.y), pos2(c.x - size / 2.0, c.y + size)]
+        } else {
+            vec![pos2(c.x - size, c.y - size / 2.0), pos2(c.x + size, c.y - size / 2.0), pos2(c.x, c.y + size / 2.0)]
+        };
+        ui.painter().add(egui::Shape::convex_polygon(points, Color32::GRAY, egui::Stroke::NONE));
+    }
+
+    fn tooltip(&self, row: &GutterRow) -> Option<String> {
+        let (range, folded) = self.ranges.get(&row.line)?;
+        let action = if *folded { "Развернуть" } else { "Свернуть" };
+        Some(format!("{} строки {}-{}", action, range.start + 2, range.end + 1))
+    }
+
+    fn click(&mut self, editor: &mut Editor, line: usize) -> Result<()> {
+        editor.folding_mut().toggle(line);
+        Ok(())
+    }
+}
+
+// ---------- Изменения относительно git ----------
+
+#[derive(Clone, Debug, PartialEq)]
+enum Change {
+    Added,
+    Modified(String), // прежний текст строк
+    Deleted(usize),   // перед строкой удалено столько строк
+}
+
+// По чему читается версия из git: другой файл, табуляция или время изменения
+// файла (сохранение, правка извне) - повод прочитать ее заново
+#[derive(Clone, Debug, PartialEq)]
+struct BaseSource {
+    path: PathBuf,
+    tabs: TabSettings,
+    modified: Option<SystemTime>,
+}
+
+// Задание потоку сравнения: текущие строки буфера
+struct ChangesJob {
+    source: BaseSource,
+    lines: Vec<String>,
+}
+
+// Изменения строк файла path
+struct ChangesResult {
+    path: PathBuf,
+    changes: HashMap<usize, Change>,
+}
+
+// Полоски изменений по сравнению с версией файла в последнем коммите
+// git show и сравнение выполняются в отдельном потоке, чтобы не задерживать кадр
+pub struct Changes {
+    worker: Option<(Sender<ChangesJob>, Receiver<ChangesResult>)>, // запускается для первого файла
+    sent: Option<(BaseSource, u64)>, // источник версии из git и правка последнего задания
+    changes: HashMap<usize, Change>,
+}
+
+impl Changes {
+    pub fn new() -> Self {
+        Self { worker: None, sent: None, changes: HashMap::new() }
+    }
+
+    // Запускает поток сравнения; notify вызывается, когда готов новый результат
+    fn spawn(notify: impl Fn() + Send + 'static) -> Option<(Sender<ChangesJob>, Receiver<ChangesResult>)> {
+        let (jobs, job_receiver) = mpsc::channel();
+        let (result_sender, results) = mpsc::channel();
+        let spawned = thread::Builder::new()
+            .name("changes".to_string())
+            .spawn(move || run_changes(job_receiver, result_sender, notify));
+        match spawned {
+            Ok(_) => Some((jobs, results)),
+            Err(e) => {
+                // Без потока редактор работает, только без полосок изменений
+                log::warn!("Не удалось запустить поток сравнения с git: {}", e);
+                None
+            }
+        }
+    }
+}
+
+// Цикл потока: выполняет задания, пока колонка не закроет канал
+// Версия из git читается заново, только когда меняется ее источник
+fn run_changes(jobs: Receiver<ChangesJob>, results: Sender<ChangesResult>, notify: impl Fn()) {
+    let mut base: Option<(BaseSource, Option<Vec<String>>)> = None;
+    while let Ok(mut job) = jobs.recv() {
+        // Задания, накопившиеся за время сравнения, устарели: нужен только последний текст
+        while let Ok(next) = jobs.try_recv() {
+            job = next;
+        }
+        if base.as_ref().map(|(source, _)| source) != Some(&job.source) {
+            let lines = read_base(&job.source.path, job.source.tabs);
+            base = Some((job.source.clone(), lines));
+        }
+        let changes = match base.as_ref().and_then(|(_, lines)| lines.as_deref()) {
+            Some(base) => compare(base, &job.lines),
+            None => HashMap::new(),
+        };
+        if results.send(ChangesResult { path: job.source.path, changes }).is_err() {
+            break;
+        }
+        notify();
+    }
+}
+
+// Строки файла в HEAD, приведенные к виду буфера; None - файла нет в репозитории
+fn read_base(path: &Path, tabs: TabSettings) -> Option<Vec<String>> {
+    let dir = path.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    let name = path.file_name()?.to_str()?;
+    let output = process::Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .arg("show")
+        .arg(format!("HEAD:./{}", name))
+        .stderr(process::Stdio::null())
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    // Буфер хранит строки без \r, табуляцию он заменяет по своей настройке
+    let text = String::from_utf8_lossy(&output.stdout).replace("\r\n", "\n");
+    let mut buffer = Buffer::new();
+    buffer.set_tab_settings(tabs);
+    buffer.set_text(text).ok()?;
+    Some(buffer.get_all_lines().iter().cloned().collect())
+}
+
+fn compare(base: &[String], lines: &[String]) -> HashMap<usize, Change> {
+    let mut changes = HashMap::new();
+    let deadline = Some(Instant::now() + DIFF_DEADLINE);
+    for op in similar::capture_diff_deadline(Algorithm::Myers, base, 0..base.len(), lines, 0..lines.len(), deadline) {
+        match op {
+            DiffOp::Equal { .. } => {}
+            DiffOp::Insert { new_index, new_len, .. } => {
+                for line in new_index..new_index + new_len {
+                    changes.insert(line, Change::Added);
+                }
+            }
+            DiffOp::Delete { new_index, old_len, .. } => {
+                // Удаление в конце файла отмечается на последней строке
+                let line = new_index.min(lines.len().saturating_sub(1));
+                changes.entry(line).or_insert(Change::Deleted(old_len));
+            }
+            DiffOp::Replace { old_index, old_len, new_index, new_len } => {
+                let old = base[old_index..old_index + old_len].join("\n");
+                for line in new_index..new_index + new_len {
+                    changes.insert(line, Change::Modified(old.clone()));
+                }
+            }
+        }
+    }
+    changes
+}
+
+impl GutterColumn for Changes {
+    fn id(&self) -> &'static str {
+        "changes"
+    }
+
+    fn title(&self) -> &'static str {
+        "Изменения (git)"
+    }
+
+    fn width(&self, ui: &Ui, _line_count: usize) -> f32 {
+        0.5 * char_width(ui)
+    }
+
+    fn update(&mut self, ui: &Ui, editor: &Editor, path: Option<&Path>, _config: &GutterConfig) {
+        // Пока новое сравнение не готово, показываются прежние изменения того же файла
+        if let Some((_, results)) = &self.worker {
+            while let Ok(result) = results.try_recv() {
+                if Some(result.path.as_path()) == path {
+                    self.changes = result.changes;
+                }
+            }
+        }
+        let Some(path) = path else {
+            self.sent = None;
+            self.changes.clear();
+            return;
+        };
+        let buffer = editor.get_buffer();
+        let source = BaseSource {
+            path: path.to_path_buf(),
+            tabs: buffer.tab_settings(),
+            modified: fs::metadata(path).and_then(|metadata| metadata.modified()).ok(),
+        };
+        if self.sent.as_ref().is_some_and(|(sent, revision)| *sent == source && *revision == buffer.revision()) {
+            return;
+        }
+        if self.sent.as_ref().is_some_and(|(sent, _)| sent.path != source.path) {
+            self.changes.clear();
+        }
+        if self.worker.is_none() {
+            let ctx = ui.ctx().clone();
+            self.worker = Self::spawn(move || ctx.request_repaint());
+        }
+        let Some((jobs, _)) = &self.worker else { return };
+        self.sent = Some((source.clone(), buffer.revision()));
+        let lines = buffer.get_all_lines().iter().cloned().collect();
+        // Поток завершился - полосок просто не будет
+        let _ = jobs.send(ChangesJob { source, lines });
+    }
+
+    fn paint(&self, ui: &Ui, rect: Rect, row: &GutterRow) {
+        let Some(change) = self.changes.get(&row.line) else { return };
+        let painter = ui.painter();
+        match change {
+            Change::Added => painter.rect_filled(rect, 0.0, Color32::from_rgb(80, 170, 80)),
+            Change::Modified(_) => painter.rect_filled(rect, 0.0, Color32::from_rgb(80, 130, 200)),
+            // Удаление отмечается треугольником на границе строк
+            Change::Deleted(_) if row.segment == 0 => {
+                let points = vec![rect.left_top(), rect.right_top(), pos2(rect.left(), rect.top() + rect.width())];
+                painter.add(egui::Shape::convex_polygon(points, Color32::from_rgb(200, 80, 80), egui::Stroke::NONE));
+            }
+            Change::Deleted(_) => {}
+        }
+    }
+
+    fn tooltip(&self, row: &GutterRow) -> Option<String> {
+        match self.changes.get(&row.line)? {
+            Change::Added => Some("Добавленная строка".to_string()),
+            Change::Modified(old) => Some(format!("Было:\n{}", old)),
+            Change::Deleted(count) => Some(format!("Удалено строк выше: {}", count)),
+        }
+    }
+}
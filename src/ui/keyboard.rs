@@ -0,0 +1,522 @@
+// Перевод событий клавиатуры egui в команды редактора
+// Все правки идут через Command, поэтому они попадают в историю отмены и журнал
+
+use std::path::PathBuf;
+
+use anyhow::Result;
+use egui::{Event, Key, Modifiers};
+
+use crate::editor::text::{
+    byte_column, char_at, char_class, char_column, end_of_buffer, line_text, lines, next_position, prev_boundary, previous_position,
+};
+use crate::editor::{
+    CaseStyle, Command, EmacsEvent, EmacsState, Editor, Key as EditorKey, Position, VimEvent, VimMode, VimState,
+};
+use crate::ui::wrap::WrapLayout;
+
+// Действия с файлами и макросами, которые выполняет окно, а не редактор
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum WindowAction {
+    New,
+    Open,
+    Save,
+    SaveAs,
+    SaveTo(PathBuf), // :w файл в Vim
+    Close,        // с вопросом, если есть несохраненные правки
+    Discard,      // закрыть без сохранения (:q! в Vim)
+    SaveAndClose, // :wq в Vim; документ закрывается, только если он сохранен
+    Exit,         // закрыть окно (C-x C-c в Emacs)
+    RecordMacro,  // начать запись макроса или закончить ее и спросить имя
+    PlayMacro,    // повторить последний макрос заданное число раз
+}
+
+// Раскладка клавиш
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Keymap {
+    Standard, // обычные сочетания: Ctrl+C, Shift+стрелки
+    Vim,      // модальное редактирование
+    Emacs,    // C-f/C-b, метка и регион, кольцо удалений
+}
+
+impl Keymap {
+    pub const ALL: [Keymap; 3] = [Keymap::Standard, Keymap::Vim, Keymap::Emacs];
+
+    // Название для меню
+    pub fn title(&self) -> &'static str {
+        match self {
+            Keymap::Standard => "Обычная",
+            Keymap::Vim => "Vim",
+            Keymap::Emacs => "Emacs",
+        }
+    }
+}
+
+pub struct Keyboard {
+    keymap: Keymap,
+    desired_column: Option<usize>, // столбец (в символах) для движения вверх/вниз
+    page_lines: usize,             // сколько строк помещается в окне (PageUp/PageDown)
+    vim: VimState,
+    emacs: EmacsState,
+    skip_text: bool, // следующий текст - символ сочетания с Alt, он уже обработан
+}
+
+impl Keyboard {
+    pub fn new() -> Self {
+        Self {
+            keymap: Keymap::Standard,
+            desired_column: None,
+            page_lines: 20,
+            vim: VimState::new(),
+            emacs: EmacsState::new(),
+            skip_text: false,
+        }
+    }
+
+    pub fn keymap(&self) -> Keymap {
+        self.keymap
+    }
+
+    // Меняет раскладку; состояние прежней сбрасывается
+    pub fn set_keymap(&mut self, keymap: Keymap) {
+        self.keymap = keymap;
+        self.reset();
+    }
+
+    // Сбрасывает состояние раскладки (например, при смене документа), сама раскладка остается
+    pub fn reset(&mut self) {
+        self.desired_column = None;
+        self.vim = VimState::new();
+        self.emacs = EmacsState::new();
+        self.skip_text = false;
+    }
+
+    // Режим и набираемая команда для строки состояния
+    pub fn status(&self) -> Option<String> {
+        match self.keymap {
+            Keymap::Standard => None,
+            Keymap::Vim => {
+                if let Some(command) = self.vim.command_line() {
+                    return Some(format!(":{}", command));
+                }
+                let mode = match self.vim.mode() {
+                    VimMode::Insert => "-- INSERT --",
+                    VimMode::Visual => "-- VISUAL --",
+                    VimMode::VisualLine => "-- VISUAL LINE --",
+                    VimMode::VisualBlock => "-- VISUAL BLOCK --",
+                    VimMode::Normal | VimMode::CommandLine => "",
+                };
+                let status = format!("{} {}", mode, self.vim.pending_keys());
+                Some(status.trim().to_string()).filter(|status| !status.is_empty())
+            }
+            Keymap::Emacs => self.emacs.status(),
+        }
+    }
+
+    // Запоминает высоту окна в строках
+    pub fn set_page_lines(&mut self, lines: usize) {
+        self.page_lines = lines.max(1);
+    }
+
+    // Обрабатывает событие; действие для окна возвращается ему
+    // wrap - разметка строк экрана для движения по перенесенным строкам
+    pub fn handle_event(&mut self, editor: &mut Editor, wrap: &WrapLayout, event: &Event) -> Result<Option<WindowAction>> {
+        if self.keymap != Keymap::Standard {
+            return self.handle_modal(editor, event);
+        }
+        match event {
+            Event::Text(text) => {
+                self.desired_column = None;
+                let mut chars = text.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => editor.execute_command(Command::InsertChar(c))?,
+                    _ => editor.execute_command(Command::InsertText(text.clone()))?,
+                }
+            }
+            Event::Copy => editor.execute_command(Command::Copy)?,
+            Event::Cut => editor.execute_command(Command::Cut)?,
+            Event::Paste(_) => editor.execute_command(Command::Paste)?,
+            Event::Key { key, pressed: true, modifiers, .. } => return self.handle_key(editor, wrap, *key, *modifiers),
+            _ => {}
+        }
+        Ok(None)
+    }
+
+    // Vim и Emacs получают нажатия в своей нотации; копирование и вставку они делают сами,
+    // поэтому события Copy/Cut/Paste пропускаются (клавиша приходит и отдельным событием)
+    fn handle_modal(&mut self, editor: &mut Editor, event: &Event) -> Result<Option<WindowAction>> {
+        let mut action = None;
+        for key in self.translate(event) {
+            let next = match self.keymap {
+                Keymap::Standard => None,
+                Keymap::Vim => match self.vim.handle_key(editor, key)? {
+                    VimEvent::None => None,
+                    VimEvent::Write(None) => Some(WindowAction::Save),
+                    VimEvent::Write(Some(path)) => Some(WindowAction::SaveTo(PathBuf::from(path))),
+                    VimEvent::Quit { force: false } => Some(WindowAction::Close),
+                    VimEvent::Quit { force: true } => Some(WindowAction::Discard),
+                    VimEvent::WriteQuit => Some(WindowAction::SaveAndClose),
+                },
+                Keymap::Emacs => match self.emacs.handle_key(editor, key)? {
+                    EmacsEvent::None => None,
+                    EmacsEvent::Save => Some(WindowAction::Save),
+                    EmacsEvent::Quit => Some(WindowAction::Exit),
+                },
+            };
+            action = next.or(action);
+        }
+        Ok(action)
+    }
+
+    // Символы приходят событием Text, а сочетания с Ctrl и Alt и служебные клавиши - событием Key
+    // Вслед за сочетанием с Alt система может прислать и его символ - он пропускается
+    fn translate(&mut self, event: &Event) -> Vec<EditorKey> {
+        let skip_text = std::mem::take(&mut self.skip_text);
+        match event {
+            Event::Text(text) if !skip_text => text.chars().map(EditorKey::Char).collect(),
+            Event::Key { key, pressed: true, modifiers, .. } => {
+                let named = match key {
+                    Key::Escape => Some(EditorKey::Esc),
+                    Key::Enter => Some(EditorKey::Enter),
+                    Key::Backspace => Some(EditorKey::Backspace),
+                    Key::Delete => Some(EditorKey::Delete),
+                    Key::Tab => Some(EditorKey::Tab),
+                    Key::ArrowLeft => Some(EditorKey::Left),
+                    Key::ArrowRight => Some(EditorKey::Right),
+                    Key::ArrowUp => Some(EditorKey::Up),
+                    Key::ArrowDown => Some(EditorKey::Down),
+                    Key::Home => Some(EditorKey::Home),
+                    Key::End => Some(EditorKey::End),
+                    _ => None,
+                };
+                if let Some(named) = named {
+                    return vec![named];
+                }
+                match key_char(*key, modifiers.shift) {
+                    Some(c) if modifiers.command => vec![EditorKey::Ctrl(c.to_ascii_lowercase())],
+                    Some(c) if modifiers.alt => {
+                        self.skip_text = true;
+                        vec![EditorKey::Alt(c)]
+                    }
+                    _ => Vec::new(),
+                }
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    fn handle_key(&mut self, editor: &mut Editor, wrap: &WrapLayout, key: Key, modifiers: Modifiers) -> Result<Option<WindowAction>> {
+        let vertical = matches!(key, Key::ArrowUp | Key::ArrowDown | Key::PageUp | Key::PageDown);
+        if !vertical {
+            self.desired_column = None;
+        }
+        let cursor = editor.get_cursor_position();
+        let extend = modifiers.shift;
+
+        // Сочетания с Ctrl (Cmd в macOS)
+        if modifiers.command {
+            match key {
+                Key::Z if modifiers.shift => editor.execute_command(Command::Redo)?,
+                Key::Z => editor.execute_command(Command::Undo)?,
+                Key::Y => editor.execute_command(Command::Redo)?,
+                Key::A => {
+                    let end = end_of_buffer(editor);
+                    editor.execute_command(Command::Batch(vec![
+                        Command::MoveCursor(end),
+                        Command::Select(Position { line: 0, column: 0 }, end),
+                    ]))?;
+                    editor.publish_selection();
+                }
+                Key::N => return Ok(Some(WindowAction::New)),
+                Key::O => return Ok(Some(WindowAction::Open)),
+                Key::S if modifiers.shift => return Ok(Some(WindowAction::SaveAs)),
+                Key::S => return Ok(Some(WindowAction::Save)),
+                Key::W => return Ok(Some(WindowAction::Close)),
+                // Ctrl+Shift+R - запись макроса, Ctrl+Shift+P - повтор последнего (с вопросом о числе повторов)
+                Key::R if modifiers.shift => return Ok(Some(WindowAction::RecordMacro)),
+                Key::P if modifiers.shift => return Ok(Some(WindowAction::PlayMacro)),
+                // Ctrl+Shift+- сворачивает блок с курсором, Ctrl+Shift++ разворачивает
+                Key::Minus if modifiers.shift => editor.folding_mut().fold_at(cursor.line),
+                Key::PlusEquals if modifiers.shift => editor.folding_mut().unfold_at(cursor.line),
+                // Ctrl+M - к парной скобке, Ctrl+Shift+M - выделить содержимое скобок вокруг курсора
+                Key::M if modifiers.shift => select_inside_brackets(editor)?,
+                // Ctrl+Shift+D - копия строки, Ctrl+Shift+K - удаление строки, Ctrl+J - объединение со следующей
+                Key::D if modifiers.shift => editor.execute_command(Command::DuplicateLines)?,
+                Key::K if modifiers.shift => editor.execute_command(Command::DeleteLines)?,
+                Key::J => editor.execute_command(Command::JoinLines)?,
+                // Ctrl+K - строчный комментарий, Ctrl+Shift+B - блочный
+                // (клавишу '/' egui 0.24 не сообщает, поэтому привычный Ctrl+/ недоступен)
+                Key::K => editor.execute_command(Command::ToggleLineComment)?,
+                Key::B if modifiers.shift => editor.execute_command(Command::ToggleBlockComment)?,
+                // Ctrl+U - верхний регистр, Ctrl+Shift+U - нижний
+                Key::U if modifiers.shift => editor.execute_command(Command::ConvertCase(CaseStyle::Lower))?,
+                Key::U => editor.execute_command(Command::ConvertCase(CaseStyle::Upper))?,
+                Key::M => {
+                    if let Some((_, pair)) = editor.get_buffer().bracket_pair_near(cursor) {
+                        move_cursor(editor, pair, extend)?;
+                    }
+                }
+                // Ctrl+Alt+стрелки добавляют курсор строкой выше верхнего или ниже нижнего
+                Key::ArrowUp if modifiers.alt => add_cursor_vertically(editor, false)?,
+                Key::ArrowDown if modifiers.alt => add_cursor_vertically(editor, true)?,
+                Key::ArrowLeft => move_cursor(editor, word_left(editor, cursor), extend)?,
+                Key::ArrowRight => move_cursor(editor, word_right(editor, cursor), extend)?,
+                Key::Home => move_cursor(editor, Position { line: 0, column: 0 }, extend)?,
+                Key::End => move_cursor(editor, end_of_buffer(editor), extend)?,
+                _ => {}
+            }
+            return Ok(None);
+        }
+
+        // Alt+стрелки переносят строки вверх и вниз
+        if modifiers.alt && matches!(key, Key::ArrowUp | Key::ArrowDown) {
+            let command = if key == Key::ArrowUp { Command::MoveLinesUp } else { Command::MoveLinesDown };
+            editor.execute_command(command)?;
+            return Ok(None);
+        }
+
+        match key {
+            // Esc оставляет один основной курсор
+            Key::Escape if editor.get_buffer().get_cursors().len() > 1 => editor.execute_command(Command::ClearCursors)?,
+            Key::ArrowLeft => {
+                let target = match selection_bounds(editor) {
+                    Some((start, _)) if !extend => start,
+                    _ => previous_position(editor, cursor).unwrap_or(cursor),
+                };
+                move_cursor(editor, target, extend)?;
+            }
+            Key::ArrowRight => {
+                let target = match selection_bounds(editor) {
+                    Some((_, end)) if !extend => end,
+                    _ => next_position(editor, cursor).unwrap_or(cursor),
+                };
+                move_cursor(editor, target, extend)?;
+            }
+            Key::ArrowUp => self.move_vertically(editor, wrap, -1, extend)?,
+            Key::ArrowDown => self.move_vertically(editor, wrap, 1, extend)?,
+            Key::PageUp => self.move_vertically(editor, wrap, -(self.page_lines as isize), extend)?,
+            Key::PageDown => self.move_vertically(editor, wrap, self.page_lines as isize, extend)?,
+            Key::Home => {
+                // В продолжении перенесенной строки - к началу отрезка;
+                // иначе первое нажатие - к первому непробельному символу, второе - к началу строки
+                let text = line_text(editor, cursor.line);
+                let (start, _) = wrap.segment_range(cursor.line, wrap.segment_of(cursor.line, cursor.column), &text);
+                let indent = text.len() - text.trim_start().len();
+                let column = if start > 0 && cursor.column != start {
+                    start
+                } else if cursor.column == indent {
+                    0
+                } else {
+                    indent
+                };
+                move_cursor(editor, Position { line: cursor.line, column }, extend)?;
+            }
+            Key::End => {
+                // Сначала к концу отрезка, повторно - к концу строки
+                let text = line_text(editor, cursor.line);
+                let (_, end) = wrap.segment_range(cursor.line, wrap.segment_of(cursor.line, cursor.column), &text);
+                let segment_end = if end < text.len() { prev_boundary(&text, end) } else { end };
+                let column = if cursor.column == segment_end { text.len() } else { segment_end };
+                move_cursor(editor, Position { line: cursor.line, column }, extend)?;
+            }
+            Key::Backspace => {
+                if selection_bounds(editor).is_some() {
+                    editor.execute_command(Command::DeleteSelection)?;
+                } else if let Some(next) = editor.get_buffer().empty_pair_at_cursor().then(|| next_position(editor, cursor)).flatten() {
+                    // Пустая пара скобок или кавычек удаляется целиком
+                    editor.execute_command(Command::Batch(vec![Command::MoveCursor(next), Command::DeleteChar, Command::DeleteChar]))?;
+                } else {
+                    editor.execute_command(Command::DeleteChar)?;
+                }
+            }
+            Key::Delete => {
+                if selection_bounds(editor).is_some() {
+                    editor.execute_command(Command::DeleteSelection)?;
+                } else if let Some(next) = next_position(editor, cursor) {
+                    // Удаление вперед - это удаление назад от следующей позиции
+                    editor.execute_command(Command::Batch(vec![Command::MoveCursor(next), Command::DeleteChar]))?;
+                }
+            }
+            Key::Enter => {
+                if selection_bounds(editor).is_some() {
+                    editor.execute_command(Command::Batch(vec![Command::DeleteSelection, Command::InsertNewline]))?;
+                } else {
+                    editor.execute_command(Command::InsertNewline)?;
+                }
+            }
+            // Tab на выделении из нескольких строк сдвигает строки, Shift+Tab сдвигает влево
+            Key::Tab if modifiers.shift => editor.execute_command(Command::UnindentLines)?,
+            Key::Tab if selection_bounds(editor).is_some_and(|(start, end)| start.line != end.line) => {
+                editor.execute_command(Command::IndentLines)?
+            }
+            Key::Tab => editor.execute_command(Command::InsertChar('\t'))?,
+            _ => {}
+        }
+        Ok(None)
+    }
+
+    // Вверх/вниз на rows строк экрана с сохранением столбца
+    // Перенесенная строка занимает несколько строк экрана, столбец считается от начала отрезка
+    fn move_vertically(&mut self, editor: &mut Editor, wrap: &WrapLayout, rows: isize, extend: bool) -> Result<()> {
+        let cursor = editor.get_cursor_position();
+        let line_count = editor.get_buffer().get_all_lines().len();
+        let text = line_text(editor, cursor.line);
+        let segment = wrap.segment_of(cursor.line, cursor.column);
+        let (start, _) = wrap.segment_range(cursor.line, segment, &text);
+        let column = *self
+            .desired_column
+            .get_or_insert_with(|| char_column(text.get(start..).unwrap_or_default(), cursor.column.saturating_sub(start)));
+
+        let last_row = wrap.row_count(line_count) - 1;
+        let row = (wrap.first_row(cursor.line) as isize + segment as isize + rows).clamp(0, last_row as isize) as usize;
+        let (line, segment) = wrap.row_position(row);
+        let line = line.min(line_count - 1);
+        let text = line_text(editor, line);
+        let (start, end) = wrap.segment_range(line, segment, &text);
+        let mut target = start + byte_column(text.get(start..end).unwrap_or_default(), column);
+        // Конец перенесенного отрезка показывается уже на следующей строке экрана
+        if target == end && end < text.len() {
+            target = prev_boundary(&text, end).max(start);
+        }
+        move_cursor(editor, Position { line, column: target }, extend)
+    }
+}
+
+impl Default for Keyboard {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Буква, цифра или пробел сочетания с Ctrl или Alt
+fn key_char(key: Key, shift: bool) -> Option<char> {
+    let c = match key {
+        Key::Space => ' ',
+        Key::Minus if shift => '_', // C-_ - отмена в Emacs
+        Key::Minus => '-',
+        _ => {
+            let mut chars = key.name().chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) if c.is_ascii_alphanumeric() => c,
+                _ => return None,
+            }
+        }
+    };
+    Some(if shift { c.to_ascii_uppercase() } else { c.to_ascii_lowercase() })
+}
+
+// Перемещает курсор; с Shift выделение расширяется от неподвижного конца
+// Свернутые строки пропускаются
+fn move_cursor(editor: &mut Editor, target: Position, extend: bool) -> Result<()> {
+    let cursor = editor.get_cursor_position();
+    let target = skip_folded(editor, cursor, target);
+    if !extend {
+        editor.clear_selection()?;
+        return editor.execute_command(Command::MoveCursor(target));
+    }
+    let anchor = match editor.get_selection() {
+        Some((start, end)) if end == cursor => start,
+        Some((start, end)) if start == cursor => end,
+        _ => cursor,
+    };
+    editor.execute_command(Command::Batch(vec![Command::MoveCursor(target), Command::Select(anchor, target)]))?;
+    if anchor == target {
+        editor.clear_selection()?;
+    }
+    editor.publish_selection();
+    Ok(())
+}
+
+// Добавляет курсор на строку выше самого верхнего курсора или ниже самого нижнего
+// в том же столбце (по символам, а не байтам); у края текста ничего не делает
+pub fn add_cursor_vertically(editor: &mut Editor, down: bool) -> Result<()> {
+    let cursors = editor.get_buffer().get_cursors();
+    let (Some(&first), Some(&last)) = (cursors.first(), cursors.last()) else { return Ok(()) };
+    let edge = if down { last } else { first };
+    let line = if down { edge.line + 1 } else if edge.line > 0 { edge.line - 1 } else { return Ok(()) };
+    if line >= lines(editor).len() {
+        return Ok(());
+    }
+    let chars = char_column(&line_text(editor, edge.line), edge.column);
+    let column = byte_column(&line_text(editor, line), chars);
+    editor.execute_command(Command::AddCursor(Position { line, column }))
+}
+
+// Позиция в скрытой строке заменяется ближайшей видимой по направлению движения:
+// вперед - начало строки после свернутой области, назад - конец ее заголовка
+fn skip_folded(editor: &mut Editor, from: Position, to: Position) -> Position {
+    let Some((header, last)) = editor.folding_mut().hidden_range(to.line) else { return to };
+    let lines = editor.get_buffer().get_all_lines();
+    if to > from && last + 1 < lines.len() {
+        Position { line: last + 1, column: 0 }
+    } else {
+        Position { line: header, column: lines[header].len() }
+    }
+}
+
+// Непустое выделение в порядке от начала к концу
+fn selection_bounds(editor: &Editor) -> Option<(Position, Position)> {
+    match editor.get_selection() {
+        Some((start, end)) if start != end => Some((start.min(end), start.max(end))),
+        _ => None,
+    }
+}
+
+// Выделяет текст между ближайшими скобками вокруг курсора
+// Если он уже выделен (или пуст), выделяются и сами скобки, затем содержимое следующей пары
+fn select_inside_brackets(editor: &mut Editor) -> Result<()> {
+    let buffer = editor.get_buffer();
+    let selection = selection_bounds(editor);
+    let from = selection.map_or(editor.get_cursor_position(), |(start, _)| start);
+    let Some((open, close)) = buffer.enclosing_brackets(from) else { return Ok(()) };
+    let inside = (Position { line: open.line, column: open.column + 1 }, close);
+    let (start, end) = if selection == Some(inside) || open.line == close.line && open.column + 1 == close.column {
+        (open, Position { line: close.line, column: close.column + 1 })
+    } else {
+        inside
+    };
+    editor.execute_command(Command::Batch(vec![Command::MoveCursor(end), Command::Select(start, end)]))?;
+    editor.publish_selection();
+    Ok(())
+}
+
+// Начало предыдущего слова
+fn word_left(editor: &Editor, position: Position) -> Position {
+    let mut position = position;
+    // Пропускаем пробелы, затем символы того же класса
+    while let Some(previous) = previous_position(editor, position) {
+        if char_class(char_at(editor, previous), false) != 0 {
+            break;
+        }
+        position = previous;
+    }
+    if let Some(previous) = previous_position(editor, position) {
+        let class = char_class(char_at(editor, previous), false);
+        position = previous;
+        while let Some(previous) = previous_position(editor, position) {
+            if char_class(char_at(editor, previous), false) != class {
+                break;
+            }
+            position = previous;
+        }
+    }
+    position
+}
+
+// Конец следующего слова
+fn word_right(editor: &Editor, position: Position) -> Position {
+    let mut position = position;
+    while char_class(char_at(editor, position), false) == 0 {
+        match next_position(editor, position) {
+            Some(next) => position = next,
+            None => return position,
+        }
+    }
+    let class = char_class(char_at(editor, position), false);
+    while char_class(char_at(editor, position), false) == class {
+        match next_position(editor, position) {
+            Some(next) => position = next,
+            None => break,
+        }
+    }
+    position
+}
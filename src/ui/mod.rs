@@ -1,28 +1,11 @@
+mod brackets;
+mod document;
+mod gutter;
+mod keyboard;
+mod mouse;
+mod view;
 mod window;
 mod widgets;
-
-use anyhow::Result;
-use egui::Context;
+mod wrap;
 
 pub use window::App;
-
-pub struct UI {
-    ctx: Context,
-    app: App,
-}
-
-impl UI {
-    pub fn new() -> Result<Self> {
-        Ok(Self {
-            ctx: Context::default(),
-            app: App::new()?,
-        })
-    }
-
-    pub fn run(&mut self) -> Result<()> {
-        todo!()
-    }
-}
-
-
-
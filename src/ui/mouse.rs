@@ -0,0 +1,272 @@
+// Перевод действий мышью в команды редактора
+// Позиции уже пересчитаны областью редактирования из пикселей в Position,
+// здесь решается только, что с ними делать: курсор, выделение или вставка
+
+use anyhow::{anyhow, Result};
+
+use crate::editor::text::{char_at, char_class, line_text, next_position, previous_position};
+use crate::editor::{Command, Editor, Position, Selection};
+
+// Чем расширяется выделение при перетаскивании
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Unit {
+    Char,
+    Word,
+    Line,
+}
+
+// Наибольший промежуток между нажатиями двойного и тройного щелчка (секунды)
+const MULTI_CLICK_TIME: f64 = 0.4;
+
+pub struct Mouse {
+    anchor: Option<(Position, Position)>, // неподвижная часть выделения (слово или строка при двойном/тройном щелчке)
+    unit: Unit,
+    last_target: Option<Position>, // последняя позиция перетаскивания, чтобы не повторять одинаковые команды
+    last_press: Option<(f64, Position)>, // время и место предыдущего нажатия
+    clicks: u32,                         // 1 - щелчок, 2 - двойной, 3 - тройной
+    moving: Option<(Position, Position)>, // перетаскиваемое выделение
+    drop_target: Option<Position>,        // куда оно будет вставлено
+}
+
+impl Mouse {
+    pub fn new() -> Self {
+        Self {
+            anchor: None,
+            unit: Unit::Char,
+            last_target: None,
+            last_press: None,
+            clicks: 0,
+            moving: None,
+            drop_target: None,
+        }
+    }
+
+    // Нажатие левой кнопки: курсор в позицию, с Shift - выделение до нее
+    // Повторные нажатия в том же месте выделяют слово, затем строку;
+    // выделение сразу при нажатии позволяет тянуть его словами или строками
+    pub fn press(&mut self, editor: &mut Editor, position: Position, extend: bool, time: f64) -> Result<()> {
+        let repeated = matches!(self.last_press, Some((last, at)) if time - last < MULTI_CLICK_TIME && at == position);
+        self.clicks = if repeated && !extend { self.clicks % 3 + 1 } else { 1 };
+        self.last_press = Some((time, position));
+        self.last_target = Some(position);
+        self.moving = None;
+        self.drop_target = None;
+        match self.clicks {
+            2 => return self.select_word(editor, position),
+            3 => return self.select_line(editor, position),
+            _ => self.unit = Unit::Char,
+        }
+        // Нажатие внутри выделения начинает его перетаскивание
+        if let Some((start, end)) = editor.get_selection().map(|(start, end)| (start.min(end), start.max(end))) {
+            if !extend && start <= position && position < end {
+                self.moving = Some((start, end));
+                self.anchor = None;
+                return Ok(());
+            }
+        }
+        if extend {
+            let cursor = editor.get_cursor_position();
+            let anchor = match editor.get_selection() {
+                Some((start, end)) if end == cursor => start,
+                Some((start, end)) if start == cursor => end,
+                _ => cursor,
+            };
+            self.anchor = Some((anchor, anchor));
+            return select(editor, anchor, position);
+        }
+        self.anchor = Some((position, position));
+        editor.clear_selection()?;
+        // Обычный щелчок оставляет один курсор
+        if editor.get_buffer().get_cursors().len() > 1 {
+            editor.execute_command(Command::ClearCursors)?;
+        }
+        editor.execute_command(Command::MoveCursor(position))
+    }
+
+    // Нажатие с Alt: дополнительный курсор, основной и выделение остаются на месте
+    pub fn add_cursor(&mut self, editor: &mut Editor, position: Position) -> Result<()> {
+        self.last_press = None;
+        self.anchor = None;
+        self.moving = None;
+        self.drop_target = None;
+        editor.execute_command(Command::AddCursor(position))
+    }
+
+    // Перетаскивание с нажатой кнопкой: выделение от точки нажатия
+    pub fn drag(&mut self, editor: &mut Editor, position: Position) -> Result<()> {
+        if let Some((start, end)) = self.moving {
+            // Внутрь самого выделения текст не вставляется
+            self.drop_target = (position <= start || position >= end).then_some(position);
+            return Ok(());
+        }
+        let (start, end) = match self.anchor {
+            Some(anchor) => anchor,
+            None => return Ok(()),
+        };
+        if self.last_target == Some(position) {
+            return Ok(());
+        }
+        self.last_target = Some(position);
+        // После перетаскивания следующее нажатие - снова одиночный щелчок
+        self.last_press = None;
+        // Выделение растет целыми словами или строками в сторону указателя
+        let (from, to) = match self.unit {
+            Unit::Char => (start, position),
+            Unit::Word if position < start => (end, word_bounds(editor, position).0),
+            Unit::Word => (start, word_bounds(editor, position).1.max(end)),
+            Unit::Line if position < start => (end, line_bounds(editor, position.line).0),
+            Unit::Line => (start, line_bounds(editor, position.line).1.max(end)),
+        };
+        select(editor, from, to)
+    }
+
+    // Кнопка отпущена в position (None - за пределами окна)
+    // Перетаскиваемый текст переносится, а с copy - копируется одной правкой
+    pub fn release(&mut self, editor: &mut Editor, position: Option<Position>, copy: bool) -> Result<()> {
+        self.anchor = None;
+        self.last_target = None;
+        let moving = self.moving.take();
+        let target = self.drop_target.take();
+        match (moving, target, position) {
+            (Some(range), Some(target), _) => drop_text(editor, range, target, copy),
+            // Щелчок по выделению без перетаскивания ставит курсор
+            (Some(_), None, Some(position)) => {
+                editor.clear_selection()?;
+                editor.execute_command(Command::MoveCursor(position))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // Идет ли перетаскивание выделенного текста
+    pub fn is_moving_text(&self) -> bool {
+        self.moving.is_some()
+    }
+
+    // Куда будет вставлен перетаскиваемый текст
+    pub fn drop_target(&self) -> Option<Position> {
+        self.drop_target
+    }
+
+    // Двойной щелчок: выделяет слово
+    fn select_word(&mut self, editor: &mut Editor, position: Position) -> Result<()> {
+        let (start, end) = word_bounds(editor, position);
+        self.unit = Unit::Word;
+        self.anchor = Some((start, end));
+        select(editor, start, end)
+    }
+
+    // Тройной щелчок: выделяет строку вместе с переводом строки
+    fn select_line(&mut self, editor: &mut Editor, position: Position) -> Result<()> {
+        let (start, end) = line_bounds(editor, position.line);
+        self.unit = Unit::Line;
+        self.anchor = Some((start, end));
+        select(editor, start, end)
+    }
+
+    // Средняя кнопка: вставляет PRIMARY в позицию щелчка (X11/Wayland)
+    // Выделение при этом не заменяется, как в других программах Linux
+    pub fn paste_primary(&mut self, editor: &mut Editor, position: Position) -> Result<()> {
+        if !cfg!(all(unix, not(target_os = "macos"))) {
+            return Ok(());
+        }
+        let text = match editor.clipboard_text(Selection::Primary) {
+            Some(text) if !text.is_empty() => text,
+            _ => return Ok(()),
+        };
+        editor.clear_selection()?;
+        editor.execute_command(Command::Batch(vec![Command::MoveCursor(position), Command::InsertText(text)]))
+    }
+}
+
+impl Default for Mouse {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Курсор в target и выделение от anchor; пустое выделение снимается
+fn select(editor: &mut Editor, anchor: Position, target: Position) -> Result<()> {
+    if anchor == target {
+        editor.clear_selection()?;
+        return editor.execute_command(Command::MoveCursor(target));
+    }
+    editor.execute_command(Command::Batch(vec![Command::MoveCursor(target), Command::Select(anchor, target)]))?;
+    editor.publish_selection();
+    Ok(())
+}
+
+// Переносит или копирует текст из range в target
+// Удаление и вставка идут одной командой, поэтому отменяются вместе
+fn drop_text(editor: &mut Editor, range: (Position, Position), target: Position, copy: bool) -> Result<()> {
+    let (start, end) = range;
+    let text = editor.get_buffer().get_text_in_range(start, end).map_err(|e| anyhow!(e))?;
+    let mut commands = Vec::new();
+    let target = if copy {
+        target
+    } else {
+        commands.push(Command::Select(start, end));
+        commands.push(Command::DeleteSelection);
+        shift_after_delete(target, start, end)
+    };
+    commands.push(Command::MoveCursor(target));
+    commands.push(Command::InsertText(text));
+
+    editor.clear_selection()?;
+    editor.execute_command(Command::Batch(commands))?;
+    // Вставленный текст остается выделенным
+    let cursor = editor.get_cursor_position();
+    editor.execute_command(Command::Select(target, cursor))
+}
+
+// Позиция target после удаления текста между start и end
+fn shift_after_delete(target: Position, start: Position, end: Position) -> Position {
+    if target <= start {
+        target
+    } else if target.line == end.line {
+        Position { line: start.line, column: start.column + target.column - end.column }
+    } else {
+        Position { line: target.line - (end.line - start.line), column: target.column }
+    }
+}
+
+// Границы слова (или группы пробелов, или знаков) под позицией
+fn word_bounds(editor: &Editor, position: Position) -> (Position, Position) {
+    // Щелчок за концом строки выделяет последнее слово
+    let mut position = position;
+    if char_at(editor, position) == '\n' {
+        match previous_position(editor, position) {
+            Some(previous) if previous.line == position.line => position = previous,
+            _ => return (position, position),
+        }
+    }
+    let class = char_class(char_at(editor, position), false);
+    let same = |p: Position| p.line == position.line && char_at(editor, p) != '\n' && char_class(char_at(editor, p), false) == class;
+
+    let mut start = position;
+    while let Some(previous) = previous_position(editor, start).filter(|&p| same(p)) {
+        start = previous;
+    }
+    let mut end = position;
+    while same(end) {
+        match next_position(editor, end) {
+            Some(next) if next.line == position.line => end = next,
+            _ => {
+                end.column = line_text(editor, end.line).len();
+                break;
+            }
+        }
+    }
+    (start, end)
+}
+
+// Строка от начала до начала следующей (последняя - до конца)
+fn line_bounds(editor: &Editor, line: usize) -> (Position, Position) {
+    let last = editor.get_buffer().get_all_lines().len() - 1;
+    let start = Position { line, column: 0 };
+    if line < last {
+        (start, Position { line: line + 1, column: 0 })
+    } else {
+        (start, Position { line, column: line_text(editor, line).len() })
+    }
+}
@@ -0,0 +1,402 @@
+// Область редактирования с виртуальной прокруткой
+// Высота содержимого считается по числу строк, а размечаются и рисуются только
+// видимые строки (и несколько строк запаса), поэтому время кадра не зависит
+// от размера документа
+
+use std::ops::Range;
+use std::path::Path;
+
+use anyhow::Result;
+use egui::{pos2, vec2, Context, CursorIcon, Id, PointerButton, Pos2, Rect, Response, ScrollArea, Sense, Stroke, Ui, Vec2};
+
+use crate::editor::text::{byte_column, prev_boundary};
+use crate::editor::{Command, Editor, Position};
+use crate::syntax::{Highlighter, Language, LanguageSettings, Span};
+use crate::ui::brackets::BracketMarks;
+use crate::ui::gutter::{Gutter, GutterConfig, GutterRow};
+use crate::ui::mouse::Mouse;
+use crate::ui::widgets::{editor_font, TextLine};
+use crate::ui::wrap::{WrapLayout, WrapMode};
+
+// Сколько строк рисуется сверх видимых сверху и снизу
+const OVERSCAN: usize = 5;
+
+// Настройки показа, общие для всех документов
+pub struct ViewOptions {
+    pub gutter: GutterConfig,
+    pub wrap: WrapMode,
+    pub rainbow_brackets: bool, // раскрашивать пары скобок по глубине вложенности
+}
+
+pub struct EditorView {
+    id: Id,
+    scroll_offset: Vec2,   // положение прокрутки этого вида
+    widest: f32,           // ширина самой длинной из показанных строк
+    visible: Range<usize>, // строки экрана, нарисованные в последнем кадре
+    page_lines: usize,     // сколько строк помещается в области
+    mouse: Mouse,
+    layout: Option<Layout>, // разметка последнего кадра
+    wrap: WrapLayout,
+    gutter: Gutter,
+    cursor: Option<Position>,          // курсор в прошлом кадре
+    highlighter: Option<Highlighter>, // запускается при первом документе с известным языком
+    brackets: BracketMarks,
+}
+
+impl EditorView {
+    pub fn new(id_source: impl std::hash::Hash) -> Self {
+        Self {
+            id: Id::new(id_source),
+            scroll_offset: Vec2::ZERO,
+            widest: 0.0,
+            visible: 0..0,
+            page_lines: 1,
+            mouse: Mouse::new(),
+            layout: None,
+            wrap: WrapLayout::new(),
+            gutter: Gutter::new(),
+            cursor: None,
+            highlighter: None,
+            brackets: BracketMarks::new(),
+        }
+    }
+
+    // Высота области в строках (для PageUp/PageDown)
+    pub fn page_lines(&self) -> usize {
+        self.page_lines
+    }
+
+    // Разметка строк с переносом, по которой вид показан в последнем кадре
+    pub fn wrap(&self) -> &WrapLayout {
+        &self.wrap
+    }
+
+    // Колонки полей (для меню настройки)
+    pub fn gutter(&self) -> &Gutter {
+        &self.gutter
+    }
+
+    pub fn show(
+        &mut self,
+        ui: &mut Ui,
+        editor: &mut Editor,
+        path: Option<&Path>,
+        settings: &LanguageSettings,
+        options: &ViewOptions,
+        scroll_to_cursor: bool,
+    ) -> Result<()> {
+        let font = editor_font();
+        let row_height = ui.fonts(|fonts| fonts.row_height(&font));
+        let char_width = ui.fonts(|fonts| fonts.glyph_width(&font, ' '));
+        let line_count = editor.get_buffer().get_all_lines().len();
+        // Перенос по столбцу идет по столбцу языка документа
+        let wrap_mode = match options.wrap {
+            WrapMode::Column(_) => WrapMode::Column(settings.wrap_column),
+            mode => mode,
+        };
+
+        self.update_highlighting(ui.ctx(), editor, settings.language);
+        let mut result = self.keep_cursor_visible(editor);
+        self.brackets.update(editor.get_buffer(), options.rainbow_brackets);
+        self.gutter.update(ui, editor, path, &options.gutter);
+        let gutter = self.gutter.width();
+
+        // Ширина текста без полей, полосы прокрутки и места под курсор
+        let text_width = ui.available_width() - gutter - ui.spacing().scroll.bar_width - char_width;
+        self.wrap.update(ui.ctx(), editor.get_buffer(), wrap_mode, text_width, editor.folding());
+        let row_count = self.wrap.row_count(line_count);
+
+        let output = ScrollArea::both()
+            .id_source(self.id)
+            .auto_shrink([false, false])
+            .scroll_offset(self.scroll_offset)
+            .show_viewport(ui, |ui, viewport| {
+                // Размер содержимого по числу строк экрана, а не по нарисованным строкам
+                let width = match wrap_mode {
+                    WrapMode::Off => (gutter + self.widest).max(viewport.width()),
+                    _ => viewport.width(),
+                };
+                let height = row_height * row_count as f32;
+                ui.set_min_size(vec2(width, height));
+                let origin = ui.min_rect().min;
+                let layout = Layout { origin, gutter, row_height, area: ui.clip_rect() };
+                self.layout = Some(layout);
+
+                // Мышь обрабатывается до рисования, чтобы кадр показал ее результат
+                // Поля слева обрабатывают щелчки сами
+                let content = Rect::from_min_size(origin + vec2(gutter, 0.0), vec2(width - gutter, height));
+                let response = ui.interact(content, self.id.with("text"), Sense::click_and_drag());
+                if let Err(e) = self.handle_mouse(ui, editor, &response, &layout) {
+                    result = Err(e);
+                }
+                if self.mouse.is_moving_text() {
+                    ui.ctx().set_cursor_icon(CursorIcon::Grabbing);
+                } else if response.hovered() {
+                    ui.ctx().set_cursor_icon(CursorIcon::Text);
+                }
+
+                let lines = editor.get_buffer().get_all_lines();
+                let cursor = editor.get_cursor_position();
+                let selection = editor.get_selection().map(|(start, end)| (start.min(end), start.max(end)));
+                let color = ui.visuals().text_color();
+
+                let first = ((viewport.min.y / row_height).floor().max(0.0) as usize).saturating_sub(OVERSCAN);
+                let last = (((viewport.max.y / row_height).ceil() as usize) + OVERSCAN).min(row_count);
+                self.visible = first.min(last)..last;
+                self.page_lines = ((viewport.height() / row_height) as usize).max(1);
+                let mut gutter_click = None;
+
+                for row_index in self.visible.clone() {
+                    let (index, segment) = self.wrap.row_position(row_index);
+                    let Some(text) = lines.get(index) else { break };
+                    let (start, end) = self.wrap.segment_range(index, segment, text);
+                    let piece = text.get(start..end).unwrap_or_default();
+                    let last_segment = end == text.len();
+
+                    let top = origin.y + row_index as f32 * row_height;
+                    let row = Rect::from_min_size(pos2(origin.x, top), vec2(width, row_height));
+                    let gutter_row = GutterRow { line: index, segment, text, cursor_line: cursor.line };
+                    if let Some(column) = self.gutter.show(ui, self.id.with("gutter"), row, &gutter_row) {
+                        gutter_click = Some((column, index));
+                    }
+                    ui.allocate_ui_at_rect(row, |ui| {
+                        ui.spacing_mut().item_spacing = Vec2::ZERO;
+                        ui.horizontal(|ui| {
+                            ui.add_space(gutter);
+                            // Курсор на границе отрезков рисуется в начале следующего
+                            let on_segment = cursor.line == index
+                                && start <= cursor.column
+                                && (cursor.column < end || last_segment);
+                            let caret = on_segment.then_some(Position { line: index, column: cursor.column - start });
+                            let spans = self.highlighter.as_ref().map_or(&[][..], |h| h.spans(index, text, lines.len()));
+                            let spans = self.brackets.colorize(index, spans);
+                            let marks = self.brackets.matched(index).into_iter();
+                            let mut line = TextLine::new(piece.to_string(), index, caret)
+                                .with_indent(self.wrap.indent(index, segment))
+                                .with_spans(segment_spans(&spans, start, end))
+                                .with_marks(marks.filter(|column| (start..end).contains(column)).map(|column| column - start).collect());
+                            if let Some((from, to)) = selection {
+                                if from.line <= index && index <= to.line && from != to {
+                                    let from = if index == from.line { from.column } else { 0 };
+                                    let newline = index < to.line;
+                                    let to = if index == to.line { to.column } else { text.len() };
+                                    let (from, to) = (from.clamp(start, end) - start, to.clamp(start, end) - start);
+                                    if from < to || (newline && last_segment) {
+                                        line = line.with_selection(from, to, newline && last_segment);
+                                    }
+                                }
+                            }
+                            line.draw(ui);
+                        });
+                    });
+                    // Под свернутой строкой - черта на месте скрытых строк
+                    if last_segment && editor.folding().is_folded(index) {
+                        let stroke = Stroke::new(1.0, ui.visuals().weak_text_color());
+                        ui.painter().hline(row.left() + gutter..=row.right(), row.bottom() - 0.5, stroke);
+                    }
+                    // Ширина строки для горизонтальной полосы прокрутки (разметка берется из кеша egui)
+                    if wrap_mode == WrapMode::Off {
+                        let text_width = ui.fonts(|fonts| fonts.layout_no_wrap(text.clone(), font.clone(), color).size().x);
+                        self.widest = self.widest.max(text_width + char_width);
+                    }
+                }
+
+                // Дополнительные курсоры рисуются так же, как основной (в свернутых строках - нет)
+                let extra_cursors = editor.get_buffer().get_cursors().into_iter()
+                    .filter(|&position| position != cursor && !editor.folding().is_hidden(position.line));
+                for extra in extra_cursors {
+                    let caret = layout.caret_rect(ui.ctx(), editor, &self.wrap, extra);
+                    ui.painter().rect_filled(caret.expand2(vec2(0.5, 0.0)), 0.0, ui.visuals().text_cursor.color);
+                }
+
+                // Место, куда упадет перетаскиваемый текст
+                if let Some(target) = self.mouse.drop_target() {
+                    let caret = layout.caret_rect(ui.ctx(), editor, &self.wrap, target);
+                    ui.painter().rect_filled(caret.expand2(vec2(0.5, 0.0)), 0.0, ui.visuals().text_cursor.color);
+                }
+
+                // Курсор может быть за пределами нарисованных строк
+                if scroll_to_cursor {
+                    ui.scroll_to_rect(layout.caret_rect(ui.ctx(), editor, &self.wrap, cursor), None);
+                }
+
+                // Щелчок по полям меняет редактор, поэтому выполняется после рисования
+                if let Some((column, line)) = gutter_click {
+                    if let Err(e) = self.gutter.click(column, editor, line) {
+                        result = Err(e);
+                    }
+                }
+            });
+        self.scroll_offset = output.state.offset;
+        result
+    }
+
+    // Отправляет текст на подсветку и передает сворачиванию области из синтаксического дерева,
+    // а буферу - строки и комментарии, в которых скобки не ищутся
+    fn update_highlighting(&mut self, ctx: &Context, editor: &mut Editor, language: Option<Language>) {
+        // Языки без грамматики показываются без подсветки
+        let language = language.filter(|language| language.grammar().is_some());
+        if language.is_some() && self.highlighter.is_none() {
+            let ctx = ctx.clone();
+            self.highlighter = Some(Highlighter::new(move || ctx.request_repaint()));
+        }
+        let (regions, literals) = match &mut self.highlighter {
+            Some(highlighter) => {
+                highlighter.update(editor.get_buffer(), language);
+                (highlighter.regions(), highlighter.literals())
+            }
+            None => (None, None),
+        };
+        match regions {
+            Some((revision, regions)) => editor.folding_mut().set_regions(revision, regions),
+            None => editor.folding_mut().clear_regions(),
+        }
+        match literals {
+            Some((revision, literals)) => editor.get_buffer_mut().set_literals(revision, literals),
+            None => editor.get_buffer_mut().clear_literals(),
+        }
+    }
+
+    // Курсор не остается в скрытой строке
+    // Если он туда перешел (поиск, отмена, переход к строке), область раскрывается;
+    // если область свернули вокруг неподвижного курсора, он переходит на ее заголовок
+    fn keep_cursor_visible(&mut self, editor: &mut Editor) -> Result<()> {
+        let cursor = editor.get_cursor_position();
+        if let Some((header, _)) = editor.folding_mut().hidden_range(cursor.line) {
+            if self.cursor == Some(cursor) {
+                let column = editor.get_buffer().get_all_lines()[header].len();
+                editor.clear_selection()?;
+                editor.execute_command(Command::MoveCursor(Position { line: header, column }))?;
+            } else {
+                editor.folding_mut().reveal(cursor.line);
+            }
+        }
+        self.cursor = Some(editor.get_cursor_position());
+        Ok(())
+    }
+
+    // Нажатия, перетаскивание и средняя кнопка над текстом
+    fn handle_mouse(&mut self, ui: &mut Ui, editor: &mut Editor, response: &Response, layout: &Layout) -> Result<()> {
+        let (pressed, middle, down, modifiers, time, latest) = ui.input(|input| {
+            (
+                input.pointer.primary_pressed(),
+                input.pointer.button_pressed(PointerButton::Middle),
+                input.pointer.primary_down(),
+                input.modifiers,
+                input.time,
+                input.pointer.latest_pos(),
+            )
+        });
+        let point = response.interact_pointer_pos().or_else(|| response.hover_pos());
+        let ctx = ui.ctx().clone();
+
+        match point {
+            Some(point) if pressed && response.hovered() && modifiers.alt => {
+                let position = layout.position_at(&ctx, editor, &self.wrap, point);
+                self.mouse.add_cursor(editor, position)?;
+            }
+            Some(point) if pressed && response.hovered() => {
+                let position = layout.position_at(&ctx, editor, &self.wrap, point);
+                self.mouse.press(editor, position, modifiers.shift, time)?;
+            }
+            Some(point) if down && response.is_pointer_button_down_on() => {
+                let position = layout.position_at(&ctx, editor, &self.wrap, point);
+                self.mouse.drag(editor, position)?;
+                self.scroll_at_edges(ui, point, layout.row_height);
+            }
+            _ if !down => {
+                // Перетаскиваемый текст с Ctrl копируется, без него - переносится
+                let position = point.or(latest).map(|point| layout.position_at(&ctx, editor, &self.wrap, point));
+                self.mouse.release(editor, position, modifiers.command)?;
+            }
+            _ => {}
+        }
+        if let Some(point) = point.filter(|_| middle && response.hovered()) {
+            let position = layout.position_at(&ctx, editor, &self.wrap, point);
+            self.mouse.paste_primary(editor, position)?;
+        }
+        Ok(())
+    }
+
+    // Позиция в тексте под точкой экрана по разметке последнего кадра
+    // None - точка вне области текста
+    pub fn position_at(&self, ctx: &Context, editor: &Editor, point: Pos2) -> Option<Position> {
+        let layout = self.layout.as_ref()?;
+        layout.area.contains(point).then(|| layout.position_at(ctx, editor, &self.wrap, point))
+    }
+
+    // Указатель за краем области при выделении: прокрутка тем быстрее, чем он дальше
+    fn scroll_at_edges(&self, ui: &mut Ui, point: Pos2, row_height: f32) {
+        let area = ui.clip_rect();
+        let limit = 3.0 * row_height;
+        let delta = vec2(
+            (area.left() - point.x).clamp(0.0, limit) - (point.x - area.right()).clamp(0.0, limit),
+            (area.top() - point.y).clamp(0.0, limit) - (point.y - area.bottom()).clamp(0.0, limit),
+        );
+        if delta != Vec2::ZERO {
+            ui.scroll_with_delta(delta);
+            // Прокрутка продолжается, даже если мышь неподвижна
+            ui.ctx().request_repaint();
+        }
+    }
+}
+
+// Отрезки подсветки, попадающие в отрезок строки [start, end), со столбцами от его начала
+fn segment_spans(spans: &[Span], start: usize, end: usize) -> Vec<Span> {
+    spans
+        .iter()
+        .filter(|span| span.end > start && span.start < end)
+        .map(|span| Span { start: span.start.max(start) - start, end: span.end.min(end) - start, style: span.style })
+        .collect()
+}
+
+// Расположение строк на экране для перевода пикселей в позиции
+#[derive(Copy, Clone)]
+struct Layout {
+    origin: Pos2, // левый верхний угол первой строки
+    gutter: f32,  // ширина полей слева
+    row_height: f32,
+    area: Rect, // видимая часть области на экране
+}
+
+impl Layout {
+    // Позиция в тексте под точкой экрана
+    // Столбец ищется по той же разметке, которой строка рисуется, поэтому
+    // пропорциональные шрифты, табуляции и широкие символы учитываются сами
+    fn position_at(&self, ctx: &Context, editor: &Editor, wrap: &WrapLayout, point: Pos2) -> Position {
+        let lines = editor.get_buffer().get_all_lines();
+        let row = ((point.y - self.origin.y) / self.row_height).floor().max(0.0) as usize;
+        let row = row.min(wrap.row_count(lines.len()) - 1);
+        let (line, segment) = wrap.row_position(row);
+        let line = line.min(lines.len() - 1);
+        let text = &lines[line];
+        let (start, end) = wrap.segment_range(line, segment, text);
+        let piece = text.get(start..end).unwrap_or_default();
+
+        let color = ctx.style().visuals.text_color();
+        let galley = ctx.fonts(|fonts| fonts.layout_no_wrap(piece.to_string(), editor_font(), color));
+        let x = point.x - self.origin.x - self.gutter - wrap.indent(line, segment);
+        let cursor = galley.cursor_from_pos(vec2(x, self.row_height / 2.0));
+        let column = start + byte_column(piece, cursor.ccursor.index);
+        // Конец перенесенного отрезка - это начало следующей строки экрана,
+        // поэтому щелчок за концом отрезка ставит курсор перед последним символом
+        let column = if column == end && end < text.len() { prev_boundary(text, end).max(start) } else { column };
+        Position { line, column }
+    }
+
+    // Прямоугольник курсора в позиции
+    fn caret_rect(&self, ctx: &Context, editor: &Editor, wrap: &WrapLayout, position: Position) -> Rect {
+        let lines = editor.get_buffer().get_all_lines();
+        let text = lines.get(position.line).map(String::as_str).unwrap_or("");
+        let segment = wrap.segment_of(position.line, position.column);
+        let (start, _) = wrap.segment_range(position.line, segment, text);
+        let head = text.get(start..position.column).unwrap_or_default();
+        let color = ctx.style().visuals.text_color();
+        let x = ctx.fonts(|fonts| fonts.layout_no_wrap(head.to_string(), editor_font(), color).size().x);
+        let row = wrap.first_row(position.line) + segment;
+        Rect::from_min_size(
+            pos2(self.origin.x + self.gutter + wrap.indent(position.line, segment) + x, self.origin.y + row as f32 * self.row_height),
+            vec2(1.0, self.row_height),
+        )
+    }
+}
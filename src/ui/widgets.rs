@@ -1,11 +1,25 @@
-use egui::{Color32, RichText, Ui, Button, Label};
+use egui::{pos2, vec2, Button, Color32, FontId, Label, Rect, Response, RichText, Sense, Stroke, Ui, Visuals};
+use egui::text::{CCursor, LayoutJob, TextFormat};
 
-use crate::editor::Position;
+use crate::editor::{Command, Position};
+use crate::syntax::{Span, Style};
+
+// Размер шрифта текста и номеров строк
+pub const FONT_SIZE: f32 = 14.0;
+
+// Моноширинный шрифт редактора
+pub fn editor_font() -> FontId {
+    FontId::monospace(FONT_SIZE)
+}
 
 pub struct TextLine {
     text: String,
     line_number: usize,
     cursor_pos: Option<Position>,
+    selection: Option<(usize, usize, bool)>, // байты начала и конца, выделен ли перевод строки
+    indent: f32,                             // сдвиг текста вправо (продолжение перенесенной строки)
+    spans: Vec<Span>,                        // подсветка синтаксиса
+    marks: Vec<usize>,                       // столбцы символов в рамке (парные скобки)
 }
 
 impl TextLine {
@@ -14,19 +28,129 @@ impl TextLine {
             text,
             line_number,
             cursor_pos,
+            selection: None,
+            indent: 0.0,
+            spans: Vec::new(),
+            marks: Vec::new(),
         }
     }
 
-    pub fn draw(&self, ui: &mut Ui) {
-        // TODO: Реализовать отрисовку строки
-        // 1. Отрисовать номер строки
-        // 2. Отрисовать текст
-        // 3. Отрисовать курсор если есть
-        // 4. Отрисовать выделение если есть
-        todo!()
+    // Выделенная часть строки (столбцы в байтах); newline - выделение продолжается на следующей строке
+    pub fn with_selection(mut self, start: usize, end: usize, newline: bool) -> Self {
+        self.selection = Some((start, end, newline));
+        self
+    }
+
+    // Сдвиг текста вправо в точках
+    pub fn with_indent(mut self, indent: f32) -> Self {
+        self.indent = indent;
+        self
+    }
+
+    // Отрезки подсветки (столбцы в байтах, по возрастанию)
+    pub fn with_spans(mut self, spans: Vec<Span>) -> Self {
+        self.spans = spans;
+        self
+    }
+
+    // Символы, обводимые рамкой (столбцы в байтах)
+    pub fn with_marks(mut self, marks: Vec<usize>) -> Self {
+        self.marks = marks;
+        self
+    }
+
+    pub fn draw(&self, ui: &mut Ui) -> Response {
+        let font = editor_font();
+        let color = ui.visuals().text_color();
+        let galley = if self.spans.is_empty() {
+            ui.fonts(|fonts| fonts.layout_no_wrap(self.text.clone(), font.clone(), color))
+        } else {
+            // Промежутки между отрезками - обычным цветом
+            let mut job = LayoutJob::default();
+            let mut position = 0;
+            for span in self.spans.iter() {
+                let (Some(gap), Some(piece)) = (self.text.get(position..span.start), self.text.get(span.start..span.end)) else {
+                    continue;
+                };
+                job.append(gap, 0.0, TextFormat::simple(font.clone(), color));
+                job.append(piece, 0.0, TextFormat::simple(font.clone(), style_color(span.style, ui.visuals())));
+                position = span.end;
+            }
+            job.append(&self.text[position..], 0.0, TextFormat::simple(font.clone(), color));
+            ui.fonts(|fonts| fonts.layout_job(job))
+        };
+        let row_height = ui.fonts(|fonts| fonts.row_height(&font));
+        let char_width = ui.fonts(|fonts| fonts.glyph_width(&font, ' '));
+
+        // Строка занимает всю ширину, чтобы по ней можно было щелкнуть за концом текста
+        let width = (self.indent + galley.size().x + char_width).max(ui.available_width());
+        let rect = ui.allocate_space(vec2(width, row_height)).1;
+        // Мышь обрабатывает область редактирования целиком, строка только показывается
+        let response = ui.interact(rect, ui.id().with(self.line_number), Sense::hover());
+
+        // Горизонтальная позиция байтового столбца
+        let x_of = |column: usize| {
+            let index = self.text.get(..column.min(self.text.len())).map_or(0, |head| head.chars().count());
+            rect.left() + self.indent + galley.pos_from_cursor(&galley.from_ccursor(CCursor::new(index))).min.x
+        };
+
+        let painter = ui.painter();
+        // 1. Выделение под текстом
+        if let Some((start, end, newline)) = self.selection {
+            let right = x_of(end) + if newline { char_width } else { 0.0 };
+            let selection = Rect::from_min_max(pos2(x_of(start), rect.top()), pos2(right, rect.bottom()));
+            painter.rect_filled(selection, 0.0, ui.visuals().selection.bg_fill);
+        }
+        // Рамка вокруг парных скобок
+        for &column in self.marks.iter() {
+            let end = self.text.get(column..).and_then(|rest| rest.chars().next()).map_or(column, |c| column + c.len_utf8());
+            let mark = Rect::from_min_max(pos2(x_of(column), rect.top()), pos2(x_of(end), rect.bottom()));
+            painter.rect_stroke(mark, 1.0, Stroke::new(1.0, ui.visuals().weak_text_color()));
+        }
+        // 2. Текст
+        painter.galley(rect.min + vec2(self.indent, 0.0), galley.clone());
+        // 3. Курсор
+        if let Some(cursor) = self.cursor_pos {
+            let x = x_of(cursor.column);
+            let stroke = Stroke::new(2.0, ui.visuals().text_cursor.color);
+            painter.line_segment([pos2(x, rect.top()), pos2(x, rect.bottom())], stroke);
+        }
+        response
     }
 }
 
+// Цвет стиля подсветки для темной или светлой темы
+pub fn style_color(style: Style, visuals: &Visuals) -> Color32 {
+    let dark = visuals.dark_mode;
+    let pick = |dark_color: (u8, u8, u8), light_color: (u8, u8, u8)| {
+        let (r, g, b) = if dark { dark_color } else { light_color };
+        Color32::from_rgb(r, g, b)
+    };
+    match style {
+        Style::Plain => visuals.text_color(),
+        Style::Keyword => pick((198, 120, 221), (160, 30, 160)),
+        Style::Type => pick((229, 192, 123), (150, 100, 0)),
+        Style::Function => pick((97, 175, 239), (20, 90, 190)),
+        Style::String => pick((152, 195, 121), (40, 130, 40)),
+        Style::Number | Style::Constant => pick((209, 154, 102), (170, 80, 0)),
+        Style::Comment => pick((120, 125, 135), (120, 120, 120)),
+        Style::Operator | Style::Punctuation => pick((171, 178, 191), (80, 80, 80)),
+        Style::Property => pick((224, 108, 117), (180, 40, 50)),
+        Style::Attribute => pick((86, 182, 194), (0, 120, 130)),
+        Style::Heading => pick((224, 108, 117), (180, 40, 50)),
+        Style::Bracket(depth) => {
+            let (dark_color, light_color) = BRACKET_COLORS[depth % BRACKET_COLORS.len()];
+            pick(dark_color, light_color)
+        }
+    }
+}
+
+// Цвета скобок по глубине вложенности (темная и светлая тема), дальше повторяются
+const BRACKET_COLORS: [(Rgb, Rgb); 3] =
+    [((255, 215, 0), (175, 130, 0)), ((218, 112, 214), (150, 40, 150)), ((23, 159, 255), (0, 90, 190))];
+
+type Rgb = (u8, u8, u8);
+
 pub struct LineNumber {
     number: usize,
 }
@@ -36,14 +160,65 @@ impl LineNumber {
         Self { number }
     }
 
-    pub fn draw(&self, ui: &mut Ui) {
-        // TODO: Реализовать отрисовку номера строки
-        // 1. Отрисовать номер в сером цвете
-        // 2. Добавить отступ
-        // 3. Выровнять по правому краю
-        todo!()
+    // Ширина колонки номеров: цифры и отступ справа в два символа
+    pub fn width(ui: &Ui, digits: usize) -> f32 {
+        let char_width = ui.fonts(|fonts| fonts.glyph_width(&editor_font(), '0'));
+        (digits + 2) as f32 * char_width
+    }
+
+    // Рисует номер в rect, выравнивая по правому краю; номер текущей строки ярче
+    pub fn paint(&self, ui: &Ui, rect: Rect, current: bool) {
+        let font = editor_font();
+        let color = if current { ui.visuals().strong_text_color() } else { Color32::GRAY };
+        let galley = ui.fonts(|fonts| fonts.layout_no_wrap(self.number.to_string(), font.clone(), color));
+        let char_width = ui.fonts(|fonts| fonts.glyph_width(&font, '0'));
+        let left = rect.right() - 2.0 * char_width - galley.size().x;
+        ui.painter().galley(pos2(left, rect.top()), galley);
     }
 }
 
+// Список истории буфера обмена для выбора вставляемого фрагмента
+pub struct ClipboardPicker {
+    entries: Vec<String>, // фрагменты, самый новый первым
+}
 
+// Сколько символов фрагмента показывать в списке
+const PREVIEW_LENGTH: usize = 60;
 
+impl ClipboardPicker {
+    pub fn new(entries: Vec<String>) -> Self {
+        Self { entries }
+    }
+
+    // Рисует список и возвращает команду вставки выбранного фрагмента:
+    // обычную или построчно по курсорам
+    pub fn draw(&self, ui: &mut Ui) -> Option<Command> {
+        if self.entries.is_empty() {
+            ui.add(Label::new(RichText::new("История пуста").color(Color32::GRAY)));
+            return None;
+        }
+        let mut chosen = None;
+        for (index, entry) in self.entries.iter().enumerate() {
+            ui.horizontal(|ui| {
+                if ui.add(Button::new(preview(entry))).clicked() {
+                    chosen = Some(Command::PasteHistory(index));
+                }
+                let distribute = ui.small_button("⇶").on_hover_text("Вставить по курсорам: каждому курсору своя строка");
+                if distribute.clicked() {
+                    chosen = Some(Command::PasteDistributed(index));
+                }
+            });
+        }
+        chosen
+    }
+}
+
+// Начало фрагмента в одну строку для списков в меню
+pub fn preview(text: &str) -> String {
+    // Переводы строк заменяются, чтобы каждый фрагмент занимал одну строку
+    let mut preview: String = text.chars().take(PREVIEW_LENGTH).collect();
+    if text.chars().count() > PREVIEW_LENGTH {
+        preview.push('…');
+    }
+    preview.replace('\n', "⏎")
+}
@@ -1,45 +1,1025 @@
-use anyhow::Result;
-use egui::{CentralPanel, Context, TopBottomPanel, Window};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
-use crate::editor::Editor;
+use anyhow::{anyhow, Result};
+use egui::{CentralPanel, Context, Key, TopBottomPanel, ViewportCommand};
+
+use crate::editor::{CaseStyle, Command, Editor, IndentStyle, JournalFormat, Macros, Registers, SortOrder};
+use crate::syntax::{Language, LanguageConfig};
+use crate::ui::document::Document;
+use crate::ui::gutter::{GutterConfig, NumberMode};
+use crate::ui::keyboard::{self, WindowAction, Keyboard, Keymap};
+use crate::ui::view::ViewOptions;
+use crate::ui::widgets::{preview, ClipboardPicker};
+use crate::ui::wrap::WrapMode;
+
+// Название окна без открытого файла
+const APP_NAME: &str = "Simple Text Editor";
+
+// Сколько уровней предлагает пункт "Свернуть до уровня"
+const FOLD_LEVELS: usize = 5;
+
+// Сколько ждать внешнюю команду, через которую пропускается текст
+const FILTER_TIMEOUT: Duration = Duration::from_secs(30);
+
+// Больше повторов макроса за раз не делается
+const MAX_MACRO_COUNT: usize = 10_000;
+
+// Что закрывается после ответа на вопрос о несохраненных правках
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Closing {
+    Document, // текущий документ
+    Window,   // окно со всеми документами
+}
 
 pub struct App {
-    editor: Editor,
+    documents: Vec<Document>, // открытые документы, всегда хотя бы один
+    active: usize,            // показываемый документ
+    next_id: usize,           // номер для следующего документа
+    title: String,            // текущий заголовок окна
     show_menu: bool,
+    keyboard: Keyboard,
+    options: ViewOptions,
+    languages: LanguageConfig,       // настройки языков
+    scroll_to_cursor: bool,          // прокрутить к курсору после ввода
+    status: Option<String>,          // последняя ошибка для строки состояния
+    filter_prompt: Option<String>,   // открытое окно ввода внешней команды и ее текст
+    last_filter: String,             // последняя выполненная внешняя команда
+    macro_prompt: Option<String>,    // окно ввода имени записанного макроса и его текст
+    macro_editor: Option<(String, String)>, // редактируемый макрос: имя и текст
+    last_macro: Option<String>,      // последний записанный или воспроизведенный макрос
+    macro_count: usize,              // сколько раз подряд воспроизводить макрос
+    repeat_prompt: Option<String>,   // окно числа повторов последнего макроса (Ctrl+Shift+P)
+    journal: Option<(PathBuf, JournalFormat)>, // каталог и формат журналов правок, если они включены
+    closing: Option<Closing>,        // открыт вопрос о сохранении перед закрытием
+    exit_confirmed: bool,            // окно можно закрыть, не спрашивая о несохраненных правках
+    registers: Arc<Mutex<Registers>>, // именованные регистры, общие для всех документов
+    macros: Arc<Mutex<Macros>>,       // сохраненные макросы, тоже общие
 }
 
 impl App {
-    pub fn new() -> Result<Self> {
-        Ok(Self {
-            editor: Editor::new(),
+    // journal - каталог и формат журналов правок: у каждого документа свой файл
+    pub fn new(journal: Option<(PathBuf, JournalFormat)>) -> Result<Self> {
+        if let Some((directory, _)) = &journal {
+            fs::create_dir_all(directory)
+                .map_err(|e| anyhow!("Не удалось создать каталог журналов {}: {}", directory.display(), e))?;
+        }
+        let registers = Arc::new(Mutex::new(Registers::load_default()));
+        let macros = Arc::new(Mutex::new(Macros::load_default()));
+        let mut app = Self {
+            documents: vec![Document::new(0, Editor::new(Arc::clone(&registers), Arc::clone(&macros)))],
+            active: 0,
+            next_id: 1,
+            title: APP_NAME.to_string(),
             show_menu: true,
-        })
+            keyboard: Keyboard::new(),
+            options: ViewOptions { gutter: GutterConfig::load_default(), wrap: WrapMode::Off, rainbow_brackets: false },
+            languages: LanguageConfig::load_default(),
+            scroll_to_cursor: false,
+            status: None,
+            filter_prompt: None,
+            last_filter: String::new(),
+            macro_prompt: None,
+            macro_editor: None,
+            last_macro: None,
+            macro_count: 1,
+            repeat_prompt: None,
+            journal,
+            closing: None,
+            exit_confirmed: false,
+            registers,
+            macros,
+        };
+        app.start_journal(0, 0);
+        Ok(app)
+    }
+
+    // Запускает главный цикл; возвращается после закрытия окна
+    pub fn run(self) -> Result<()> {
+        let options = eframe::NativeOptions::default();
+        eframe::run_native(APP_NAME, options, Box::new(|_creation_context| Box::new(self)))
+            .map_err(|e| anyhow!("Ошибка главного цикла: {}", e))
     }
 
-    pub fn run(&mut self) -> Result<()> {
-        // TODO: Реализовать главный цикл приложения
-        // 1. Создать окно
-        // 2. Отрисовать меню
-        // 3. Отрисовать область редактирования
-        // 4. Обработать события
-        todo!()
+    // Переводит события клавиатуры в команды редактора
+    fn handle_input(&mut self, ctx: &Context) {
+        // Клавиатура занята другим виджетом (например, полем в меню) или открыт вопрос о сохранении
+        if ctx.wants_keyboard_input() || self.closing.is_some() {
+            return;
+        }
+        self.keyboard.set_page_lines(self.document().view.page_lines());
+
+        let events = ctx.input(|input| input.events.clone());
+        for event in events.iter() {
+            let document = &mut self.documents[self.active];
+            match self.keyboard.handle_event(&mut document.editor, document.view.wrap(), event) {
+                Ok(Some(action)) => self.window_action(ctx, action),
+                Ok(None) => {}
+                Err(e) => self.status = Some(e.to_string()),
+            }
+            if matches!(event, egui::Event::Text(_) | egui::Event::Key { pressed: true, .. } | egui::Event::Paste(_) | egui::Event::Cut) {
+                self.scroll_to_cursor = true;
+            }
+        }
     }
 
     fn draw_menu(&mut self, ctx: &Context) {
-        // TODO: Реализовать отрисовку меню
-        // 1. Создать меню File (New, Open, Save)
-        // 2. Создать меню Edit (Copy, Paste, Cut)
-        // 3. Создать меню View (Settings)
-        todo!()
+        if !self.show_menu {
+            return;
+        }
+        TopBottomPanel::top("menu").show(ctx, |ui| {
+            egui::menu::bar(ui, |ui| {
+                ui.menu_button("Файл", |ui| {
+                    let actions = [
+                        ("Новый", WindowAction::New),
+                        ("Открыть…", WindowAction::Open),
+                        ("Сохранить", WindowAction::Save),
+                        ("Сохранить как…", WindowAction::SaveAs),
+                        ("Закрыть", WindowAction::Close),
+                    ];
+                    for (label, action) in actions {
+                        if ui.button(label).clicked() {
+                            ui.close_menu();
+                            self.window_action(ctx, action);
+                        }
+                    }
+                    ui.separator();
+                    if ui.button("Выход").clicked() {
+                        self.window_action(ctx, WindowAction::Exit);
+                    }
+                });
+
+                ui.menu_button("Правка", |ui| {
+                    let commands = [
+                        ("Отменить", Command::Undo),
+                        ("Повторить", Command::Redo),
+                        ("Вырезать", Command::Cut),
+                        ("Копировать", Command::Copy),
+                        ("Вставить", Command::Paste),
+                    ];
+                    for (index, (label, command)) in commands.into_iter().enumerate() {
+                        if index == 2 {
+                            ui.separator();
+                        }
+                        if ui.button(label).clicked() {
+                            ui.close_menu();
+                            self.execute(command);
+                        }
+                    }
+                    ui.menu_button("Вставить из истории", |ui| {
+                        let picker = ClipboardPicker::new(self.document_mut().editor.clipboard_history());
+                        if let Some(command) = picker.draw(ui) {
+                            ui.close_menu();
+                            self.execute(command);
+                        }
+                    });
+                    ui.menu_button("Именованные регистры", |ui| self.draw_registers_menu(ui));
+                    ui.menu_button("Курсоры", |ui| self.draw_cursors_menu(ui));
+                    ui.separator();
+                    ui.menu_button("Строки", |ui| self.draw_lines_menu(ui));
+                    ui.menu_button("Регистр", |ui| {
+                        for style in CaseStyle::ALL {
+                            if ui.button(style.title()).clicked() {
+                                ui.close_menu();
+                                self.execute(Command::ConvertCase(style));
+                            }
+                        }
+                    });
+                    if ui.button("Через команду...").clicked() {
+                        ui.close_menu();
+                        self.filter_prompt = Some(self.last_filter.clone());
+                    }
+                    ui.menu_button("Преобразовать", |ui| {
+                        let transforms: Vec<_> = self.document().editor.transforms().iter().cloned().collect();
+                        for transform in transforms {
+                            if ui.button(&transform.title).clicked() {
+                                ui.close_menu();
+                                self.execute(Command::Transform(transform.name));
+                            }
+                        }
+                    });
+                });
+
+                ui.menu_button("Макросы", |ui| self.draw_macro_menu(ui));
+
+                ui.menu_button("Вид", |ui| {
+                    ui.menu_button("Поля слева", |ui| self.draw_gutter_menu(ui));
+                    ui.menu_button("Сворачивание", |ui| {
+                        let folding = self.document_mut().editor.folding_mut();
+                        if ui.button("Свернуть всё").clicked() {
+                            ui.close_menu();
+                            folding.fold_all();
+                        }
+                        if ui.button("Развернуть всё").clicked() {
+                            ui.close_menu();
+                            folding.unfold_all();
+                        }
+                        ui.menu_button("Свернуть до уровня", |ui| {
+                            for level in 1..=FOLD_LEVELS {
+                                if ui.button(level.to_string()).clicked() {
+                                    ui.close_menu();
+                                    folding.fold_to_level(level);
+                                }
+                            }
+                        });
+                    });
+                    ui.menu_button("Перенос строк", |ui| {
+                        // Столбец переноса берется из настроек языка текущего документа
+                        let wrap_column = self.document().settings.wrap_column;
+                        if let WrapMode::Column(_) = self.options.wrap {
+                            self.options.wrap = WrapMode::Column(wrap_column);
+                        }
+                        let column_label = format!("По {}-му столбцу", wrap_column);
+                        let modes = [
+                            ("Без переноса", WrapMode::Off),
+                            ("По краю окна", WrapMode::Window),
+                            (column_label.as_str(), WrapMode::Column(wrap_column)),
+                        ];
+                        for (label, mode) in modes {
+                            if ui.radio_value(&mut self.options.wrap, mode, label).clicked() {
+                                ui.close_menu();
+                                self.scroll_to_cursor = true;
+                            }
+                        }
+                    });
+                    if ui.checkbox(&mut self.options.rainbow_brackets, "Раскрашивать пары скобок").clicked() {
+                        ui.close_menu();
+                    }
+                    ui.menu_button("Клавиши", |ui| {
+                        for keymap in Keymap::ALL {
+                            if ui.radio(self.keyboard.keymap() == keymap, keymap.title()).clicked() {
+                                ui.close_menu();
+                                self.keyboard.set_keymap(keymap);
+                            }
+                        }
+                    });
+                });
+            });
+        });
+    }
+
+    // Построчные команды для строк курсоров или выделения
+    // Копирование и вырезание в регистры a-z, вставка из непустых
+    fn draw_registers_menu(&mut self, ui: &mut egui::Ui) {
+        for (label, cut) in [("Копировать в", false), ("Вырезать в", true)] {
+            ui.menu_button(label, |ui| {
+                ui.horizontal_wrapped(|ui| {
+                    for name in 'a'..='z' {
+                        if ui.small_button(name.to_string()).clicked() {
+                            ui.close_menu();
+                            self.execute(if cut { Command::CutToRegister(name) } else { Command::CopyToRegister(name) });
+                        }
+                    }
+                });
+            });
+        }
+        ui.menu_button("Вставить из", |ui| {
+            let editor = &self.document().editor;
+            let registers: Vec<_> = editor
+                .register_names()
+                .into_iter()
+                .filter_map(|name| editor.register(name).map(|register| (name, register.text)))
+                .collect();
+            if registers.is_empty() {
+                ui.label(egui::RichText::new("Регистры пусты").color(egui::Color32::GRAY));
+            }
+            for (name, text) in registers {
+                if ui.button(format!("{}: {}", name, preview(&text))).clicked() {
+                    ui.close_menu();
+                    self.execute(Command::PasteRegister(name));
+                }
+            }
+        });
+    }
+
+    fn draw_cursors_menu(&mut self, ui: &mut egui::Ui) {
+        if ui.button("Добавить выше (Ctrl+Alt+↑)").clicked() {
+            ui.close_menu();
+            self.add_cursor_vertically(false);
+        }
+        if ui.button("Добавить ниже (Ctrl+Alt+↓)").clicked() {
+            ui.close_menu();
+            self.add_cursor_vertically(true);
+        }
+        if ui.button("Оставить один (Esc)").clicked() {
+            ui.close_menu();
+            self.execute(Command::ClearCursors);
+        }
+    }
+
+    fn add_cursor_vertically(&mut self, down: bool) {
+        if let Err(e) = keyboard::add_cursor_vertically(&mut self.document_mut().editor, down) {
+            self.status = Some(e.to_string());
+        }
+    }
+
+    fn draw_lines_menu(&mut self, ui: &mut egui::Ui) {
+        let commands = [
+            ("Копировать ниже", Command::DuplicateLines),
+            ("Перенести вверх", Command::MoveLinesUp),
+            ("Перенести вниз", Command::MoveLinesDown),
+            ("Удалить", Command::DeleteLines),
+            ("Объединить", Command::JoinLines),
+            ("Строчный комментарий", Command::ToggleLineComment),
+            ("Блочный комментарий", Command::ToggleBlockComment),
+        ];
+        for (label, command) in commands {
+            if ui.button(label).clicked() {
+                ui.close_menu();
+                self.execute(command);
+            }
+        }
+        ui.separator();
+        ui.menu_button("Сортировать", |ui| {
+            for order in SortOrder::ALL {
+                if ui.button(order.title()).clicked() {
+                    ui.close_menu();
+                    self.execute(Command::SortLines(order));
+                }
+            }
+        });
+        // Начальное значение генератора берется из часов, а в журнал попадает в команде
+        let seed = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |time| time.as_nanos() as u64);
+        let commands = [
+            ("Обратный порядок", Command::ReverseLines),
+            ("Удалить повторы", Command::UniqueLines),
+            ("Перемешать", Command::ShuffleLines(seed)),
+        ];
+        for (label, command) in commands {
+            if ui.button(label).clicked() {
+                ui.close_menu();
+                self.execute(command);
+            }
+        }
+    }
+
+    // Запись, воспроизведение и правка макросов текущего документа
+    fn draw_macro_menu(&mut self, ui: &mut egui::Ui) {
+        let recording = self.document().editor.is_recording_macro();
+        let label = if recording { "Закончить запись…" } else { "Начать запись" };
+        if ui.button(label).clicked() {
+            ui.close_menu();
+            if let Err(e) = self.record_macro() {
+                self.status = Some(e.to_string());
+            }
+        }
+        if recording && ui.button("Прервать запись").clicked() {
+            ui.close_menu();
+            self.document_mut().editor.cancel_macro_recording();
+        }
+        let names = self.document().editor.macro_names();
+        if names.is_empty() {
+            return;
+        }
+        ui.separator();
+        let menus = [("Воспроизвести", false), ("На каждой строке выделения", true)];
+        for (title, on_selection) in menus {
+            ui.menu_button(title, |ui| {
+                if !on_selection {
+                    ui.horizontal(|ui| {
+                        ui.label("Повторов:");
+                        ui.add(egui::DragValue::new(&mut self.macro_count).clamp_range(1..=MAX_MACRO_COUNT));
+                    });
+                    ui.separator();
+                }
+                for name in names.iter() {
+                    if ui.button(name).clicked() {
+                        ui.close_menu();
+                        let count = if on_selection { None } else { Some(self.macro_count) };
+                        if let Err(e) = self.play_macro(name, count) {
+                            self.status = Some(e.to_string());
+                        }
+                    }
+                }
+            });
+        }
+        ui.menu_button("Изменить", |ui| {
+            for name in names.iter() {
+                if ui.button(name).clicked() {
+                    ui.close_menu();
+                    let text = self.document().editor.macro_text(name).unwrap_or_default();
+                    self.macro_editor = Some((name.clone(), text));
+                }
+            }
+        });
+    }
+
+    // Начинает запись макроса; если он уже записывается - спрашивает имя для сохранения
+    fn record_macro(&mut self) -> Result<()> {
+        if self.document().editor.is_recording_macro() {
+            self.macro_prompt = Some(self.last_macro.clone().unwrap_or_default());
+            return Ok(());
+        }
+        self.document_mut().editor.start_macro_recording()
+    }
+
+    // Воспроизводит макрос count раз от строки курсора, а без count - на каждой строке выделения
+    fn play_macro(&mut self, name: &str, count: Option<usize>) -> Result<()> {
+        self.last_macro = Some(name.to_string());
+        self.scroll_to_cursor = true;
+        let editor = &mut self.document_mut().editor;
+        match count {
+            Some(count) => editor.play_macro(name, count),
+            None => editor.play_macro_on_selection(name),
+        }
+    }
+
+    // Окно числа повторов последнего макроса; Enter воспроизводит его
+    fn draw_repeat_prompt(&mut self, ctx: &Context) {
+        let (Some(count), Some(name)) = (&mut self.repeat_prompt, self.last_macro.clone()) else { return };
+        let mut open = true;
+        let mut play = false;
+        egui::Window::new(format!("Воспроизвести макрос {}", name))
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.label("Сколько раз повторить");
+                let response = ui.text_edit_singleline(count);
+                response.request_focus();
+                play = response.lost_focus() && ui.input(|input| input.key_pressed(Key::Enter));
+                play |= ui.button("Воспроизвести").clicked();
+            });
+        if !open || ctx.input(|input| input.key_pressed(Key::Escape)) {
+            self.repeat_prompt = None;
+        } else if play {
+            match count.trim().parse::<usize>() {
+                Ok(count) if (1..=MAX_MACRO_COUNT).contains(&count) => {
+                    self.repeat_prompt = None;
+                    self.macro_count = count;
+                    if let Err(e) = self.play_macro(&name, Some(count)) {
+                        self.status = Some(e.to_string());
+                    }
+                }
+                _ => self.status = Some(format!("Число повторов - от 1 до {}", MAX_MACRO_COUNT)),
+            }
+        }
+    }
+
+    // Окно ввода имени, под которым сохраняется записанный макрос
+    // Закрытие окна прерывает запись
+    fn draw_macro_prompt(&mut self, ctx: &Context) {
+        let Some(name) = &mut self.macro_prompt else { return };
+        let mut open = true;
+        let mut save = false;
+        egui::Window::new("Сохранить макрос")
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.label("Имя макроса (без пробелов)");
+                let response = ui.text_edit_singleline(name);
+                response.request_focus();
+                save = response.lost_focus() && ui.input(|input| input.key_pressed(Key::Enter));
+                save |= ui.button("Сохранить").clicked();
+            });
+        if !open || ctx.input(|input| input.key_pressed(Key::Escape)) {
+            self.macro_prompt = None;
+            self.document_mut().editor.cancel_macro_recording();
+        } else if save {
+            let name = name.trim().to_string();
+            match self.documents[self.active].editor.stop_macro_recording(&name) {
+                Ok(()) => {
+                    self.macro_prompt = None;
+                    self.last_macro = Some(name);
+                }
+                Err(e) => self.status = Some(e.to_string()),
+            }
+        }
+    }
+
+    // Текст макроса по одной команде на строку; сохраняется после проверки
+    fn draw_macro_editor(&mut self, ctx: &Context) {
+        let Some((name, text)) = &mut self.macro_editor else { return };
+        let mut open = true;
+        let (mut save, mut remove) = (false, false);
+        egui::Window::new(format!("Макрос {}", name)).open(&mut open).collapsible(false).show(ctx, |ui| {
+            ui.add(egui::TextEdit::multiline(text).code_editor().desired_rows(12));
+            ui.horizontal(|ui| {
+                save = ui.button("Сохранить").clicked();
+                remove = ui.button("Удалить").clicked();
+            });
+        });
+        let name = name.clone();
+        let editor = &mut self.documents[self.active].editor;
+        let result = if save {
+            editor.set_macro_text(&name, text)
+        } else if remove {
+            editor.remove_macro(&name)
+        } else {
+            Ok(())
+        };
+        match result {
+            Ok(()) if save || remove || !open => self.macro_editor = None,
+            Ok(()) => {}
+            Err(e) => self.status = Some(e.to_string()),
+        }
+    }
+
+    // Видимость и порядок колонок полей, режим номеров строк
+    // Изменения сразу сохраняются в настройку
+    fn draw_gutter_menu(&mut self, ui: &mut egui::Ui) {
+        let config = &mut self.options.gutter;
+        let before = config.clone();
+        for (id, title) in self.documents[self.active].view.gutter().columns() {
+            ui.horizontal(|ui| {
+                let mut visible = config.is_visible(id);
+                if ui.checkbox(&mut visible, title).changed() {
+                    config.set_visible(id, visible);
+                }
+                if visible {
+                    if ui.small_button("←").on_hover_text("Левее").clicked() {
+                        config.move_column(id, -1);
+                    }
+                    if ui.small_button("→").on_hover_text("Правее").clicked() {
+                        config.move_column(id, 1);
+                    }
+                }
+            });
+        }
+        ui.separator();
+        let modes = [
+            ("Номера по порядку", NumberMode::Absolute),
+            ("Относительные номера", NumberMode::Relative),
+            ("Номер строки с курсором и относительные", NumberMode::Hybrid),
+        ];
+        for (label, mode) in modes {
+            ui.radio_value(&mut config.numbers, mode, label);
+        }
+        if *config != before {
+            if let Err(e) = config.save() {
+                self.status = Some(e.to_string());
+            }
+        }
+    }
+
+    // Окно ввода команды оболочки, через которую пропускается выделение
+    fn draw_filter_prompt(&mut self, ctx: &Context) {
+        let Some(command) = &mut self.filter_prompt else { return };
+        let mut open = true;
+        let mut run = false;
+        egui::Window::new("Пропустить через команду")
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.label("Выделение (без него - весь текст) подается на вход команды и заменяется ее выводом");
+                let response = ui.text_edit_singleline(command);
+                response.request_focus();
+                run = response.lost_focus() && ui.input(|input| input.key_pressed(Key::Enter));
+                run |= ui.button("Выполнить").clicked();
+            });
+        if !open || ctx.input(|input| input.key_pressed(Key::Escape)) {
+            self.filter_prompt = None;
+        } else if run && !command.trim().is_empty() {
+            let command = command.trim().to_string();
+            self.filter_prompt = None;
+            self.last_filter = command.clone();
+            let repaint = ctx.clone();
+            if let Err(e) = self.document_mut().start_filter(&command, FILTER_TIMEOUT, move || repaint.request_repaint()) {
+                self.status = Some(e.to_string());
+            }
+        }
+    }
+
+    // Вставляет вывод завершившихся внешних команд
+    fn poll_filters(&mut self) {
+        for document in self.documents.iter_mut() {
+            if let Some(Err(e)) = document.poll_filter() {
+                self.status = Some(e.to_string());
+            }
+        }
+    }
+
+    fn draw_status(&mut self, ctx: &Context) {
+        TopBottomPanel::bottom("status").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                let cursor = self.document().editor.get_cursor_position();
+                ui.label(format!("Стр {}, стлб {}", cursor.line + 1, cursor.column + 1));
+                ui.separator();
+                self.draw_language_picker(ui);
+                ui.separator();
+                self.draw_indent_picker(ui);
+                if self.document().editor.is_recording_macro() {
+                    ui.separator();
+                    ui.colored_label(ui.visuals().error_fg_color, "● Запись макроса");
+                }
+                if let Some(status) = self.keyboard.status() {
+                    ui.separator();
+                    ui.monospace(status);
+                }
+                if let Some(command) = self.document().filter_command() {
+                    ui.separator();
+                    ui.spinner();
+                    ui.label(format!("Выполняется \"{}\"", command));
+                    if ui.small_button("Прервать").clicked() {
+                        self.document().cancel_filter();
+                    }
+                }
+                if let Some(status) = &self.status {
+                    ui.separator();
+                    ui.colored_label(ui.visuals().error_fg_color, status);
+                }
+            });
+        });
+    }
+
+    // Язык документа: определяется автоматически или выбирается вручную
+    fn draw_language_picker(&mut self, ui: &mut egui::Ui) {
+        let document = &mut self.documents[self.active];
+        let current = document.settings.language;
+        let title = |language: Option<Language>| language.map_or("Текст", |language| language.title());
+        let selected = match document.language_fixed() {
+            true => title(current).to_string(),
+            false => format!("{} (авто)", title(current)),
+        };
+        egui::ComboBox::from_id_source("language").selected_text(selected).show_ui(ui, |ui| {
+            if ui.selectable_label(!document.language_fixed(), "Определять автоматически").clicked() {
+                document.detect_language(&self.languages);
+            }
+            ui.separator();
+            let choices = std::iter::once(None).chain(Language::ALL.into_iter().map(Some));
+            for language in choices {
+                let chosen = document.language_fixed() && current == language;
+                if ui.selectable_label(chosen, title(language)).clicked() {
+                    document.set_language(language, &self.languages);
+                }
+            }
+        });
+    }
+
+    // Отступы документа: как в файле или выбранные вручную
+    fn draw_indent_picker(&mut self, ui: &mut egui::Ui) {
+        let document = &mut self.documents[self.active];
+        let title = match document.settings.use_tabs {
+            true => "Табуляция".to_string(),
+            false => format!("Пробелы: {}", document.settings.tab_width),
+        };
+        ui.menu_button(title, |ui| {
+            let fixed = document.indent_fixed();
+            if ui.selectable_label(fixed.is_none(), "Как в файле").clicked() {
+                ui.close_menu();
+                document.set_indent_style(None, &self.languages);
+            }
+            ui.separator();
+            let styles = [
+                ("Табуляция", IndentStyle::Tabs),
+                ("Пробелы: 2", IndentStyle::Spaces(2)),
+                ("Пробелы: 4", IndentStyle::Spaces(4)),
+                ("Пробелы: 8", IndentStyle::Spaces(8)),
+            ];
+            for (label, style) in styles {
+                if ui.selectable_label(fixed == Some(style), label).clicked() {
+                    ui.close_menu();
+                    document.set_indent_style(Some(style), &self.languages);
+                }
+            }
+        });
+    }
+
+    // Вкладки показываются, когда документов больше одного
+    fn draw_tabs(&mut self, ctx: &Context) {
+        if self.documents.len() < 2 {
+            return;
+        }
+        TopBottomPanel::top("tabs").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                let mut chosen = None;
+                for (index, document) in self.documents.iter().enumerate() {
+                    let name = match document.is_modified() {
+                        true => format!("{} *", document.name()),
+                        false => document.name(),
+                    };
+                    if ui.selectable_label(index == self.active, name).clicked() {
+                        chosen = Some(index);
+                    }
+                }
+                if let Some(index) = chosen {
+                    self.active = index;
+                    self.keyboard.reset();
+                }
+            });
+        });
     }
 
     fn draw_editor(&mut self, ctx: &Context) {
-        // TODO: Реализовать отрисовку редактора
-        // 1. Отрисовать номера строк
-        // 2. Отрисовать текст
-        // 3. Отрисовать курсор
-        // 4. Отрисовать выделение
-        todo!()
+        let scroll_to_cursor = std::mem::take(&mut self.scroll_to_cursor);
+        let document = &mut self.documents[self.active];
+        document.update_language(&self.languages);
+        let path = document.file_manager.current_file();
+        let settings = &document.settings;
+        CentralPanel::default().show(ctx, |ui| {
+            if let Err(e) = document.view.show(ui, &mut document.editor, path, settings, &self.options, scroll_to_cursor) {
+                self.status = Some(e.to_string());
+            }
+        });
+    }
+
+    // Заголовок окна по текущему документу; меняется только при изменении
+    fn update_title(&mut self, ctx: &Context) {
+        let modified = if self.document().is_modified() { " *" } else { "" };
+        let title = match self.document().file_manager.current_file() {
+            Some(_) => format!("{}{} - {}", self.document().name(), modified, APP_NAME),
+            None => format!("{}{}", APP_NAME, modified),
+        };
+        if title != self.title {
+            ctx.send_viewport_cmd(ViewportCommand::Title(title.clone()));
+            self.title = title;
+        }
+    }
+
+    // Файлы, перетащенные на окно из файлового менеджера
+    // Обычно они открываются, а с зажатой клавишей (Shift, Ctrl или Alt) их пути
+    // вставляются в текст там, где их отпустили
+    fn handle_dropped_files(&mut self, ctx: &Context) {
+        let (files, modifiers, pointer) = ctx.input(|input| (input.raw.dropped_files.clone(), input.modifiers, input.pointer.hover_pos()));
+        let paths: Vec<PathBuf> = files.into_iter().filter_map(|file| file.path).collect();
+        if paths.is_empty() {
+            return;
+        }
+
+        if modifiers.shift || modifiers.command || modifiers.alt {
+            let document = &mut self.documents[self.active];
+            let position = pointer
+                .and_then(|point| document.view.position_at(ctx, &document.editor, point))
+                .unwrap_or_else(|| document.editor.get_cursor_position());
+            let text = paths.iter().map(|path| path.display().to_string()).collect::<Vec<_>>().join(" ");
+            let result = document.editor.clear_selection().and_then(|()| {
+                document.editor.execute_command(Command::Batch(vec![Command::MoveCursor(position), Command::InsertText(text)]))
+            });
+            if let Err(e) = result {
+                self.status = Some(e.to_string());
+            }
+            return;
+        }
+
+        for path in paths {
+            match self.open_path(&path) {
+                Ok(()) => self.status = None,
+                Err(e) => self.status = Some(e.to_string()),
+            }
+        }
+    }
+
+    fn document(&self) -> &Document {
+        &self.documents[self.active]
+    }
+
+    fn document_mut(&mut self) -> &mut Document {
+        &mut self.documents[self.active]
+    }
+
+    // Выполняет команду из меню
+    fn execute(&mut self, command: Command) {
+        if let Err(e) = self.document_mut().editor.execute_command(command) {
+            self.status = Some(e.to_string());
+        }
+        self.scroll_to_cursor = true;
+    }
+
+    // ---------- Файлы ----------
+
+    fn window_action(&mut self, ctx: &Context, action: WindowAction) {
+        let result = match action {
+            WindowAction::New => {
+                self.new_file();
+                Ok(())
+            }
+            WindowAction::Open => self.open_file(),
+            WindowAction::Save => self.save_file(),
+            WindowAction::SaveAs => self.save_file_as(),
+            WindowAction::SaveTo(path) => self.write_file(path),
+            WindowAction::Close => {
+                self.request_close();
+                Ok(())
+            }
+            WindowAction::Discard => {
+                self.close_file();
+                Ok(())
+            }
+            WindowAction::SaveAndClose => self.save_file().map(|()| {
+                // Диалог сохранения могли закрыть без выбора файла
+                if !self.document().is_modified() {
+                    self.close_file();
+                }
+            }),
+            WindowAction::RecordMacro => self.record_macro(),
+            WindowAction::PlayMacro => match self.last_macro {
+                Some(_) => {
+                    self.repeat_prompt = Some(self.macro_count.to_string());
+                    Ok(())
+                }
+                None => Err(anyhow!("Макрос еще не записан")),
+            },
+            WindowAction::Exit => {
+                ctx.send_viewport_cmd(ViewportCommand::Close);
+                Ok(())
+            }
+        };
+        match result {
+            Ok(()) => self.status = None,
+            Err(e) => self.status = Some(e.to_string()),
+        }
+    }
+
+    // Редактор для нового документа: регистры у всех документов общие
+    fn new_editor(&self) -> Editor {
+        Editor::new(Arc::clone(&self.registers), Arc::clone(&self.macros))
+    }
+
+    // Новый документ становится текущим
+    fn new_file(&mut self) {
+        self.documents.push(Document::new(self.next_id, self.new_editor()));
+        self.active = self.documents.len() - 1;
+        self.start_journal(self.active, self.next_id);
+        self.next_id += 1;
+        self.keyboard.reset();
+    }
+
+    // Начинает журнал правок документа, если журналы включены
+    // Документ с ошибкой журнала открывается без него
+    fn start_journal(&mut self, index: usize, id: usize) {
+        let Some((directory, format)) = &self.journal else { return };
+        let seconds = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |time| time.as_secs());
+        let extension = match format {
+            JournalFormat::Json => "jsonl",
+            JournalFormat::Binary => "journal",
+        };
+        let path = directory.join(format!("{}-{}.{}", seconds, id, extension));
+        if let Err(e) = self.documents[index].editor.start_journal_file(&path, *format) {
+            self.status = Some(e.to_string());
+        }
+    }
+
+    // Закрывает текущий документ, а с несохраненными правками сначала спрашивает
+    fn request_close(&mut self) {
+        match self.document().is_modified() {
+            true => self.closing = Some(Closing::Document),
+            false => self.close_file(),
+        }
+    }
+
+    // Закрытие окна (кнопкой заголовка, "Выход", C-x C-c) отменяется, пока есть
+    // несохраненные документы: сначала задается вопрос
+    fn handle_close_request(&mut self, ctx: &Context) {
+        if self.exit_confirmed || !ctx.input(|input| input.viewport().close_requested()) {
+            return;
+        }
+        if self.documents.iter().any(Document::is_modified) {
+            ctx.send_viewport_cmd(ViewportCommand::CancelClose);
+            self.closing = Some(Closing::Window);
+        }
+    }
+
+    // Вопрос о несохраненных правках: сохранить, не сохранять или отменить закрытие
+    fn draw_close_prompt(&mut self, ctx: &Context) {
+        let Some(closing) = self.closing else { return };
+        let names: Vec<String> = match closing {
+            Closing::Document => vec![self.document().name()],
+            Closing::Window => self.documents.iter().filter(|document| document.is_modified()).map(Document::name).collect(),
+        };
+        let (mut save, mut discard, mut cancel) = (false, false, false);
+        egui::Window::new("Несохраненные изменения")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+            .show(ctx, |ui| {
+                ui.label(format!("Сохранить изменения в {}?", names.join(", ")));
+                ui.horizontal(|ui| {
+                    save = ui.button("Сохранить").clicked();
+                    discard = ui.button("Не сохранять").clicked();
+                    cancel = ui.button("Отмена").clicked();
+                });
+            });
+        cancel |= ctx.input(|input| input.key_pressed(Key::Escape));
+        if !(save || discard || cancel) {
+            return;
+        }
+        self.closing = None;
+        if cancel {
+            return;
+        }
+        if save {
+            if let Err(e) = self.save_closing(closing) {
+                self.status = Some(e.to_string());
+                return;
+            }
+        }
+        match closing {
+            // Диалог сохранения могли закрыть без выбора файла - тогда документ остается
+            Closing::Document if discard || !self.document().is_modified() => self.close_file(),
+            Closing::Window if discard || !self.documents.iter().any(Document::is_modified) => {
+                self.exit_confirmed = true;
+                ctx.send_viewport_cmd(ViewportCommand::Close);
+            }
+            _ => {}
+        }
+    }
+
+    // Сохраняет закрываемый документ или все несохраненные документы окна
+    fn save_closing(&mut self, closing: Closing) -> Result<()> {
+        if closing == Closing::Document {
+            return self.save_file();
+        }
+        for index in 0..self.documents.len() {
+            if self.documents[index].is_modified() {
+                self.active = index;
+                self.save_file()?;
+            }
+        }
+        Ok(())
+    }
+
+    // Закрывает текущий документ; последний заменяется пустым
+    fn close_file(&mut self) {
+        self.documents.remove(self.active);
+        if self.documents.is_empty() {
+            self.new_file();
+        }
+        self.active = self.active.min(self.documents.len() - 1);
+        self.keyboard.reset();
+    }
+
+    fn open_file(&mut self) -> Result<()> {
+        let mut dialog = rfd::FileDialog::new();
+        if let Some(directory) = self.document().file_manager.last_directory() {
+            dialog = dialog.set_directory(directory);
+        }
+        match dialog.pick_file() {
+            Some(path) => self.open_path(&path),
+            None => Ok(()), // диалог закрыт без выбора
+        }
+    }
+
+    // Открывает файл в новом документе
+    // Уже открытый файл просто показывается, пустой новый документ заменяется
+    fn open_path(&mut self, path: &Path) -> Result<()> {
+        if let Some(index) = self.documents.iter().position(|document| document.file_manager.current_file() == Some(path)) {
+            self.active = index;
+            return Ok(());
+        }
+        let document = Document::open(self.next_id, self.new_editor(), path, &self.languages)?;
+        if self.document().is_blank() {
+            self.documents[self.active] = document;
+        } else {
+            self.documents.push(document);
+            self.active = self.documents.len() - 1;
+        }
+        self.start_journal(self.active, self.next_id);
+        self.next_id += 1;
+        self.keyboard.reset();
+        self.scroll_to_cursor = true;
+        Ok(())
+    }
+
+    fn save_file(&mut self) -> Result<()> {
+        match self.document().file_manager.current_file().map(PathBuf::from) {
+            Some(path) => self.write_file(path),
+            None => self.save_file_as(),
+        }
+    }
+
+    fn save_file_as(&mut self) -> Result<()> {
+        let file_manager = &self.document().file_manager;
+        let mut dialog = rfd::FileDialog::new();
+        if let Some(directory) = file_manager.last_directory() {
+            dialog = dialog.set_directory(directory);
+        }
+        if let Some(name) = file_manager.current_file().and_then(|path| path.file_name()) {
+            dialog = dialog.set_file_name(&name.to_string_lossy());
+        }
+        match dialog.save_file() {
+            Some(path) => self.write_file(path),
+            None => Ok(()),
+        }
+    }
+
+    fn write_file(&mut self, path: PathBuf) -> Result<()> {
+        let document = self.document_mut();
+        let text = document.editor.get_text()?;
+        document.file_manager.save_file(&path, &text)?;
+        document.mark_saved();
+        Ok(())
     }
 }
 
+impl eframe::App for App {
+    fn update(&mut self, ctx: &Context, _frame: &mut eframe::Frame) {
+        self.handle_close_request(ctx);
+        self.handle_dropped_files(ctx);
+        self.poll_filters();
+        self.handle_input(ctx);
+        self.draw_menu(ctx);
+        self.draw_tabs(ctx);
+        self.draw_status(ctx);
+        self.draw_editor(ctx);
+        self.draw_filter_prompt(ctx);
+        self.draw_macro_prompt(ctx);
+        self.draw_repeat_prompt(ctx);
+        self.draw_macro_editor(ctx);
+        self.draw_close_prompt(ctx);
+        self.update_title(ctx);
+    }
+}
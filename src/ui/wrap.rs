@@ -0,0 +1,238 @@
+// Перенос длинных строк
+// Слой между Buffer и TextLine: делит строки документа на отрезки, каждый из
+// которых показывается отдельной строкой экрана. Отрезки хранятся для каждой
+// строки документа, и после правки пересчитываются только изменившиеся строки.
+// Строки внутри свернутых областей не занимают строк экрана
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+use egui::epaint::text::Fonts;
+use egui::Context;
+
+use crate::editor::{Buffer, Folding};
+use crate::ui::widgets::editor_font;
+
+// Где переносить строки
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum WrapMode {
+    Off,
+    Window,        // по краю окна
+    Column(usize), // после заданного числа символов
+}
+
+// Отрезки одной строки документа
+struct LineWrap {
+    hash: u64,          // по нему видно, изменилась ли строка
+    starts: Vec<usize>, // байтовые начала отрезков, первый всегда 0
+    indent: f32,        // отступ продолжений, равный отступу самой строки
+}
+
+// Без переноса у каждой строки один отрезок
+const SINGLE: &[usize] = &[0];
+
+pub struct WrapLayout {
+    mode: WrapMode,
+    width: f32,             // ширина, под которую посчитаны отрезки
+    tab_width: usize,       // ширина табуляции в пробелах, с которой посчитаны отрезки
+    revision: Option<u64>,  // номер правки буфера, для которого посчитаны отрезки
+    folds: Option<u64>,     // состояние сворачивания, для которого посчитаны строки экрана
+    identity: bool,         // без переноса и сворачивания строка экрана - это строка документа
+    lines: Vec<LineWrap>,   // без переноса пусто
+    first_rows: Vec<usize>, // первая строка экрана каждой строки документа; последний элемент - всего строк экрана
+}
+
+impl WrapLayout {
+    pub fn new() -> Self {
+        Self {
+            mode: WrapMode::Off,
+            width: 0.0,
+            tab_width: 0,
+            revision: None,
+            folds: None,
+            identity: true,
+            lines: Vec::new(),
+            first_rows: vec![0],
+        }
+    }
+
+    // Приводит отрезки в соответствие с буфером и сворачиванием
+    // width - ширина текста на экране (для переноса по краю окна)
+    pub fn update(&mut self, ctx: &Context, buffer: &Buffer, mode: WrapMode, width: f32, folding: &Folding) {
+        let font = editor_font();
+        let char_width = ctx.fonts(|fonts| fonts.glyph_width(&font, ' '));
+        let width = match mode {
+            WrapMode::Off => 0.0,
+            WrapMode::Window => width,
+            WrapMode::Column(columns) => columns as f32 * char_width,
+        }
+        .max(8.0 * char_width);
+
+        // Другой режим, ширина или табуляция - пересчитывается все
+        let tab_width = buffer.tab_settings().width;
+        if mode != self.mode || (width - self.width).abs() > 0.5 || tab_width != self.tab_width {
+            self.mode = mode;
+            self.width = width;
+            self.tab_width = tab_width;
+            self.lines.clear();
+            self.revision = None;
+        }
+        self.identity = mode == WrapMode::Off && !folding.has_folds();
+        if self.identity {
+            self.revision = None;
+            self.folds = None;
+            self.first_rows = vec![0];
+            return;
+        }
+        if self.revision == Some(buffer.revision()) && self.folds == Some(folding.generation()) {
+            return;
+        }
+
+        let lines = buffer.get_all_lines();
+        if mode != WrapMode::Off && self.revision != Some(buffer.revision()) {
+            // Правка меняет строки подряд: общие начало и конец со старой разметкой остаются
+            let common = self.lines.len().min(lines.len());
+            let mut prefix = 0;
+            while prefix < common && self.lines[prefix].hash == hash_line(&lines[prefix]) {
+                prefix += 1;
+            }
+            let mut suffix = 0;
+            while suffix < common - prefix
+                && self.lines[self.lines.len() - 1 - suffix].hash == hash_line(&lines[lines.len() - 1 - suffix])
+            {
+                suffix += 1;
+            }
+
+            let changed = prefix..lines.len() - suffix;
+            let fresh: Vec<LineWrap> = ctx.fonts(|fonts| {
+                changed.map(|index| wrap_line(fonts, &lines[index], width, tab_width)).collect()
+            });
+            let old_end = self.lines.len() - suffix;
+            self.lines.splice(prefix..old_end, fresh);
+        }
+        self.revision = Some(buffer.revision());
+        self.folds = Some(folding.generation());
+
+        self.first_rows.clear();
+        let mut row = 0;
+        for line in 0..lines.len() {
+            self.first_rows.push(row);
+            if !folding.is_hidden(line) {
+                row += self.starts(line).len();
+            }
+        }
+        self.first_rows.push(row);
+    }
+
+    // Сколько строк экрана занимает документ из line_count строк
+    pub fn row_count(&self, line_count: usize) -> usize {
+        if self.identity {
+            return line_count;
+        }
+        let known = self.first_rows.len() - 1;
+        self.first_rows[known] + line_count.saturating_sub(known)
+    }
+
+    // Строка экрана, с которой начинается строка документа
+    pub fn first_row(&self, line: usize) -> usize {
+        if self.identity {
+            return line;
+        }
+        let known = self.first_rows.len() - 1;
+        match self.first_rows.get(line) {
+            Some(&row) => row,
+            None => self.first_rows[known] + line - known,
+        }
+    }
+
+    // Строка документа и номер отрезка по строке экрана
+    pub fn row_position(&self, row: usize) -> (usize, usize) {
+        if self.identity {
+            return (row, 0);
+        }
+        let known = self.first_rows.len() - 1;
+        let total = self.first_rows[known];
+        if row >= total {
+            return (known + row - total, 0);
+        }
+        let line = self.first_rows.partition_point(|&first| first <= row) - 1;
+        (line, row - self.first_rows[line])
+    }
+
+    // Байтовые начала отрезков строки
+    pub fn starts(&self, line: usize) -> &[usize] {
+        self.lines.get(line).map_or(SINGLE, |wrap| &wrap.starts)
+    }
+
+    // Байтовые границы отрезка; text - сама строка документа
+    pub fn segment_range(&self, line: usize, segment: usize, text: &str) -> (usize, usize) {
+        let starts = self.starts(line);
+        // Разметка могла отстать от строки, границы ограничиваются ее длиной
+        let start = starts.get(segment).copied().unwrap_or(0).min(text.len());
+        let end = starts.get(segment + 1).copied().unwrap_or(text.len()).min(text.len());
+        (start, end.max(start))
+    }
+
+    // Отрезок, в котором стоит столбец
+    // Столбец на границе относится к следующему отрезку, где он и рисуется
+    pub fn segment_of(&self, line: usize, column: usize) -> usize {
+        self.starts(line).partition_point(|&start| start <= column).max(1) - 1
+    }
+
+    // Отступ отрезка от левого края текста
+    pub fn indent(&self, line: usize, segment: usize) -> f32 {
+        match self.lines.get(line) {
+            Some(wrap) if segment > 0 => wrap.indent,
+            _ => 0.0,
+        }
+    }
+}
+
+impl Default for WrapLayout {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn hash_line(text: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    text.hash(&mut hasher);
+    hasher.finish()
+}
+
+// Делит строку на отрезки не шире width; табуляция занимает tab_width пробелов
+// Перенос делается после пробела; слово длиннее строки экрана режется по символам
+fn wrap_line(fonts: &Fonts, text: &str, width: f32, tab_width: usize) -> LineWrap {
+    let font = editor_font();
+    let tab = tab_width as f32 * fonts.glyph_width(&font, ' ');
+    let glyph = |c: char| if c == '\t' { tab } else { fonts.glyph_width(&font, c) };
+
+    // Продолжения выравниваются по отступу строки, если он не занимает полэкрана
+    let indent: f32 = text.chars().take_while(|c| c.is_whitespace()).map(glyph).sum();
+    let indent = if indent < width / 2.0 { indent } else { 0.0 };
+
+    let indent_bytes = text.len() - text.trim_start().len();
+
+    let mut starts = vec![0];
+    let mut x = 0.0;
+    let mut break_at: Option<(usize, f32)> = None; // место после последнего пробела отрезка и x в нем
+    for (index, c) in text.char_indices() {
+        let w = glyph(c);
+        let start = *starts.last().unwrap_or(&0);
+        // Пробелы в конце отрезка могут выходить за край, перенос ставится перед следующим словом
+        if x + w > width + 0.5 && index > start && !c.is_whitespace() {
+            let (next, x_at_break) = match break_at {
+                Some((position, x_at_break)) if position > start => (position, x_at_break),
+                _ => (index, x),
+            };
+            starts.push(next);
+            x = indent + (x - x_at_break);
+            break_at = None;
+        }
+        x += w;
+        if c.is_whitespace() && index >= indent_bytes {
+            break_at = Some((index + c.len_utf8(), x));
+        }
+    }
+    LineWrap { hash: hash_line(text), starts, indent }
+}
@@ -0,0 +1,419 @@
+// Поддержка EditorConfig (https://editorconfig.org)
+// Для файла читаются все .editorconfig от его каталога вверх до файла с root = true.
+// Секции применяются от дальнего файла к ближнему и сверху вниз, так что
+// ближняя и более поздняя секция перекрывает остальные
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use regex::Regex;
+
+use super::file::Encoding;
+
+const EDITORCONFIG_FILE: &str = ".editorconfig";
+
+// Перевод строки при сохранении
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum LineEnding {
+    Lf,
+    Crlf,
+    Cr,
+}
+
+impl LineEnding {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LineEnding::Lf => "\n",
+            LineEnding::Crlf => "\r\n",
+            LineEnding::Cr => "\r",
+        }
+    }
+}
+
+// Ширина отступа: число столбцов или ширина табуляции
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum IndentSize {
+    Columns(usize),
+    Tab,
+}
+
+// Свойства файла; None - свойство не задано и действует настройка редактора
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct EditorConfig {
+    pub use_tabs: Option<bool>, // indent_style
+    pub indent_size: Option<IndentSize>,
+    pub tab_width: Option<usize>,
+    pub end_of_line: Option<LineEnding>,
+    pub charset: Option<Encoding>,
+    pub trim_trailing_whitespace: Option<bool>,
+    pub insert_final_newline: Option<bool>,
+    pub max_line_length: Option<usize>, // "off" тоже дает None
+}
+
+impl EditorConfig {
+    // Свойства для файла path (файла может еще не быть)
+    pub fn resolve(path: &Path) -> Self {
+        let path = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+        // Файлы от ближнего к дальнему
+        let mut files = Vec::new();
+        for dir in path.ancestors().skip(1) {
+            let file = dir.join(EDITORCONFIG_FILE);
+            if !file.is_file() {
+                continue;
+            }
+            match fs::read_to_string(&file) {
+                Ok(content) => {
+                    let parsed = ConfigFile::parse(dir, &content);
+                    let root = parsed.root;
+                    files.push(parsed);
+                    if root {
+                        break;
+                    }
+                }
+                Err(e) => log::warn!("Не удалось прочитать {}: {}", file.display(), e),
+            }
+        }
+
+        let name = slashed(&path);
+        let mut config = Self::default();
+        for file in files.iter().rev() {
+            for section in file.sections.iter().filter(|section| section.matches(&name)) {
+                for (key, value) in section.properties.iter() {
+                    config.set(key, value);
+                }
+            }
+        }
+        config
+    }
+
+    // Ширина отступа в столбцах: indent_size, а если он не задан или равен tab - tab_width
+    pub fn indent_width(&self) -> Option<usize> {
+        match self.indent_size {
+            Some(IndentSize::Columns(columns)) => Some(columns),
+            Some(IndentSize::Tab) | None => self.tab_width,
+        }
+    }
+
+    // Применяет свойство; неизвестные свойства и значения пропускаются, unset сбрасывает
+    fn set(&mut self, key: &str, value: &str) {
+        let number = || value.parse::<usize>().ok().filter(|&number| number > 0);
+        let flag = || match value {
+            "true" => Some(true),
+            "false" => Some(false),
+            _ => None,
+        };
+        match key {
+            "indent_style" => {
+                self.use_tabs = match value {
+                    "tab" => Some(true),
+                    "space" => Some(false),
+                    _ => None,
+                }
+            }
+            "indent_size" => self.indent_size = if value == "tab" { Some(IndentSize::Tab) } else { number().map(IndentSize::Columns) },
+            "tab_width" => self.tab_width = number(),
+            "end_of_line" => {
+                self.end_of_line = match value {
+                    "lf" => Some(LineEnding::Lf),
+                    "crlf" => Some(LineEnding::Crlf),
+                    "cr" => Some(LineEnding::Cr),
+                    _ => None,
+                }
+            }
+            "charset" => {
+                self.charset = match value {
+                    "utf-8" => Some(Encoding::Utf8),
+                    "utf-8-bom" => Some(Encoding::Utf8Bom),
+                    "utf-16le" => Some(Encoding::Utf16Le),
+                    "utf-16be" => Some(Encoding::Utf16Be),
+                    "unset" => None,
+                    _ => {
+                        log::warn!("Кодировка {} из .editorconfig не поддерживается", value);
+                        None
+                    }
+                }
+            }
+            "trim_trailing_whitespace" => self.trim_trailing_whitespace = flag(),
+            "insert_final_newline" => self.insert_final_newline = flag(),
+            "max_line_length" => self.max_line_length = number(),
+            _ => {}
+        }
+    }
+}
+
+// Разобранный .editorconfig
+struct ConfigFile {
+    root: bool,
+    sections: Vec<Section>,
+}
+
+impl ConfigFile {
+    fn parse(dir: &Path, content: &str) -> Self {
+        let dir = slashed(dir);
+        let mut file = Self { root: false, sections: Vec::new() };
+        let mut in_preamble = true;
+        for line in content.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+                continue;
+            }
+            if let Some(glob) = line.strip_prefix('[').and_then(|rest| rest.strip_suffix(']')) {
+                in_preamble = false;
+                file.sections.push(Section::new(&dir, glob));
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else { continue };
+            let (key, value) = (key.trim().to_ascii_lowercase(), value.trim().to_ascii_lowercase());
+            match file.sections.last_mut() {
+                Some(section) => section.properties.push((key, value)),
+                None if in_preamble && key == "root" => file.root = value == "true",
+                None => {}
+            }
+        }
+        file
+    }
+}
+
+// Секция [glob] со свойствами
+struct Section {
+    regex: Option<Regex>,    // None - непонятный шаблон, который ничему не соответствует
+    ranges: Vec<(i64, i64)>, // границы {num1..num2} по порядку групп регулярного выражения
+    properties: Vec<(String, String)>,
+}
+
+impl Section {
+    fn new(dir: &str, glob: &str) -> Self {
+        // Шаблон без / относится к файлу в любом подкаталоге
+        let glob = match glob.strip_prefix('/') {
+            Some(rest) => rest.to_string(),
+            None if glob.contains('/') => glob.to_string(),
+            None => format!("**/{}", glob),
+        };
+        let mut ranges = Vec::new();
+        let pattern = format!("^{}/{}$", regex::escape(dir.trim_end_matches('/')), glob_pattern(&glob, &mut ranges));
+        let regex = Regex::new(&pattern).map_err(|e| log::warn!("Непонятный шаблон [{}] в .editorconfig: {}", glob, e)).ok();
+        Self { regex, ranges, properties: Vec::new() }
+    }
+
+    fn matches(&self, path: &str) -> bool {
+        let Some(captures) = self.regex.as_ref().and_then(|regex| regex.captures(path)) else { return false };
+        // Группа из невыбранного варианта {a,b} в сравнении не участвует
+        self.ranges.iter().enumerate().all(|(index, &(from, to))| {
+            captures
+                .get(index + 1)
+                .is_none_or(|number| number.as_str().parse::<i64>().is_ok_and(|number| from <= number && number <= to))
+        })
+    }
+}
+
+// Путь с / в качестве разделителя: с ним сравниваются шаблоны
+fn slashed(path: &Path) -> String {
+    let path: PathBuf = path.components().collect();
+    path.to_string_lossy().replace('\\', "/")
+}
+
+// Регулярное выражение по шаблону EditorConfig
+// * - любые символы кроме /, ** - любые, ? - один символ, [abc] и [!abc] - набор,
+// {a,b} - варианты, {1..10} - число из промежутка (проверяется после сравнения)
+fn glob_pattern(glob: &str, ranges: &mut Vec<(i64, i64)>) -> String {
+    let chars: Vec<char> = glob.chars().collect();
+    let mut pattern = String::new();
+    let mut index = 0;
+    while index < chars.len() {
+        let c = chars[index];
+        index += 1;
+        match c {
+            '\\' if index < chars.len() => {
+                pattern.push_str(&regex::escape(&chars[index].to_string()));
+                index += 1;
+            }
+            '*' if chars.get(index) == Some(&'*') => {
+                index += 1;
+                // "**/" в начале или после / захватывает и ноль каталогов
+                let at_boundary = index == 2 || chars.get(index.wrapping_sub(3)) == Some(&'/');
+                if at_boundary && chars.get(index) == Some(&'/') {
+                    index += 1;
+                    pattern.push_str("(?:.*/)?");
+                } else {
+                    pattern.push_str(".*");
+                }
+            }
+            '*' => pattern.push_str("[^/]*"),
+            '?' => pattern.push_str("[^/]"),
+            '[' => match chars[index..].iter().position(|&c| c == ']') {
+                Some(length) if !chars[index..index + length].contains(&'/') => {
+                    let body = &chars[index..index + length];
+                    let (negated, body) = match body.first() {
+                        Some('!') => (true, &body[1..]),
+                        _ => (false, body),
+                    };
+                    pattern.push('[');
+                    if negated {
+                        pattern.push('^');
+                    }
+                    for &c in body {
+                        match c {
+                            '-' => pattern.push('-'),
+                            c => pattern.push_str(&regex::escape(&c.to_string())),
+                        }
+                    }
+                    pattern.push(']');
+                    index += length + 1;
+                }
+                _ => pattern.push_str(r"\["),
+            },
+            '{' => match closing_brace(&chars, index) {
+                Some(end) => {
+                    let body: String = chars[index..end].iter().collect();
+                    index = end + 1;
+                    if let Some(range) = numeric_range(&body) {
+                        ranges.push(range);
+                        pattern.push_str(r"([+-]?\d+)");
+                    } else if let Some(alternatives) = split_alternatives(&body) {
+                        let alternatives: Vec<String> =
+                            alternatives.iter().map(|alternative| glob_pattern(alternative, ranges)).collect();
+                        pattern.push_str(&format!("(?:{})", alternatives.join("|")));
+                    } else {
+                        // {одно} - просто текст в скобках
+                        pattern.push_str(&regex::escape(&format!("{{{}}}", body)));
+                    }
+                }
+                None => pattern.push_str(r"\{"),
+            },
+            c => pattern.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    pattern
+}
+
+// Закрывающая скобка для { перед позицией start с учетом вложенности
+fn closing_brace(chars: &[char], start: usize) -> Option<usize> {
+    let mut depth = 0;
+    let mut index = start;
+    while index < chars.len() {
+        match chars[index] {
+            '\\' => index += 1,
+            '{' => depth += 1,
+            '}' if depth == 0 => return Some(index),
+            '}' => depth -= 1,
+            _ => {}
+        }
+        index += 1;
+    }
+    None
+}
+
+fn numeric_range(body: &str) -> Option<(i64, i64)> {
+    let (from, to) = body.split_once("..")?;
+    let (from, to) = (from.parse::<i64>().ok()?, to.parse::<i64>().ok()?);
+    Some((from.min(to), from.max(to)))
+}
+
+// Варианты через запятую верхнего уровня; без запятой - None
+fn split_alternatives(body: &str) -> Option<Vec<String>> {
+    let mut alternatives = vec![String::new()];
+    let mut depth = 0;
+    let mut escaped = false;
+    for c in body.chars() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '{' => depth += 1,
+            '}' => depth -= 1,
+            ',' if depth == 0 => {
+                alternatives.push(String::new());
+                continue;
+            }
+            _ => {}
+        }
+        if let Some(last) = alternatives.last_mut() {
+            last.push(c);
+        }
+    }
+    (alternatives.len() > 1).then_some(alternatives)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn glob_table() {
+        // Шаблон, путь файла (секция из /p/.editorconfig), совпадает ли
+        let table = [
+            ("*.rs", "/p/main.rs", true),
+            ("*.rs", "/p/src/deep/main.rs", true),
+            ("*.rs", "/p/main.rsx", false),
+            ("src/*.rs", "/p/src/a.rs", true),
+            ("src/*.rs", "/p/src/x/a.rs", false),
+            ("src/*.rs", "/p/other/src/a.rs", false),
+            ("/src/*.rs", "/p/src/a.rs", true),
+            ("src/**/*.rs", "/p/src/a.rs", true),
+            ("src/**/*.rs", "/p/src/x/y/a.rs", true),
+            ("src/**/*.rs", "/p/a.rs", false),
+            ("**/test/*", "/p/test/a", true),
+            ("**/test/*", "/p/x/test/a", true),
+            ("a**z", "/p/a/b/z", true),
+            ("?.txt", "/p/a.txt", true),
+            ("?.txt", "/p/ab.txt", false),
+            ("*.{js,ts}", "/p/a.ts", true),
+            ("*.{js,ts}", "/p/a.rs", false),
+            ("{a,b{c,d}}.txt", "/p/bd.txt", true),
+            ("{a,b{c,d}}.txt", "/p/b.txt", false),
+            ("{single}.txt", "/p/{single}.txt", true),
+            ("file{1..10}.txt", "/p/file5.txt", true),
+            ("file{1..10}.txt", "/p/file10.txt", true),
+            ("file{1..10}.txt", "/p/file11.txt", false),
+            ("file{1..10}.txt", "/p/file0.txt", false),
+            ("file{1..10}.txt", "/p/filex.txt", false),
+            ("{-3..3}", "/p/-2", true),
+            ("{a,{1..2}}", "/p/a", true),
+            ("{a,{1..2}}", "/p/3", false),
+            ("[!x]y", "/p/ay", true),
+            ("[!x]y", "/p/xy", false),
+            ("[a-c].md", "/p/b.md", true),
+            ("[a-c].md", "/p/d.md", false),
+            ("[ab", "/p/[ab", true),
+            ("a\\*b", "/p/a*b", true),
+            ("a\\*b", "/p/axb", false),
+        ];
+        for (glob, path, expected) in table {
+            assert_eq!(Section::new("/p", glob).matches(path), expected, "[{}] и {}", glob, path);
+        }
+    }
+
+    #[test]
+    fn root_and_precedence() {
+        let outer = std::env::temp_dir().join(format!("editorconfig-test-{}", std::process::id()));
+        let base = outer.join("base");
+        fs::create_dir_all(base.join("sub")).unwrap();
+        fs::write(outer.join(EDITORCONFIG_FILE), "[*]\nend_of_line = crlf\nindent_style = space\n").unwrap();
+        fs::write(
+            base.join(EDITORCONFIG_FILE),
+            "root = true\n[*]\nindent_style = tab\ntab_width = 8\n[*.rs]\nindent_size = 2\n[*.md]\nindent_size = 3\n[README.md]\nindent_size = unset\n",
+        )
+        .unwrap();
+        fs::write(base.join("sub").join(EDITORCONFIG_FILE), "[*.rs]\nindent_size = 4\n[lib.rs]\nindent_style = space\n").unwrap();
+
+        // Ближний файл перекрывает дальний, root = true останавливает поиск
+        let config = EditorConfig::resolve(&base.join("sub").join("lib.rs"));
+        assert_eq!(config.use_tabs, Some(false));
+        assert_eq!(config.indent_size, Some(IndentSize::Columns(4)));
+        assert_eq!(config.tab_width, Some(8));
+        assert_eq!(config.end_of_line, None);
+
+        let config = EditorConfig::resolve(&base.join("main.rs"));
+        assert_eq!(config.use_tabs, Some(true));
+        assert_eq!(config.indent_width(), Some(2));
+        // Более поздняя секция того же файла перекрывает раннюю, unset сбрасывает свойство
+        assert_eq!(EditorConfig::resolve(&base.join("notes.md")).indent_size, Some(IndentSize::Columns(3)));
+        let config = EditorConfig::resolve(&base.join("README.md"));
+        assert_eq!(config.indent_size, None);
+        assert_eq!(config.indent_width(), Some(8));
+
+        // Вне корня действует только внешний файл
+        let config = EditorConfig::resolve(&outer.join("other.txt"));
+        assert_eq!(config.end_of_line, Some(LineEnding::Crlf));
+        assert_eq!(config.use_tabs, Some(false));
+        let _ = fs::remove_dir_all(outer);
+    }
+}
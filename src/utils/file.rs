@@ -1,9 +1,24 @@
-use std::path::PathBuf;
-use anyhow::Result;
+use std::fs;
+use std::path::{Path, PathBuf};
+use anyhow::{anyhow, Result};
+
+use super::editorconfig::{EditorConfig, LineEnding};
+
+// Кодировка открытого файла; при сохранении файл пишется в той же кодировке
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Encoding {
+    Utf8,
+    Utf8Bom,
+    Utf16Le,
+    Utf16Be,
+}
 
 pub struct FileManager {
     current_file: Option<PathBuf>,
     last_directory: Option<PathBuf>,
+    encoding: Encoding,
+    line_ending: LineEnding,    // переводы строк файла
+    editorconfig: EditorConfig, // свойства текущего файла из .editorconfig
 }
 
 impl FileManager {
@@ -11,27 +26,147 @@ impl FileManager {
         Self {
             current_file: None,
             last_directory: None,
+            encoding: Encoding::Utf8,
+            line_ending: LineEnding::Lf,
+            editorconfig: EditorConfig::default(),
         }
     }
 
-    pub fn open_file(&mut self, path: &PathBuf) -> Result<String> {
-        // TODO: Реализовать открытие файла
-        // 1. Проверить существование файла
-        // 2. Прочитать содержимое файла
-        // 3. Определить кодировку
-        // 4. Обновить текущий файл
-        todo!()
+    // Текущий файл (None - новый, еще не сохраненный документ)
+    pub fn current_file(&self) -> Option<&Path> {
+        self.current_file.as_deref()
+    }
+
+    // Каталог, из которого последний раз открывали или куда сохраняли файл
+    pub fn last_directory(&self) -> Option<&Path> {
+        self.last_directory.as_deref()
     }
 
-    pub fn save_file(&self, path: &PathBuf, content: &str) -> Result<()> {
-        // TODO: Реализовать сохранение файла
-        // 1. Проверить права доступа
-        // 2. Создать резервную копию если файл существует
-        // 3. Записать содержимое
-        // 4. Обновить текущий файл
-        todo!()
+    // Свойства текущего файла из .editorconfig
+    pub fn editorconfig(&self) -> &EditorConfig {
+        &self.editorconfig
+    }
+
+    pub fn open_file(&mut self, path: &Path) -> Result<String> {
+        // Проверяем существование файла
+        if !path.is_file() {
+            return Err(anyhow!("Файл {} не найден", path.display()));
+        }
+
+        // Читаем содержимое и определяем кодировку
+        let bytes = fs::read(path).map_err(|e| anyhow!("Не удалось прочитать {}: {}", path.display(), e))?;
+        let (text, encoding) = decode(&bytes);
+
+        // Редактор работает с \n, исходные переводы строк восстанавливаются при сохранении
+        self.line_ending = if text.contains("\r\n") { LineEnding::Crlf } else { LineEnding::Lf };
+        let text = if self.line_ending == LineEnding::Crlf { text.replace("\r\n", "\n") } else { text };
+
+        // Обновляем текущий файл
+        self.encoding = encoding;
+        self.editorconfig = EditorConfig::resolve(path);
+        self.remember(path);
+        Ok(text)
+    }
+
+    pub fn save_file(&mut self, path: &Path, content: &str) -> Result<()> {
+        // Проверяем права доступа
+        if let Ok(metadata) = fs::metadata(path) {
+            if metadata.permissions().readonly() {
+                return Err(anyhow!("Файл {} доступен только для чтения", path.display()));
+            }
+        }
+
+        // Создаем резервную копию, если файл существует
+        if path.is_file() {
+            let backup = backup_path(path);
+            fs::copy(path, &backup).map_err(|e| anyhow!("Не удалось создать копию {}: {}", backup.display(), e))?;
+        }
+
+        // Записываем содержимое в исходной кодировке и с исходными переводами строк
+        // (для нового файла - UTF-8 и \n); .editorconfig может задать и то, и другое
+        if self.current_file.as_deref() != Some(path) {
+            self.encoding = Encoding::Utf8;
+            self.line_ending = LineEnding::Lf;
+            self.editorconfig = EditorConfig::resolve(path);
+        }
+        if let Some(charset) = self.editorconfig.charset {
+            self.encoding = charset;
+        }
+        if let Some(line_ending) = self.editorconfig.end_of_line {
+            self.line_ending = line_ending;
+        }
+        let content = apply_editorconfig(content, &self.editorconfig).replace('\n', self.line_ending.as_str());
+        fs::write(path, encode(&content, self.encoding))
+            .map_err(|e| anyhow!("Не удалось сохранить {}: {}", path.display(), e))?;
+
+        // Обновляем текущий файл
+        self.remember(path);
+        Ok(())
+    }
+
+    fn remember(&mut self, path: &Path) {
+        self.current_file = Some(path.to_path_buf());
+        self.last_directory = path.parent().map(Path::to_path_buf);
     }
 }
 
+// Убирает пробелы в концах строк и добавляет или убирает перевод строки в конце файла
+fn apply_editorconfig(content: &str, config: &EditorConfig) -> String {
+    let mut content = match config.trim_trailing_whitespace {
+        Some(true) => content.split('\n').map(str::trim_end).collect::<Vec<_>>().join("\n"),
+        _ => content.to_string(),
+    };
+    match config.insert_final_newline {
+        Some(true) if !content.is_empty() && !content.ends_with('\n') => content.push('\n'),
+        Some(false) => content.truncate(content.trim_end_matches('\n').len()),
+        _ => {}
+    }
+    content
+}
+
+// Резервная копия: имя файла с ~ в конце
+fn backup_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().map(|name| name.to_os_string()).unwrap_or_default();
+    name.push("~");
+    path.with_file_name(name)
+}
 
+// Определяет кодировку по BOM; без BOM файл считается UTF-8,
+// а недопустимые последовательности заменяются символом �
+fn decode(bytes: &[u8]) -> (String, Encoding) {
+    if let Some(rest) = bytes.strip_prefix(&[0xEF, 0xBB, 0xBF]) {
+        return (String::from_utf8_lossy(rest).into_owned(), Encoding::Utf8Bom);
+    }
+    if let Some(rest) = bytes.strip_prefix(&[0xFF, 0xFE]) {
+        let units = rest.chunks_exact(2).map(|pair| u16::from_le_bytes([pair[0], pair[1]]));
+        return (char::decode_utf16(units).map(|c| c.unwrap_or('\u{FFFD}')).collect(), Encoding::Utf16Le);
+    }
+    if let Some(rest) = bytes.strip_prefix(&[0xFE, 0xFF]) {
+        let units = rest.chunks_exact(2).map(|pair| u16::from_be_bytes([pair[0], pair[1]]));
+        return (char::decode_utf16(units).map(|c| c.unwrap_or('\u{FFFD}')).collect(), Encoding::Utf16Be);
+    }
+    match String::from_utf8(bytes.to_vec()) {
+        Ok(text) => (text, Encoding::Utf8),
+        Err(e) => {
+            log::warn!("Файл не в UTF-8, недопустимые байты заменены");
+            (String::from_utf8_lossy(e.as_bytes()).into_owned(), Encoding::Utf8)
+        }
+    }
+}
 
+fn encode(text: &str, encoding: Encoding) -> Vec<u8> {
+    match encoding {
+        Encoding::Utf8 => text.as_bytes().to_vec(),
+        Encoding::Utf8Bom => [&[0xEF, 0xBB, 0xBF][..], text.as_bytes()].concat(),
+        Encoding::Utf16Le => {
+            let mut bytes = vec![0xFF, 0xFE];
+            bytes.extend(text.encode_utf16().flat_map(u16::to_le_bytes));
+            bytes
+        }
+        Encoding::Utf16Be => {
+            let mut bytes = vec![0xFE, 0xFF];
+            bytes.extend(text.encode_utf16().flat_map(u16::to_be_bytes));
+            bytes
+        }
+    }
+}
@@ -0,0 +1,218 @@
+// Фильтр через внешнюю команду (как ! в Vim): текст подается на stdin команды
+// оболочки, результат - ее stdout. Команда выполняется в отдельном потоке,
+// окно опрашивает результат и может прервать команду
+
+use std::io::{Read, Write};
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, TryRecvError};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, Result};
+
+// Как часто поток проверяет завершение, прерывание и время ожидания
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+pub struct Filter {
+    command: String,
+    result: Receiver<Result<String, String>>,
+    cancel: Arc<AtomicBool>,
+}
+
+impl Filter {
+    // Запускает команду с текстом input; notify вызывается, когда готов результат
+    // Команда, не завершившаяся за timeout, прерывается
+    pub fn start(command: &str, input: String, timeout: Duration, notify: impl Fn() + Send + 'static) -> Result<Self> {
+        let child = shell(command)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| anyhow!("Не удалось запустить \"{}\": {}", command, e))?;
+        let (sender, result) = mpsc::channel();
+        let cancel = Arc::new(AtomicBool::new(false));
+        let flag = Arc::clone(&cancel);
+        thread::Builder::new()
+            .name("filter".to_string())
+            .spawn(move || {
+                // Окно могло уже закрыть документ - тогда результат никому не нужен
+                let _ = sender.send(wait(child, input, timeout, &flag));
+                notify();
+            })
+            .map_err(|e| anyhow!("Не удалось запустить поток команды: {}", e))?;
+        Ok(Self { command: command.to_string(), result, cancel })
+    }
+
+    pub fn command(&self) -> &str {
+        &self.command
+    }
+
+    // Вывод команды, ее ошибка или None, пока она выполняется
+    pub fn poll(&self) -> Option<Result<String, String>> {
+        match self.result.try_recv() {
+            Ok(result) => Some(result),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => Some(Err("Поток команды завершился без результата".to_string())),
+        }
+    }
+
+    // Прерывает команду; результатом станет ошибка "Команда прервана"
+    pub fn cancel(&self) {
+        self.cancel.store(true, Ordering::Relaxed);
+    }
+}
+
+impl Drop for Filter {
+    fn drop(&mut self) {
+        self.cancel();
+    }
+}
+
+#[cfg(windows)]
+fn shell(command: &str) -> Command {
+    let mut shell = Command::new("cmd");
+    shell.args(["/C", command]);
+    shell
+}
+
+#[cfg(not(windows))]
+fn shell(command: &str) -> Command {
+    use std::os::unix::process::CommandExt;
+
+    let mut shell = Command::new("sh");
+    // Своя группа процессов, чтобы при прерывании остановить и запущенные оболочкой команды
+    shell.args(["-c", command]).process_group(0);
+    shell
+}
+
+// Прерывает команду вместе с запущенными ею процессами
+#[cfg(windows)]
+fn kill(child: &mut Child) {
+    let _ = child.kill();
+    let _ = child.wait();
+}
+
+// Прерывает команду вместе с запущенными ею процессами
+#[cfg(not(windows))]
+fn kill(child: &mut Child) {
+    // Номер группы процессов совпадает с pid оболочки; группа живет, пока в ней есть процессы,
+    // даже если сама оболочка уже завершилась
+    let _ = Command::new("kill").args(["-s", "KILL", "--", &format!("-{}", child.id())]).status();
+    let _ = child.kill();
+    let _ = child.wait();
+}
+
+// Ждет завершения команды; stdin пишется, а stdout и stderr читаются в своих потоках,
+// чтобы команда не застряла на заполненном канале
+fn wait(mut child: Child, input: String, timeout: Duration, cancel: &AtomicBool) -> Result<String, String> {
+    if let Some(mut stdin) = child.stdin.take() {
+        // Команда может не читать ввод (например, date) - ошибка записи не важна
+        thread::spawn(move || stdin.write_all(input.as_bytes()));
+    }
+    let stdout = read_all(child.stdout.take());
+    let stderr = read_all(child.stderr.take());
+    let started = Instant::now();
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break status,
+            Ok(None) => {}
+            Err(e) => return Err(format!("Ошибка ожидания команды: {}", e)),
+        }
+        if let Some(error) = interrupted(started, timeout, cancel) {
+            kill(&mut child);
+            return Err(error);
+        }
+        thread::sleep(POLL_INTERVAL);
+    };
+    // Запущенный в фоне процесс (sleep 100 &) держит каналы открытыми и после выхода оболочки,
+    // поэтому чтение ограничено тем же временем ожидания и прерыванием
+    let stdout = receive(&stdout, &mut child, started, timeout, cancel)?;
+    let stderr = receive(&stderr, &mut child, started, timeout, cancel)?;
+    if !status.success() {
+        let stderr = String::from_utf8_lossy(&stderr);
+        return Err(match stderr.trim() {
+            "" => format!("Команда завершилась с ошибкой ({})", status),
+            message => message.to_string(),
+        });
+    }
+    String::from_utf8(stdout).map_err(|_| "Вывод команды не является текстом UTF-8".to_string())
+}
+
+// Ошибка, если команду прервали или время ожидания истекло
+fn interrupted(started: Instant, timeout: Duration, cancel: &AtomicBool) -> Option<String> {
+    if cancel.load(Ordering::Relaxed) {
+        Some("Команда прервана".to_string())
+    } else if started.elapsed() > timeout {
+        Some(format!("Команда не завершилась за {} с и прервана", timeout.as_secs()))
+    } else {
+        None
+    }
+}
+
+// Ждет, пока поток дочитает канал до конца
+fn receive(
+    bytes: &Receiver<Vec<u8>>,
+    child: &mut Child,
+    started: Instant,
+    timeout: Duration,
+    cancel: &AtomicBool,
+) -> Result<Vec<u8>, String> {
+    loop {
+        match bytes.recv_timeout(POLL_INTERVAL) {
+            Ok(bytes) => return Ok(bytes),
+            Err(RecvTimeoutError::Disconnected) => return Ok(Vec::new()),
+            Err(RecvTimeoutError::Timeout) => {}
+        }
+        if let Some(error) = interrupted(started, timeout, cancel) {
+            kill(child);
+            return Err(error);
+        }
+    }
+}
+
+fn read_all(source: Option<impl Read + Send + 'static>) -> Receiver<Vec<u8>> {
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let mut bytes = Vec::new();
+        if let Some(mut source) = source {
+            let _ = source.read_to_end(&mut bytes);
+        }
+        let _ = sender.send(bytes);
+    });
+    receiver
+}
+
+#[cfg(all(test, not(windows)))]
+mod tests {
+    use super::*;
+
+    // Результат фильтра; тест ждет не дольше 10 с
+    fn run(command: &str, input: &str, timeout: Duration) -> Result<String, String> {
+        let filter = Filter::start(command, input.to_string(), timeout, || {}).unwrap();
+        let started = Instant::now();
+        while started.elapsed() < Duration::from_secs(10) {
+            if let Some(result) = filter.poll() {
+                return result;
+            }
+            thread::sleep(POLL_INTERVAL);
+        }
+        panic!("фильтр \"{}\" не вернул результат", command);
+    }
+
+    #[test]
+    fn output_and_errors() {
+        assert_eq!(run("tr a-z A-Z", "abc\n", Duration::from_secs(5)), Ok("ABC\n".to_string()));
+        assert_eq!(run("echo oops >&2; exit 3", "", Duration::from_secs(5)), Err("oops".to_string()));
+    }
+
+    #[test]
+    fn background_process_does_not_block() {
+        // Фоновый sleep держит stdout открытым после выхода оболочки
+        let started = Instant::now();
+        let result = run("sleep 30 & echo started", "", Duration::from_secs(1));
+        assert!(result.unwrap_err().contains("не завершилась"));
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+}
@@ -1,17 +1,23 @@
+mod editorconfig;
 mod file;
+mod filter;
 
+use std::path::PathBuf;
+
+pub use editorconfig::EditorConfig;
 pub use file::FileManager;
+pub use filter::Filter;
 
-pub struct Utils {
-    file_manager: FileManager,
-}
+// Имя каталога с настройками и сохраненными данными редактора
+const APP_DIR: &str = "simple-text-editor";
 
-impl Utils {
-    pub fn new() -> Self {
-        Self {
-            file_manager: FileManager::new(),
-        }
-    }
+// Каталог настроек: $XDG_CONFIG_HOME, ~/.config или %APPDATA% (в Windows)
+pub fn config_dir() -> Option<PathBuf> {
+    let base = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .or_else(|| std::env::var_os("APPDATA").map(PathBuf::from))?;
+    Some(base.join(APP_DIR))
 }
 
 